@@ -1,11 +1,11 @@
 //! This contains an actor spawned on a separate thread to process replica and store operations.
 
 use std::{
-    collections::{hash_map, HashMap},
+    collections::{hash_map, HashMap, VecDeque},
     num::NonZeroU64,
     sync::Arc,
-    thread::JoinHandle,
-    time::Duration,
+    thread::{JoinHandle, ThreadId},
+    time::{Duration, Instant},
 };
 
 use anyhow::{anyhow, Context, Result};
@@ -22,15 +22,18 @@ use crate::{
     ranger::Message,
     store::{
         fs::{ContentHashesIterator, StoreInstance},
-        DownloadPolicy, ImportNamespaceOutcome, Query, Store,
+        DownloadPolicy, ImportNamespaceOutcome, OpenError, Query, Store,
     },
-    Author, AuthorHeads, AuthorId, Capability, CapabilityKind, ContentStatus,
-    ContentStatusCallback, Event, NamespaceId, NamespaceSecret, PeerIdBytes, Replica, ReplicaInfo,
-    SignedEntry, SyncOutcome,
+    Author, AuthorHeads, AuthorId, Capability, CapabilityKind, ContentChunks, ContentStatus,
+    ContentStatusCallback, Event, NamespaceId, NamespaceSecret, PeerIdBytes, RecordIdentifier,
+    Replica, ReplicaInfo, SignedEntry, SyncEventMode, SyncOutcome,
 };
 
 const ACTION_CAP: usize = 1024;
 pub(crate) const MAX_COMMIT_DELAY: Duration = Duration::from_millis(500);
+/// Time after which the actor gives up waiting for a flush of the store, see
+/// [`Store::flush_with_timeout`].
+pub(crate) const FLUSH_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(derive_more::Debug, derive_more::Display)]
 enum Action {
@@ -73,11 +76,45 @@ enum Action {
         #[debug("reply")]
         reply: oneshot::Sender<Result<ContentHashesIterator>>,
     },
+    #[display("SubscribeAll")]
+    SubscribeAll {
+        sender: async_channel::Sender<(NamespaceId, Event)>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    #[display("UnsubscribeAll")]
+    UnsubscribeAll {
+        sender: async_channel::Sender<(NamespaceId, Event)>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
     #[display("FlushStore")]
     FlushStore {
         #[debug("reply")]
         reply: oneshot::Sender<Result<()>>,
     },
+    #[display("NotifyContentStatus")]
+    NotifyContentStatus {
+        namespace: NamespaceId,
+        hash: Hash,
+        status: ContentStatus,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<bool>>,
+    },
+    #[display("PutContentChunks")]
+    PutContentChunks {
+        namespace: NamespaceId,
+        manifest: Bytes,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<usize>>,
+    },
+    #[display("PutNamespaceMeta")]
+    PutNamespaceMeta {
+        namespace: NamespaceId,
+        meta: Bytes,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<usize>>,
+    },
     #[display("Replica({}, {})", _0.fmt_short(), _1)]
     Replica(NamespaceId, ReplicaAction),
     #[display("Shutdown")]
@@ -93,6 +130,7 @@ enum ReplicaAction {
         #[debug("reply")]
         reply: oneshot::Sender<Result<()>>,
         opts: OpenOpts,
+        deadline: Option<Instant>,
     },
     Close {
         #[debug("reply")]
@@ -112,6 +150,23 @@ enum ReplicaAction {
         #[debug("reply")]
         reply: oneshot::Sender<Result<()>>,
     },
+    SubscribeWithReplay {
+        sender: async_channel::Sender<Event>,
+        n: usize,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    SubscribeWithSnapshot {
+        query: Query,
+        sender: async_channel::Sender<Event>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<Vec<SignedEntry>>>,
+    },
+    SetEventReplayCapacity {
+        capacity: usize,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
     Unsubscribe {
         sender: async_channel::Sender<Event>,
         #[debug("reply")]
@@ -125,12 +180,31 @@ enum ReplicaAction {
         #[debug("reply")]
         reply: oneshot::Sender<Result<()>>,
     },
+    InsertLocalMany {
+        author: AuthorId,
+        entries: Vec<(Bytes, Hash, u64)>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
     DeletePrefix {
         author: AuthorId,
         key: Bytes,
         #[debug("reply")]
         reply: oneshot::Sender<Result<usize>>,
     },
+    InsertChunked {
+        author: AuthorId,
+        key: Bytes,
+        chunks: ContentChunks,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    SetNamespaceMeta {
+        author: AuthorId,
+        meta: Bytes,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
     InsertRemote {
         entry: SignedEntry,
         from: PeerIdBytes,
@@ -142,6 +216,26 @@ enum ReplicaAction {
         #[debug("reply")]
         reply: oneshot::Sender<Result<Message<SignedEntry>>>,
     },
+    SetSyncEventMode {
+        mode: SyncEventMode,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    SetMaxContentLen {
+        max_content_len: Option<u64>,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    EnableReadCache {
+        capacity: usize,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
+    SyncBatchApplied {
+        count: usize,
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<()>>,
+    },
     SyncProcessMessage {
         message: Message<SignedEntry>,
         from: PeerIdBytes,
@@ -164,16 +258,28 @@ enum ReplicaAction {
         include_empty: bool,
         reply: oneshot::Sender<Result<Option<SignedEntry>>>,
     },
+    GetExactMany {
+        keys: Vec<(AuthorId, Bytes)>,
+        include_empty: bool,
+        reply: oneshot::Sender<Result<Vec<Option<SignedEntry>>>>,
+    },
     GetMany {
         query: Query,
         reply: async_channel::Sender<Result<SignedEntry>>,
     },
+    GetNamespaceMeta {
+        reply: oneshot::Sender<Result<Option<Bytes>>>,
+    },
     DropReplica {
         reply: oneshot::Sender<Result<()>>,
     },
     ExportSecretKey {
         reply: oneshot::Sender<Result<NamespaceSecret>>,
     },
+    AuthorHeads {
+        #[debug("reply")]
+        reply: oneshot::Sender<Result<AuthorHeads>>,
+    },
     HasNewsForUs {
         heads: AuthorHeads,
         #[debug("reply")]
@@ -233,6 +339,15 @@ pub struct OpenOpts {
     pub sync: bool,
     /// Optionally subscribe to replica events.
     pub subscribe: Option<async_channel::Sender<Event>>,
+    /// Optionally subscribe to replica events, including the per-entry events of silent syncs.
+    ///
+    /// See [`ReplicaInfo::subscribe_internal`].
+    pub subscribe_internal: Option<async_channel::Sender<Event>>,
+    /// Optionally fail with [`OpenError::Timeout`] if the replica is not opened in time.
+    ///
+    /// The actor processes store operations one after another, so opening a replica waits for
+    /// all operations queued before it. Without a timeout, this waits indefinitely.
+    pub timeout: Option<Duration>,
 }
 impl OpenOpts {
     /// Set sync state to true.
@@ -245,6 +360,16 @@ impl OpenOpts {
         self.subscribe = Some(subscribe);
         self
     }
+    /// Subscribe to replica events, including the per-entry events of silent syncs.
+    pub fn subscribe_internal(mut self, subscribe: async_channel::Sender<Event>) -> Self {
+        self.subscribe_internal = Some(subscribe);
+        self
+    }
+    /// Fail with [`OpenError::Timeout`] if the replica is not opened within `timeout`.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
 }
 
 #[allow(missing_docs)]
@@ -281,11 +406,28 @@ impl SyncHandle {
         }
     }
 
+    /// Open a replica.
+    ///
+    /// If [`OpenOpts::timeout`] is set, this fails with [`OpenError::Timeout`] if the actor
+    /// did not open the replica in time. The replica is then not opened.
     pub async fn open(&self, namespace: NamespaceId, opts: OpenOpts) -> Result<()> {
         let (reply, rx) = oneshot::channel();
-        let action = ReplicaAction::Open { reply, opts };
-        self.send_replica(namespace, action).await?;
-        rx.await?
+        let deadline = opts.timeout.map(|timeout| Instant::now() + timeout);
+        let action = ReplicaAction::Open {
+            reply,
+            opts,
+            deadline,
+        };
+        let fut = async {
+            self.send_replica(namespace, action).await?;
+            rx.await?
+        };
+        match deadline {
+            None => fut.await,
+            Some(deadline) => tokio::time::timeout_at(deadline.into(), fut)
+                .await
+                .map_err(|_elapsed| OpenError::Timeout)?,
+        }
     }
 
     pub async fn close(&self, namespace: NamespaceId) -> Result<bool> {
@@ -306,6 +448,74 @@ impl SyncHandle {
         rx.await?
     }
 
+    /// Subscribe to replica events, replaying up to `n` recent events first, see
+    /// [`ReplicaInfo::subscribe_with_replay`].
+    pub async fn subscribe_with_replay(
+        &self,
+        namespace: NamespaceId,
+        sender: async_channel::Sender<Event>,
+        n: usize,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SubscribeWithReplay { sender, n, reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Subscribe to replica events, and get the entries matching `query` at the same time.
+    ///
+    /// The entries are read and `sender` is subscribed while handling a single action, so no
+    /// write can happen in between: every change after the returned snapshot is delivered to
+    /// `sender`, and no change already contained in the snapshot is.
+    pub async fn subscribe_with_snapshot(
+        &self,
+        namespace: NamespaceId,
+        query: Query,
+        sender: async_channel::Sender<Event>,
+    ) -> Result<Vec<SignedEntry>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SubscribeWithSnapshot {
+            query,
+            sender,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Subscribe to replica events, coalescing rapid updates of the same entry.
+    ///
+    /// Insert events are held back for up to `window`. If more inserts for the same
+    /// [`RecordIdentifier`] arrive in the meantime, only the latest of them is delivered. Other
+    /// events are delivered right away, after all insert events held back so far.
+    ///
+    /// The events are forwarded by a task on the current tokio runtime, so `sender` can not be
+    /// removed with [`Self::unsubscribe`]. Drop the receiver instead to end the subscription.
+    pub async fn subscribe_coalesced(
+        &self,
+        namespace: NamespaceId,
+        sender: async_channel::Sender<Event>,
+        window: Duration,
+    ) -> Result<()> {
+        let (inner_tx, inner_rx) = async_channel::bounded(sender.capacity().unwrap_or(ACTION_CAP));
+        self.subscribe(namespace, inner_tx).await?;
+        tokio::task::spawn(coalesce_events(inner_rx, sender, window));
+        Ok(())
+    }
+
+    /// Set the number of recent events kept for replaying, see
+    /// [`ReplicaInfo::set_event_replay_capacity`].
+    pub async fn set_event_replay_capacity(
+        &self,
+        namespace: NamespaceId,
+        capacity: usize,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetEventReplayCapacity { capacity, reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
     pub async fn unsubscribe(
         &self,
         namespace: NamespaceId,
@@ -317,6 +527,26 @@ impl SyncHandle {
         rx.await?
     }
 
+    /// Subscribe to the events of all replicas, see [`Store::subscribe_all`].
+    pub async fn subscribe_all(
+        &self,
+        sender: async_channel::Sender<(NamespaceId, Event)>,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Action::SubscribeAll { sender, reply }).await?;
+        rx.await?
+    }
+
+    /// Unsubscribe a sender added with [`Self::subscribe_all`].
+    pub async fn unsubscribe_all(
+        &self,
+        sender: async_channel::Sender<(NamespaceId, Event)>,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        self.send(Action::UnsubscribeAll { sender, reply }).await?;
+        rx.await?
+    }
+
     pub async fn set_sync(&self, namespace: NamespaceId, sync: bool) -> Result<()> {
         let (reply, rx) = oneshot::channel();
         let action = ReplicaAction::SetSync { sync, reply };
@@ -324,6 +554,49 @@ impl SyncHandle {
         rx.await?
     }
 
+    pub async fn set_sync_event_mode(
+        &self,
+        namespace: NamespaceId,
+        mode: SyncEventMode,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetSyncEventMode { mode, reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Set the maximum content length accepted for entries, see
+    /// [`ReplicaInfo::set_max_content_len`].
+    pub async fn set_max_content_len(
+        &self,
+        namespace: NamespaceId,
+        max_content_len: Option<u64>,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetMaxContentLen {
+            max_content_len,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Enable the read cache of a replica, see [`ReplicaInfo::enable_read_cache`].
+    pub async fn enable_read_cache(&self, namespace: NamespaceId, capacity: usize) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::EnableReadCache { capacity, reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Emit [`Event::SyncBatchApplied`] after a silent sync, see [`Replica::sync_batch_applied`].
+    pub async fn sync_batch_applied(&self, namespace: NamespaceId, count: usize) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SyncBatchApplied { count, reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
     pub async fn insert_local(
         &self,
         namespace: NamespaceId,
@@ -344,6 +617,45 @@ impl SyncHandle {
         rx.await?
     }
 
+    /// Insert many local entries of the same author with a single message to the actor.
+    ///
+    /// The entries are given as `(key, hash, len)` and inserted in order. Insertion stops at the
+    /// first error, entries inserted before are kept.
+    pub async fn insert_local_many(
+        &self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        entries: Vec<(Bytes, Hash, u64)>,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::InsertLocalMany {
+            author,
+            entries,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Insert a chunked entry, see [`Replica::insert_chunked`].
+    pub async fn insert_chunked(
+        &self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        key: Bytes,
+        chunks: ContentChunks,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::InsertChunked {
+            author,
+            key,
+            chunks,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
     pub async fn delete_prefix(
         &self,
         namespace: NamespaceId,
@@ -356,6 +668,33 @@ impl SyncHandle {
         rx.await?
     }
 
+    /// Set the metadata of a namespace, see [`Replica::set_namespace_meta`].
+    pub async fn set_namespace_meta(
+        &self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        meta: Bytes,
+    ) -> Result<()> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::SetNamespaceMeta {
+            author,
+            meta,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
+    /// Get the latest metadata of a namespace.
+    ///
+    /// See [`Store::get_namespace_meta`].
+    pub async fn get_namespace_meta(&self, namespace: NamespaceId) -> Result<Option<Bytes>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetNamespaceMeta { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
     pub async fn insert_remote(
         &self,
         namespace: NamespaceId,
@@ -420,6 +759,14 @@ impl SyncHandle {
         rx.await?
     }
 
+    /// Get the timestamp of the latest entry of each author, see [`Store::author_heads`].
+    pub async fn author_heads(&self, namespace: NamespaceId) -> Result<AuthorHeads> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::AuthorHeads { reply };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
     pub async fn has_news_for_us(
         &self,
         namespace: NamespaceId,
@@ -460,6 +807,25 @@ impl SyncHandle {
         rx.await?
     }
 
+    /// Get many entries by author and key with a single call into the actor.
+    ///
+    /// See [`Store::get_exact_many`].
+    pub async fn get_exact_many(
+        &self,
+        namespace: NamespaceId,
+        keys: Vec<(AuthorId, Bytes)>,
+        include_empty: bool,
+    ) -> Result<Vec<Option<SignedEntry>>> {
+        let (reply, rx) = oneshot::channel();
+        let action = ReplicaAction::GetExactMany {
+            keys,
+            include_empty,
+            reply,
+        };
+        self.send_replica(namespace, action).await?;
+        rx.await?
+    }
+
     pub async fn drop_replica(&self, namespace: NamespaceId) -> Result<()> {
         let (reply, rx) = oneshot::channel();
         let action = ReplicaAction::DropReplica { reply };
@@ -558,12 +924,82 @@ impl SyncHandle {
     ///
     /// It's not necessary to call this function before shutdown, as `shutdown` will
     /// trigger a flush on its own.
+    ///
+    /// Fails with [`crate::store::FlushTimeout`] if the database did not complete the commit
+    /// within 30 seconds.
     pub async fn flush_store(&self) -> Result<()> {
         let (reply, rx) = oneshot::channel();
         self.send(Action::FlushStore { reply }).await?;
         rx.await?
     }
 
+    /// Get a [`SyncHandleBlocking`] to call the actor from non-async code.
+    pub fn blocking(&self) -> SyncHandleBlocking {
+        let actor_thread = self
+            .join_handle
+            .as_ref()
+            .as_ref()
+            .map(|handle| handle.thread().id());
+        SyncHandleBlocking {
+            handle: self.clone(),
+            actor_thread,
+        }
+    }
+
+    /// Record a change of the availability of content referenced in a namespace.
+    ///
+    /// See [`Replica::notify_content_status`]. If the replica is not open, only the subscribers
+    /// of all replicas receive the event, see [`Store::notify_content_status`].
+    pub async fn notify_content_status(
+        &self,
+        namespace: NamespaceId,
+        hash: Hash,
+        status: ContentStatus,
+    ) -> Result<bool> {
+        let (reply, rx) = oneshot::channel();
+        let action = Action::NotifyContentStatus {
+            namespace,
+            hash,
+            status,
+            reply,
+        };
+        self.send(action).await?;
+        rx.await?
+    }
+
+    /// Store the manifest of chunked entries of a namespace, once its blob is available.
+    ///
+    /// See [`Replica::put_content_chunks`]. The replica does not have to be open, see
+    /// [`Store::put_content_chunks`].
+    pub async fn put_content_chunks(
+        &self,
+        namespace: NamespaceId,
+        manifest: Bytes,
+    ) -> Result<usize> {
+        let (reply, rx) = oneshot::channel();
+        let action = Action::PutContentChunks {
+            namespace,
+            manifest,
+            reply,
+        };
+        self.send(action).await?;
+        rx.await?
+    }
+
+    /// Store the bytes of namespace metadata, once its blob is available.
+    ///
+    /// See [`Store::put_namespace_meta`]. The replica does not have to be open.
+    pub async fn put_namespace_meta(&self, namespace: NamespaceId, meta: Bytes) -> Result<usize> {
+        let (reply, rx) = oneshot::channel();
+        let action = Action::PutNamespaceMeta {
+            namespace,
+            meta,
+            reply,
+        };
+        self.send(action).await?;
+        rx.await?
+    }
+
     async fn send(&self, action: Action) -> Result<()> {
         self.tx
             .send(action)
@@ -577,6 +1013,96 @@ impl SyncHandle {
     }
 }
 
+/// Blocking wrapper around a [`SyncHandle`] for callers outside of an async runtime.
+///
+/// Each method sends a message to the actor and blocks the current thread until the reply
+/// arrives. Calling these methods from the actor thread itself would deadlock, so they return
+/// an error in that case instead.
+#[derive(Debug, Clone)]
+pub struct SyncHandleBlocking {
+    handle: SyncHandle,
+    actor_thread: Option<ThreadId>,
+}
+
+#[allow(missing_docs)]
+impl SyncHandleBlocking {
+    /// Get the underlying async [`SyncHandle`].
+    pub fn handle(&self) -> &SyncHandle {
+        &self.handle
+    }
+
+    pub fn open(&self, namespace: NamespaceId, opts: OpenOpts) -> Result<()> {
+        if opts.timeout.is_none() {
+            return self.block_on(self.handle.open(namespace, opts));
+        }
+        // the timeout needs a tokio timer, which is only available within a runtime.
+        if tokio::runtime::Handle::try_current().is_ok() {
+            self.block_on(self.handle.open(namespace, opts))
+        } else {
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()?;
+            self.block_on(async { rt.block_on(self.handle.open(namespace, opts)) })
+        }
+    }
+
+    pub fn close(&self, namespace: NamespaceId) -> Result<bool> {
+        self.block_on(self.handle.close(namespace))
+    }
+
+    pub fn insert_local(
+        &self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        key: Bytes,
+        hash: Hash,
+        len: u64,
+    ) -> Result<()> {
+        self.block_on(self.handle.insert_local(namespace, author, key, hash, len))
+    }
+
+    pub fn get_exact(
+        &self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        key: Bytes,
+        include_empty: bool,
+    ) -> Result<Option<SignedEntry>> {
+        self.block_on(self.handle.get_exact(namespace, author, key, include_empty))
+    }
+
+    pub fn list_authors(&self) -> Result<Vec<AuthorId>> {
+        let (tx, rx) = async_channel::bounded(64);
+        self.block_on(self.handle.list_authors(tx))?;
+        let mut authors = Vec::new();
+        while let Ok(author) = rx.recv_blocking() {
+            authors.push(author?);
+        }
+        Ok(authors)
+    }
+
+    pub fn import_author(&self, author: Author) -> Result<AuthorId> {
+        self.block_on(self.handle.import_author(author))
+    }
+
+    pub fn import_namespace(&self, capability: Capability) -> Result<NamespaceId> {
+        self.block_on(self.handle.import_namespace(capability))
+    }
+
+    pub fn flush_store(&self) -> Result<()> {
+        self.block_on(self.handle.flush_store())
+    }
+
+    fn block_on<T>(&self, fut: impl std::future::Future<Output = Result<T>>) -> Result<T> {
+        if self.actor_thread == Some(std::thread::current().id()) {
+            return Err(anyhow!(
+                "blocking calls into the sync actor must not be made from the actor thread"
+            ));
+        }
+        futures_lite::future::block_on(fut)
+    }
+}
+
 impl Drop for SyncHandle {
     fn drop(&mut self) {
         // this means we're dropping the last reference
@@ -612,16 +1138,27 @@ impl Actor {
     }
 
     async fn run_async(mut self) {
+        let mut auto_flush = self
+            .store
+            .auto_flush()
+            .every_interval
+            .map(tokio::time::interval);
         let reply = loop {
             let timeout = tokio::time::sleep(MAX_COMMIT_DELAY);
             tokio::pin!(timeout);
             let action = tokio::select! {
                 _ = &mut timeout => {
-                    if let Err(cause) = self.store.flush() {
+                    if let Err(cause) = self.store.flush_with_timeout(FLUSH_TIMEOUT) {
                         error!(?cause, "failed to flush store");
                     }
                     continue;
                 }
+                _ = async { auto_flush.as_mut().expect("checked").tick().await }, if auto_flush.is_some() => {
+                    if let Err(cause) = self.store.flush_with_timeout(FLUSH_TIMEOUT) {
+                        error!(?cause, "failed to auto flush store");
+                    }
+                    continue;
+                }
                 action = self.action_rx.recv() => {
                     match action {
                         Ok(action) => action,
@@ -647,7 +1184,7 @@ impl Actor {
             }
         };
 
-        if let Err(cause) = self.store.flush() {
+        if let Err(cause) = self.store.flush_with_timeout(FLUSH_TIMEOUT) {
             warn!(?cause, "failed to flush store");
         }
         self.close_all();
@@ -683,6 +1220,14 @@ impl Actor {
                 }
                 Ok(id)
             }),
+            Action::SubscribeAll { sender, reply } => {
+                self.store.subscribe_all(sender);
+                send_reply(reply, Ok(()))
+            }
+            Action::UnsubscribeAll { sender, reply } => {
+                self.store.unsubscribe_all(&sender);
+                send_reply(reply, Ok(()))
+            }
             Action::ListAuthors { reply } => {
                 let iter = self
                     .store
@@ -701,7 +1246,41 @@ impl Actor {
             Action::ContentHashes { reply } => {
                 send_reply_with(reply, self, |this| this.store.content_hashes())
             }
-            Action::FlushStore { reply } => send_reply(reply, self.store.flush()),
+            Action::FlushStore { reply } => {
+                send_reply(reply, self.store.flush_with_timeout(FLUSH_TIMEOUT))
+            }
+            Action::NotifyContentStatus {
+                namespace,
+                hash,
+                status,
+                reply,
+            } => send_reply_with(reply, self, |this| {
+                if this.states.is_open(&namespace) {
+                    let mut replica = this.states.replica(namespace, &mut this.store)?;
+                    Ok(replica.notify_content_status(hash, status))
+                } else {
+                    Ok(this.store.notify_content_status(namespace, hash, status))
+                }
+            }),
+            Action::PutContentChunks {
+                namespace,
+                manifest,
+                reply,
+            } => send_reply_with(reply, self, |this| {
+                if this.states.is_open(&namespace) {
+                    let mut replica = this.states.replica(namespace, &mut this.store)?;
+                    replica.put_content_chunks(&manifest)
+                } else {
+                    this.store.put_content_chunks(namespace, &manifest)
+                }
+            }),
+            Action::PutNamespaceMeta {
+                namespace,
+                meta,
+                reply,
+            } => send_reply_with(reply, self, |this| {
+                this.store.put_namespace_meta(namespace, &meta)
+            }),
             Action::Replica(namespace, action) => self.on_replica_action(namespace, action),
         }
     }
@@ -712,11 +1291,32 @@ impl Actor {
         action: ReplicaAction,
     ) -> Result<(), SendReplyError> {
         match action {
-            ReplicaAction::Open { reply, opts } => {
+            ReplicaAction::Open {
+                reply,
+                opts,
+                deadline,
+            } => {
+                // the caller gave up waiting, don't open the replica on their behalf.
+                if reply.is_closed() || deadline.is_some_and(|deadline| Instant::now() >= deadline)
+                {
+                    debug!(namespace = %namespace.fmt_short(), "open timed out");
+                    reply.send(Err(OpenError::Timeout.into())).ok();
+                    return Ok(());
+                }
                 tracing::trace!("open in");
                 let res = self.open(namespace, opts);
                 tracing::trace!("open out");
-                send_reply(reply, res)
+                let opened = res.is_ok();
+                if let Err(_err) = reply.send(res) {
+                    // the caller timed out while we were opening, so nobody will close it.
+                    if opened {
+                        self.close(namespace);
+                    }
+                    if deadline.is_none() {
+                        return Err(SendReplyError);
+                    }
+                }
+                Ok(())
             }
             ReplicaAction::Close { reply } => {
                 let res = self.close(namespace);
@@ -729,6 +1329,33 @@ impl Actor {
                 state.info.subscribe(sender);
                 Ok(())
             }),
+            ReplicaAction::SubscribeWithReplay { sender, n, reply } => {
+                send_reply_with(reply, self, |this| {
+                    let state = this.states.get_mut(&namespace)?;
+                    state.info.subscribe_with_replay(sender, n);
+                    Ok(())
+                })
+            }
+            ReplicaAction::SubscribeWithSnapshot {
+                query,
+                sender,
+                reply,
+            } => send_reply_with(reply, self, |this| {
+                let state = this.states.get_mut(&namespace)?;
+                let entries = this
+                    .store
+                    .get_many(namespace, query)?
+                    .collect::<Result<Vec<_>>>()?;
+                state.info.subscribe(sender);
+                Ok(entries)
+            }),
+            ReplicaAction::SetEventReplayCapacity { capacity, reply } => {
+                send_reply_with(reply, self, |this| {
+                    let state = this.states.get_mut(&namespace)?;
+                    state.info.set_event_replay_capacity(capacity);
+                    Ok(())
+                })
+            }
             ReplicaAction::Unsubscribe { sender, reply } => send_reply_with(reply, self, |this| {
                 let state = this.states.get_mut(&namespace)?;
                 state.info.unsubscribe(&sender);
@@ -740,6 +1367,35 @@ impl Actor {
                 state.sync = sync;
                 Ok(())
             }),
+            ReplicaAction::SetSyncEventMode { mode, reply } => {
+                send_reply_with(reply, self, |this| {
+                    let state = this.states.get_mut(&namespace)?;
+                    state.info.set_sync_event_mode(mode);
+                    Ok(())
+                })
+            }
+            ReplicaAction::SetMaxContentLen {
+                max_content_len,
+                reply,
+            } => send_reply_with(reply, self, |this| {
+                let state = this.states.get_mut(&namespace)?;
+                state.info.set_max_content_len(max_content_len);
+                Ok(())
+            }),
+            ReplicaAction::EnableReadCache { capacity, reply } => {
+                send_reply_with(reply, self, |this| {
+                    let state = this.states.get_mut(&namespace)?;
+                    state.info.enable_read_cache(capacity);
+                    Ok(())
+                })
+            }
+            ReplicaAction::SyncBatchApplied { count, reply } => {
+                send_reply_with(reply, self, move |this| {
+                    let mut replica = this.states.replica(namespace, &mut this.store)?;
+                    replica.sync_batch_applied(count);
+                    Ok(())
+                })
+            }
             ReplicaAction::InsertLocal {
                 author,
                 key,
@@ -752,6 +1408,18 @@ impl Actor {
                 replica.insert(&key, &author, hash, len)?;
                 Ok(())
             }),
+            ReplicaAction::InsertLocalMany {
+                author,
+                entries,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                let author = get_author(&mut this.store, &author)?;
+                let mut replica = this.states.replica(namespace, &mut this.store)?;
+                for (key, hash, len) in entries {
+                    replica.insert(&key, &author, hash, len)?;
+                }
+                Ok(())
+            }),
             ReplicaAction::DeletePrefix { author, key, reply } => {
                 send_reply_with(reply, self, |this| {
                     let author = get_author(&mut this.store, &author)?;
@@ -760,6 +1428,27 @@ impl Actor {
                     Ok(res)
                 })
             }
+            ReplicaAction::InsertChunked {
+                author,
+                key,
+                chunks,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                let author = get_author(&mut this.store, &author)?;
+                let mut replica = this.states.replica(namespace, &mut this.store)?;
+                replica.insert_chunked(&key, &author, chunks)?;
+                Ok(())
+            }),
+            ReplicaAction::SetNamespaceMeta {
+                author,
+                meta,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                let author = get_author(&mut this.store, &author)?;
+                let mut replica = this.states.replica(namespace, &mut this.store)?;
+                replica.set_namespace_meta(&author, meta)?;
+                Ok(())
+            }),
             ReplicaAction::InsertRemote {
                 entry,
                 from,
@@ -809,8 +1498,20 @@ impl Actor {
                 include_empty,
                 reply,
             } => send_reply_with(reply, self, move |this| {
+                let mut replica = this.states.replica(namespace, &mut this.store)?;
+                replica.get_exact(author, key, include_empty)
+            }),
+            ReplicaAction::GetExactMany {
+                keys,
+                include_empty,
+                reply,
+            } => send_reply_with(reply, self, move |this| {
+                this.states.ensure_open(&namespace)?;
+                this.store.get_exact_many(namespace, keys, include_empty)
+            }),
+            ReplicaAction::GetNamespaceMeta { reply } => send_reply_with(reply, self, |this| {
                 this.states.ensure_open(&namespace)?;
-                this.store.get_exact(namespace, author, key, include_empty)
+                this.store.get_namespace_meta(namespace)
             }),
             ReplicaAction::GetMany { query, reply } => {
                 let iter = self
@@ -843,6 +1544,9 @@ impl Actor {
                     subscribers,
                 })
             }),
+            ReplicaAction::AuthorHeads { reply } => {
+                send_reply(reply, self.store.author_heads(namespace))
+            }
             ReplicaAction::HasNewsForUs { heads, reply } => {
                 let res = self.store.has_news_for_us(namespace, &heads);
                 send_reply(reply, res)
@@ -937,6 +1641,9 @@ impl OpenReplicas {
                 if let Some(sender) = opts.subscribe {
                     info.subscribe(sender);
                 }
+                if let Some(sender) = opts.subscribe_internal {
+                    info.subscribe_internal(sender);
+                }
                 debug!(namespace = %namespace.fmt_short(), "open");
                 let state = OpenReplica {
                     info,
@@ -952,6 +1659,9 @@ impl OpenReplicas {
                 if let Some(sender) = opts.subscribe {
                     state.info.subscribe(sender);
                 }
+                if let Some(sender) = opts.subscribe_internal {
+                    state.info.subscribe_internal(sender);
+                }
             }
         }
         Ok(())
@@ -966,7 +1676,8 @@ impl OpenReplicas {
                 let state = e.get_mut();
                 state.handles = state.handles.wrapping_sub(1);
                 if state.handles == 0 {
-                    let _ = e.remove_entry();
+                    let (_, mut state) = e.remove_entry();
+                    state.info.close();
                     debug!(namespace = %namespace.fmt_short(), "close");
                     true
                 } else {
@@ -977,7 +1688,10 @@ impl OpenReplicas {
     }
 
     fn close_all(&mut self) -> impl Iterator<Item = NamespaceId> + '_ {
-        self.0.drain().map(|(n, _s)| n)
+        self.0.drain().map(|(n, mut s)| {
+            s.info.close();
+            n
+        })
     }
 }
 
@@ -1007,6 +1721,75 @@ fn send_reply<T>(sender: oneshot::Sender<T>, value: T) -> Result<(), SendReplyEr
     sender.send(value).map_err(send_reply_error)
 }
 
+/// Forwards `events` to `sender`, coalescing insert events of the same entry within `window`.
+///
+/// See [`SyncHandle::subscribe_coalesced`].
+async fn coalesce_events(
+    events: async_channel::Receiver<Event>,
+    sender: async_channel::Sender<Event>,
+    window: Duration,
+) {
+    let mut pending = HashMap::new();
+    // deadlines are pushed in order, so the front always expires first
+    let mut deadlines: VecDeque<(tokio::time::Instant, RecordIdentifier)> = VecDeque::new();
+    loop {
+        let next_deadline = deadlines.front().map(|(deadline, _)| *deadline);
+        let event = tokio::select! {
+            event = events.recv() => event.ok(),
+            _ = tokio::time::sleep_until(next_deadline.unwrap_or_else(tokio::time::Instant::now)),
+                if next_deadline.is_some() =>
+            {
+                let (_, id) = deadlines.pop_front().expect("checked above");
+                let event = pending.remove(&id).expect("pending events have a deadline");
+                if sender.send(event).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+        let Some(event) = event else {
+            // the replica was closed or we were unsubscribed
+            flush_coalesced(&mut pending, &mut deadlines, &sender).await;
+            return;
+        };
+        let id = match &event {
+            Event::LocalInsert { entry, .. } | Event::RemoteInsert { entry, .. } => {
+                Some(entry.id().clone())
+            }
+            _ => None,
+        };
+        match id {
+            Some(id) => {
+                if pending.insert(id.clone(), event).is_none() {
+                    deadlines.push_back((tokio::time::Instant::now() + window, id));
+                }
+            }
+            None => {
+                if !flush_coalesced(&mut pending, &mut deadlines, &sender).await
+                    || sender.send(event).await.is_err()
+                {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Sends all pending events in order. Returns `false` if the receiver was dropped.
+async fn flush_coalesced(
+    pending: &mut HashMap<RecordIdentifier, Event>,
+    deadlines: &mut VecDeque<(tokio::time::Instant, RecordIdentifier)>,
+    sender: &async_channel::Sender<Event>,
+) -> bool {
+    for (_, id) in deadlines.drain(..) {
+        let event = pending.remove(&id).expect("pending events have a deadline");
+        if sender.send(event).await.is_err() {
+            return false;
+        }
+    }
+    true
+}
+
 fn send_reply_with<T>(
     sender: oneshot::Sender<Result<T>>,
     this: &mut Actor,
@@ -1034,7 +1817,360 @@ mod tests {
         let (tx, rx) = async_channel::bounded(10);
         sync.subscribe(id, tx).await?;
         sync.close(id).await?;
+        assert!(matches!(
+            rx.recv().await,
+            Ok(Event::Closed { namespace }) if namespace == id
+        ));
+        assert!(rx.recv().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn notify_content_status() -> anyhow::Result<()> {
+        let store = store::Store::memory();
+        let sync = SyncHandle::spawn(store, None, "foo".into());
+        let namespace = NamespaceSecret::new(&mut rand::rngs::OsRng {});
+        let id = namespace.id();
+        let hash = Hash::new(b"hello");
+        sync.import_namespace(namespace.into()).await?;
+        let (all_tx, all_rx) = async_channel::bounded(10);
+        sync.subscribe_all(all_tx).await?;
+
+        // without an open replica, only the subscribers of all replicas are notified
+        assert!(
+            sync.notify_content_status(id, hash, ContentStatus::Missing)
+                .await?
+        );
+        assert!(matches!(
+            all_rx.recv().await?,
+            (
+                _,
+                Event::ContentStatusChanged {
+                    status: ContentStatus::Missing,
+                    ..
+                }
+            )
+        ));
+
+        // the subscribers of an open replica are notified as well
+        let (tx, rx) = async_channel::bounded(10);
+        sync.open(id, OpenOpts::default().subscribe(tx)).await?;
+        assert!(
+            sync.notify_content_status(id, hash, ContentStatus::Complete)
+                .await?
+        );
+        assert!(matches!(
+            rx.recv().await?,
+            Event::ContentStatusChanged { namespace, hash: h, status: ContentStatus::Complete }
+                if namespace == id && h == hash
+        ));
+        assert!(matches!(
+            all_rx.recv().await?,
+            (
+                _,
+                Event::ContentStatusChanged {
+                    status: ContentStatus::Complete,
+                    ..
+                }
+            )
+        ));
+
+        // an unchanged status is not emitted again
+        assert!(
+            !sync
+                .notify_content_status(id, hash, ContentStatus::Complete)
+                .await?
+        );
+        assert!(rx.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn set_namespace_meta() -> anyhow::Result<()> {
+        let store = store::Store::memory();
+        let sync = SyncHandle::spawn(store, None, "foo".into());
+        let mut rng = rand::rngs::OsRng {};
+        let id = sync
+            .import_namespace(NamespaceSecret::new(&mut rng).into())
+            .await?;
+        let author = sync.import_author(Author::new(&mut rng)).await?;
+        let (tx, rx) = async_channel::bounded(10);
+        sync.open(id, OpenOpts::default().subscribe(tx)).await?;
+
+        let meta = Bytes::from_static(b"title");
+        sync.set_namespace_meta(id, author, meta.clone()).await?;
+        // subscribers of the replica see the metadata entry
+        assert!(matches!(
+            rx.recv().await,
+            Ok(Event::LocalInsert { entry, .. })
+                if entry.key() == crate::sync::NAMESPACE_META_KEY
+                    && entry.content_hash() == Hash::new(&meta)
+        ));
+        assert_eq!(sync.get_namespace_meta(id).await?, Some(meta));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_timeout() -> anyhow::Result<()> {
+        let store = store::Store::memory();
+        let sync = SyncHandle::spawn(store, None, "foo".into());
+        let mut rng = rand::rngs::OsRng {};
+        let busy = sync
+            .import_namespace(NamespaceSecret::new(&mut rng).into())
+            .await?;
+        let other = sync
+            .import_namespace(NamespaceSecret::new(&mut rng).into())
+            .await?;
+        let author = sync.import_author(Author::new(&mut rng)).await?;
+
+        // keep the actor busy: events are sent blockingly to subscribers, so the second
+        // insert blocks the actor until the subscriber makes room.
+        let (tx, rx) = async_channel::bounded(1);
+        sync.open(busy, OpenOpts::default().subscribe(tx)).await?;
+        let hash = Hash::new(b"hello");
+        sync.insert_local(busy, author, Bytes::from_static(b"a"), hash, 5)
+            .await?;
+        let blocked = tokio::spawn({
+            let sync = sync.clone();
+            async move {
+                sync.insert_local(busy, author, Bytes::from_static(b"b"), hash, 5)
+                    .await
+            }
+        });
+        // give the insert time to reach the actor.
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let opts = OpenOpts::default().timeout(Duration::from_millis(100));
+        let res = tokio::time::timeout(Duration::from_secs(5), sync.open(other, opts)).await?;
+        let err = res.expect_err("open timed out");
+        assert!(matches!(
+            err.downcast_ref::<OpenError>(),
+            Some(OpenError::Timeout)
+        ));
+
+        // unblock the actor. the timed out open must not leave the replica open.
+        rx.recv().await?;
+        rx.recv().await?;
+        blocked.await??;
+        assert!(sync.get_state(other).await.is_err());
+
+        // without contention, opening succeeds within the timeout.
+        let opts = OpenOpts::default().timeout(Duration::from_secs(5));
+        sync.open(other, opts).await?;
+        assert!(sync.get_state(other).await.is_ok());
+        Ok(())
+    }
+
+    #[test]
+    fn blocking_handle() -> anyhow::Result<()> {
+        let store = store::Store::memory();
+        let sync = SyncHandle::spawn(store, None, "foo".into()).blocking();
+        std::thread::spawn(move || -> anyhow::Result<()> {
+            let mut rng = rand::rngs::OsRng {};
+            let namespace = NamespaceSecret::new(&mut rng);
+            let id = sync.import_namespace(namespace.into())?;
+            let author = sync.import_author(Author::new(&mut rng))?;
+            assert_eq!(sync.list_authors()?, vec![author]);
+            sync.open(id, Default::default())?;
+            let hash = Hash::new(b"hello");
+            sync.insert_local(id, author, Bytes::from_static(b"key"), hash, 5)?;
+            let entry = sync
+                .get_exact(id, author, Bytes::from_static(b"key"), false)?
+                .expect("entry exists");
+            assert_eq!(entry.content_hash(), hash);
+            sync.flush_store()?;
+            assert!(sync.close(id)?);
+            Ok(())
+        })
+        .join()
+        .expect("thread panicked")
+    }
+
+    #[tokio::test]
+    async fn auto_flush_interval() -> anyhow::Result<()> {
+        let mut store = store::Store::memory();
+        store.set_auto_flush(store::fs::AutoFlush {
+            every_interval: Some(Duration::from_millis(50)),
+            ..Default::default()
+        });
+        let mut rng = rand::rngs::OsRng {};
+        let namespace = NamespaceSecret::new(&mut rng);
+        let id = namespace.id();
+        let author = Author::new(&mut rng);
+        store.import_namespace(namespace.into())?;
+        store.import_author(author.clone())?;
+        // reads through the handle only see committed data
+        let reader = store.read_handle(id)?;
+        let sync = SyncHandle::spawn(store, None, "foo".into());
+        sync.open(id, Default::default()).await?;
+        let hash = Hash::new(b"hello");
+        sync.insert_local(id, author.id(), Bytes::from_static(b"key"), hash, 5)
+            .await?;
+        // well below the delay after which an idle actor flushes the store
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        let entry = reader
+            .get_exact(author.id(), b"key", false)?
+            .expect("entry is committed");
+        assert_eq!(entry.content_hash(), hash);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn blocking_handle_fails_on_actor_thread() -> anyhow::Result<()> {
+        let store = store::Store::memory();
+        let sync = SyncHandle::spawn(store, None, "foo".into());
+        let mut blocking = sync.blocking();
+        // pretend to be the actor thread
+        blocking.actor_thread = Some(std::thread::current().id());
+        assert!(blocking.flush_store().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn close_sends_closed_event_on_last_handle() -> anyhow::Result<()> {
+        let store = store::Store::memory();
+        let sync = SyncHandle::spawn(store, None, "foo".into());
+        let namespace = NamespaceSecret::new(&mut rand::rngs::OsRng {});
+        let id = namespace.id();
+        sync.import_namespace(namespace.into()).await?;
+        sync.open(id, Default::default()).await?;
+        sync.open(id, Default::default()).await?;
+        let (tx, rx) = async_channel::bounded(10);
+        sync.subscribe(id, tx).await?;
+        // the replica stays open while a handle is left
+        assert!(!sync.close(id).await?);
+        assert!(rx.try_recv().is_err());
+        assert!(!rx.is_closed());
+        assert!(sync.close(id).await?);
+        assert!(matches!(
+            rx.recv().await,
+            Ok(Event::Closed { namespace }) if namespace == id
+        ));
         assert!(rx.recv().await.is_err());
         Ok(())
     }
+
+    #[tokio::test]
+    async fn subscribe_coalesced() -> anyhow::Result<()> {
+        let store = store::Store::memory();
+        let sync = SyncHandle::spawn(store, None, "foo".into());
+        let mut rng = rand::rngs::OsRng {};
+        let namespace = NamespaceSecret::new(&mut rng);
+        let author = Author::new(&mut rng);
+        let id = namespace.id();
+        sync.import_namespace(namespace.into()).await?;
+        sync.import_author(author.clone()).await?;
+        sync.open(id, Default::default()).await?;
+
+        let (tx, rx) = async_channel::bounded(64);
+        sync.subscribe(id, tx).await?;
+        let (coalesced_tx, coalesced_rx) = async_channel::bounded(64);
+        sync.subscribe_coalesced(id, coalesced_tx, Duration::from_millis(200))
+            .await?;
+
+        let key = Bytes::from_static(b"cursor");
+        for i in 0..20u64 {
+            let hash = Hash::new(i.to_be_bytes());
+            sync.insert_local(id, author.id(), key.clone(), hash, 8)
+                .await?;
+        }
+        let final_hash = Hash::new(19u64.to_be_bytes());
+
+        // the latest value is delivered once the window expired
+        let mut coalesced = vec![];
+        while coalesced.last() != Some(&final_hash) {
+            let event = tokio::time::timeout(Duration::from_secs(5), coalesced_rx.recv()).await??;
+            if let Event::LocalInsert { entry, .. } = event {
+                coalesced.push(entry.content_hash());
+            }
+        }
+        assert!(coalesced.len() < 20);
+
+        sync.close(id).await?;
+        let mut all = vec![];
+        while let Ok(event) = rx.recv().await {
+            if let Event::LocalInsert { entry, .. } = event {
+                all.push(entry.content_hash());
+            }
+        }
+        assert_eq!(all.len(), 20);
+        assert_eq!(all.last(), Some(&final_hash));
+        assert!(matches!(
+            coalesced_rx.recv().await,
+            Ok(Event::Closed { namespace }) if namespace == id
+        ));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn subscribe_with_snapshot() -> anyhow::Result<()> {
+        let store = store::Store::memory();
+        let sync = SyncHandle::spawn(store, None, "foo".into());
+        let mut rng = rand::rngs::OsRng {};
+        let namespace = NamespaceSecret::new(&mut rng);
+        let author = Author::new(&mut rng);
+        let id = namespace.id();
+        sync.import_namespace(namespace.into()).await?;
+        sync.import_author(author.clone()).await?;
+        sync.open(id, Default::default()).await?;
+
+        // write continuously, and keep writing for a while after the subscription
+        let subscribed = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let (started_tx, started_rx) = oneshot::channel();
+        let writer = tokio::task::spawn({
+            let sync = sync.clone();
+            let subscribed = subscribed.clone();
+            let author = author.id();
+            async move {
+                let mut started_tx = Some(started_tx);
+                let mut after = 0;
+                let mut i = 0u64;
+                while after < 20 {
+                    let key = Bytes::from(format!("k{i}"));
+                    sync.insert_local(id, author, key, Hash::new(i.to_be_bytes()), 8)
+                        .await?;
+                    i += 1;
+                    if i == 10 {
+                        started_tx.take().unwrap().send(()).ok();
+                    }
+                    if subscribed.load(std::sync::atomic::Ordering::SeqCst) {
+                        after += 1;
+                    }
+                }
+                anyhow::Ok(i)
+            }
+        });
+
+        started_rx.await?;
+        let (tx, rx) = async_channel::unbounded();
+        let snapshot = sync
+            .subscribe_with_snapshot(id, Query::all().build(), tx)
+            .await?;
+        subscribed.store(true, std::sync::atomic::Ordering::SeqCst);
+        let count = writer.await??;
+
+        sync.close(id).await?;
+        let mut events = vec![];
+        while let Ok(event) = rx.recv().await {
+            if let Event::LocalInsert { entry, .. } = event {
+                events.push(entry.key().to_vec());
+            }
+        }
+        assert!(snapshot.len() >= 10);
+        assert!(events.len() >= 20);
+
+        // every entry is either in the snapshot or delivered as event, exactly once
+        let mut keys = snapshot
+            .iter()
+            .map(|entry| entry.key().to_vec())
+            .chain(events)
+            .collect::<Vec<_>>();
+        keys.sort();
+        let mut expected = (0..count)
+            .map(|i| format!("k{i}").into_bytes())
+            .collect::<Vec<_>>();
+        expected.sort();
+        assert_eq!(keys, expected);
+        Ok(())
+    }
 }
@@ -296,9 +296,9 @@ pub enum DlPolicyCmd {
         /// Add an exception to the download policy.
         /// An exception must be formatted as `<matching_kind>:<encoding>:<pattern>`.
         ///
-        /// - <matching_kind> can be either `prefix` or `exact`.
+        /// - <matching_kind> can be either `prefix`, `exact` or `glob`.
         ///
-        /// - `<encoding>` can be either `utf8` or `hex`.
+        /// - `<encoding>` can be either `utf8` or `hex`. Glob patterns must be valid utf8.
         #[clap(short, long, value_name = "matching_kind>:<encoding>:<pattern")]
         except: Vec<FilterKind>,
     },
@@ -851,6 +851,12 @@ impl DocCommands {
                         LiveEvent::PendingContentReady => {
                             println!("all pending content is now ready")
                         }
+                        LiveEvent::SyncBatchApplied { count } => {
+                            println!("sync inserted {count} entries")
+                        }
+                        LiveEvent::ContentStatusChanged { hash, status } => {
+                            println!("content {} is now {status:?}", fmt_short(hash.as_bytes()))
+                        }
                     }
                 }
             }
@@ -885,23 +891,7 @@ impl DocCommands {
             Self::DlPolicy(DlPolicyCmd::Get { doc }) => {
                 let doc = get_doc(docs, env, doc).await?;
                 match doc.get_download_policy().await {
-                    Ok(dl_policy) => {
-                        let (kind, exceptions) = match dl_policy {
-                            DownloadPolicy::NothingExcept(exceptions) => {
-                                (FetchKind::Nothing, exceptions)
-                            }
-                            DownloadPolicy::EverythingExcept(exceptions) => {
-                                (FetchKind::Everything, exceptions)
-                            }
-                        };
-                        println!("Download {kind} in this document.");
-                        if !exceptions.is_empty() {
-                            println!("Exceptions:");
-                            for exception in exceptions {
-                                println!("{exception}")
-                            }
-                        }
-                    }
+                    Ok(dl_policy) => print_download_policy(dl_policy),
                     Err(x) => {
                         println!("Could not get the document's download policy: {x}")
                     }
@@ -912,6 +902,30 @@ impl DocCommands {
     }
 }
 
+/// Prints a download policy, including its prefix overrides.
+fn print_download_policy(dl_policy: DownloadPolicy) {
+    let (kind, exceptions) = match dl_policy {
+        DownloadPolicy::NothingExcept(exceptions) => (FetchKind::Nothing, exceptions),
+        DownloadPolicy::EverythingExcept(exceptions) => (FetchKind::Everything, exceptions),
+        DownloadPolicy::WithOverrides { base, overrides } => {
+            print_download_policy(*base);
+            println!("Overrides:");
+            for rule in overrides {
+                let action = if rule.download { "download" } else { "skip" };
+                println!("{action} {}", FilterKind::Prefix(rule.prefix));
+            }
+            return;
+        }
+    };
+    println!("Download {kind} in this document.");
+    if !exceptions.is_empty() {
+        println!("Exceptions:");
+        for exception in exceptions {
+            println!("{exception}")
+        }
+    }
+}
+
 /// Gets the document given the client, the environment (and maybe the [`crate::keys::NamespaceId`]).
 async fn get_doc(
     docs: &docs::Client,
@@ -13,7 +13,12 @@ use crate::{cli::fmt_short, Author, AuthorId};
 #[derive(Debug, Clone, Parser)]
 pub enum AuthorCommands {
     /// Set the active author (Note: only works within the Iroh console).
-    Switch { author: AuthorId },
+    ///
+    /// Accepts both the checksummed form printed by `author list` and a hex encoded author id.
+    Switch {
+        #[clap(value_parser = parse_author_id)]
+        author: AuthorId,
+    },
     /// Create a new author.
     Create {
         /// Switch to the created author (Note: only works in the Iroh console).
@@ -48,7 +53,7 @@ impl AuthorCommands {
             Self::List => {
                 let mut stream = authors.list().await?;
                 while let Some(author_id) = stream.try_next().await? {
-                    println!("{}", author_id);
+                    println!("{}", author_id.to_checked_string());
                 }
             }
             Self::Default { switch } => {
@@ -56,7 +61,7 @@ impl AuthorCommands {
                     bail!("The --switch flag is only supported within the Iroh console.");
                 }
                 let author_id = authors.default().await?;
-                println!("{}", author_id);
+                println!("{}", author_id.to_checked_string());
                 if switch {
                     env.set_author(author_id)?;
                     println!("Active author is now {}", fmt_short(author_id.as_bytes()));
@@ -68,7 +73,7 @@ impl AuthorCommands {
                 }
 
                 let author_id = authors.create().await?;
-                println!("{}", author_id);
+                println!("{}", author_id.to_checked_string());
 
                 if switch {
                     env.set_author(author_id)?;
@@ -101,3 +106,11 @@ impl AuthorCommands {
         Ok(())
     }
 }
+
+/// Parses an [`AuthorId`] from either its checksummed form or its hex encoding.
+fn parse_author_id(s: &str) -> Result<AuthorId> {
+    match AuthorId::from_checked_string(s) {
+        Ok(author) => Ok(author),
+        Err(err) => AuthorId::from_str(s).map_err(|_| err),
+    }
+}
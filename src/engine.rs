@@ -24,11 +24,13 @@ use tracing::{error, error_span, Instrument};
 
 use self::live::{LiveActor, ToLiveActor};
 pub use self::{
-    live::SyncEvent,
-    state::{Origin, SyncReason},
+    gossip::GossipDedupConfig,
+    live::{SyncEvent, SyncFailureObserver, SyncFailureReason},
+    state::{HeadsGossip, Origin, RetryPolicy, SyncReason, SyncSchedule},
 };
 use crate::{
-    actor::SyncHandle, Author, AuthorId, ContentStatus, ContentStatusCallback, Entry, NamespaceId,
+    actor::SyncHandle, net::AcceptPolicy, sync::entry_content_status, Author, AuthorId,
+    ContentChunks, ContentStatus, ContentStatusCallback, Entry, NamespaceId,
 };
 
 mod gossip;
@@ -76,10 +78,7 @@ impl<D: iroh_blobs::store::Store> Engine<D> {
         let (live_actor_tx, to_live_actor_recv) = mpsc::channel(ACTOR_CHANNEL_CAP);
         let me = endpoint.node_id().fmt_short();
 
-        let content_status_cb = {
-            let bao_store = bao_store.clone();
-            Arc::new(move |hash| entry_to_content_status(bao_store.entry_status_sync(&hash)))
-        };
+        let content_status_cb = content_status_callback(bao_store.clone());
         let sync = SyncHandle::spawn(replica_store, Some(content_status_cb.clone()), me.clone());
 
         let actor = LiveActor::new(
@@ -88,6 +87,7 @@ impl<D: iroh_blobs::store::Store> Engine<D> {
             gossip.clone(),
             bao_store.clone(),
             downloader,
+            local_pool_handle.clone(),
             to_live_actor_recv,
             live_actor_tx.clone(),
         );
@@ -172,6 +172,91 @@ impl<D: iroh_blobs::store::Store> Engine<D> {
         Ok(())
     }
 
+    /// Sync several documents with a peer over a single connection.
+    ///
+    /// The documents are reconciled concurrently over one stream, see
+    /// [`crate::net::connect_and_sync_many`]. Documents which are not syncing or which are
+    /// already syncing with the peer are skipped. The peer has to accept connections on
+    /// [`crate::net::ALPN_MULTI`]. The results are reported as [`LiveEvent::SyncFinished`] for each
+    /// document.
+    pub async fn sync_many_with_peer(
+        &self,
+        peer: NodeAddr,
+        namespaces: Vec<NamespaceId>,
+    ) -> Result<()> {
+        let (reply, reply_rx) = oneshot::channel();
+        self.to_live_actor
+            .send(ToLiveActor::SyncMany {
+                peer,
+                namespaces,
+                reply,
+            })
+            .await?;
+        reply_rx.await??;
+        Ok(())
+    }
+
+    /// Insert a chunked entry into a document.
+    ///
+    /// The manifest of `chunks` is added to the blob store, so that peers can download it and
+    /// resolve the chunks of the entry, see [`crate::Replica::insert_chunked`]. The chunks
+    /// themselves have to be added to the blob store separately. Returns the hash of the manifest.
+    pub async fn insert_chunked(
+        &self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        key: impl Into<bytes::Bytes>,
+        chunks: ContentChunks,
+    ) -> Result<Hash> {
+        let manifest = chunks.to_manifest();
+        // keep the temp tag until the entry is inserted
+        let tag = self
+            .blob_store
+            .import_bytes(manifest.into(), iroh_blobs::BlobFormat::Raw)
+            .await?;
+        self.sync
+            .insert_chunked(namespace, author, key.into(), chunks)
+            .await?;
+        Ok(*tag.hash())
+    }
+
+    /// Set the metadata of a document, signed by `author`.
+    ///
+    /// The metadata is added to the blob store, so that peers can download it and read it with
+    /// [`crate::store::Store::get_namespace_meta`], see [`crate::Replica::set_namespace_meta`].
+    /// Returns the hash of the metadata blob.
+    pub async fn set_namespace_meta(
+        &self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        meta: impl Into<bytes::Bytes>,
+    ) -> Result<Hash> {
+        let meta = meta.into();
+        // keep the temp tag until the entry is inserted
+        let tag = self
+            .blob_store
+            .import_bytes(meta.clone(), iroh_blobs::BlobFormat::Raw)
+            .await?;
+        self.sync
+            .set_namespace_meta(namespace, author, meta)
+            .await?;
+        Ok(*tag.hash())
+    }
+
+    /// Set how events are emitted for entries inserted while syncing a document.
+    ///
+    /// With [`crate::SyncEventMode::Silent`], subscribers receive a single
+    /// [`LiveEvent::SyncBatchApplied`] per sync instead of an event for each entry. Content of the
+    /// entries is still downloaded. The document must be open, and the mode is reset when it is
+    /// closed.
+    pub async fn set_sync_event_mode(
+        &self,
+        namespace: NamespaceId,
+        mode: crate::SyncEventMode,
+    ) -> Result<()> {
+        self.sync.set_sync_event_mode(namespace, mode).await
+    }
+
     /// Stop the live sync for a document and leave the gossip swarm.
     ///
     /// If `kill_subscribers` is true, all existing event subscribers will be dropped. This means
@@ -189,6 +274,119 @@ impl<D: iroh_blobs::store::Store> Engine<D> {
         Ok(())
     }
 
+    /// Set the [`SyncSchedule`] for a document.
+    ///
+    /// The schedule controls how syncs triggered by changes from our neighbors are coalesced.
+    /// Passing `None` removes the schedule, after which such syncs are started right away.
+    pub async fn set_sync_schedule(
+        &self,
+        namespace: NamespaceId,
+        schedule: Option<SyncSchedule>,
+    ) -> Result<()> {
+        self.to_live_actor
+            .send(ToLiveActor::SetSyncSchedule {
+                namespace,
+                schedule,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Pause the live sync for a document.
+    ///
+    /// While paused, no set-reconciliation syncs are started or accepted and entries received
+    /// via gossip are dropped. The gossip subscription and the known peers are kept, so that
+    /// [`Self::resume_sync`] can catch up with them.
+    pub async fn pause_sync(&self, namespace: NamespaceId) -> Result<()> {
+        let (reply, reply_rx) = oneshot::channel();
+        self.to_live_actor
+            .send(ToLiveActor::PauseSync { namespace, reply })
+            .await?;
+        reply_rx.await??;
+        Ok(())
+    }
+
+    /// Resume the live sync for a document paused with [`Self::pause_sync`].
+    ///
+    /// This starts a sync with all peers known for the document. Does nothing if the document
+    /// is not paused.
+    pub async fn resume_sync(&self, namespace: NamespaceId) -> Result<()> {
+        let (reply, reply_rx) = oneshot::channel();
+        self.to_live_actor
+            .send(ToLiveActor::ResumeSync { namespace, reply })
+            .await?;
+        reply_rx.await??;
+        Ok(())
+    }
+
+    /// Limit the number of content downloads in flight per document.
+    ///
+    /// Downloads exceeding the limit are queued and started once running downloads of the same
+    /// document finished. Passing `None` removes the limit.
+    pub async fn set_max_concurrent_downloads(&self, max: Option<usize>) -> Result<()> {
+        self.to_live_actor
+            .send(ToLiveActor::SetMaxConcurrentDownloads { max })
+            .await?;
+        Ok(())
+    }
+
+    /// Set which namespaces incoming sync requests are accepted for.
+    ///
+    /// Requests for other namespaces are rejected with [`crate::net::AbortReason::NotAllowed`]
+    /// before reconciliation starts. By default, requests are accepted for all namespaces which
+    /// are open for sync.
+    pub async fn set_accept_policy(&self, policy: AcceptPolicy) -> Result<()> {
+        self.to_live_actor
+            .send(ToLiveActor::SetAcceptPolicy { policy })
+            .await?;
+        Ok(())
+    }
+
+    /// Set how entries received via gossip are deduplicated, see [`GossipDedupConfig`].
+    ///
+    /// Deduplication is enabled with the default config. Passing `None` disables it.
+    pub async fn set_gossip_dedup(&self, config: Option<GossipDedupConfig>) -> Result<()> {
+        self.to_live_actor
+            .send(ToLiveActor::SetGossipDedup { config })
+            .await?;
+        Ok(())
+    }
+
+    /// Set a callback which is invoked whenever a sync with a peer failed, or remove it with
+    /// `None`.
+    ///
+    /// This can be used to block peers which misbehave, see [`SyncFailureReason`].
+    pub async fn set_sync_failure_observer(
+        &self,
+        observer: Option<SyncFailureObserver>,
+    ) -> Result<()> {
+        self.to_live_actor
+            .send(ToLiveActor::SetSyncFailureObserver { observer })
+            .await?;
+        Ok(())
+    }
+
+    /// Set the [`RetryPolicy`] for failed syncs, or disable retries with `None`.
+    ///
+    /// By default, failed syncs are not retried.
+    pub async fn set_retry_policy(&self, policy: Option<RetryPolicy>) -> Result<()> {
+        self.to_live_actor
+            .send(ToLiveActor::SetRetryPolicy { policy })
+            .await?;
+        Ok(())
+    }
+
+    /// Enable the heads gossip mode with `config`, or disable it with `None`.
+    ///
+    /// See [`HeadsGossip`] for details. By default, heads gossip is disabled and a full sync is
+    /// started with each new neighbor.
+    pub async fn set_heads_gossip(&self, config: Option<HeadsGossip>) -> Result<()> {
+        self.to_live_actor
+            .send(ToLiveActor::SetHeadsGossip { config })
+            .await?;
+        Ok(())
+    }
+
     /// Subscribe to replica and sync progress events.
     pub async fn subscribe(
         &self,
@@ -204,7 +402,7 @@ impl<D: iroh_blobs::store::Store> Engine<D> {
         let a = {
             let (s, r) = async_channel::bounded(SUBSCRIBE_CHANNEL_CAP);
             this.sync.subscribe(namespace, s).await?;
-            Box::pin(r).map(move |ev| LiveEvent::from_replica_event(ev, &content_status_cb))
+            Box::pin(r).filter_map(move |ev| LiveEvent::from_replica_event(ev, &content_status_cb))
         };
 
         // Subscribe to events from the [`live::Actor`].
@@ -263,6 +461,22 @@ pub fn entry_to_content_status(entry: io::Result<EntryStatus>) -> ContentStatus
     }
 }
 
+/// Creates a [`ContentStatusCallback`] which looks up the status of content in a blob store.
+///
+/// Complete blobs are reported as [`ContentStatus::Complete`], partial blobs as
+/// [`ContentStatus::Incomplete`] and unknown blobs as [`ContentStatus::Missing`].
+pub fn content_status_callback<S: iroh_blobs::store::MapMut>(
+    blob_store: S,
+) -> ContentStatusCallback {
+    content_status_callback_with(move |hash| blob_store.entry_status_sync(hash))
+}
+
+fn content_status_callback_with(
+    entry_status: impl Fn(&Hash) -> io::Result<EntryStatus> + Send + Sync + 'static,
+) -> ContentStatusCallback {
+    Arc::new(move |hash| entry_to_content_status(entry_status(&hash)))
+}
+
 /// Events informing about actions of the live sync progress.
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq, strum::Display)]
 pub enum LiveEvent {
@@ -301,6 +515,23 @@ pub enum LiveEvent {
     NeighborDown(PublicKey),
     /// A set-reconciliation sync finished.
     SyncFinished(SyncEvent),
+    /// A sync in [`crate::SyncEventMode::Silent`] inserted entries.
+    ///
+    /// Emitted once per sync instead of an [`Self::InsertRemote`] event for each entry.
+    SyncBatchApplied {
+        /// Number of inserted entries.
+        count: usize,
+    },
+    /// The availability of content referenced in the document changed.
+    ///
+    /// Emitted for statuses reported with [`crate::actor::SyncHandle::notify_content_status`].
+    /// Completed content is announced with [`Self::ContentReady`] instead.
+    ContentStatusChanged {
+        /// Hash of the content.
+        hash: Hash,
+        /// New status of the content.
+        status: ContentStatus,
+    },
 }
 
 impl From<live::Event> for LiveEvent {
@@ -316,20 +547,41 @@ impl From<live::Event> for LiveEvent {
 }
 
 impl LiveEvent {
+    /// Convert a replica event into a live event.
+    ///
+    /// Returns `None` for [`crate::Event::Closed`], the event stream ends after it anyway, and
+    /// for [`crate::Event::ContentStatusChanged`] to [`ContentStatus::Complete`], which is
+    /// covered by [`Self::ContentReady`].
     fn from_replica_event(
         ev: crate::Event,
         content_status_cb: &ContentStatusCallback,
-    ) -> Result<Self> {
-        Ok(match ev {
+    ) -> Option<Result<Self>> {
+        let ev = match ev {
             crate::Event::LocalInsert { entry, .. } => Self::InsertLocal {
                 entry: entry.into(),
             },
-            crate::Event::RemoteInsert { entry, from, .. } => Self::InsertRemote {
-                content_status: content_status_cb(entry.content_hash()),
-                entry: entry.into(),
-                from: PublicKey::from_bytes(&from)?,
-            },
-        })
+            crate::Event::RemoteInsert { entry, from, .. } => {
+                let from = match PublicKey::from_bytes(&from) {
+                    Ok(from) => from,
+                    Err(err) => return Some(Err(err.into())),
+                };
+                Self::InsertRemote {
+                    content_status: entry_content_status(&entry, content_status_cb),
+                    entry: entry.into(),
+                    from,
+                }
+            }
+            crate::Event::SyncBatchApplied { count, .. } => Self::SyncBatchApplied { count },
+            crate::Event::ContentStatusChanged {
+                status: ContentStatus::Complete,
+                ..
+            }
+            | crate::Event::Closed { .. } => return None,
+            crate::Event::ContentStatusChanged { hash, status, .. } => {
+                Self::ContentStatusChanged { hash, status }
+            }
+        };
+        Some(Ok(ev))
     }
 }
 
@@ -451,3 +703,306 @@ impl DefaultAuthor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use iroh::protocol::ProtocolHandler;
+    use iroh_blobs::{net_protocol::Blobs, store::Store as _, BlobFormat};
+
+    use super::*;
+    use crate::{
+        ranger::Store as _, store::fs::StoreInstance, NamespaceSecret, Record, RecordIdentifier,
+        SignedEntry,
+    };
+
+    async fn spawn_engine(
+        replica_store: crate::store::Store,
+    ) -> Result<Arc<Engine<iroh_blobs::store::mem::Store>>> {
+        let endpoint = Endpoint::builder()
+            .relay_mode(iroh::RelayMode::Disabled)
+            .alpns(vec![
+                crate::ALPN.to_vec(),
+                crate::net::ALPN_MULTI.to_vec(),
+                iroh_blobs::ALPN.to_vec(),
+            ])
+            .bind()
+            .await?;
+        let blobs = Blobs::memory().build(&endpoint);
+        let gossip = Gossip::builder().spawn(endpoint.clone()).await?;
+        let engine = Engine::spawn(
+            endpoint.clone(),
+            gossip,
+            replica_store,
+            blobs.store().clone(),
+            blobs.downloader().clone(),
+            DefaultAuthorStorage::Mem,
+            blobs.rt().clone(),
+        )
+        .await?;
+        let engine = Arc::new(engine);
+        let engine2 = engine.clone();
+        tokio::task::spawn(async move {
+            while let Some(incoming) = endpoint.accept().await {
+                let Ok(mut connecting) = incoming.accept() else {
+                    continue;
+                };
+                let Ok(alpn) = connecting.alpn().await else {
+                    continue;
+                };
+                if alpn == iroh_blobs::ALPN {
+                    tokio::task::spawn(blobs.accept(connecting));
+                } else {
+                    engine2.handle_connection(connecting).await.ok();
+                }
+            }
+        });
+        Ok(engine)
+    }
+
+    #[test]
+    fn test_content_status_callback_with() {
+        let complete = Hash::new(b"complete");
+        let partial = Hash::new(b"partial");
+        let failing = Hash::new(b"failing");
+        let statuses = HashMap::from([
+            (complete, EntryStatus::Complete),
+            (partial, EntryStatus::Partial),
+        ]);
+        let cb = content_status_callback_with(move |hash| {
+            if *hash == failing {
+                return Err(io::Error::other("failed to read"));
+            }
+            Ok(statuses.get(hash).cloned().unwrap_or(EntryStatus::NotFound))
+        });
+        assert_eq!(cb(complete), ContentStatus::Complete);
+        assert_eq!(cb(partial), ContentStatus::Incomplete);
+        assert_eq!(cb(Hash::new(b"missing")), ContentStatus::Missing);
+        assert_eq!(cb(failing), ContentStatus::Missing);
+    }
+
+    #[tokio::test]
+    async fn test_sync_failure_observer_validation() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let wrong_namespace = NamespaceSecret::new(&mut rng);
+        let author = Author::new(&mut rng);
+
+        // bob has a valid entry, and an entry signed with the wrong namespace key, which is
+        // written to the store directly, bypassing validation.
+        let mut bob_store = crate::store::Store::memory();
+        let mut replica = bob_store.new_replica(namespace.clone())?;
+        replica.hash_and_insert("good", &author, b"good")?;
+        let id = RecordIdentifier::new(namespace.id(), author.id(), "bad");
+        let entry = crate::Entry::new(id, Record::new_current(Hash::new(b"bad"), 3));
+        let bad_entry = SignedEntry::from_entry(entry, &wrong_namespace, &author);
+        drop(replica);
+        StoreInstance::new(namespace.id(), &mut bob_store).entry_put(bad_entry)?;
+        let bob = spawn_engine(bob_store).await?;
+        bob.start_sync(namespace.id(), vec![]).await?;
+
+        let mut alice_store = crate::store::Store::memory();
+        alice_store.new_replica(namespace.clone())?;
+        let alice = spawn_engine(alice_store).await?;
+        let (tx, rx) = async_channel::unbounded();
+        let observer: SyncFailureObserver = Arc::new(move |namespace, peer, reason| {
+            tx.try_send((namespace, peer, reason)).ok();
+        });
+        alice.set_sync_failure_observer(Some(observer)).await?;
+
+        let bob_addr = bob.endpoint.node_addr().await?;
+        alice.start_sync(namespace.id(), vec![bob_addr]).await?;
+        let (observed_namespace, peer, reason) =
+            tokio::time::timeout(std::time::Duration::from_secs(10), rx.recv()).await??;
+        assert_eq!(observed_namespace, namespace.id());
+        assert_eq!(peer, *bob.endpoint.node_id().as_bytes());
+        assert_eq!(reason, SyncFailureReason::Validation { num_invalid: 1 });
+
+        // the valid entry was still inserted
+        let good = alice
+            .sync
+            .get_exact(namespace.id(), author.id(), "good".into(), false)
+            .await?;
+        assert!(good.is_some());
+
+        alice.shutdown().await?;
+        bob.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_many_with_peer() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let namespaces = [
+            NamespaceSecret::new(&mut rng),
+            NamespaceSecret::new(&mut rng),
+        ];
+        let author = Author::new(&mut rng);
+
+        let mut alice_store = crate::store::Store::memory();
+        let mut bob_store = crate::store::Store::memory();
+        for namespace in namespaces.iter() {
+            alice_store
+                .new_replica(namespace.clone())?
+                .hash_and_insert("alice", &author, b"alice")?;
+            bob_store
+                .new_replica(namespace.clone())?
+                .hash_and_insert("bob", &author, b"bob")?;
+        }
+        let alice = spawn_engine(alice_store).await?;
+        let bob = spawn_engine(bob_store).await?;
+        let mut events = Vec::new();
+        for namespace in namespaces.iter() {
+            alice.start_sync(namespace.id(), vec![]).await?;
+            bob.start_sync(namespace.id(), vec![]).await?;
+            events.push(alice.subscribe(namespace.id()).await?);
+        }
+
+        let bob_addr = bob.endpoint.node_addr().await?;
+        let ids = namespaces.iter().map(|namespace| namespace.id()).collect();
+        alice.sync_many_with_peer(bob_addr, ids).await?;
+        for events in events.iter_mut() {
+            let finished = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+                while let Some(event) = events.next().await {
+                    if let LiveEvent::SyncFinished(event) = event? {
+                        return Ok(event);
+                    }
+                }
+                anyhow::bail!("event stream closed")
+            })
+            .await??;
+            assert_eq!(finished.peer, bob.endpoint.node_id());
+            assert_eq!(finished.origin, Origin::Connect(SyncReason::DirectJoin));
+            let details = finished.result.map_err(|err| anyhow::anyhow!(err))?;
+            assert_eq!(details.entries_received, 1);
+            assert_eq!(details.entries_sent, 1);
+        }
+
+        for namespace in namespaces.iter() {
+            for engine in [&alice, &bob] {
+                for key in ["alice", "bob"] {
+                    let entry = engine
+                        .sync
+                        .get_exact(namespace.id(), author.id(), key.into(), false)
+                        .await?;
+                    assert!(entry.is_some());
+                }
+            }
+        }
+
+        alice.shutdown().await?;
+        bob.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_chunked_entry_resolves_after_manifest_download() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let author = Author::new(&mut rng);
+        let mut alice_store = crate::store::Store::memory();
+        alice_store.new_replica(namespace.clone())?;
+        alice_store.import_author(author.clone())?;
+        let mut bob_store = crate::store::Store::memory();
+        bob_store.new_replica(namespace.clone())?;
+        let alice = spawn_engine(alice_store).await?;
+        let bob = spawn_engine(bob_store).await?;
+
+        // the chunks are in alice's blob store, so that the entry content is complete
+        let mut chunks = Vec::new();
+        for data in [&b"chunk 1"[..], b"chunk 2"] {
+            let tag = alice
+                .blob_store
+                .import_bytes(bytes::Bytes::from_static(data), BlobFormat::Raw)
+                .await?;
+            chunks.push((*tag.hash(), data.len() as u64));
+        }
+        let chunks = ContentChunks::new(chunks);
+        alice.start_sync(namespace.id(), vec![]).await?;
+        let manifest_hash = alice
+            .insert_chunked(namespace.id(), author.id(), "chunked", chunks.clone())
+            .await?;
+        assert_eq!(manifest_hash, chunks.manifest_hash());
+
+        // bob downloads the manifest after syncing the entry, and resolves the chunks
+        bob.start_sync(namespace.id(), vec![]).await?;
+        let mut events = bob.subscribe(namespace.id()).await?;
+        let alice_addr = alice.endpoint.node_addr().await?;
+        bob.start_sync(namespace.id(), vec![alice_addr]).await?;
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while let Some(event) = events.next().await {
+                if matches!(event?, LiveEvent::ContentReady { hash } if hash == manifest_hash) {
+                    return Ok(());
+                }
+            }
+            anyhow::bail!("event stream closed")
+        })
+        .await??;
+        let entry = bob
+            .sync
+            .get_exact(namespace.id(), author.id(), "chunked".into(), false)
+            .await?
+            .expect("entry was synced");
+        assert_eq!(entry.content_chunks(), Some(chunks.chunks()));
+
+        alice.shutdown().await?;
+        bob.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_namespace_meta_syncs_with_blob() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let author = Author::new(&mut rng);
+        let mut alice_store = crate::store::Store::memory();
+        alice_store.new_replica(namespace.clone())?;
+        alice_store.import_author(author.clone())?;
+        let mut bob_store = crate::store::Store::memory();
+        bob_store.new_replica(namespace.clone())?;
+        let alice = spawn_engine(alice_store).await?;
+        let bob = spawn_engine(bob_store).await?;
+
+        let meta = bytes::Bytes::from_static(b"title: shared notes");
+        alice.start_sync(namespace.id(), vec![]).await?;
+        let meta_hash = alice
+            .set_namespace_meta(namespace.id(), author.id(), meta.clone())
+            .await?;
+
+        // bob downloads the metadata blob after syncing the entry, and can read the metadata
+        bob.start_sync(namespace.id(), vec![]).await?;
+        let mut events = bob.subscribe(namespace.id()).await?;
+        let alice_addr = alice.endpoint.node_addr().await?;
+        bob.start_sync(namespace.id(), vec![alice_addr]).await?;
+        tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            while let Some(event) = events.next().await {
+                if matches!(event?, LiveEvent::ContentReady { hash } if hash == meta_hash) {
+                    return Ok(());
+                }
+            }
+            anyhow::bail!("event stream closed")
+        })
+        .await??;
+        assert_eq!(
+            bob.sync.get_namespace_meta(namespace.id()).await?,
+            Some(meta)
+        );
+
+        alice.shutdown().await?;
+        bob.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_content_status_callback_mem_store() -> Result<()> {
+        let blob_store = iroh_blobs::store::mem::Store::new();
+        let tag = blob_store
+            .import_bytes(bytes::Bytes::from_static(b"hello"), BlobFormat::Raw)
+            .await?;
+        let cb = content_status_callback(blob_store);
+        assert_eq!(cb(*tag.hash()), ContentStatus::Complete);
+        assert_eq!(cb(Hash::new(b"missing")), ContentStatus::Missing);
+        Ok(())
+    }
+}
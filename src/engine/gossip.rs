@@ -1,4 +1,10 @@
-use std::collections::{hash_map, HashMap};
+use std::{
+    collections::{hash_map, HashMap},
+    future::Future,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use anyhow::{Context, Result};
 use bytes::Bytes;
@@ -6,6 +12,7 @@ use futures_lite::StreamExt;
 use futures_util::FutureExt;
 use iroh::NodeId;
 use iroh_gossip::net::{Event, Gossip, GossipEvent, GossipReceiver, GossipSender, JoinOptions};
+use serde::{Deserialize, Serialize};
 use tokio::{
     sync::mpsc,
     task::{AbortHandle, JoinSet},
@@ -13,7 +20,68 @@ use tokio::{
 use tracing::{debug, instrument, warn};
 
 use super::live::{Op, ToLiveActor};
-use crate::{actor::SyncHandle, ContentStatus, NamespaceId};
+use crate::{
+    actor::SyncHandle,
+    ranger::{Fingerprint, RangeEntry},
+    ContentStatus, NamespaceId, SignedEntry,
+};
+
+/// Configuration of the deduplication of entries received via gossip.
+///
+/// The same entry is often received from several neighbors. Entries which were inserted from
+/// gossip within the last `ttl` are dropped before they reach the store. At most `capacity`
+/// entries are remembered, the least recently seen are forgotten first.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub struct GossipDedupConfig {
+    /// Maximum number of entries to remember.
+    pub capacity: usize,
+    /// Time for which an entry is remembered.
+    pub ttl: Duration,
+}
+
+impl Default for GossipDedupConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Fingerprints of entries recently inserted from gossip.
+#[derive(Debug)]
+struct SeenEntries {
+    ttl: Duration,
+    cache: lru::LruCache<Fingerprint, Instant>,
+}
+
+impl SeenEntries {
+    /// Returns `None` if the config has a capacity of zero.
+    fn new(config: GossipDedupConfig) -> Option<Self> {
+        let capacity = NonZeroUsize::new(config.capacity)?;
+        Some(Self {
+            ttl: config.ttl,
+            cache: lru::LruCache::new(capacity),
+        })
+    }
+
+    fn contains(&mut self, fingerprint: &Fingerprint, now: Instant) -> bool {
+        match self.cache.get(fingerprint) {
+            Some(seen) if now.saturating_duration_since(*seen) < self.ttl => true,
+            Some(_) => {
+                self.cache.pop(fingerprint);
+                false
+            }
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, fingerprint: Fingerprint, now: Instant) {
+        self.cache.put(fingerprint, now);
+    }
+}
+
+type SharedSeenEntries = Arc<Mutex<Option<SeenEntries>>>;
 
 #[derive(Debug)]
 struct ActiveState {
@@ -28,6 +96,7 @@ pub struct GossipState {
     to_live_actor: mpsc::Sender<ToLiveActor>,
     active: HashMap<NamespaceId, ActiveState>,
     active_tasks: JoinSet<(NamespaceId, Result<()>)>,
+    seen: SharedSeenEntries,
 }
 
 impl GossipState {
@@ -38,9 +107,17 @@ impl GossipState {
             to_live_actor,
             active: Default::default(),
             active_tasks: Default::default(),
+            seen: Arc::new(Mutex::new(SeenEntries::new(Default::default()))),
         }
     }
 
+    /// Set the deduplication of entries received via gossip, or disable it with `None`.
+    ///
+    /// This forgets all entries seen so far.
+    pub fn set_dedup(&self, config: Option<GossipDedupConfig>) {
+        *self.seen.lock().expect("poisoned") = config.and_then(SeenEntries::new);
+    }
+
     pub async fn join(&mut self, namespace: NamespaceId, bootstrap: Vec<NodeId>) -> Result<()> {
         match self.active.entry(namespace) {
             hash_map::Entry::Occupied(entry) => {
@@ -60,6 +137,7 @@ impl GossipState {
                         stream,
                         self.to_live_actor.clone(),
                         self.sync.clone(),
+                        self.seen.clone(),
                     )
                     .map(move |res| (namespace, res)),
                 );
@@ -135,6 +213,7 @@ async fn receive_loop(
     mut recv: GossipReceiver,
     to_sync_actor: mpsc::Sender<ToLiveActor>,
     sync: SyncHandle,
+    seen: SharedSeenEntries,
 ) -> Result<()> {
     for peer in recv.neighbors() {
         to_sync_actor
@@ -166,11 +245,12 @@ async fn receive_loop(
                             false => ContentStatus::Missing,
                         };
                         let from = *msg.delivered_from.as_bytes();
-                        if let Err(err) = sync
-                            .insert_remote(namespace, entry, from, content_status)
-                            .await
-                        {
-                            debug!("ignoring entry received via gossip: {err}");
+                        let inserted = insert_deduplicated(&seen, entry, |entry| {
+                            sync.insert_remote(namespace, entry, from, content_status)
+                        })
+                        .await;
+                        if !inserted {
+                            debug!("dropping entry received via gossip: seen recently");
                         }
                     }
                     Op::ContentReady(hash) => {
@@ -213,3 +293,113 @@ async fn receive_loop(
     }
     Ok(())
 }
+
+/// Insert an entry received via gossip, unless the same entry was inserted recently.
+///
+/// Returns false if the entry was dropped as a duplicate.
+async fn insert_deduplicated<F>(
+    seen: &Mutex<Option<SeenEntries>>,
+    entry: SignedEntry,
+    insert: impl FnOnce(SignedEntry) -> F,
+) -> bool
+where
+    F: Future<Output = Result<()>>,
+{
+    let fingerprint = entry.as_fingerprint();
+    let is_duplicate = seen
+        .lock()
+        .expect("poisoned")
+        .as_mut()
+        .is_some_and(|seen| seen.contains(&fingerprint, Instant::now()));
+    if is_duplicate {
+        return false;
+    }
+    match insert(entry).await {
+        Ok(()) => {
+            // Only remember entries which made it into the store, so that an entry which failed
+            // to insert, e.g. because sync was paused, is accepted when received again.
+            if let Some(seen) = seen.lock().expect("poisoned").as_mut() {
+                seen.insert(fingerprint, Instant::now());
+            }
+        }
+        Err(err) => debug!("ignoring entry received via gossip: {err}"),
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{Author, Entry, NamespaceSecret, Record, RecordIdentifier};
+
+    fn entry(key: &[u8]) -> SignedEntry {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let author = Author::new(&mut rng);
+        let id = RecordIdentifier::new(namespace.id(), author.id(), key);
+        Entry::new(id, Record::new_current(iroh_blobs::Hash::new(key), 1)).sign(&namespace, &author)
+    }
+
+    #[tokio::test]
+    async fn gossip_dedup_drops_duplicates() {
+        let seen = Mutex::new(SeenEntries::new(Default::default()));
+        let puts = AtomicUsize::new(0);
+        let put = |_entry| {
+            puts.fetch_add(1, Ordering::SeqCst);
+            async { Ok(()) }
+        };
+
+        let op = entry(b"a");
+        assert!(insert_deduplicated(&seen, op.clone(), put).await);
+        assert!(!insert_deduplicated(&seen, op, put).await);
+        assert_eq!(puts.load(Ordering::SeqCst), 1);
+        assert!(insert_deduplicated(&seen, entry(b"b"), put).await);
+        assert_eq!(puts.load(Ordering::SeqCst), 2);
+
+        // failed inserts are not remembered
+        let op = entry(b"c");
+        let fail = |_entry| async { Err(anyhow::anyhow!("not syncing")) };
+        assert!(insert_deduplicated(&seen, op.clone(), fail).await);
+        assert!(insert_deduplicated(&seen, op, put).await);
+        assert_eq!(puts.load(Ordering::SeqCst), 3);
+
+        // without deduplication, every op is inserted
+        let seen = Mutex::new(None);
+        let op = entry(b"a");
+        assert!(insert_deduplicated(&seen, op.clone(), put).await);
+        assert!(insert_deduplicated(&seen, op, put).await);
+        assert_eq!(puts.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn gossip_dedup_ttl_and_capacity() {
+        let config = GossipDedupConfig {
+            capacity: 2,
+            ttl: Duration::from_secs(10),
+        };
+        let mut seen = SeenEntries::new(config).unwrap();
+        let [a, b, c] = [b"a", b"b", b"c"].map(|key| entry(key).as_fingerprint());
+        let now = Instant::now();
+
+        seen.insert(a, now);
+        assert!(seen.contains(&a, now + Duration::from_secs(9)));
+        assert!(!seen.contains(&a, now + Duration::from_secs(10)));
+        // expired entries are forgotten
+        assert!(!seen.contains(&a, now));
+
+        seen.insert(a, now);
+        seen.insert(b, now);
+        seen.insert(c, now);
+        assert!(!seen.contains(&a, now));
+        assert!(seen.contains(&b, now));
+        assert!(seen.contains(&c, now));
+
+        assert!(SeenEntries::new(GossipDedupConfig {
+            capacity: 0,
+            ..config
+        })
+        .is_none());
+    }
+}
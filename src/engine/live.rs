@@ -1,20 +1,24 @@
 #![allow(missing_docs)]
 
 use std::{
-    collections::{HashMap, HashSet},
-    time::SystemTime,
+    collections::{HashMap, HashSet, VecDeque},
+    sync::Arc,
+    time::{Instant, SystemTime},
 };
 
 use anyhow::{Context, Result};
+use bytes::Bytes;
 use futures_lite::FutureExt;
 use iroh::{Endpoint, NodeAddr, NodeId, PublicKey};
 use iroh_blobs::{
     downloader::{DownloadError, DownloadRequest, Downloader},
     get::Stats,
-    store::EntryStatus,
+    store::{EntryStatus, MapEntry},
+    util::local_pool::LocalPoolHandle,
     Hash, HashAndFormat,
 };
 use iroh_gossip::net::Gossip;
+use iroh_io::AsyncSliceReader;
 use iroh_metrics::inc;
 use serde::{Deserialize, Serialize};
 use tokio::{
@@ -24,16 +28,21 @@ use tokio::{
 use tracing::{debug, error, info, instrument, trace, warn, Instrument, Span};
 
 // use super::gossip::{GossipActor, ToGossipActor};
-use super::state::{NamespaceStates, Origin, SyncReason};
+use super::state::{
+    HeadsGossip, HeadsGossipTimer, NamespaceStates, Origin, RetryPolicy, SyncBackoff, SyncReason,
+    SyncSchedule, SyncScheduler,
+};
 use crate::{
     actor::{OpenOpts, SyncHandle},
-    engine::gossip::GossipState,
+    engine::gossip::{GossipDedupConfig, GossipState},
     metrics::Metrics,
     net::{
-        connect_and_sync, handle_connection, AbortReason, AcceptError, AcceptOutcome, ConnectError,
-        SyncFinished,
+        connect_and_sync, connect_and_sync_many, handle_connection_many,
+        handle_connection_with_policy, AbortReason, AcceptError, AcceptOutcome, AcceptPolicy,
+        ConnectError, SyncFinished, ALPN_MULTI,
     },
-    AuthorHeads, ContentStatus, NamespaceId, SignedEntry,
+    AuthorHeads, ContentChunks, ContentStatus, NamespaceId, PeerIdBytes, SignedEntry,
+    MAX_NAMESPACE_META_LEN,
 };
 
 /// Name used for logging when new node addresses are added from the docs engine.
@@ -69,6 +78,12 @@ pub enum ToLiveActor {
         #[debug("onsehot::Sender")]
         reply: sync::oneshot::Sender<anyhow::Result<()>>,
     },
+    SyncMany {
+        peer: NodeAddr,
+        namespaces: Vec<NamespaceId>,
+        #[debug("onsehot::Sender")]
+        reply: sync::oneshot::Sender<anyhow::Result<()>>,
+    },
     Leave {
         namespace: NamespaceId,
         kill_subscribers: bool,
@@ -78,6 +93,39 @@ pub enum ToLiveActor {
     Shutdown {
         reply: sync::oneshot::Sender<()>,
     },
+    SetSyncSchedule {
+        namespace: NamespaceId,
+        schedule: Option<SyncSchedule>,
+    },
+    PauseSync {
+        namespace: NamespaceId,
+        #[debug("onsehot::Sender")]
+        reply: sync::oneshot::Sender<anyhow::Result<()>>,
+    },
+    ResumeSync {
+        namespace: NamespaceId,
+        #[debug("onsehot::Sender")]
+        reply: sync::oneshot::Sender<anyhow::Result<()>>,
+    },
+    SetMaxConcurrentDownloads {
+        max: Option<usize>,
+    },
+    SetAcceptPolicy {
+        policy: AcceptPolicy,
+    },
+    SetGossipDedup {
+        config: Option<GossipDedupConfig>,
+    },
+    SetSyncFailureObserver {
+        #[debug("SyncFailureObserver")]
+        observer: Option<SyncFailureObserver>,
+    },
+    SetRetryPolicy {
+        policy: Option<RetryPolicy>,
+    },
+    SetHeadsGossip {
+        config: Option<HeadsGossip>,
+    },
     Subscribe {
         namespace: NamespaceId,
         #[debug("sender")]
@@ -144,7 +192,7 @@ type SyncConnectRes = (
     SyncReason,
     Result<SyncFinished, ConnectError>,
 );
-type SyncAcceptRes = Result<SyncFinished, AcceptError>;
+type SyncAcceptRes = Vec<Result<SyncFinished, AcceptError>>;
 type DownloadRes = (NamespaceId, Hash, Result<Stats, DownloadError>);
 
 // Currently peers might double-sync in both directions.
@@ -155,6 +203,8 @@ pub struct LiveActor<B: iroh_blobs::store::Store> {
     endpoint: Endpoint,
     bao_store: B,
     downloader: Downloader,
+    /// Pool to read blobs on, as blob readers are not `Send`.
+    local_pool: LocalPoolHandle,
     replica_events_tx: async_channel::Sender<crate::Event>,
     replica_events_rx: async_channel::Receiver<crate::Event>,
 
@@ -164,9 +214,9 @@ pub struct LiveActor<B: iroh_blobs::store::Store> {
     sync_actor_tx: mpsc::Sender<ToLiveActor>,
     gossip: GossipState,
 
-    /// Running sync futures (from connect).
-    running_sync_connect: JoinSet<SyncConnectRes>,
-    /// Running sync futures (from accept).
+    /// Running sync futures (from connect), with one result per namespace.
+    running_sync_connect: JoinSet<Vec<SyncConnectRes>>,
+    /// Running sync futures (from accept), with one result per namespace.
     running_sync_accept: JoinSet<SyncAcceptRes>,
     /// Running download futures.
     download_tasks: JoinSet<DownloadRes>,
@@ -174,12 +224,24 @@ pub struct LiveActor<B: iroh_blobs::store::Store> {
     missing_hashes: HashSet<Hash>,
     /// Content hashes queued in downloader.
     queued_hashes: QueuedHashes,
+    /// Limits the downloads in flight per namespace.
+    download_limiter: DownloadLimiter,
+    /// Namespaces we accept incoming sync requests for.
+    accept_policy: AcceptPolicy,
+    /// Called for each failed sync.
+    sync_failure_observer: Option<SyncFailureObserver>,
 
     /// Subscribers to actor events
     subscribers: SubscribersMap,
 
     /// Sync state per replica and peer
     state: NamespaceStates,
+    /// Schedules and pending change-triggered syncs
+    scheduler: SyncScheduler,
+    /// Backoff and pending retries for failed syncs
+    backoff: SyncBackoff,
+    /// Periodic broadcasts of our author heads, if enabled
+    heads_gossip: HeadsGossipTimer,
 }
 impl<B: iroh_blobs::store::Store> LiveActor<B> {
     /// Create the live actor.
@@ -190,6 +252,7 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         gossip: Gossip,
         bao_store: B,
         downloader: Downloader,
+        local_pool: LocalPoolHandle,
         inbox: mpsc::Receiver<ToLiveActor>,
         sync_actor_tx: mpsc::Sender<ToLiveActor>,
     ) -> Self {
@@ -204,14 +267,21 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
             gossip: gossip_state,
             bao_store,
             downloader,
+            local_pool,
             sync_actor_tx,
             running_sync_connect: Default::default(),
             running_sync_accept: Default::default(),
             subscribers: Default::default(),
             download_tasks: Default::default(),
             state: Default::default(),
+            scheduler: Default::default(),
+            backoff: Default::default(),
+            heads_gossip: Default::default(),
             missing_hashes: Default::default(),
             queued_hashes: Default::default(),
+            download_limiter: Default::default(),
+            accept_policy: Default::default(),
+            sync_failure_observer: None,
         }
     }
 
@@ -237,6 +307,13 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
             i += 1;
             trace!(?i, "tick wait");
             inc!(Metrics, doc_live_tick_main);
+            let next_scheduled_sync = self
+                .scheduler
+                .next_due()
+                .into_iter()
+                .chain(self.backoff.next_due())
+                .chain(self.heads_gossip.next_due())
+                .min();
             tokio::select! {
                 biased;
                 msg = self.inbox.recv() => {
@@ -263,15 +340,19 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                 Some(res) = self.running_sync_connect.join_next(), if !self.running_sync_connect.is_empty() => {
                     trace!(?i, "tick: running_sync_connect");
                     inc!(Metrics, doc_live_tick_running_sync_connect);
-                    let (namespace, peer, reason, res) = res.context("running_sync_connect closed")?;
-                    self.on_sync_via_connect_finished(namespace, peer, reason, res).await;
+                    let results = res.context("running_sync_connect closed")?;
+                    for (namespace, peer, reason, res) in results {
+                        self.on_sync_via_connect_finished(namespace, peer, reason, res).await;
+                    }
 
                 }
                 Some(res) = self.running_sync_accept.join_next(), if !self.running_sync_accept.is_empty() => {
                     trace!(?i, "tick: running_sync_accept");
                     inc!(Metrics, doc_live_tick_running_sync_accept);
-                    let res = res.context("running_sync_accept closed")?;
-                    self.on_sync_via_accept_finished(res).await;
+                    let results = res.context("running_sync_accept closed")?;
+                    for res in results {
+                        self.on_sync_via_accept_finished(res).await;
+                    }
                 }
                 Some(res) = self.download_tasks.join_next(), if !self.download_tasks.is_empty() => {
                     trace!(?i, "tick: pending_downloads");
@@ -279,6 +360,11 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                     let (namespace, hash, res) = res.context("pending_downloads closed")?;
                     self.on_download_ready(namespace, hash, res).await;
                 }
+                _ = tokio::time::sleep_until(next_scheduled_sync.unwrap_or_else(Instant::now).into()), if next_scheduled_sync.is_some() => {
+                    trace!(?i, "tick: scheduled_sync");
+                    self.on_scheduled_syncs_due();
+                    self.on_heads_gossip_due().await;
+                }
                 res = self.gossip.progress(), if !self.gossip.is_empty() => {
                     if let Err(error) = res {
                         warn!(?error, "gossip state failed");
@@ -298,7 +384,12 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
             }
             ToLiveActor::NeighborUp { namespace, peer } => {
                 debug!(peer = %peer.fmt_short(), namespace = %namespace.fmt_short(), "neighbor up");
-                self.sync_with_peer(namespace, peer, SyncReason::NewNeighbor);
+                if self.heads_gossip.is_enabled() {
+                    // only sync if the heads exchanged with the new neighbor show a difference
+                    self.broadcast_heads(namespace).await;
+                } else {
+                    self.sync_with_peer(namespace, peer, SyncReason::NewNeighbor);
+                }
                 self.subscribers
                     .send(&namespace, Event::NeighborUp(peer))
                     .await;
@@ -317,6 +408,20 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                 let res = self.start_sync(namespace, peers).await;
                 reply.send(res).ok();
             }
+            ToLiveActor::SyncMany {
+                peer,
+                namespaces,
+                reply,
+            } => {
+                let peer_id = peer.node_id;
+                if !peer.is_empty() {
+                    if let Err(err) = self.endpoint.add_node_addr_with_source(peer, SOURCE_NAME) {
+                        warn!(peer = %peer_id.fmt_short(), "failed to add known addrs: {err:?}");
+                    }
+                }
+                self.sync_many_with_peer(namespaces, peer_id, SyncReason::DirectJoin);
+                reply.send(Ok(())).ok();
+            }
             ToLiveActor::Leave {
                 namespace,
                 kill_subscribers,
@@ -325,6 +430,41 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                 let res = self.leave(namespace, kill_subscribers).await;
                 reply.send(res).ok();
             }
+            ToLiveActor::SetSyncSchedule {
+                namespace,
+                schedule,
+            } => {
+                for peer in self.scheduler.set_schedule(namespace, schedule) {
+                    self.sync_with_peer(namespace, peer, SyncReason::SyncReport);
+                }
+            }
+            ToLiveActor::PauseSync { namespace, reply } => {
+                let res = self.pause_sync(namespace).await;
+                reply.send(res).ok();
+            }
+            ToLiveActor::ResumeSync { namespace, reply } => {
+                let res = self.resume_sync(namespace).await;
+                reply.send(res).ok();
+            }
+            ToLiveActor::SetMaxConcurrentDownloads { max } => {
+                self.download_limiter.set_max_in_flight(max);
+                self.start_pending_downloads().await;
+            }
+            ToLiveActor::SetAcceptPolicy { policy } => {
+                self.accept_policy = policy;
+            }
+            ToLiveActor::SetGossipDedup { config } => {
+                self.gossip.set_dedup(config);
+            }
+            ToLiveActor::SetSyncFailureObserver { observer } => {
+                self.sync_failure_observer = observer;
+            }
+            ToLiveActor::SetRetryPolicy { policy } => {
+                self.backoff.set_policy(policy);
+            }
+            ToLiveActor::SetHeadsGossip { config } => {
+                self.heads_gossip.set_config(config, Instant::now());
+            }
             ToLiveActor::Subscribe {
                 namespace,
                 sender,
@@ -355,21 +495,133 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         Ok(true)
     }
 
+    /// Check whether a sync with `peer` should be started, and mark it as started if so.
+    fn start_connect(
+        &mut self,
+        namespace: NamespaceId,
+        peer: PublicKey,
+        reason: SyncReason,
+    ) -> bool {
+        // syncs requested with a join are started even if previous syncs failed
+        if reason != SyncReason::DirectJoin
+            && self
+                .backoff
+                .is_backing_off(&namespace, &peer, Instant::now())
+        {
+            debug!(?reason, "skip connect: backing off after failed syncs");
+            return false;
+        }
+        if !self.state.start_connect(&namespace, peer, reason) {
+            return false;
+        }
+        // a queued change-triggered sync is covered by this sync
+        self.scheduler.clear(&namespace, &peer);
+        true
+    }
+
     #[instrument("connect", skip_all, fields(peer = %peer.fmt_short(), namespace = %namespace.fmt_short()))]
     fn sync_with_peer(&mut self, namespace: NamespaceId, peer: PublicKey, reason: SyncReason) {
-        if !self.state.start_connect(&namespace, peer, reason) {
+        if !self.start_connect(namespace, peer, reason) {
             return;
         }
         let endpoint = self.endpoint.clone();
         let sync = self.sync.clone();
         let fut = async move {
             let res = connect_and_sync(&endpoint, &sync, namespace, NodeAddr::new(peer)).await;
-            (namespace, peer, reason, res)
+            vec![(namespace, peer, reason, res)]
         }
         .instrument(Span::current());
         self.running_sync_connect.spawn(fut);
     }
 
+    /// Sync several namespaces with `peer` over a single stream, see [`connect_and_sync_many`].
+    #[instrument("connect", skip_all, fields(peer = %peer.fmt_short()))]
+    fn sync_many_with_peer(
+        &mut self,
+        namespaces: Vec<NamespaceId>,
+        peer: PublicKey,
+        reason: SyncReason,
+    ) {
+        let mut started = Vec::new();
+        for namespace in namespaces {
+            if !started.contains(&namespace) && self.start_connect(namespace, peer, reason) {
+                started.push(namespace);
+            }
+        }
+        if started.is_empty() {
+            return;
+        }
+        let endpoint = self.endpoint.clone();
+        let sync = self.sync.clone();
+        let fut = async move {
+            match connect_and_sync_many(&endpoint, &sync, &started, NodeAddr::new(peer)).await {
+                Ok(results) => results
+                    .into_iter()
+                    .map(|(namespace, res)| (namespace, peer, reason, res))
+                    .collect(),
+                Err(err) => started
+                    .into_iter()
+                    .map(|namespace| (namespace, peer, reason, Err(copy_connect_error(&err))))
+                    .collect(),
+            }
+        }
+        .instrument(Span::current());
+        self.running_sync_connect.spawn(fut);
+    }
+
+    /// Request a sync that was triggered by a change announced by `peer`.
+    ///
+    /// The sync is started right away unless the namespace has a [`SyncSchedule`], in which case
+    /// it may be queued to be coalesced with further changes.
+    fn sync_on_change(&mut self, namespace: NamespaceId, peer: PublicKey, reason: SyncReason) {
+        if self.scheduler.request(namespace, peer, Instant::now()) {
+            self.sync_with_peer(namespace, peer, reason);
+        } else {
+            debug!(peer = %peer.fmt_short(), namespace = %namespace.fmt_short(), "sync queued by schedule");
+        }
+    }
+
+    fn on_scheduled_syncs_due(&mut self) {
+        let now = Instant::now();
+        for (namespace, peer) in self.scheduler.take_due(now) {
+            self.sync_with_peer(namespace, peer, SyncReason::SyncReport);
+        }
+        for (namespace, peer) in self.backoff.take_due(now) {
+            self.sync_with_peer(namespace, peer, SyncReason::Retry);
+        }
+    }
+
+    async fn on_heads_gossip_due(&mut self) {
+        for namespace in self.heads_gossip.take_due(Instant::now()) {
+            self.broadcast_heads(namespace).await;
+        }
+    }
+
+    /// Broadcast the heads of all authors to our neighbors, see [`HeadsGossip`].
+    ///
+    /// Neighbors which find news for them in our heads start a sync with us.
+    async fn broadcast_heads(&self, namespace: NamespaceId) {
+        let heads = match self.sync.author_heads(namespace).await {
+            Ok(heads) => heads,
+            Err(err) => {
+                warn!(?err, "Failed to get author heads");
+                return;
+            }
+        };
+        if heads.is_empty() {
+            return;
+        }
+        match heads.encode(Some(self.gossip.max_message_size())) {
+            Err(err) => warn!(?err, "Failed to encode author heads"),
+            Ok(heads) => {
+                debug!(namespace = %namespace.fmt_short(), "broadcast heads to neighbors");
+                let report = SyncReport { namespace, heads };
+                self.broadcast_neighbors(namespace, &Op::SyncReport(report))
+                    .await;
+            }
+        }
+    }
+
     async fn shutdown(&mut self) -> anyhow::Result<()> {
         // cancel all subscriptions
         self.subscribers.clear();
@@ -391,9 +643,10 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         if !self.state.is_syncing(&namespace) {
             let opts = OpenOpts::default()
                 .sync()
-                .subscribe(self.replica_events_tx.clone());
+                .subscribe_internal(self.replica_events_tx.clone());
             self.sync.open(namespace, opts).await?;
             self.state.insert(namespace);
+            self.heads_gossip.insert(namespace, Instant::now());
         }
         // add the peers stored for this document
         match self.sync.get_sync_peers(namespace).await {
@@ -429,6 +682,9 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         kill_subscribers: bool,
     ) -> anyhow::Result<()> {
         // self.subscribers.remove(&namespace);
+        self.scheduler.remove_namespace(&namespace);
+        self.backoff.remove_namespace(&namespace);
+        self.heads_gossip.remove(&namespace);
         if self.state.remove(&namespace) {
             self.sync.set_sync(namespace, false).await?;
             self.sync
@@ -443,6 +699,26 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         Ok(())
     }
 
+    async fn pause_sync(&mut self, namespace: NamespaceId) -> anyhow::Result<()> {
+        anyhow::ensure!(self.state.pause(&namespace), "document is not syncing");
+        // stop inserting entries received from the network, the gossip subscription stays active
+        self.sync.set_sync(namespace, false).await?;
+        Ok(())
+    }
+
+    async fn resume_sync(&mut self, namespace: NamespaceId) -> anyhow::Result<()> {
+        anyhow::ensure!(self.state.is_syncing(&namespace), "document is not syncing");
+        let Some(peers) = self.state.resume(&namespace) else {
+            return Ok(());
+        };
+        self.sync.set_sync(namespace, true).await?;
+        // catch up on everything we missed while paused
+        for peer in peers {
+            self.sync_with_peer(namespace, peer, SyncReason::Resume);
+        }
+        Ok(())
+    }
+
     async fn join_peers(&mut self, namespace: NamespaceId, peers: Vec<NodeAddr>) -> Result<()> {
         let mut peer_ids = Vec::new();
 
@@ -490,6 +766,13 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                 debug!(?reason, "remote abort, already syncing");
             }
             res => {
+                if let Some(reason) = res
+                    .as_ref()
+                    .err()
+                    .and_then(SyncFailureReason::from_connect_error)
+                {
+                    self.report_sync_failure(namespace, peer, reason);
+                }
                 self.on_sync_finished(
                     namespace,
                     peer,
@@ -514,6 +797,9 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
             }
             Err(err) => {
                 if let (Some(peer), Some(namespace)) = (err.peer(), err.namespace()) {
+                    if let Some(reason) = SyncFailureReason::from_accept_error(&err) {
+                        self.report_sync_failure(namespace, peer, reason);
+                    }
                     self.on_sync_finished(
                         namespace,
                         peer,
@@ -528,6 +814,17 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         }
     }
 
+    fn report_sync_failure(
+        &self,
+        namespace: NamespaceId,
+        peer: PublicKey,
+        reason: SyncFailureReason,
+    ) {
+        if let Some(observer) = &self.sync_failure_observer {
+            observer(namespace, *peer.as_bytes(), reason);
+        }
+    }
+
     async fn on_sync_finished(
         &mut self,
         namespace: NamespaceId,
@@ -548,6 +845,16 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                     "sync finished",
                 );
 
+                if details.outcome.num_invalid > 0 {
+                    let num_invalid = details.outcome.num_invalid;
+                    warn!(%num_invalid, "peer sent invalid entries");
+                    self.report_sync_failure(
+                        namespace,
+                        peer,
+                        SyncFailureReason::Validation { num_invalid },
+                    );
+                }
+
                 // register the peer as useful for the document
                 if let Err(e) = self
                     .sync
@@ -557,6 +864,15 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                     debug!(%e, "failed to register peer for document")
                 }
 
+                // emit a single event for the inserted entries, if the sync was silent
+                if let Err(e) = self
+                    .sync
+                    .sync_batch_applied(namespace, details.outcome.num_inserted)
+                    .await
+                {
+                    debug!(%e, "failed to emit sync batch event")
+                }
+
                 // broadcast a sync report to our neighbors, but only if we received new entries.
                 if details.outcome.num_recv > 0 {
                     info!("broadcast sync report to neighbors");
@@ -581,10 +897,25 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
             Err(err) => Err(err.to_string()),
         };
 
+        let failed = result.is_err();
         let Some((started, resync)) = self.state.finish(&namespace, peer, &origin, result) else {
             return;
         };
 
+        // only retry syncs we initiated, the remote retries syncs it initiated itself
+        if !failed {
+            self.backoff.succeeded(&namespace, &peer);
+        } else if matches!(origin, Origin::Connect(_)) {
+            if let Some(retry_at) =
+                self.backoff
+                    .failed(namespace, peer, Instant::now(), &mut rand::thread_rng())
+            {
+                let failures = self.backoff.failures(&namespace, &peer);
+                let retry_in = retry_at.saturating_duration_since(Instant::now());
+                debug!(%failures, ?retry_in, "sync retry scheduled");
+            }
+        }
+
         let ev = SyncEvent {
             peer,
             origin,
@@ -611,7 +942,7 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         }
 
         if resync {
-            self.sync_with_peer(namespace, peer, SyncReason::Resync);
+            self.sync_on_change(namespace, peer, SyncReason::Resync);
         }
     }
 
@@ -639,9 +970,23 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         hash: Hash,
         res: Result<Stats, DownloadError>,
     ) {
+        let mut namespaces = self.queued_hashes.namespaces(&hash);
         let completed_namespaces = self.queued_hashes.remove_hash(&hash);
         debug!(namespace=%namespace.fmt_short(), success=res.is_ok(), completed_namespaces=completed_namespaces.len(), "download ready");
+        self.download_limiter.finish(&namespace);
+        self.start_pending_downloads().await;
         if res.is_ok() {
+            if let Err(err) = self
+                .sync
+                .notify_content_status(namespace, hash, ContentStatus::Complete)
+                .await
+            {
+                warn!(?err, "failed to notify content status");
+            }
+            if !namespaces.contains(&namespace) {
+                namespaces.push(namespace);
+            }
+            self.resolve_downloaded_blob(namespaces, hash).await;
             self.subscribers
                 .send(&namespace, Event::ContentReady { hash })
                 .await;
@@ -660,6 +1005,55 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         }
     }
 
+    /// Resolve chunked entries and namespace metadata whose content is the downloaded blob `hash`.
+    ///
+    /// Any downloaded blob which is a valid manifest is stored for the matching chunked entries
+    /// of `namespaces`, see [`SyncHandle::put_content_chunks`]. Any downloaded blob which is not
+    /// longer than [`MAX_NAMESPACE_META_LEN`] is stored for the matching namespace metadata of
+    /// `namespaces`, see [`SyncHandle::put_namespace_meta`].
+    async fn resolve_downloaded_blob(&self, namespaces: Vec<NamespaceId>, hash: Hash) {
+        let store = self.bao_store.clone();
+        let max_len = ContentChunks::MAX_MANIFEST_LEN.max(MAX_NAMESPACE_META_LEN);
+        let res = self
+            .local_pool
+            .spawn(move || read_blob(store, hash, max_len))
+            .await;
+        let bytes = match res {
+            Ok(Ok(Some(bytes))) => bytes,
+            Ok(Ok(None)) => return,
+            Ok(Err(err)) => {
+                warn!(?err, %hash, "failed to read downloaded blob");
+                return;
+            }
+            Err(err) => {
+                warn!(?err, "failed to spawn on the local pool");
+                return;
+            }
+        };
+        let is_manifest =
+            bytes.len() as u64 <= ContentChunks::MAX_MANIFEST_LEN && is_manifest(&bytes);
+        let is_meta = bytes.len() as u64 <= MAX_NAMESPACE_META_LEN;
+        for namespace in namespaces {
+            if is_manifest {
+                match self.sync.put_content_chunks(namespace, bytes.clone()).await {
+                    Ok(count) => {
+                        debug!(namespace=%namespace.fmt_short(), %hash, %count, "resolved content chunks")
+                    }
+                    Err(err) => warn!(?err, "failed to store content chunks"),
+                }
+            }
+            if is_meta {
+                match self.sync.put_namespace_meta(namespace, bytes.clone()).await {
+                    Ok(0) => {}
+                    Ok(count) => {
+                        debug!(namespace=%namespace.fmt_short(), %hash, %count, "resolved namespace metadata")
+                    }
+                    Err(err) => warn!(?err, "failed to store namespace metadata"),
+                }
+            }
+        }
+    }
+
     async fn on_neighbor_content_ready(
         &mut self,
         namespace: NamespaceId,
@@ -685,7 +1079,7 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         match self.sync.has_news_for_us(report.namespace, heads).await {
             Ok(Some(updated_authors)) => {
                 info!(%updated_authors, "news reported: sync now");
-                self.sync_with_peer(report.namespace, from, SyncReason::SyncReport);
+                self.sync_on_change(report.namespace, from, SyncReason::SyncReport);
             }
             Ok(None) => {
                 debug!("no news reported: nothing to do");
@@ -727,6 +1121,17 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
                     }
                 }
             }
+            crate::Event::SyncBatchApplied { namespace, count } => {
+                debug!(namespace=%namespace.fmt_short(), %count, "replica event: SyncBatchApplied");
+            }
+            crate::Event::ContentStatusChanged {
+                namespace, status, ..
+            } => {
+                debug!(namespace=%namespace.fmt_short(), ?status, "replica event: ContentStatusChanged");
+            }
+            crate::Event::Closed { namespace } => {
+                debug!(namespace=%namespace.fmt_short(), "replica event: Closed");
+            }
         }
 
         Ok(())
@@ -746,18 +1151,35 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         }
         if self.queued_hashes.contains_hash(&hash) {
             self.queued_hashes.insert(hash, namespace);
-            self.downloader.nodes_have(hash, vec![node]).await;
+            if !self.download_limiter.add_pending_node(&hash, node) {
+                self.downloader.nodes_have(hash, vec![node]).await;
+            }
         } else if !only_if_missing || self.missing_hashes.contains(&hash) {
-            let req = DownloadRequest::new(HashAndFormat::raw(hash), vec![node]);
-            let handle = self.downloader.queue(req).await;
-
             self.queued_hashes.insert(hash, namespace);
             self.missing_hashes.remove(&hash);
-            self.download_tasks
-                .spawn(async move { (namespace, hash, handle.await) });
+            if self.download_limiter.try_start(namespace) {
+                self.queue_download(namespace, hash, vec![node]).await;
+            } else {
+                debug!(namespace=%namespace.fmt_short(), %hash, "download limit reached, queue download");
+                self.download_limiter.push_pending(namespace, hash, node);
+            }
+        }
+    }
+
+    /// Start downloads that were held back by the [`DownloadLimiter`] and may start now.
+    async fn start_pending_downloads(&mut self) {
+        while let Some((namespace, hash, nodes)) = self.download_limiter.next_ready() {
+            self.queue_download(namespace, hash, nodes).await;
         }
     }
 
+    async fn queue_download(&mut self, namespace: NamespaceId, hash: Hash, nodes: Vec<NodeId>) {
+        let req = DownloadRequest::new(HashAndFormat::raw(hash), nodes);
+        let handle = self.downloader.queue(req).await;
+        self.download_tasks
+            .spawn(async move { (namespace, hash, handle.await) });
+    }
+
     #[instrument("accept", skip_all)]
     pub async fn handle_connection(&mut self, conn: iroh::endpoint::Connecting) {
         let to_actor_tx = self.sync_actor_tx.clone();
@@ -787,9 +1209,23 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
         };
         debug!("incoming connection");
         let sync = self.sync.clone();
+        let policy = self.accept_policy.clone();
+        let mut conn = conn;
         self.running_sync_accept.spawn(
-            async move { handle_connection(sync, conn, accept_request_cb).await }
-                .instrument(Span::current()),
+            async move {
+                match conn.alpn().await {
+                    Ok(alpn) if alpn == ALPN_MULTI => {
+                        match handle_connection_many(sync, conn, policy, accept_request_cb).await {
+                            Ok(results) => results.into_iter().map(|(_, res)| res).collect(),
+                            Err(err) => vec![Err(err)],
+                        }
+                    }
+                    _ => vec![
+                        handle_connection_with_policy(sync, conn, policy, accept_request_cb).await,
+                    ],
+                }
+            }
+            .instrument(Span::current()),
         );
     }
 
@@ -803,6 +1239,76 @@ impl<B: iroh_blobs::store::Store> LiveActor<B> {
     }
 }
 
+/// Copy the error of a multiplexed sync which failed as a whole, to report it for each namespace.
+fn copy_connect_error(err: &ConnectError) -> ConnectError {
+    let error = anyhow::anyhow!("{err:#}");
+    match err {
+        ConnectError::Connect { .. } => ConnectError::Connect { error },
+        ConnectError::RemoteAbort(reason) => ConnectError::RemoteAbort(*reason),
+        ConnectError::Sync { .. } => ConnectError::Sync { error },
+        ConnectError::Close { .. } => ConnectError::Close { error },
+    }
+}
+
+/// Callback invoked with the namespace, the peer and the reason when a sync with a peer failed.
+///
+/// See [`crate::engine::Engine::set_sync_failure_observer`].
+pub type SyncFailureObserver =
+    Arc<dyn Fn(NamespaceId, PeerIdBytes, SyncFailureReason) + Send + Sync + 'static>;
+
+/// Reason why a sync with a peer failed.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub enum SyncFailureReason {
+    /// The peer sent entries which failed validation, e.g. because of a bad signature.
+    ///
+    /// The sync itself completed, and the valid entries were inserted.
+    Validation {
+        /// Number of entries which failed validation.
+        num_invalid: usize,
+    },
+    /// The connection to the peer failed or was closed.
+    Transport,
+    /// The peer sent a message which violates the sync protocol.
+    Protocol,
+}
+
+impl SyncFailureReason {
+    /// Categorize the error of an outgoing sync.
+    ///
+    /// Returns `None` if the remote aborted the sync, because a peer declining to sync is not
+    /// a failure.
+    fn from_connect_error(err: &ConnectError) -> Option<Self> {
+        match err {
+            ConnectError::Connect { .. } | ConnectError::Close { .. } => Some(Self::Transport),
+            ConnectError::Sync { error } => Some(Self::from_sync_error(error)),
+            ConnectError::RemoteAbort(_) => None,
+        }
+    }
+
+    /// Categorize the error of an incoming sync.
+    ///
+    /// Returns `None` if the failure was on our side, i.e. we could not open the replica or
+    /// aborted the sync ourselves.
+    fn from_accept_error(err: &AcceptError) -> Option<Self> {
+        match err {
+            AcceptError::Connect { .. } | AcceptError::Close { .. } => Some(Self::Transport),
+            AcceptError::Sync { error, .. } => Some(Self::from_sync_error(error)),
+            AcceptError::Open { .. } | AcceptError::Abort { .. } => None,
+        }
+    }
+
+    fn from_sync_error(error: &anyhow::Error) -> Self {
+        let is_transport = error
+            .chain()
+            .any(|err| err.is::<std::io::Error>() || err.is::<iroh::endpoint::ConnectionError>());
+        if is_transport {
+            Self::Transport
+        } else {
+            Self::Protocol
+        }
+    }
+}
+
 /// Event emitted when a sync operation completes
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
 pub struct SyncEvent {
@@ -824,6 +1330,8 @@ pub struct SyncDetails {
     pub entries_received: usize,
     /// Number of entries sent
     pub entries_sent: usize,
+    /// Whether both peers were already in sync and no entries had to be exchanged
+    pub already_in_sync: bool,
 }
 
 impl From<&SyncFinished> for SyncDetails {
@@ -831,6 +1339,7 @@ impl From<&SyncFinished> for SyncDetails {
         Self {
             entries_received: value.outcome.num_recv,
             entries_sent: value.outcome.num_sent,
+            already_in_sync: value.outcome.already_in_sync,
         }
     }
 }
@@ -864,6 +1373,30 @@ impl SubscribersMap {
     }
 }
 
+/// Read the blob `hash` from `store` if it is complete and not longer than `max_len`.
+async fn read_blob<B: iroh_blobs::store::Store>(
+    store: B,
+    hash: Hash,
+    max_len: u64,
+) -> Result<Option<Bytes>> {
+    let Some(entry) = store.get(&hash).await? else {
+        return Ok(None);
+    };
+    let size = entry.size().value();
+    if !entry.is_complete() || size > max_len {
+        return Ok(None);
+    }
+    let mut reader = entry.data_reader().await?;
+    let bytes = reader.read_at(0, size as usize).await?;
+    Ok(Some(bytes))
+}
+
+/// Returns `true` if `bytes` is the manifest of [`ContentChunks`].
+fn is_manifest(bytes: &[u8]) -> bool {
+    // Only the canonical encoding of a chunk list is the manifest of a chunked entry.
+    matches!(ContentChunks::from_manifest(bytes), Ok(chunks) if chunks.to_manifest() == bytes)
+}
+
 #[derive(Debug, Default)]
 struct QueuedHashes {
     by_hash: HashMap<Hash, HashSet<NamespaceId>>,
@@ -894,6 +1427,14 @@ impl QueuedHashes {
         removed_namespaces
     }
 
+    /// Get the namespaces which queued a hash.
+    fn namespaces(&self, hash: &Hash) -> Vec<NamespaceId> {
+        self.by_hash
+            .get(hash)
+            .map(|namespaces| namespaces.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
     fn contains_hash(&self, hash: &Hash) -> bool {
         self.by_hash.contains_key(hash)
     }
@@ -903,6 +1444,96 @@ impl QueuedHashes {
     }
 }
 
+/// Limits the number of content downloads in flight per namespace.
+///
+/// Downloads exceeding the limit are held back, in the order they were requested, until a
+/// download of the same namespace finished.
+#[derive(Debug, Default)]
+struct DownloadLimiter {
+    /// Maximum number of downloads in flight per namespace, unlimited if `None`.
+    max_in_flight: Option<usize>,
+    in_flight: HashMap<NamespaceId, usize>,
+    pending: HashMap<NamespaceId, VecDeque<Hash>>,
+    /// Nodes to download each pending hash from.
+    pending_nodes: HashMap<Hash, Vec<NodeId>>,
+}
+
+impl DownloadLimiter {
+    fn set_max_in_flight(&mut self, max: Option<usize>) {
+        self.max_in_flight = max;
+    }
+
+    fn has_capacity(&self, namespace: &NamespaceId) -> bool {
+        match self.max_in_flight {
+            None => true,
+            Some(max) => self.in_flight(namespace) < max,
+        }
+    }
+
+    fn in_flight(&self, namespace: &NamespaceId) -> usize {
+        self.in_flight.get(namespace).copied().unwrap_or_default()
+    }
+
+    /// Count a new download for `namespace` as in flight, if the limit allows it.
+    ///
+    /// Returns `false` if the download has to be held back with [`Self::push_pending`]. New
+    /// downloads also wait while older downloads of the namespace are pending.
+    fn try_start(&mut self, namespace: NamespaceId) -> bool {
+        if !self.has_capacity(&namespace) || self.pending.contains_key(&namespace) {
+            return false;
+        }
+        *self.in_flight.entry(namespace).or_default() += 1;
+        true
+    }
+
+    /// Hold back a download of `hash` from `node` until the limit allows it.
+    fn push_pending(&mut self, namespace: NamespaceId, hash: Hash, node: NodeId) {
+        self.pending.entry(namespace).or_default().push_back(hash);
+        self.pending_nodes.entry(hash).or_default().push(node);
+    }
+
+    /// Add a node to download a held back hash from.
+    ///
+    /// Returns `false` if the hash is not held back.
+    fn add_pending_node(&mut self, hash: &Hash, node: NodeId) -> bool {
+        match self.pending_nodes.get_mut(hash) {
+            Some(nodes) => {
+                if !nodes.contains(&node) {
+                    nodes.push(node);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Mark a download of `namespace` as finished.
+    fn finish(&mut self, namespace: &NamespaceId) {
+        if let Some(count) = self.in_flight.get_mut(namespace) {
+            *count -= 1;
+            if *count == 0 {
+                self.in_flight.remove(namespace);
+            }
+        }
+    }
+
+    /// Take the next held back download which may start now, counting it as in flight.
+    fn next_ready(&mut self) -> Option<(NamespaceId, Hash, Vec<NodeId>)> {
+        let namespace = *self
+            .pending
+            .keys()
+            .find(|namespace| self.has_capacity(namespace))?;
+        let queue = self.pending.get_mut(&namespace).expect("just found");
+        let hash = queue.pop_front().expect("empty queues are removed");
+        if queue.is_empty() {
+            self.pending.remove(&namespace);
+        }
+        let nodes = self.pending_nodes.remove(&hash).unwrap_or_default();
+        *self.in_flight.entry(namespace).or_default() += 1;
+        Some((namespace, hash, nodes))
+    }
+}
+
 #[derive(Debug, Default)]
 struct Subscribers(Vec<async_channel::Sender<Event>>);
 
@@ -960,4 +1591,75 @@ mod tests {
         drop(b_rx);
         subscribers.send(Event::NeighborUp(pk)).await;
     }
+
+    /// Downloader stand-in that records the downloads in flight per namespace.
+    #[derive(Debug, Default)]
+    struct MockDownloader {
+        running: Vec<(NamespaceId, Hash)>,
+        max_in_flight: HashMap<NamespaceId, usize>,
+        completed: usize,
+    }
+
+    impl MockDownloader {
+        fn queue(&mut self, namespace: NamespaceId, hash: Hash) {
+            self.running.push((namespace, hash));
+            let in_flight = self
+                .running
+                .iter()
+                .filter(|(ns, _)| *ns == namespace)
+                .count();
+            let max = self.max_in_flight.entry(namespace).or_default();
+            *max = (*max).max(in_flight);
+        }
+    }
+
+    #[test]
+    fn test_download_limiter() {
+        use rand::{Rng, SeedableRng};
+
+        const MAX: usize = 3;
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let namespaces = [NamespaceId::from(&[1; 32]), NamespaceId::from(&[2; 32])];
+        let node = PublicKey::from_bytes(&[1; 32]).unwrap();
+        let mut limiter = DownloadLimiter::default();
+        limiter.set_max_in_flight(Some(MAX));
+        let mut downloader = MockDownloader::default();
+
+        let total = 200;
+        for i in 0..total {
+            let namespace = namespaces[i % namespaces.len()];
+            let hash = Hash::new(i.to_be_bytes());
+            if limiter.try_start(namespace) {
+                downloader.queue(namespace, hash);
+            } else {
+                limiter.push_pending(namespace, hash, node);
+            }
+            // finish some of the running downloads
+            while !downloader.running.is_empty() && rng.gen_bool(0.4) {
+                let i = rng.gen_range(0..downloader.running.len());
+                let (namespace, _hash) = downloader.running.swap_remove(i);
+                downloader.completed += 1;
+                limiter.finish(&namespace);
+                while let Some((namespace, hash, nodes)) = limiter.next_ready() {
+                    assert_eq!(nodes, vec![node]);
+                    downloader.queue(namespace, hash);
+                }
+            }
+        }
+        while let Some((namespace, _hash)) = downloader.running.pop() {
+            downloader.completed += 1;
+            limiter.finish(&namespace);
+            while let Some((namespace, hash, _nodes)) = limiter.next_ready() {
+                downloader.queue(namespace, hash);
+            }
+        }
+
+        assert_eq!(downloader.completed, total);
+        for namespace in namespaces {
+            assert_eq!(downloader.max_in_flight[&namespace], MAX);
+            assert_eq!(limiter.in_flight(&namespace), 0);
+        }
+        assert!(limiter.pending.is_empty());
+        assert!(limiter.pending_nodes.is_empty());
+    }
 }
@@ -1,10 +1,11 @@
 use std::{
-    collections::BTreeMap,
-    time::{Instant, SystemTime},
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::Result;
 use iroh::NodeId;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use tracing::{debug, warn};
 
@@ -24,6 +25,10 @@ pub enum SyncReason {
     SyncReport,
     /// We received a sync report while a sync was running, so run again afterwars
     Resync,
+    /// Live sync for the namespace was resumed after being paused
+    Resume,
+    /// A previous sync failed and is retried according to the [`RetryPolicy`]
+    Retry,
 }
 
 /// Why we performed a sync exchange
@@ -35,6 +40,413 @@ pub enum Origin {
     Accept,
 }
 
+/// Scheduling configuration for change-triggered syncs of a namespace.
+///
+/// Syncs triggered by changes announced by our neighbors are coalesced: a sync is only started
+/// once no further change was announced for `min_interval`. To not fall behind on documents that
+/// change constantly, a sync is forced once a change has been pending for `max_staleness`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub struct SyncSchedule {
+    /// Time to wait for further changes before starting a sync.
+    pub min_interval: Duration,
+    /// Maximum time a change may stay pending before a sync is forced.
+    pub max_staleness: Duration,
+}
+
+impl SyncSchedule {
+    /// Create a new schedule.
+    pub fn new(min_interval: Duration, max_staleness: Duration) -> Self {
+        Self {
+            min_interval,
+            max_staleness,
+        }
+    }
+}
+
+/// Retry policy for failed syncs with a peer.
+///
+/// After a sync with a peer failed, it is retried after a backoff which starts at
+/// `initial_backoff` and doubles with each consecutive failure, up to `max_backoff`. Each
+/// backoff is reduced by a random fraction of up to `jitter`, so that peers which failed at the
+/// same time are not retried at the same time. A successful sync resets the backoff.
+///
+/// While backing off, no other syncs with the peer are started for the namespace, except for
+/// those requested explicitly with a join.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct RetryPolicy {
+    /// Backoff after the first failure.
+    pub initial_backoff: Duration,
+    /// Maximum backoff.
+    pub max_backoff: Duration,
+    /// Fraction of the backoff, between 0 and 1, by which each backoff is randomly reduced.
+    ///
+    /// Values outside of the range are clamped, and non-finite values disable the jitter.
+    #[serde(deserialize_with = "deserialize_jitter")]
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5 * 60),
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Create a new retry policy with the default jitter.
+    pub fn new(initial_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            initial_backoff,
+            max_backoff,
+            ..Default::default()
+        }
+    }
+
+    /// Set the jitter, see [`Self::jitter`].
+    pub fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = sanitize_jitter(jitter);
+        self
+    }
+
+    /// Returns the backoff after `failures` consecutive failures, without jitter.
+    pub fn backoff(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+
+    fn backoff_with_jitter(&self, failures: u32, rng: &mut impl Rng) -> Duration {
+        let backoff = self.backoff(failures);
+        let jitter = sanitize_jitter(self.jitter);
+        if jitter == 0. {
+            return backoff;
+        }
+        backoff.mul_f64(1. - jitter * rng.gen::<f64>())
+    }
+}
+
+/// Clamp `jitter` to the range from 0 to 1, mapping non-finite values to 0.
+fn sanitize_jitter(jitter: f64) -> f64 {
+    if jitter.is_finite() {
+        jitter.clamp(0., 1.)
+    } else {
+        0.
+    }
+}
+
+fn deserialize_jitter<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    f64::deserialize(deserializer).map(sanitize_jitter)
+}
+
+/// Tracks consecutive sync failures per namespace and node, and the retries scheduled for them
+/// according to the [`RetryPolicy`].
+///
+/// Like the [`SyncScheduler`], all methods take the current time as argument.
+#[derive(Debug, Default)]
+pub struct SyncBackoff {
+    policy: Option<RetryPolicy>,
+    failed: BTreeMap<(NamespaceId, NodeId), FailedSync>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct FailedSync {
+    failures: u32,
+    retry_at: Instant,
+    retry_pending: bool,
+}
+
+impl SyncBackoff {
+    /// Set or clear the retry policy.
+    ///
+    /// Clearing the policy drops all scheduled retries.
+    pub fn set_policy(&mut self, policy: Option<RetryPolicy>) {
+        if policy.is_none() {
+            self.failed.clear();
+        }
+        self.policy = policy;
+    }
+
+    /// Record a failed sync with `node`.
+    ///
+    /// Returns the time at which the sync should be retried, or `None` if no retry policy is set.
+    pub fn failed(
+        &mut self,
+        namespace: NamespaceId,
+        node: NodeId,
+        now: Instant,
+        rng: &mut impl Rng,
+    ) -> Option<Instant> {
+        let policy = self.policy?;
+        let failed = self.failed.entry((namespace, node)).or_insert(FailedSync {
+            failures: 0,
+            retry_at: now,
+            retry_pending: false,
+        });
+        failed.failures = failed.failures.saturating_add(1);
+        failed.retry_at = now + policy.backoff_with_jitter(failed.failures, rng);
+        failed.retry_pending = true;
+        Some(failed.retry_at)
+    }
+
+    /// Record a successful sync with `node`, which resets the backoff.
+    pub fn succeeded(&mut self, namespace: &NamespaceId, node: &NodeId) {
+        self.failed.remove(&(*namespace, *node));
+    }
+
+    /// Returns true if syncs with `node` should not be started because of previous failures.
+    pub fn is_backing_off(&self, namespace: &NamespaceId, node: &NodeId, now: Instant) -> bool {
+        self.failed
+            .get(&(*namespace, *node))
+            .is_some_and(|failed| failed.retry_at > now)
+    }
+
+    /// Returns the number of consecutive failed syncs with `node`.
+    pub fn failures(&self, namespace: &NamespaceId, node: &NodeId) -> u32 {
+        self.failed
+            .get(&(*namespace, *node))
+            .map_or(0, |failed| failed.failures)
+    }
+
+    /// Remove all failures recorded for a namespace.
+    pub fn remove_namespace(&mut self, namespace: &NamespaceId) {
+        self.failed.retain(|(ns, _), _| ns != namespace);
+    }
+
+    /// Returns the time at which the next retry becomes due.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.failed
+            .values()
+            .filter(|failed| failed.retry_pending)
+            .map(|failed| failed.retry_at)
+            .min()
+    }
+
+    /// Take all retries which are due at `now`.
+    ///
+    /// The failures are kept until the retried sync finished, so that the backoff keeps growing
+    /// if the retry fails again.
+    pub fn take_due(&mut self, now: Instant) -> Vec<(NamespaceId, NodeId)> {
+        self.failed
+            .iter_mut()
+            .filter(|(_, failed)| failed.retry_pending && failed.retry_at <= now)
+            .map(|(key, failed)| {
+                failed.retry_pending = false;
+                *key
+            })
+            .collect()
+    }
+}
+
+/// Configuration for the heads gossip mode of the engine.
+///
+/// In this mode, the engine broadcasts the [`crate::AuthorHeads`] of each syncing document to
+/// its gossip neighbors every `interval`, and does not start a full sync when a new neighbor
+/// shows up. A sync with a neighbor is only started once the heads received from it show that
+/// it has entries we do not have. This avoids reconciling large documents which are already in
+/// sync.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Eq, PartialEq)]
+pub struct HeadsGossip {
+    /// Time between two broadcasts of the heads of a document.
+    pub interval: Duration,
+}
+
+impl Default for HeadsGossip {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(30),
+        }
+    }
+}
+
+impl HeadsGossip {
+    /// Create a new config which broadcasts the heads every `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self { interval }
+    }
+}
+
+/// Tracks when the heads of each syncing namespace are broadcast next, see [`HeadsGossip`].
+///
+/// Like the [`SyncScheduler`], all methods take the current time as argument.
+#[derive(Debug, Default)]
+pub struct HeadsGossipTimer {
+    config: Option<HeadsGossip>,
+    next: HashMap<NamespaceId, Instant>,
+}
+
+impl HeadsGossipTimer {
+    /// Enable heads gossip with `config`, or disable it with `None`.
+    pub fn set_config(&mut self, config: Option<HeadsGossip>, now: Instant) {
+        self.config = config;
+        if let Some(config) = config {
+            for next in self.next.values_mut() {
+                *next = now + config.interval;
+            }
+        }
+    }
+
+    /// Whether heads gossip is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.config.is_some()
+    }
+
+    /// Start broadcasting the heads of a namespace, the first time after one interval.
+    pub fn insert(&mut self, namespace: NamespaceId, now: Instant) {
+        let interval = self.config.unwrap_or_default().interval;
+        self.next.insert(namespace, now + interval);
+    }
+
+    /// Stop broadcasting the heads of a namespace.
+    pub fn remove(&mut self, namespace: &NamespaceId) {
+        self.next.remove(namespace);
+    }
+
+    /// Returns the time at which the next broadcast becomes due.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.config?;
+        self.next.values().min().copied()
+    }
+
+    /// Return all namespaces whose heads are due to be broadcast at `now`.
+    ///
+    /// The next broadcast of the returned namespaces is scheduled one interval after `now`.
+    pub fn take_due(&mut self, now: Instant) -> Vec<NamespaceId> {
+        let Some(config) = self.config else {
+            return vec![];
+        };
+        self.next
+            .iter_mut()
+            .filter(|(_, next)| **next <= now)
+            .map(|(namespace, next)| {
+                *next = now + config.interval;
+                *namespace
+            })
+            .collect()
+    }
+}
+
+/// Coalesces change-triggered syncs according to the [`SyncSchedule`] of each namespace.
+///
+/// All methods take the current time as argument, so that the scheduler does not depend on the
+/// system clock.
+#[derive(Debug, Default)]
+pub struct SyncScheduler {
+    schedules: HashMap<NamespaceId, SyncSchedule>,
+    pending: BTreeMap<(NamespaceId, NodeId), PendingSync>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PendingSync {
+    first: Instant,
+    last: Instant,
+}
+
+impl PendingSync {
+    fn due(&self, schedule: &SyncSchedule) -> Instant {
+        std::cmp::min(
+            self.last + schedule.min_interval,
+            self.first + schedule.max_staleness,
+        )
+    }
+}
+
+impl SyncScheduler {
+    /// Set or clear the schedule for a namespace.
+    ///
+    /// Clearing the schedule drops all pending syncs for the namespace, the caller is expected
+    /// to start them right away.
+    pub fn set_schedule(
+        &mut self,
+        namespace: NamespaceId,
+        schedule: Option<SyncSchedule>,
+    ) -> Vec<NodeId> {
+        match schedule {
+            Some(schedule) => {
+                self.schedules.insert(namespace, schedule);
+                vec![]
+            }
+            None => {
+                self.schedules.remove(&namespace);
+                self.remove_namespace(&namespace)
+            }
+        }
+    }
+
+    /// Request a change-triggered sync with `node`.
+    ///
+    /// Returns true if the sync should be started right away, and false if it was queued.
+    pub fn request(&mut self, namespace: NamespaceId, node: NodeId, now: Instant) -> bool {
+        let Some(schedule) = self.schedules.get(&namespace) else {
+            return true;
+        };
+        let pending = self
+            .pending
+            .entry((namespace, node))
+            .and_modify(|p| p.last = now)
+            .or_insert(PendingSync {
+                first: now,
+                last: now,
+            });
+        if pending.due(schedule) <= now {
+            self.pending.remove(&(namespace, node));
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Remove a pending sync, because a sync with the node was started.
+    pub fn clear(&mut self, namespace: &NamespaceId, node: &NodeId) {
+        self.pending.remove(&(*namespace, *node));
+    }
+
+    /// Remove all pending syncs for a namespace.
+    ///
+    /// Returns the nodes for which a sync was pending.
+    pub fn remove_namespace(&mut self, namespace: &NamespaceId) -> Vec<NodeId> {
+        let nodes: Vec<_> = self
+            .pending
+            .keys()
+            .filter(|(ns, _)| ns == namespace)
+            .map(|(_, node)| *node)
+            .collect();
+        for node in nodes.iter() {
+            self.pending.remove(&(*namespace, *node));
+        }
+        nodes
+    }
+
+    /// Returns the time at which the next pending sync becomes due.
+    pub fn next_due(&self) -> Option<Instant> {
+        self.pending
+            .iter()
+            .filter_map(|((namespace, _), p)| Some(p.due(self.schedules.get(namespace)?)))
+            .min()
+    }
+
+    /// Remove and return all pending syncs which are due at `now`.
+    pub fn take_due(&mut self, now: Instant) -> Vec<(NamespaceId, NodeId)> {
+        let due: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|((namespace, _), p)| {
+                self.schedules
+                    .get(namespace)
+                    .map_or(true, |schedule| p.due(schedule) <= now)
+            })
+            .map(|(key, _)| *key)
+            .collect();
+        for key in due.iter() {
+            self.pending.remove(key);
+        }
+        due
+    }
+}
+
 /// The state we're in for a node and a namespace
 #[derive(Debug, Clone)]
 pub enum SyncState {
@@ -57,6 +469,7 @@ pub struct NamespaceStates(BTreeMap<NamespaceId, NamespaceState>);
 struct NamespaceState {
     nodes: BTreeMap<NodeId, PeerState>,
     may_emit_ready: bool,
+    paused: bool,
 }
 
 impl NamespaceStates {
@@ -70,6 +483,40 @@ impl NamespaceStates {
         self.0.entry(namespace).or_default();
     }
 
+    /// Pause the live sync of a namespace.
+    ///
+    /// While paused, no sync requests are started or accepted. The nodes we know for the
+    /// namespace are kept, so that they can be synced with once the namespace is resumed.
+    ///
+    /// Returns false if the namespace is not syncing.
+    pub fn pause(&mut self, namespace: &NamespaceId) -> bool {
+        match self.0.get_mut(namespace) {
+            None => false,
+            Some(state) => {
+                state.paused = true;
+                true
+            }
+        }
+    }
+
+    /// Resume the live sync of a paused namespace.
+    ///
+    /// Returns the nodes known for the namespace, or `None` if the namespace is not syncing or
+    /// was not paused.
+    pub fn resume(&mut self, namespace: &NamespaceId) -> Option<Vec<NodeId>> {
+        let state = self.0.get_mut(namespace)?;
+        if !state.paused {
+            return None;
+        }
+        state.paused = false;
+        Some(state.nodes.keys().copied().collect())
+    }
+
+    /// Is the live sync of this namespace paused?
+    pub fn is_paused(&self, namespace: &NamespaceId) -> bool {
+        self.0.get(namespace).is_some_and(|state| state.paused)
+    }
+
     /// Start a sync request.
     ///
     /// Returns true if the request should be performed, and false if it should be aborted.
@@ -79,11 +526,16 @@ impl NamespaceStates {
         node: NodeId,
         reason: SyncReason,
     ) -> bool {
+        let paused = self.is_paused(namespace);
         match self.entry(namespace, node) {
             None => {
                 debug!("abort connect: namespace is not in sync set");
                 false
             }
+            Some(_) if paused => {
+                debug!("abort connect: namespace is paused");
+                false
+            }
             Some(state) => state.start_connect(reason),
         }
     }
@@ -97,9 +549,13 @@ impl NamespaceStates {
         namespace: &NamespaceId,
         node: NodeId,
     ) -> AcceptOutcome {
+        let paused = self.is_paused(namespace);
         let Some(state) = self.entry(namespace, node) else {
             return AcceptOutcome::Reject(AbortReason::NotFound);
         };
+        if paused {
+            return AcceptOutcome::Reject(AbortReason::Paused);
+        }
         state.accept_request(me, &node)
     }
 
@@ -258,3 +714,154 @@ fn expected_sync_direction(self_node_id: &NodeId, other_node_id: &NodeId) -> Syn
         SyncDirection::Connect
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sync_scheduler_coalesce_and_force() {
+        let namespace = NamespaceId::from(&[1u8; 32]);
+        let node = iroh::SecretKey::from_bytes(&[2u8; 32]).public();
+        let schedule = SyncSchedule::new(Duration::from_secs(5), Duration::from_secs(20));
+        let mut scheduler = SyncScheduler::default();
+
+        // without a schedule, syncs start right away
+        let t0 = Instant::now();
+        assert!(scheduler.request(namespace, node, t0));
+        assert_eq!(scheduler.next_due(), None);
+
+        scheduler.set_schedule(namespace, Some(schedule));
+        let at = |secs| t0 + Duration::from_secs(secs);
+
+        // changes within min_interval are coalesced
+        assert!(!scheduler.request(namespace, node, at(0)));
+        assert!(!scheduler.request(namespace, node, at(3)));
+        assert_eq!(scheduler.next_due(), Some(at(8)));
+        assert!(scheduler.take_due(at(7)).is_empty());
+        assert_eq!(scheduler.take_due(at(8)), vec![(namespace, node)]);
+        assert_eq!(scheduler.next_due(), None);
+
+        // a constant stream of changes forces a sync after max_staleness
+        let mut started = vec![];
+        for secs in (10..=40).step_by(2) {
+            if scheduler.request(namespace, node, at(secs)) {
+                started.push(secs);
+            }
+            started.extend(scheduler.take_due(at(secs)).iter().map(|_| secs));
+        }
+        assert_eq!(started, vec![30]);
+        assert_eq!(scheduler.next_due(), Some(at(45)));
+
+        // clearing the schedule releases the pending syncs
+        assert_eq!(scheduler.set_schedule(namespace, None), vec![node]);
+        assert_eq!(scheduler.next_due(), None);
+        assert!(scheduler.request(namespace, node, at(41)));
+    }
+
+    #[test]
+    fn sync_backoff_grows_and_resets() {
+        let namespace = NamespaceId::from(&[1u8; 32]);
+        let node = iroh::SecretKey::from_bytes(&[2u8; 32]).public();
+        let policy =
+            RetryPolicy::new(Duration::from_secs(1), Duration::from_secs(10)).with_jitter(0.);
+        let mut rng = rand::thread_rng();
+        let mut backoff = SyncBackoff::default();
+        let t0 = Instant::now();
+        let at = |secs| t0 + Duration::from_secs(secs);
+
+        // without a policy, failed syncs are not retried
+        assert_eq!(backoff.failed(namespace, node, t0, &mut rng), None);
+        assert!(!backoff.is_backing_off(&namespace, &node, t0));
+
+        backoff.set_policy(Some(policy));
+        // the backoff doubles with each consecutive failure, up to the maximum
+        let mut now = t0;
+        let mut delays = vec![];
+        for _ in 0..6 {
+            let retry_at = backoff.failed(namespace, node, now, &mut rng).unwrap();
+            assert!(backoff.is_backing_off(&namespace, &node, now));
+            assert_eq!(backoff.next_due(), Some(retry_at));
+            assert!(backoff.take_due(now).is_empty());
+            delays.push((retry_at - now).as_secs());
+            now = retry_at;
+            assert!(!backoff.is_backing_off(&namespace, &node, now));
+            assert_eq!(backoff.take_due(now), vec![(namespace, node)]);
+            assert_eq!(backoff.next_due(), None);
+        }
+        assert_eq!(delays, vec![1, 2, 4, 8, 10, 10]);
+        assert_eq!(backoff.failures(&namespace, &node), 6);
+
+        // a successful sync resets the backoff
+        backoff.succeeded(&namespace, &node);
+        assert_eq!(backoff.failures(&namespace, &node), 0);
+        assert_eq!(
+            backoff.failed(namespace, node, at(100), &mut rng),
+            Some(at(101))
+        );
+
+        // jitter reduces the backoff by at most the configured fraction
+        backoff.set_policy(Some(policy.with_jitter(0.5)));
+        backoff.succeeded(&namespace, &node);
+        for _ in 0..3 {
+            let retry_at = backoff.failed(namespace, node, at(200), &mut rng).unwrap();
+            let failures = backoff.failures(&namespace, &node);
+            let max = policy.backoff(failures);
+            assert!(retry_at <= at(200) + max);
+            assert!(retry_at >= at(200) + max / 2);
+        }
+
+        // non-finite jitter disables the jitter instead of panicking
+        assert_eq!(policy.with_jitter(f64::NAN).jitter, 0.);
+        assert_eq!(policy.with_jitter(f64::INFINITY).jitter, 0.);
+        assert_eq!(policy.with_jitter(2.).jitter, 1.);
+        let stored = postcard::to_stdvec(&RetryPolicy {
+            jitter: f64::NAN,
+            ..policy
+        })
+        .unwrap();
+        let decoded: RetryPolicy = postcard::from_bytes(&stored).unwrap();
+        assert_eq!(decoded, policy);
+        let nan_policy = RetryPolicy {
+            jitter: f64::NAN,
+            ..policy
+        };
+        assert_eq!(
+            nan_policy.backoff_with_jitter(2, &mut rng),
+            policy.backoff(2)
+        );
+
+        backoff.remove_namespace(&namespace);
+        assert_eq!(backoff.next_due(), None);
+    }
+
+    #[test]
+    fn heads_gossip_timer() {
+        let a = NamespaceId::from(&[1u8; 32]);
+        let b = NamespaceId::from(&[2u8; 32]);
+        let mut timer = HeadsGossipTimer::default();
+        let t0 = Instant::now();
+        let at = |secs| t0 + Duration::from_secs(secs);
+
+        // disabled by default
+        timer.insert(a, t0);
+        assert!(!timer.is_enabled());
+        assert_eq!(timer.next_due(), None);
+        assert!(timer.take_due(at(100)).is_empty());
+
+        timer.set_config(Some(HeadsGossip::new(Duration::from_secs(10))), t0);
+        assert!(timer.is_enabled());
+        assert_eq!(timer.next_due(), Some(at(10)));
+        timer.insert(b, at(5));
+        assert!(timer.take_due(at(9)).is_empty());
+        assert_eq!(timer.take_due(at(10)), vec![a]);
+        assert_eq!(timer.next_due(), Some(at(15)));
+        assert_eq!(timer.take_due(at(15)), vec![b]);
+        assert_eq!(timer.next_due(), Some(at(20)));
+
+        timer.remove(&a);
+        assert_eq!(timer.next_due(), Some(at(25)));
+        timer.set_config(None, at(30));
+        assert_eq!(timer.next_due(), None);
+    }
+}
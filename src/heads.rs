@@ -14,6 +14,17 @@ pub struct AuthorHeads {
     heads: BTreeMap<AuthorId, Timestamp>,
 }
 
+/// Result of comparing two [`AuthorHeads`], see [`AuthorHeads::compare`].
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
+pub struct HeadComparison {
+    /// Authors for which our latest entry is newer than theirs, or which they do not know.
+    pub we_ahead: Vec<AuthorId>,
+    /// Authors for which their latest entry is newer than ours, or which we do not know.
+    pub they_ahead: Vec<AuthorId>,
+    /// Whether the latest timestamps are the same for all authors.
+    pub in_sync: bool,
+}
+
 impl AuthorHeads {
     /// Insert a new timestamp.
     pub fn insert(&mut self, author: AuthorId, timestamp: Timestamp) {
@@ -53,6 +64,28 @@ impl AuthorHeads {
         NonZeroU64::new(updates)
     }
 
+    /// Compare our heads with the heads of a remote.
+    ///
+    /// The authors in the returned lists are sorted by [`AuthorId`].
+    pub fn compare(&self, remote: &Self) -> HeadComparison {
+        let we_ahead: Vec<_> = self
+            .iter()
+            .filter(|(author, ours)| remote.get(author).map_or(true, |theirs| **ours > theirs))
+            .map(|(author, _)| *author)
+            .collect();
+        let they_ahead: Vec<_> = remote
+            .iter()
+            .filter(|(author, theirs)| self.get(author).map_or(true, |ours| **theirs > ours))
+            .map(|(author, _)| *author)
+            .collect();
+        let in_sync = we_ahead.is_empty() && they_ahead.is_empty();
+        HeadComparison {
+            we_ahead,
+            they_ahead,
+            in_sync,
+        }
+    }
+
     /// Merge another author head state into this one.
     pub fn merge(&mut self, other: &Self) {
         for (a, t) in other.iter() {
@@ -2,19 +2,25 @@
 
 use std::{cmp::Ordering, fmt, str::FromStr};
 
+use curve25519_dalek::edwards::CompressedEdwardsY;
 use ed25519_dalek::{Signature, SignatureError, Signer, SigningKey, VerifyingKey};
 use rand_core::CryptoRngCore;
 use serde::{Deserialize, Serialize};
+use zeroize::{ZeroizeOnDrop, Zeroizing};
 
 use crate::store::PublicKeyStore;
 
 /// Author key to insert entries in a [`crate::Replica`]
 ///
 /// Internally, an author is a [`SigningKey`] which is used to sign entries.
+/// The secret key is zeroized when the author is dropped.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Author {
     signing_key: SigningKey,
 }
+
+// The signing key zeroizes its secret on drop.
+impl ZeroizeOnDrop for Author {}
 impl Author {
     /// Create a new [`Author`] with a random key.
     pub fn new<R: CryptoRngCore + ?Sized>(rng: &mut R) -> Self {
@@ -86,11 +92,15 @@ impl AuthorPublicKey {
 ///
 /// Holders of this key can insert new entries into a [`crate::Replica`].
 /// Internally, a [`NamespaceSecret`] is a [`SigningKey`] which is used to sign entries.
+/// The secret key is zeroized when the namespace secret is dropped.
 #[derive(Clone, Serialize, Deserialize)]
 pub struct NamespaceSecret {
     signing_key: SigningKey,
 }
 
+// The signing key zeroizes its secret on drop.
+impl ZeroizeOnDrop for NamespaceSecret {}
+
 impl NamespaceSecret {
     /// Create a new [`NamespaceSecret`] with a random key.
     pub fn new<R: CryptoRngCore + ?Sized>(rng: &mut R) -> Self {
@@ -119,6 +129,58 @@ impl NamespaceSecret {
         NamespaceId::from(self.public_key())
     }
 
+    /// Generate a [`NamespaceSecret`] whose [`NamespaceId`] starts with `prefix` in hex.
+    ///
+    /// Keys are generated until the hex encoding of the id starts with `prefix`, which is
+    /// matched case-insensitively. The cost grows exponentially with the length of the prefix:
+    /// each hex character multiplies the expected number of attempts by 16, so a prefix of `n`
+    /// characters needs about `16^n` attempts. Prefixes of more than five or six characters are
+    /// impractical.
+    ///
+    /// Fails with [`VanityError::NotFound`] if no matching key was found within `max_attempts`.
+    pub fn generate_vanity<R: CryptoRngCore + ?Sized>(
+        prefix: &str,
+        rng: &mut R,
+        max_attempts: u64,
+    ) -> Result<Self, VanityError> {
+        let nibbles = prefix
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<_>>>()
+            .filter(|nibbles| nibbles.len() <= 64)
+            .ok_or_else(|| VanityError::InvalidPrefix(prefix.to_string()))?;
+        let matches = |id: &NamespaceId| {
+            nibbles.iter().enumerate().all(|(i, nibble)| {
+                let byte = id.as_bytes()[i / 2];
+                let actual = if i % 2 == 0 { byte >> 4 } else { byte & 0x0f };
+                actual == *nibble
+            })
+        };
+        for _ in 0..max_attempts {
+            let secret = Self::new(rng);
+            if matches(&secret.id()) {
+                return Ok(secret);
+            }
+        }
+        Err(VanityError::NotFound {
+            attempts: max_attempts,
+        })
+    }
+
+    /// Derive a child [`NamespaceSecret`] from this secret and a `label`.
+    ///
+    /// The derivation is deterministic: the same parent and label always yield the same child,
+    /// so a whole tree of namespaces can be recreated from a single root secret. Child
+    /// namespaces are independent namespaces, they are not linked to their parent in any way
+    /// that is visible to other peers.
+    pub fn derive_child(&self, label: &[u8]) -> NamespaceSecret {
+        let key = Zeroizing::new(self.to_bytes());
+        let mut hasher = blake3::Hasher::new_keyed(&key);
+        hasher.update(NAMESPACE_CHILD_DOMAIN);
+        hasher.update(label);
+        NamespaceSecret::from_bytes(hasher.finalize().as_bytes())
+    }
+
     /// Sign a message with this [`NamespaceSecret`] key.
     pub fn sign(&self, msg: &[u8]) -> Signature {
         self.signing_key.sign(msg)
@@ -130,6 +192,9 @@ impl NamespaceSecret {
     }
 }
 
+/// Domain separation tag for [`NamespaceSecret::derive_child`].
+const NAMESPACE_CHILD_DOMAIN: &[u8] = b"iroh-docs/namespace-child/v1";
+
 /// The corresponding [`VerifyingKey`] for a [`NamespaceSecret`].
 /// It is used as an identifier, and can be used to verify [`Signature`]s.
 #[derive(Default, Copy, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, derive_more::From)]
@@ -157,6 +222,66 @@ impl NamespacePublicKey {
     }
 }
 
+/// Collects signatures to verify them in a single batch.
+///
+/// Verifying a batch is considerably faster than verifying each signature on its own, but only
+/// tells whether all signatures in the batch are valid.
+#[derive(Debug, Default)]
+pub(crate) struct BatchVerifier<'a> {
+    messages: Vec<&'a [u8]>,
+    signatures: Vec<Signature>,
+    keys: Vec<VerifyingKey>,
+}
+
+impl<'a> BatchVerifier<'a> {
+    /// Add a signature on `msg` to be verified with an author's public key.
+    pub(crate) fn push_author(
+        &mut self,
+        msg: &'a [u8],
+        signature: &Signature,
+        key: &AuthorPublicKey,
+    ) {
+        self.push(msg, signature, key.0);
+    }
+
+    /// Add a signature on `msg` to be verified with a namespace's public key.
+    pub(crate) fn push_namespace(
+        &mut self,
+        msg: &'a [u8],
+        signature: &Signature,
+        key: &NamespacePublicKey,
+    ) {
+        self.push(msg, signature, key.0);
+    }
+
+    fn push(&mut self, msg: &'a [u8], signature: &Signature, key: VerifyingKey) {
+        self.messages.push(msg);
+        self.signatures.push(*signature);
+        self.keys.push(key);
+    }
+
+    /// Verify all signatures, failing if any one of them is invalid.
+    ///
+    /// Plain batch verification accepts keys and signatures of small order, which
+    /// [`VerifyingKey::verify_strict`] rejects. These are rejected here too, so that a batch
+    /// accepts exactly the signatures which pass [`AuthorPublicKey::verify`] and
+    /// [`NamespacePublicKey::verify`].
+    pub(crate) fn verify(&self) -> Result<(), SignatureError> {
+        if self.keys.iter().any(VerifyingKey::is_weak) {
+            return Err(SignatureError::new());
+        }
+        let weak_signature = self.signatures.iter().any(|signature| {
+            CompressedEdwardsY(*signature.r_bytes())
+                .decompress()
+                .map_or(true, |r| r.is_small_order())
+        });
+        if weak_signature {
+            return Err(SignatureError::new());
+        }
+        ed25519_dalek::verify_batch(&self.messages, &self.signatures, &self.keys)
+    }
+}
+
 impl fmt::Display for Author {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", hex::encode(self.to_bytes()))
@@ -396,6 +521,100 @@ impl AuthorId {
     pub fn fmt_short(&self) -> String {
         hex::encode(self.0).chars().take(10).collect()
     }
+
+    /// Convert to a z-base-32 string with a checksum suffix.
+    ///
+    /// The string encodes the 32 key bytes followed by the first
+    /// [`CHECKSUM_LEN`] bytes of the blake3 hash of the key, so that typos are
+    /// detected when parsing it back with [`Self::from_checked_string`].
+    pub fn to_checked_string(&self) -> String {
+        let mut bytes = [0u8; 32 + CHECKSUM_LEN];
+        bytes[..32].copy_from_slice(&self.0);
+        bytes[32..].copy_from_slice(&checksum(&self.0));
+        zbase32_encode(&bytes)
+    }
+
+    /// Parse a string created with [`Self::to_checked_string`].
+    ///
+    /// Fails if the string is not valid z-base-32, has the wrong length, or if
+    /// the checksum does not match the key.
+    pub fn from_checked_string(s: &str) -> anyhow::Result<Self> {
+        let bytes = zbase32_decode(s)?;
+        let bytes: [u8; 32 + CHECKSUM_LEN] = bytes
+            .try_into()
+            .map_err(|_| anyhow::anyhow!("invalid length"))?;
+        let (key, sum) = bytes.split_at(32);
+        let key: [u8; 32] = key.try_into().expect("length checked");
+        anyhow::ensure!(checksum(&key) == sum, "checksum mismatch");
+        Ok(Self(key))
+    }
+}
+
+/// Error returned from [`NamespaceSecret::generate_vanity`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum VanityError {
+    /// The prefix is not a hex string of at most 64 characters.
+    #[error("invalid vanity prefix: {0:?}")]
+    InvalidPrefix(String),
+    /// No matching key was found within the maximum number of attempts.
+    #[error("no matching key found in {attempts} attempts")]
+    NotFound {
+        /// Number of keys that were generated.
+        attempts: u64,
+    },
+}
+
+/// Number of checksum bytes appended in [`AuthorId::to_checked_string`].
+///
+/// 32 key bytes plus 3 checksum bytes are 280 bits, which encode to exactly
+/// 56 z-base-32 characters without padding.
+const CHECKSUM_LEN: usize = 3;
+
+/// The z-base-32 alphabet.
+const ZBASE32_ALPHABET: &[u8; 32] = b"ybndrfg8ejkmcpqxot1uwisza345h769";
+
+fn checksum(key: &[u8; 32]) -> [u8; CHECKSUM_LEN] {
+    let hash = blake3::hash(key);
+    let mut out = [0u8; CHECKSUM_LEN];
+    out.copy_from_slice(&hash.as_bytes()[..CHECKSUM_LEN]);
+    out
+}
+
+fn zbase32_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            out.push(ZBASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        out.push(ZBASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    out
+}
+
+fn zbase32_decode(s: &str) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(s.len() * 5 / 8);
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for c in s.bytes() {
+        let value = ZBASE32_ALPHABET
+            .iter()
+            .position(|x| *x == c)
+            .ok_or_else(|| anyhow::anyhow!("invalid z-base-32 character {:?}", c as char))?;
+        buffer = (buffer << 5) | value as u16;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Ok(out)
 }
 
 impl NamespaceId {
@@ -490,6 +709,17 @@ impl From<NamespaceSecret> for NamespaceId {
     }
 }
 
+impl From<&Author> for AuthorId {
+    fn from(value: &Author) -> Self {
+        value.id()
+    }
+}
+impl From<&NamespaceSecret> for NamespaceId {
+    fn from(value: &NamespaceSecret) -> Self {
+        value.id()
+    }
+}
+
 impl TryFrom<NamespaceId> for NamespacePublicKey {
     type Error = SignatureError;
     fn try_from(value: NamespaceId) -> Result<Self, Self::Error> {
@@ -519,3 +749,239 @@ impl FromStr for NamespaceId {
         NamespacePublicKey::from_str(s).map(|x| x.into())
     }
 }
+
+/// Identifier of a [`WriteToken`], used to revoke the token.
+#[derive(
+    Default,
+    Clone,
+    Copy,
+    PartialOrd,
+    Ord,
+    Eq,
+    PartialEq,
+    Hash,
+    derive_more::From,
+    derive_more::Into,
+    derive_more::AsRef,
+    Serialize,
+    Deserialize,
+)]
+pub struct WriteTokenId([u8; 16]);
+
+impl WriteTokenId {
+    /// Create a new random [`WriteTokenId`].
+    pub fn new<R: CryptoRngCore + ?Sized>(rng: &mut R) -> Self {
+        let mut bytes = [0u8; 16];
+        rng.fill_bytes(&mut bytes);
+        Self(bytes)
+    }
+
+    /// Convert to byte slice.
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+}
+
+impl fmt::Display for WriteTokenId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", hex::encode(self.0))
+    }
+}
+
+impl fmt::Debug for WriteTokenId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "WriteTokenId({})", self)
+    }
+}
+
+/// A revocable write capability for a namespace.
+///
+/// A write token is issued by the holder of the [`NamespaceSecret`] to a single author, and
+/// allows this author to insert entries into the namespace until the token expires or is revoked,
+/// without having access to the [`NamespaceSecret`]. See [`crate::Replica::insert_with_token`]
+/// and [`crate::Replica::revoke_write_token`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WriteToken {
+    namespace: NamespaceId,
+    author: AuthorId,
+    id: WriteTokenId,
+    expires_at: u64,
+    signature: Signature,
+}
+
+impl WriteToken {
+    /// Issue a new write token for `author`, signed with the `namespace` secret.
+    ///
+    /// `expires_at` is a timestamp in microseconds since the Unix epoch. Entries signed under
+    /// the token are only accepted until `expires_at`, and only if their timestamp is not after
+    /// `expires_at`.
+    pub fn issue<R: CryptoRngCore + ?Sized>(
+        rng: &mut R,
+        namespace: &NamespaceSecret,
+        author: AuthorId,
+        expires_at: u64,
+    ) -> Self {
+        let id = WriteTokenId::new(rng);
+        let bytes = Self::signed_bytes(namespace.id(), author, id, expires_at);
+        let signature = namespace.sign(&bytes);
+        Self {
+            namespace: namespace.id(),
+            author,
+            id,
+            expires_at,
+            signature,
+        }
+    }
+
+    /// Verify that the token was signed by the secret of its namespace.
+    pub fn verify<S: PublicKeyStore>(&self, store: &S) -> Result<(), SignatureError> {
+        let bytes = Self::signed_bytes(self.namespace, self.author, self.id, self.expires_at);
+        self.namespace
+            .public_key(store)?
+            .verify(&bytes, &self.signature)
+    }
+
+    /// The namespace this token grants write access to.
+    pub fn namespace(&self) -> NamespaceId {
+        self.namespace
+    }
+
+    /// The author this token was issued to.
+    pub fn author(&self) -> AuthorId {
+        self.author
+    }
+
+    /// The identifier of this token.
+    pub fn id(&self) -> WriteTokenId {
+        self.id
+    }
+
+    /// The expiry of this token, in microseconds since the Unix epoch.
+    pub fn expires_at(&self) -> u64 {
+        self.expires_at
+    }
+
+    /// Serialize this token to bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        postcard::to_stdvec(self).expect("serialization to vec cannot fail")
+    }
+
+    /// Deserialize a token from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        Ok(postcard::from_bytes(bytes)?)
+    }
+
+    fn signed_bytes(
+        namespace: NamespaceId,
+        author: AuthorId,
+        id: WriteTokenId,
+        expires_at: u64,
+    ) -> Vec<u8> {
+        let fields = (WRITE_TOKEN_DOMAIN, namespace, author, id, expires_at);
+        postcard::to_stdvec(&fields).expect("serialization to vec cannot fail")
+    }
+}
+
+/// Domain separation tag for write token signatures.
+const WRITE_TOKEN_DOMAIN: &str = "iroh-docs/write-token/v1";
+
+#[cfg(test)]
+mod tests {
+    use rand_core::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_namespace_generate_vanity() {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let secret = NamespaceSecret::generate_vanity("a", &mut rng, 1000).unwrap();
+        assert!(secret.id().to_string().starts_with('a'));
+        let secret = NamespaceSecret::generate_vanity("B", &mut rng, 1000).unwrap();
+        assert!(secret.id().to_string().starts_with('b'));
+
+        assert_eq!(
+            NamespaceSecret::generate_vanity("ffffffff", &mut rng, 10).unwrap_err(),
+            VanityError::NotFound { attempts: 10 }
+        );
+        assert!(matches!(
+            NamespaceSecret::generate_vanity("xyz", &mut rng, 10),
+            Err(VanityError::InvalidPrefix(_))
+        ));
+    }
+
+    #[test]
+    fn test_author_id_checked_string_roundtrip() {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        for _ in 0..16 {
+            let id = Author::new(&mut rng).id();
+            let s = id.to_checked_string();
+            assert_eq!(s.len(), 56);
+            assert_eq!(AuthorId::from_checked_string(&s).unwrap(), id);
+        }
+    }
+
+    #[test]
+    fn test_author_id_checked_string_detects_corruption() {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let id = Author::new(&mut rng).id();
+        let s = id.to_checked_string();
+        for i in 0..s.len() {
+            let mut corrupted = s.clone().into_bytes();
+            corrupted[i] = if corrupted[i] == b'y' { b'b' } else { b'y' };
+            let corrupted = String::from_utf8(corrupted).unwrap();
+            let err = AuthorId::from_checked_string(&corrupted).unwrap_err();
+            assert_eq!(err.to_string(), "checksum mismatch", "position {i}");
+        }
+    }
+
+    /// Drop `value` in place and check that `secret` no longer appears in its memory.
+    fn assert_zeroized_on_drop<T: ZeroizeOnDrop>(value: T, secret: &[u8; 32]) {
+        let mut slot = std::mem::MaybeUninit::new(value);
+        // SAFETY: the slot is initialized and not used as a `T` after dropping.
+        let raw = unsafe {
+            slot.assume_init_drop();
+            std::slice::from_raw_parts(slot.as_ptr() as *const u8, std::mem::size_of::<T>())
+        };
+        assert!(!raw.windows(secret.len()).any(|bytes| bytes == secret));
+    }
+
+    #[test]
+    fn test_secrets_zeroized_on_drop() {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let author = Author::new(&mut rng);
+        let secret = author.to_bytes();
+        assert_zeroized_on_drop(author, &secret);
+
+        let namespace = NamespaceSecret::new(&mut rng);
+        let secret = namespace.to_bytes();
+        assert_zeroized_on_drop(namespace, &secret);
+    }
+
+    #[test]
+    fn test_namespace_derive_child() {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let root = NamespaceSecret::new(&mut rng);
+
+        // derivation is deterministic
+        let docs = root.derive_child(b"docs");
+        assert_eq!(docs.to_bytes(), root.derive_child(b"docs").to_bytes());
+        let restored = NamespaceSecret::from_bytes(&root.to_bytes());
+        assert_eq!(restored.derive_child(b"docs").id(), docs.id());
+
+        // different labels and parents yield different namespaces
+        let photos = root.derive_child(b"photos");
+        assert_ne!(docs.id(), photos.id());
+        assert_ne!(docs.id(), root.id());
+        assert_ne!(root.derive_child(b"").id(), root.id());
+        let other_root = NamespaceSecret::new(&mut rng);
+        assert_ne!(other_root.derive_child(b"docs").id(), docs.id());
+
+        // children can be derived further
+        let nested = docs.derive_child(b"photos");
+        assert_ne!(nested.id(), photos.id());
+        assert_eq!(
+            nested.id(),
+            root.derive_child(b"docs").derive_child(b"photos").id()
+        );
+    }
+}
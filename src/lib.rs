@@ -54,6 +54,8 @@ pub mod cli;
 pub mod actor;
 pub mod store;
 pub mod sync;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
 
 mod heads;
 mod keys;
@@ -61,8 +63,8 @@ mod ranger;
 
 #[cfg(feature = "net")]
 #[doc(inline)]
-pub use net::ALPN;
+pub use net::{ALPN, ALPN_MULTI};
 
 #[cfg(feature = "net")]
-pub use self::ticket::DocTicket;
+pub use self::ticket::{DocTicket, DocTicketInfo};
 pub use self::{heads::*, keys::*, sync::*};
@@ -1,5 +1,8 @@
 //! Metrics for iroh-docs
 
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
 use iroh_metrics::{
     core::{Counter, Metric},
     struct_iterable::Iterable,
@@ -31,6 +34,11 @@ pub struct Metrics {
     pub doc_live_tick_running_sync_connect: Counter,
     pub doc_live_tick_running_sync_accept: Counter,
     pub doc_live_tick_pending_downloads: Counter,
+
+    pub store_put_latency: Histogram,
+    pub store_get_exact_latency: Histogram,
+    pub store_get_range_latency: Histogram,
+    pub store_get_fingerprint_latency: Histogram,
 }
 
 impl Default for Metrics {
@@ -74,12 +82,94 @@ impl Default for Metrics {
             doc_live_tick_pending_downloads: Counter::new(
                 "Number of times the live actor processed a pending download",
             ),
+
+            store_put_latency: Histogram::new("Latency of inserting an entry, in seconds"),
+            store_get_exact_latency: Histogram::new(
+                "Latency of getting an entry by author and key, in seconds",
+            ),
+            store_get_range_latency: Histogram::new(
+                "Latency of reading a range of entries for sync, in seconds",
+            ),
+            store_get_fingerprint_latency: Histogram::new(
+                "Latency of computing the fingerprint of a range, in seconds",
+            ),
         }
     }
 }
 
 impl Metric for Metrics {
+    #[cfg(feature = "metrics")]
+    fn new(registry: &mut prometheus_client::registry::Registry) -> Self {
+        // Same as the default implementation, but also registers the histograms.
+        let sub_registry = registry.sub_registry_with_prefix(Self::name());
+        let this = Self::default();
+        for (metric, value) in this.iter() {
+            if let Some(counter) = value.downcast_ref::<Counter>() {
+                sub_registry.register(metric, counter.description, counter.counter.clone());
+            } else if let Some(histogram) = value.downcast_ref::<Histogram>() {
+                sub_registry.register(metric, histogram.description, histogram.histogram.clone());
+            }
+        }
+        this
+    }
+
     fn name() -> &'static str {
         "iroh_docs"
     }
 }
+
+/// Open Metrics histogram to measure the distribution of latencies.
+///
+/// The buckets range from one microsecond to about four seconds.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    /// The actual prometheus histogram.
+    #[cfg(feature = "metrics")]
+    pub histogram: prometheus_client::metrics::histogram::Histogram,
+    /// What this histogram measures.
+    pub description: &'static str,
+}
+
+impl Histogram {
+    /// Constructs a new histogram, based on the given `description`.
+    pub fn new(description: &'static str) -> Self {
+        Histogram {
+            #[cfg(feature = "metrics")]
+            histogram: prometheus_client::metrics::histogram::Histogram::new(
+                prometheus_client::metrics::histogram::exponential_buckets(1e-6, 4.0, 12),
+            ),
+            description,
+        }
+    }
+
+    /// Record a single observation.
+    pub fn observe(&self, _value: f64) {
+        #[cfg(feature = "metrics")]
+        self.histogram.observe(_value);
+    }
+}
+
+/// Records the time from its creation until it is dropped into a histogram of [`Metrics`].
+#[cfg(feature = "metrics")]
+pub(crate) struct LatencyTimer {
+    start: Instant,
+    histogram: fn(&Metrics) -> &Histogram,
+}
+
+#[cfg(feature = "metrics")]
+impl LatencyTimer {
+    pub(crate) fn new(histogram: fn(&Metrics) -> &Histogram) -> Self {
+        Self {
+            start: Instant::now(),
+            histogram,
+        }
+    }
+}
+
+#[cfg(feature = "metrics")]
+impl Drop for LatencyTimer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        Metrics::with_metric(|metrics| (self.histogram)(metrics).observe(elapsed));
+    }
+}
@@ -1,6 +1,7 @@
 //! Network implementation of the iroh-docs protocol
 
 use std::{
+    collections::{BTreeSet, HashSet},
     future::Future,
     time::{Duration, Instant},
 };
@@ -9,21 +10,83 @@ use iroh::{Endpoint, NodeAddr, PublicKey};
 #[cfg(feature = "metrics")]
 use iroh_metrics::inc;
 use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
 use tracing::{debug, error_span, trace, Instrument};
 
 #[cfg(feature = "metrics")]
 use crate::metrics::Metrics;
 use crate::{
     actor::SyncHandle,
-    net::codec::{run_alice, BobState},
+    net::codec::{run_alice, run_alice_many, run_bob_many, BobState},
     NamespaceId, SyncOutcome,
 };
 
 /// The ALPN identifier for the iroh-docs protocol
 pub const ALPN: &[u8] = b"/iroh-sync/1";
 
+/// The ALPN identifier for the multiplexed iroh-docs protocol, see [`connect_and_sync_many`].
+pub const ALPN_MULTI: &[u8] = b"/iroh-sync-multi/1";
+
+/// Maximum number of namespaces synced over a single stream, see [`connect_and_sync_many`].
+///
+/// [`handle_connection_many`] fails the stream if the remote requests more namespaces.
+pub const MAX_NAMESPACES_PER_STREAM: usize = 256;
+
 mod codec;
 
+/// Role of the local peer in a sync exchange run with [`run_sync`].
+#[derive(Debug, Clone, Copy)]
+pub enum SyncRole {
+    /// We start the exchange for a namespace.
+    Initiator {
+        /// The namespace to sync.
+        namespace: NamespaceId,
+        /// The peer on the other end of the stream.
+        peer: PublicKey,
+    },
+    /// We accept an exchange started by the other peer.
+    ///
+    /// Any namespace requested by the other peer is accepted, as long as it is open for sync.
+    Acceptor {
+        /// The peer on the other end of the stream.
+        peer: PublicKey,
+    },
+}
+
+/// Run a sync exchange over any bidirectional byte stream.
+///
+/// This runs the same protocol as [`connect_and_sync`] and [`handle_connection`], but does not
+/// require an iroh connection. The stream is shut down once the exchange completed.
+pub async fn run_sync<S>(
+    sync: &SyncHandle,
+    stream: S,
+    role: SyncRole,
+) -> anyhow::Result<SyncOutcome>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let outcome = match role {
+        SyncRole::Initiator { namespace, peer } => {
+            run_alice(&mut writer, &mut reader, sync, namespace, peer).await?
+        }
+        SyncRole::Acceptor { peer } => {
+            let mut state = BobState::new(peer);
+            state
+                .run(
+                    &mut writer,
+                    &mut reader,
+                    sync.clone(),
+                    |_namespace, _peer| std::future::ready(AcceptOutcome::Allow),
+                )
+                .await?;
+            state.into_outcome()
+        }
+    };
+    writer.shutdown().await?;
+    Ok(outcome)
+}
+
 /// Connect to a peer and sync a replica
 pub async fn connect_and_sync(
     endpoint: &Endpoint,
@@ -94,6 +157,101 @@ pub async fn connect_and_sync(
     Ok(res)
 }
 
+/// Connect to a peer and sync many replicas over a single stream.
+///
+/// The messages of all namespaces are interleaved on one stream, each tagged with its
+/// namespace, which saves a connection setup for each namespace. The peer has to handle
+/// connections on [`ALPN_MULTI`] with [`handle_connection_many`].
+///
+/// At most [`MAX_NAMESPACES_PER_STREAM`] namespaces are synced over one connection, more
+/// namespaces are synced in batches over one connection each.
+///
+/// Returns the result for each namespace, in the order of the namespace ids. Fails as a whole
+/// only if a connection fails.
+pub async fn connect_and_sync_many(
+    endpoint: &Endpoint,
+    sync: &SyncHandle,
+    namespaces: &[NamespaceId],
+    peer: NodeAddr,
+) -> Result<Vec<(NamespaceId, Result<SyncFinished, ConnectError>)>, ConnectError> {
+    let namespaces: Vec<_> = namespaces
+        .iter()
+        .copied()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let mut results = Vec::with_capacity(namespaces.len());
+    for batch in namespaces.chunks(MAX_NAMESPACES_PER_STREAM) {
+        let res = connect_and_sync_batch(endpoint, sync, batch, peer.clone()).await?;
+        results.extend(res);
+    }
+    Ok(results)
+}
+
+/// Sync a batch of at most [`MAX_NAMESPACES_PER_STREAM`] namespaces over a single connection.
+async fn connect_and_sync_batch(
+    endpoint: &Endpoint,
+    sync: &SyncHandle,
+    namespaces: &[NamespaceId],
+    peer: NodeAddr,
+) -> Result<Vec<(NamespaceId, Result<SyncFinished, ConnectError>)>, ConnectError> {
+    let t_start = Instant::now();
+    let peer_id = peer.node_id;
+    trace!("connect");
+    let connection = endpoint
+        .connect(peer, ALPN_MULTI)
+        .await
+        .map_err(ConnectError::connect)?;
+
+    let (mut send_stream, mut recv_stream) =
+        connection.open_bi().await.map_err(ConnectError::connect)?;
+
+    let t_connect = t_start.elapsed();
+    debug!(?t_connect, namespaces = namespaces.len(), "connected");
+
+    let res = run_alice_many(
+        &mut send_stream,
+        &mut recv_stream,
+        sync,
+        namespaces,
+        peer_id,
+    )
+    .await;
+
+    send_stream.finish().map_err(ConnectError::close)?;
+    send_stream.stopped().await.map_err(ConnectError::close)?;
+    recv_stream
+        .read_to_end(0)
+        .await
+        .map_err(ConnectError::close)?;
+
+    let t_process = t_start.elapsed() - t_connect;
+    debug!(?t_connect, ?t_process, ok = res.is_ok(), "done");
+    let timings = Timings {
+        connect: t_connect,
+        process: t_process,
+    };
+    let results = res?
+        .into_iter()
+        .map(|(namespace, res)| {
+            #[cfg(feature = "metrics")]
+            if res.is_ok() {
+                inc!(Metrics, sync_via_connect_success);
+            } else {
+                inc!(Metrics, sync_via_connect_failure);
+            }
+            let res = res.map(|outcome| SyncFinished {
+                namespace,
+                peer: peer_id,
+                outcome,
+                timings: timings.clone(),
+            });
+            (namespace, res)
+        })
+        .collect();
+    Ok(results)
+}
+
 /// Whether we want to accept or reject an incoming sync request.
 #[derive(Debug, Clone)]
 pub enum AcceptOutcome {
@@ -103,12 +261,55 @@ pub enum AcceptOutcome {
     Reject(AbortReason),
 }
 
+/// Which namespaces we accept incoming sync requests for.
+///
+/// The policy is checked before the accept callback of [`handle_connection_with_policy`].
+/// Requests for namespaces which are not allowed are rejected with
+/// [`AbortReason::NotAllowed`], before any reconciliation starts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum AcceptPolicy {
+    /// Accept requests for all namespaces.
+    #[default]
+    AllowAll,
+    /// Only accept requests for the listed namespaces.
+    Allow(HashSet<NamespaceId>),
+    /// Accept requests for all namespaces except the listed ones.
+    Deny(HashSet<NamespaceId>),
+}
+
+impl AcceptPolicy {
+    /// Whether sync requests for `namespace` may be accepted.
+    pub fn allows(&self, namespace: &NamespaceId) -> bool {
+        match self {
+            Self::AllowAll => true,
+            Self::Allow(namespaces) => namespaces.contains(namespace),
+            Self::Deny(namespaces) => !namespaces.contains(namespace),
+        }
+    }
+}
+
 /// Handle an iroh-docs connection and sync all shared documents in the replica store.
 pub async fn handle_connection<F, Fut>(
     sync: SyncHandle,
     connecting: iroh::endpoint::Connecting,
     accept_cb: F,
 ) -> Result<SyncFinished, AcceptError>
+where
+    F: Fn(NamespaceId, PublicKey) -> Fut,
+    Fut: Future<Output = AcceptOutcome>,
+{
+    handle_connection_with_policy(sync, connecting, AcceptPolicy::default(), accept_cb).await
+}
+
+/// Handle an iroh-docs connection, only accepting namespaces allowed by `policy`.
+///
+/// See [`handle_connection`].
+pub async fn handle_connection_with_policy<F, Fut>(
+    sync: SyncHandle,
+    connecting: iroh::endpoint::Connecting,
+    policy: AcceptPolicy,
+    accept_cb: F,
+) -> Result<SyncFinished, AcceptError>
 where
     F: Fn(NamespaceId, PublicKey) -> Fut,
     Fut: Future<Output = AcceptOutcome>,
@@ -127,7 +328,7 @@ where
         debug!(?t_connect, "connection established");
     });
 
-    let mut state = BobState::new(peer);
+    let mut state = BobState::new(peer).with_accept_policy(policy);
     let res = state
         .run(&mut send_stream, &mut recv_stream, sync, accept_cb)
         .instrument(span.clone())
@@ -187,6 +388,81 @@ where
     Ok(res)
 }
 
+/// Handle a multiplexed iroh-docs connection, see [`connect_and_sync_many`].
+///
+/// Each namespace requested by the remote is accepted if `policy` allows it and `accept_cb`
+/// returns [`AcceptOutcome::Allow`]. Returns the result for each requested namespace. Fails as a
+/// whole only if the connection fails.
+pub async fn handle_connection_many<F, Fut>(
+    sync: SyncHandle,
+    connecting: iroh::endpoint::Connecting,
+    policy: AcceptPolicy,
+    accept_cb: F,
+) -> Result<Vec<(NamespaceId, Result<SyncFinished, AcceptError>)>, AcceptError>
+where
+    F: Fn(NamespaceId, PublicKey) -> Fut,
+    Fut: Future<Output = AcceptOutcome>,
+{
+    let t_start = Instant::now();
+    let connection = connecting.await.map_err(AcceptError::connect)?;
+    let peer = connection.remote_node_id().map_err(AcceptError::connect)?;
+    let (mut send_stream, mut recv_stream) = connection
+        .accept_bi()
+        .await
+        .map_err(|e| AcceptError::open(peer, e))?;
+
+    let t_connect = t_start.elapsed();
+    let span = error_span!("accept", peer = %peer.fmt_short());
+    span.in_scope(|| {
+        debug!(?t_connect, "connection established");
+    });
+
+    let res = run_bob_many(
+        &mut send_stream,
+        &mut recv_stream,
+        sync,
+        peer,
+        &policy,
+        accept_cb,
+    )
+    .instrument(span.clone())
+    .await;
+
+    send_stream
+        .finish()
+        .map_err(|error| AcceptError::close(peer, None, error))?;
+    send_stream
+        .stopped()
+        .await
+        .map_err(|error| AcceptError::close(peer, None, error))?;
+
+    let t_process = t_start.elapsed() - t_connect;
+    span.in_scope(|| debug!(?t_connect, ?t_process, ok = res.is_ok(), "done"));
+    let timings = Timings {
+        connect: t_connect,
+        process: t_process,
+    };
+    let results = res?
+        .into_iter()
+        .map(|(namespace, res)| {
+            #[cfg(feature = "metrics")]
+            if res.is_ok() {
+                inc!(Metrics, sync_via_accept_success);
+            } else {
+                inc!(Metrics, sync_via_accept_failure);
+            }
+            let res = res.map(|outcome| SyncFinished {
+                namespace,
+                peer,
+                outcome,
+                timings: timings.clone(),
+            });
+            (namespace, res)
+        })
+        .collect();
+    Ok(results)
+}
+
 /// Details of a finished sync operation.
 #[derive(Debug, Clone)]
 pub struct SyncFinished {
@@ -287,6 +563,10 @@ pub enum AbortReason {
     AlreadySyncing,
     /// We experienced an error while trying to provide the requested resource
     InternalServerError,
+    /// The namespace is not allowed by our [`AcceptPolicy`].
+    NotAllowed,
+    /// Live sync for the namespace is paused.
+    Paused,
 }
 
 impl AcceptError {
@@ -1,10 +1,13 @@
-use std::future::Future;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    future::Future,
+};
 
 use anyhow::{anyhow, ensure};
 use bytes::{Buf, BufMut, BytesMut};
 use futures_util::SinkExt;
 use iroh::PublicKey;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_stream::StreamExt;
 use tokio_util::codec::{Decoder, Encoder, FramedRead, FramedWrite};
@@ -12,7 +15,11 @@ use tracing::{debug, trace, Span};
 
 use crate::{
     actor::SyncHandle,
-    net::{AbortReason, AcceptError, AcceptOutcome, ConnectError},
+    net::{
+        AbortReason, AcceptError, AcceptOutcome, AcceptPolicy, ConnectError,
+        MAX_NAMESPACES_PER_STREAM,
+    },
+    sync::{CompactMessage, MessageLimits, ProtocolMessage},
     NamespaceId, SyncOutcome,
 };
 
@@ -21,6 +28,13 @@ struct SyncCodec;
 
 const MAX_MESSAGE_SIZE: usize = 1024 * 1024 * 1024; // This is likely too large, but lets have some restrictions
 
+/// Protocol version of peers which do not send a version in their [`Message::Init`].
+const PROTOCOL_VERSION_INITIAL: u8 = 1;
+/// Protocol version which adds [`Message::SyncCompact`].
+const PROTOCOL_VERSION_COMPACT: u8 = 2;
+/// Protocol version supported by this implementation.
+const PROTOCOL_VERSION: u8 = PROTOCOL_VERSION_COMPACT;
+
 impl Decoder for SyncCodec {
     type Item = Message;
     type Error = anyhow::Error;
@@ -39,12 +53,55 @@ impl Decoder for SyncCodec {
             return Ok(None);
         }
 
-        let message: Message = postcard::from_bytes(&src[4..4 + frame_len])?;
+        let message = Message::decode(&src[4..4 + frame_len], &MessageLimits::default())?;
         src.advance(4 + frame_len);
         Ok(Some(message))
     }
 }
 
+impl Message {
+    /// Decode a postcard-encoded message, enforcing `limits` on the contained protocol message.
+    ///
+    /// This mirrors the [`Serialize`] impl: postcard encodes the variant index as a varint,
+    /// followed by the fields of the variant.
+    fn decode(bytes: &[u8], limits: &MessageLimits) -> anyhow::Result<Self> {
+        let mut de = postcard::Deserializer::from_bytes(bytes);
+        let message = match u32::deserialize(&mut de)? {
+            0 => {
+                let namespace = NamespaceId::deserialize(&mut de)?;
+                let message = ProtocolMessage::decode_from(&mut de, limits)?;
+                // peers predating protocol versions end the message here
+                let version = de
+                    .finalize()?
+                    .first()
+                    .copied()
+                    .unwrap_or(PROTOCOL_VERSION_INITIAL);
+                Message::Init {
+                    namespace,
+                    message,
+                    version,
+                }
+            }
+            1 => Message::Sync(ProtocolMessage::decode_from(&mut de, limits)?),
+            2 => {
+                let reason = AbortReason::deserialize(&mut de)?;
+                Message::Abort { reason }
+            }
+            3 => Message::SyncCompact(ProtocolMessage::decode_compact_from(&mut de, limits)?),
+            index => return Err(anyhow!("invalid message variant {index}")),
+        };
+        Ok(message)
+    }
+
+    /// Wrap a protocol message, in the compact encoding if `compact` is set.
+    fn sync(message: ProtocolMessage, compact: bool) -> Self {
+        match compact {
+            true => Message::SyncCompact(message),
+            false => Message::Sync(message),
+        }
+    }
+}
+
 impl Encoder<Message> for SyncCodec {
     type Error = anyhow::Error;
 
@@ -73,7 +130,12 @@ impl Encoder<Message> for SyncCodec {
 /// - N Sync messages
 ///
 /// On any error and on success the substream is closed.
-#[derive(Debug, Clone, Serialize, Deserialize)]
+///
+/// The accepting peer replies with [`Message::SyncCompact`] if the dialing peer announced support
+/// for it in its init message. The dialing peer switches to [`Message::SyncCompact`] once it
+/// received one. Peers which predate protocol versions ignore the version in the init message
+/// and only send [`Message::Sync`].
+#[derive(Debug, Clone, Serialize)]
 enum Message {
     /// Init message (sent by the dialing peer)
     Init {
@@ -81,11 +143,121 @@ enum Message {
         namespace: NamespaceId,
         /// Initial message
         message: crate::sync::ProtocolMessage,
+        /// Protocol version of the dialing peer
+        ///
+        /// This must remain the last field, so that older peers can ignore it.
+        version: u8,
     },
     /// Sync messages (sent by both peers)
     Sync(crate::sync::ProtocolMessage),
     /// Abort message (sent by the accepting peer to decline a request)
     Abort { reason: AbortReason },
+    /// Sync messages in the compact encoding (sent by both peers, since protocol version 2)
+    SyncCompact(#[serde(serialize_with = "serialize_compact")] crate::sync::ProtocolMessage),
+}
+
+fn serialize_compact<S: Serializer>(
+    message: &ProtocolMessage,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    CompactMessage(message).serialize(serializer)
+}
+
+#[derive(Debug, Default)]
+struct MuxCodec;
+
+/// Frame of the multiplexed sync protocol, see [`run_alice_many`].
+///
+/// Each frame is prefixed with its length as u32 big-endian, followed by the namespace id, a tag
+/// byte and, for [`MuxFrame::Message`], the postcard-encoded [`Message`].
+#[derive(Debug, Clone)]
+enum MuxFrame {
+    /// A message of the single namespace protocol, tag 0.
+    Message {
+        namespace: NamespaceId,
+        message: Message,
+    },
+    /// The sender finished the reconciliation of a namespace, tag 1.
+    ///
+    /// This takes the place of closing the stream in the single namespace protocol.
+    Done { namespace: NamespaceId },
+}
+
+const MUX_TAG_MESSAGE: u8 = 0;
+const MUX_TAG_DONE: u8 = 1;
+/// Length of the namespace id and the tag of a [`MuxFrame`].
+const MUX_HEADER_LEN: usize = 33;
+
+impl MuxFrame {
+    fn message(namespace: NamespaceId, message: Message) -> Self {
+        Self::Message { namespace, message }
+    }
+}
+
+impl Decoder for MuxCodec {
+    type Item = MuxFrame;
+    type Error = anyhow::Error;
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if src.len() < 4 {
+            return Ok(None);
+        }
+        let bytes: [u8; 4] = src[..4].try_into().unwrap();
+        let frame_len = u32::from_be_bytes(bytes) as usize;
+        ensure!(
+            frame_len <= MAX_MESSAGE_SIZE,
+            "received message that is too large: {}",
+            frame_len
+        );
+        ensure!(frame_len >= MUX_HEADER_LEN, "received frame without header");
+        if src.len() < 4 + frame_len {
+            return Ok(None);
+        }
+        let frame = &src[4..4 + frame_len];
+        let namespace: [u8; 32] = frame[..32].try_into().expect("checked length");
+        let namespace = NamespaceId::from(namespace);
+        let frame = match frame[32] {
+            MUX_TAG_MESSAGE => {
+                let message = Message::decode(&frame[MUX_HEADER_LEN..], &MessageLimits::default())?;
+                MuxFrame::Message { namespace, message }
+            }
+            MUX_TAG_DONE => MuxFrame::Done { namespace },
+            tag => return Err(anyhow!("invalid frame tag {tag}")),
+        };
+        src.advance(4 + frame_len);
+        Ok(Some(frame))
+    }
+}
+
+impl Encoder<MuxFrame> for MuxCodec {
+    type Error = anyhow::Error;
+
+    fn encode(&mut self, item: MuxFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let (namespace, tag, message) = match &item {
+            MuxFrame::Message { namespace, message } => (namespace, MUX_TAG_MESSAGE, Some(message)),
+            MuxFrame::Done { namespace } => (namespace, MUX_TAG_DONE, None),
+        };
+        let message_len = match message {
+            Some(message) => {
+                postcard::serialize_with_flavor(message, postcard::ser_flavors::Size::default())?
+            }
+            None => 0,
+        };
+        let len = MUX_HEADER_LEN + message_len;
+        ensure!(
+            len <= MAX_MESSAGE_SIZE,
+            "attempting to send message that is too large {}",
+            len
+        );
+        dst.put_u32(u32::try_from(len).expect("already checked"));
+        dst.put_slice(namespace.as_bytes());
+        dst.put_u8(tag);
+        if let Some(message) = message {
+            let start = dst.len();
+            dst.resize(start + message_len, 0u8);
+            postcard::to_slice(message, &mut dst[start..])?;
+        }
+        Ok(())
+    }
 }
 
 /// Runs the initiator side of the sync protocol.
@@ -108,7 +280,11 @@ pub(super) async fn run_alice<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
         .sync_initial_message(namespace)
         .await
         .map_err(ConnectError::sync)?;
-    let init_message = Message::Init { namespace, message };
+    let init_message = Message::Init {
+        namespace,
+        message,
+        version: PROTOCOL_VERSION,
+    };
     trace!("send init message");
     writer
         .send(init_message)
@@ -116,14 +292,20 @@ pub(super) async fn run_alice<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
         .map_err(ConnectError::sync)?;
 
     // Sync message loop
+    let mut received_reply = false;
+    // Only send compact messages once the remote sent one, as it might not support them.
+    let mut compact = false;
     while let Some(msg) = reader.next().await {
         let msg = msg.map_err(ConnectError::sync)?;
+        let is_compact = matches!(msg, Message::SyncCompact(_));
         match msg {
             Message::Init { .. } => {
                 return Err(ConnectError::sync(anyhow!("unexpected init message")));
             }
-            Message::Sync(msg) => {
-                trace!("recv process message");
+            Message::Sync(msg) | Message::SyncCompact(msg) => {
+                received_reply = true;
+                compact |= is_compact;
+                trace!(msg = %msg.describe(), "recv process message");
                 let current_progress = progress.take().unwrap();
                 let (reply, next_progress) = handle
                     .sync_process_message(namespace, msg, peer_bytes, current_progress)
@@ -131,9 +313,9 @@ pub(super) async fn run_alice<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
                     .map_err(ConnectError::sync)?;
                 progress = Some(next_progress);
                 if let Some(msg) = reply {
-                    trace!("send process message");
+                    trace!(msg = %msg.describe(), "send process message");
                     writer
-                        .send(Message::Sync(msg))
+                        .send(Message::sync(msg, compact))
                         .await
                         .map_err(ConnectError::sync)?;
                 } else {
@@ -147,7 +329,345 @@ pub(super) async fn run_alice<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
     }
 
     trace!("done");
-    Ok(progress.unwrap())
+    let mut outcome = progress.unwrap();
+    // The remote closes the stream without replying if our initial fingerprint matched.
+    outcome.already_in_sync = !received_reply;
+    Ok(outcome)
+}
+
+/// State of a namespace in a multiplexed sync.
+#[derive(Debug)]
+struct MuxState<E> {
+    progress: Option<SyncOutcome>,
+    /// Whether messages beyond the initial message were exchanged.
+    exchanged: bool,
+    /// Whether the remote supports [`Message::SyncCompact`].
+    compact: bool,
+    result: Option<Result<SyncOutcome, E>>,
+}
+
+impl<E> MuxState<E> {
+    fn new(compact: bool) -> Self {
+        Self {
+            progress: Some(SyncOutcome::default()),
+            exchanged: false,
+            compact,
+            result: None,
+        }
+    }
+
+    fn is_pending(&self) -> bool {
+        self.result.is_none()
+    }
+
+    fn finish(&mut self) {
+        let mut outcome = self.progress.take().unwrap_or_default();
+        outcome.already_in_sync = !self.exchanged;
+        self.result = Some(Ok(outcome));
+    }
+
+    fn fail(&mut self, error: E) {
+        self.result = Some(Err(error));
+    }
+
+    fn into_result(self) -> Result<SyncOutcome, E> {
+        self.result.expect("only called once finished")
+    }
+}
+
+/// Results of a multiplexed sync, for each namespace.
+pub(super) type MuxResults<E> = Vec<(NamespaceId, Result<SyncOutcome, E>)>;
+
+/// Runs the initiator side of the multiplexed sync protocol.
+///
+/// The multiplexed protocol runs the single namespace protocol for each of `namespaces` over a
+/// single stream. Each message is wrapped in a [`MuxFrame`] tagged with its namespace, so that
+/// the reconciliations of all namespaces are interleaved. Instead of closing the stream, a peer
+/// sends [`MuxFrame::Done`] once it finished a namespace. Once all namespaces finished, the
+/// initiator closes the stream.
+///
+/// Failures of single namespaces are returned in the results, only failures of the stream fail
+/// the whole exchange. Fails if there are more than [`MAX_NAMESPACES_PER_STREAM`] namespaces.
+pub(super) async fn run_alice_many<R: AsyncRead + Unpin, W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    reader: &mut R,
+    handle: &SyncHandle,
+    namespaces: &[NamespaceId],
+    peer: PublicKey,
+) -> Result<MuxResults<ConnectError>, ConnectError> {
+    let peer_bytes = *peer.as_bytes();
+    let namespaces: BTreeSet<_> = namespaces.iter().collect();
+    if namespaces.len() > MAX_NAMESPACES_PER_STREAM {
+        return Err(ConnectError::sync(anyhow!(
+            "cannot sync more than {MAX_NAMESPACES_PER_STREAM} namespaces over one stream"
+        )));
+    }
+    let mut reader = FramedRead::new(reader, MuxCodec);
+    let mut writer = FramedWrite::new(writer, MuxCodec);
+    // Frames are sent from a separate loop, so that we never block on sending while the remote
+    // blocks on sending to us. The remote only replies to our frames, so there is at most one
+    // frame queued for each namespace. If the remote keeps sending without reading, the queue
+    // fills up and we stop reading as well.
+    let (tx, mut rx) = tokio::sync::mpsc::channel(namespaces.len().max(1));
+
+    let mut states = BTreeMap::new();
+    for namespace in namespaces {
+        let mut state = MuxState::new(false);
+        match handle.sync_initial_message(*namespace).await {
+            Ok(message) => {
+                trace!(namespace = %namespace.fmt_short(), "send init message");
+                let message = Message::Init {
+                    namespace: *namespace,
+                    message,
+                    version: PROTOCOL_VERSION,
+                };
+                tx.send(MuxFrame::message(*namespace, message)).await.ok();
+            }
+            Err(err) => state.fail(ConnectError::sync(err)),
+        }
+        states.insert(*namespace, state);
+    }
+
+    let read_loop = async move {
+        while states.values().any(MuxState::is_pending) {
+            let Some(frame) = reader.next().await else {
+                return Err(ConnectError::sync(anyhow!(
+                    "stream closed before all namespaces finished"
+                )));
+            };
+            let frame = frame.map_err(ConnectError::sync)?;
+            let (namespace, message) = match frame {
+                MuxFrame::Message { namespace, message } => (namespace, Some(message)),
+                MuxFrame::Done { namespace } => (namespace, None),
+            };
+            let state = states
+                .get_mut(&namespace)
+                .ok_or_else(|| ConnectError::sync(anyhow!("frame for unknown namespace")))?;
+            if !state.is_pending() {
+                debug!(namespace = %namespace.fmt_short(), "ignore frame for finished namespace");
+                continue;
+            }
+            let Some(message) = message else {
+                trace!(namespace = %namespace.fmt_short(), "remote done");
+                state.finish();
+                continue;
+            };
+            let is_compact = matches!(message, Message::SyncCompact(_));
+            match message {
+                Message::Init { .. } => {
+                    return Err(ConnectError::sync(anyhow!("unexpected init message")));
+                }
+                Message::Sync(msg) | Message::SyncCompact(msg) => {
+                    state.exchanged = true;
+                    state.compact |= is_compact;
+                    trace!(namespace = %namespace.fmt_short(), msg = %msg.describe(), "recv process message");
+                    let progress = state.progress.take().unwrap_or_default();
+                    match handle
+                        .sync_process_message(namespace, msg, peer_bytes, progress)
+                        .await
+                    {
+                        Ok((Some(reply), progress)) => {
+                            state.progress = Some(progress);
+                            let reply = Message::sync(reply, state.compact);
+                            tx.send(MuxFrame::message(namespace, reply)).await.ok();
+                        }
+                        Ok((None, progress)) => {
+                            state.progress = Some(progress);
+                            state.finish();
+                            tx.send(MuxFrame::Done { namespace }).await.ok();
+                        }
+                        Err(err) => {
+                            state.fail(ConnectError::sync(err));
+                            let reason = AbortReason::InternalServerError;
+                            tx.send(MuxFrame::message(namespace, Message::Abort { reason }))
+                                .await
+                                .ok();
+                        }
+                    }
+                }
+                Message::Abort { reason } => {
+                    state.fail(ConnectError::remote_abort(reason));
+                }
+            }
+        }
+        trace!("done");
+        // dropping the sender ends the write loop
+        drop(tx);
+        Ok(states)
+    };
+    let write_loop = async {
+        while let Some(frame) = rx.recv().await {
+            writer.send(frame).await.map_err(ConnectError::sync)?;
+        }
+        Ok(())
+    };
+    let (states, ()) = tokio::try_join!(read_loop, write_loop)?;
+    Ok(states
+        .into_iter()
+        .map(|(namespace, state)| (namespace, state.into_result()))
+        .collect())
+}
+
+/// Runs the receiver side of the multiplexed sync protocol, see [`run_alice_many`].
+///
+/// Each namespace requested by the remote is accepted if `policy` allows it and `accept_cb`
+/// returns [`AcceptOutcome::Allow`]. Runs until the remote closes the stream. Fails if the
+/// remote requests more than [`MAX_NAMESPACES_PER_STREAM`] namespaces, including rejected ones.
+pub(super) async fn run_bob_many<R, W, F, Fut>(
+    writer: &mut W,
+    reader: &mut R,
+    handle: SyncHandle,
+    peer: PublicKey,
+    policy: &AcceptPolicy,
+    accept_cb: F,
+) -> Result<MuxResults<AcceptError>, AcceptError>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+    F: Fn(NamespaceId, PublicKey) -> Fut,
+    Fut: Future<Output = AcceptOutcome>,
+{
+    let peer_bytes = *peer.as_bytes();
+    let fail = |namespace: Option<NamespaceId>, err| AcceptError::sync(peer, namespace, err);
+    let mut reader = FramedRead::new(reader, MuxCodec);
+    let mut writer = FramedWrite::new(writer, MuxCodec);
+    // As in [`run_alice_many`], there is at most one frame queued for each namespace, unless the
+    // remote keeps sending without reading, in which case we stop reading as well.
+    let (tx, mut rx) = tokio::sync::mpsc::channel(MAX_NAMESPACES_PER_STREAM);
+
+    let read_loop = async move {
+        let mut states: BTreeMap<NamespaceId, MuxState<AcceptError>> = BTreeMap::new();
+        while let Some(frame) = reader.next().await {
+            let frame = frame.map_err(|err| fail(None, err))?;
+            let (namespace, message) = match frame {
+                MuxFrame::Message { namespace, message } => (namespace, Some(message)),
+                MuxFrame::Done { namespace } => (namespace, None),
+            };
+            let (state, progress, message) = match (message, states.get_mut(&namespace)) {
+                (
+                    Some(Message::Init {
+                        namespace: inner, ..
+                    }),
+                    _,
+                ) if inner != namespace => {
+                    return Err(fail(
+                        Some(namespace),
+                        anyhow!("init message for a different namespace"),
+                    ));
+                }
+                (
+                    Some(Message::Init {
+                        message, version, ..
+                    }),
+                    None,
+                ) => {
+                    trace!(namespace = %namespace.fmt_short(), "recv init message");
+                    if states.len() >= MAX_NAMESPACES_PER_STREAM {
+                        return Err(fail(
+                            Some(namespace),
+                            anyhow!("more than {MAX_NAMESPACES_PER_STREAM} namespaces requested"),
+                        ));
+                    }
+                    let mut state = MuxState::new(version >= PROTOCOL_VERSION_COMPACT);
+                    let accept = if policy.allows(&namespace) {
+                        accept_cb(namespace, peer).await
+                    } else {
+                        AcceptOutcome::Reject(AbortReason::NotAllowed)
+                    };
+                    if let AcceptOutcome::Reject(reason) = accept {
+                        debug!(namespace = %namespace.fmt_short(), ?reason, "reject request");
+                        tx.send(MuxFrame::message(namespace, Message::Abort { reason }))
+                            .await
+                            .ok();
+                        state.fail(AcceptError::Abort {
+                            namespace,
+                            peer,
+                            reason,
+                        });
+                        states.insert(namespace, state);
+                        continue;
+                    }
+                    let state = states.entry(namespace).or_insert(state);
+                    (state, SyncOutcome::default(), message)
+                }
+                (Some(Message::Init { .. }), Some(_)) => {
+                    return Err(fail(Some(namespace), anyhow!("double init message")));
+                }
+                (_, None) => {
+                    return Err(fail(
+                        Some(namespace),
+                        anyhow!("unexpected sync message before init"),
+                    ));
+                }
+                (_, Some(state)) if !state.is_pending() => {
+                    debug!(namespace = %namespace.fmt_short(), "ignore frame for finished namespace");
+                    continue;
+                }
+                (None, Some(state)) => {
+                    trace!(namespace = %namespace.fmt_short(), "remote done");
+                    state.finish();
+                    continue;
+                }
+                (Some(Message::Abort { reason }), Some(state)) => {
+                    state.fail(fail(
+                        Some(namespace),
+                        anyhow!("remote aborted sync: {reason:?}"),
+                    ));
+                    continue;
+                }
+                (Some(Message::Sync(msg) | Message::SyncCompact(msg)), Some(state)) => {
+                    trace!(namespace = %namespace.fmt_short(), msg = %msg.describe(), "recv process message");
+                    let progress = state.progress.take().unwrap_or_default();
+                    (state, progress, msg)
+                }
+            };
+            match handle
+                .sync_process_message(namespace, message, peer_bytes, progress)
+                .await
+            {
+                Ok((Some(reply), progress)) => {
+                    state.progress = Some(progress);
+                    state.exchanged = true;
+                    let reply = Message::sync(reply, state.compact);
+                    tx.send(MuxFrame::message(namespace, reply)).await.ok();
+                }
+                Ok((None, progress)) => {
+                    state.progress = Some(progress);
+                    state.finish();
+                    tx.send(MuxFrame::Done { namespace }).await.ok();
+                }
+                Err(err) => {
+                    state.fail(fail(Some(namespace), err));
+                    let reason = AbortReason::InternalServerError;
+                    tx.send(MuxFrame::message(namespace, Message::Abort { reason }))
+                        .await
+                        .ok();
+                }
+            }
+        }
+        trace!("done");
+        drop(tx);
+        for (namespace, state) in states.iter_mut() {
+            if state.is_pending() {
+                state.fail(fail(
+                    Some(*namespace),
+                    anyhow!("stream closed before sync finished"),
+                ));
+            }
+        }
+        Ok(states)
+    };
+    let write_loop = async {
+        while let Some(frame) = rx.recv().await {
+            writer.send(frame).await.map_err(|err| fail(None, err))?;
+        }
+        Ok(())
+    };
+    let (states, ()) = tokio::try_join!(read_loop, write_loop)?;
+    Ok(states
+        .into_iter()
+        .map(|(namespace, state)| (namespace, state.into_result()))
+        .collect())
 }
 
 /// Runs the receiver side of the sync protocol.
@@ -175,6 +695,9 @@ pub struct BobState {
     namespace: Option<NamespaceId>,
     peer: PublicKey,
     progress: Option<SyncOutcome>,
+    /// Whether the remote supports [`Message::SyncCompact`].
+    compact: bool,
+    accept_policy: AcceptPolicy,
 }
 
 impl BobState {
@@ -184,9 +707,17 @@ impl BobState {
             peer,
             namespace: None,
             progress: Some(Default::default()),
+            compact: false,
+            accept_policy: Default::default(),
         }
     }
 
+    /// Only accept sync requests for namespaces allowed by `policy`.
+    pub fn with_accept_policy(mut self, policy: AcceptPolicy) -> Self {
+        self.accept_policy = policy;
+        self
+    }
+
     fn fail(&self, reason: impl Into<anyhow::Error>) -> AcceptError {
         AcceptError::sync(self.peer, self.namespace(), reason.into())
     }
@@ -209,12 +740,24 @@ impl BobState {
         let mut writer = FramedWrite::new(writer, SyncCodec);
         while let Some(msg) = reader.next().await {
             let msg = msg.map_err(|e| self.fail(e))?;
+            let is_init = matches!(msg, Message::Init { .. });
             let next = match (msg, self.namespace.as_ref()) {
-                (Message::Init { namespace, message }, None) => {
+                (
+                    Message::Init {
+                        namespace,
+                        message,
+                        version,
+                    },
+                    None,
+                ) => {
                     Span::current()
                         .record("namespace", tracing::field::display(&namespace.fmt_short()));
                     trace!("recv init message");
-                    let accept = accept_cb(namespace, self.peer).await;
+                    let accept = if self.accept_policy.allows(&namespace) {
+                        accept_cb(namespace, self.peer).await
+                    } else {
+                        AcceptOutcome::Reject(AbortReason::NotAllowed)
+                    };
                     match accept {
                         AcceptOutcome::Allow => {
                             trace!("allow request");
@@ -232,6 +775,7 @@ impl BobState {
                             });
                         }
                     }
+                    self.compact = version >= PROTOCOL_VERSION_COMPACT;
                     let last_progress = self.progress.take().unwrap();
                     let next = sync
                         .sync_process_message(
@@ -244,8 +788,8 @@ impl BobState {
                     self.namespace = Some(namespace);
                     next
                 }
-                (Message::Sync(msg), Some(namespace)) => {
-                    trace!("recv process message");
+                (Message::Sync(msg) | Message::SyncCompact(msg), Some(namespace)) => {
+                    trace!(msg = %msg.describe(), "recv process message");
                     let last_progress = self.progress.take().unwrap();
                     sync.sync_process_message(*namespace, msg, *self.peer.as_bytes(), last_progress)
                         .await
@@ -253,20 +797,23 @@ impl BobState {
                 (Message::Init { .. }, Some(_)) => {
                     return Err(self.fail(anyhow!("double init message")))
                 }
-                (Message::Sync(_), None) => {
+                (Message::Sync(_) | Message::SyncCompact(_), None) => {
                     return Err(self.fail(anyhow!("unexpected sync message before init")))
                 }
                 (Message::Abort { .. }, _) => {
                     return Err(self.fail(anyhow!("unexpected sync abort message")))
                 }
             };
-            let (reply, progress) = next.map_err(|e| self.fail(e))?;
+            let (reply, mut progress) = next.map_err(|e| self.fail(e))?;
+            if is_init && reply.is_none() {
+                progress.already_in_sync = true;
+            }
             self.progress = Some(progress);
             match reply {
                 Some(msg) => {
-                    trace!("send process message");
+                    trace!(msg = %msg.describe(), "send process message");
                     writer
-                        .send(Message::Sync(msg))
+                        .send(Message::sync(msg, self.compact))
                         .await
                         .map_err(|e| self.fail(e))?;
                 }
@@ -297,15 +844,55 @@ mod tests {
     use iroh::SecretKey;
     use iroh_blobs::Hash;
     use rand_core::{CryptoRngCore, SeedableRng};
+    use tokio::io::AsyncWriteExt;
     use tracing_test::traced_test;
 
     use super::*;
     use crate::{
         actor::OpenOpts,
+        net::{run_sync as run_sync_stream, SyncRole},
         store::{self, Query, Store},
         AuthorId, NamespaceSecret,
     };
 
+    #[test]
+    fn test_init_message_version() -> Result<()> {
+        let mut store = store::Store::memory();
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let message = store
+            .new_replica(namespace.clone())?
+            .sync_initial_message()?;
+        let limits = MessageLimits::default();
+
+        let init = super::Message::Init {
+            namespace: namespace.id(),
+            message: message.clone(),
+            version: PROTOCOL_VERSION,
+        };
+        let bytes = postcard::to_stdvec(&init)?;
+        assert!(matches!(
+            super::Message::decode(&bytes, &limits)?,
+            super::Message::Init { version, .. } if version == PROTOCOL_VERSION
+        ));
+
+        // init messages of peers predating protocol versions end after the initial message
+        let bytes = postcard::to_stdvec(&(0u32, namespace.id(), &message))?;
+        assert!(matches!(
+            super::Message::decode(&bytes, &limits)?,
+            super::Message::Init {
+                version: PROTOCOL_VERSION_INITIAL,
+                ..
+            }
+        ));
+
+        let bytes = postcard::to_stdvec(&super::Message::sync(message.clone(), true))?;
+        assert!(matches!(
+            super::Message::decode(&bytes, &limits)?,
+            super::Message::SyncCompact(decoded) if decoded == message
+        ));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_sync_simple() -> Result<()> {
         let mut rng = rand::thread_rng();
@@ -419,6 +1006,414 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    #[traced_test]
+    async fn test_run_sync_over_duplex() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(42);
+        let alice_node_pubkey = SecretKey::generate(&mut rng).public();
+        let bob_node_pubkey = SecretKey::generate(&mut rng).public();
+        let namespace = NamespaceSecret::new(&mut rng);
+
+        let mut alice_store = store::Store::memory();
+        let mut alice_replica = alice_store.new_replica(namespace.clone())?;
+        let alice_messages = insert_messages(&mut rng, &mut alice_replica, 2, 3, |author, i| {
+            (format!("alice/{i}"), format!("{author}: {i}"))
+        });
+        alice_store.close_replica(namespace.id());
+
+        let mut bob_store = store::Store::memory();
+        let mut bob_replica = bob_store.new_replica(namespace.clone())?;
+        let bob_messages = insert_messages(&mut rng, &mut bob_replica, 2, 3, |author, i| {
+            (format!("bob/{i}"), format!("{author}: {i}"))
+        });
+        bob_store.close_replica(namespace.id());
+
+        let mut all_messages = [alice_messages, bob_messages].concat();
+        all_messages.sort();
+
+        let alice_handle = SyncHandle::spawn(alice_store, None, "alice".to_string());
+        let bob_handle = SyncHandle::spawn(bob_store, None, "bob".to_string());
+        alice_handle
+            .open(namespace.id(), OpenOpts::default().sync())
+            .await?;
+        bob_handle
+            .open(namespace.id(), OpenOpts::default().sync())
+            .await?;
+
+        let (alice, bob) = tokio::io::duplex(64);
+        let alice_role = SyncRole::Initiator {
+            namespace: namespace.id(),
+            peer: bob_node_pubkey,
+        };
+        let bob_role = SyncRole::Acceptor {
+            peer: alice_node_pubkey,
+        };
+        let (alice_outcome, bob_outcome) = tokio::try_join!(
+            run_sync_stream(&alice_handle, alice, alice_role),
+            run_sync_stream(&bob_handle, bob, bob_role),
+        )?;
+        assert_eq!(alice_outcome.num_recv, 6);
+        assert_eq!(bob_outcome.num_recv, 6);
+        assert!(!alice_outcome.already_in_sync);
+        assert!(!bob_outcome.already_in_sync);
+
+        let mut alice_store = alice_handle.shutdown().await?;
+        let mut bob_store = bob_handle.shutdown().await?;
+        assert_eq!(get_messages(&mut alice_store, namespace.id()), all_messages);
+        assert_eq!(get_messages(&mut bob_store, namespace.id()), all_messages);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_accept_policy() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(9);
+        let alice_node_pubkey = SecretKey::generate(&mut rng).public();
+        let bob_node_pubkey = SecretKey::generate(&mut rng).public();
+        let allowed = NamespaceSecret::new(&mut rng);
+        let denied = NamespaceSecret::new(&mut rng);
+
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+        for namespace in [&allowed, &denied] {
+            let mut replica = alice_store.new_replica(namespace.clone())?;
+            insert_messages(&mut rng, &mut replica, 1, 2, |author, i| {
+                (format!("key/{i}"), format!("{author}: {i}"))
+            });
+            alice_store.close_replica(namespace.id());
+            bob_store.new_replica(namespace.clone())?;
+            bob_store.close_replica(namespace.id());
+        }
+
+        let alice_handle = SyncHandle::spawn(alice_store, None, "alice".to_string());
+        let bob_handle = SyncHandle::spawn(bob_store, None, "bob".to_string());
+        for namespace in [&allowed, &denied] {
+            for handle in [&alice_handle, &bob_handle] {
+                handle
+                    .open(namespace.id(), OpenOpts::default().sync())
+                    .await?;
+            }
+        }
+        let policy = AcceptPolicy::Allow([allowed.id()].into_iter().collect());
+
+        for namespace in [allowed.id(), denied.id()] {
+            let (alice, bob) = tokio::io::duplex(64);
+            let (mut alice_reader, mut alice_writer) = tokio::io::split(alice);
+            let (mut bob_reader, mut bob_writer) = tokio::io::split(bob);
+            let mut bob_state = BobState::new(alice_node_pubkey).with_accept_policy(policy.clone());
+            let bob_task = async {
+                let res = bob_state
+                    .run(
+                        &mut bob_writer,
+                        &mut bob_reader,
+                        bob_handle.clone(),
+                        |_namespace, _peer| std::future::ready(AcceptOutcome::Allow),
+                    )
+                    .await;
+                // signal the end of the exchange to alice
+                bob_writer.shutdown().await.ok();
+                res
+            };
+            let (alice_res, bob_res) = tokio::join!(
+                run_alice(
+                    &mut alice_writer,
+                    &mut alice_reader,
+                    &alice_handle,
+                    namespace,
+                    bob_node_pubkey,
+                ),
+                bob_task,
+            );
+            if namespace == allowed.id() {
+                assert_eq!(alice_res?.num_sent, 2);
+                assert_eq!(bob_res?, namespace);
+            } else {
+                assert!(matches!(
+                    alice_res,
+                    Err(ConnectError::RemoteAbort(AbortReason::NotAllowed))
+                ));
+                assert!(matches!(
+                    bob_res,
+                    Err(AcceptError::Abort {
+                        reason: AbortReason::NotAllowed,
+                        ..
+                    })
+                ));
+            }
+        }
+
+        alice_handle.shutdown().await?;
+        let mut bob_store = bob_handle.shutdown().await?;
+        assert_eq!(get_messages(&mut bob_store, allowed.id()).len(), 2);
+        assert!(get_messages(&mut bob_store, denied.id()).is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_many_namespaces_multiplexed() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(10);
+        let alice_node_pubkey = SecretKey::generate(&mut rng).public();
+        let bob_node_pubkey = SecretKey::generate(&mut rng).public();
+        let namespaces: Vec<_> = (0..3).map(|_| NamespaceSecret::new(&mut rng)).collect();
+        let denied = NamespaceSecret::new(&mut rng);
+
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+        for (i, namespace) in namespaces.iter().chain([&denied]).enumerate() {
+            let mut replica = alice_store.new_replica(namespace.clone())?;
+            insert_messages(&mut rng, &mut replica, 1, i + 1, |author, i| {
+                (format!("alice/{i}"), format!("{author}: {i}"))
+            });
+            alice_store.close_replica(namespace.id());
+            let mut replica = bob_store.new_replica(namespace.clone())?;
+            insert_messages(&mut rng, &mut replica, 1, 2, |author, i| {
+                (format!("bob/{i}"), format!("{author}: {i}"))
+            });
+            bob_store.close_replica(namespace.id());
+        }
+
+        let alice_handle = SyncHandle::spawn(alice_store, None, "alice".to_string());
+        let bob_handle = SyncHandle::spawn(bob_store, None, "bob".to_string());
+        for namespace in namespaces.iter().chain([&denied]) {
+            for handle in [&alice_handle, &bob_handle] {
+                handle
+                    .open(namespace.id(), OpenOpts::default().sync())
+                    .await?;
+            }
+        }
+        let policy = AcceptPolicy::Deny([denied.id()].into_iter().collect());
+        let ids: Vec<_> = namespaces
+            .iter()
+            .chain([&denied])
+            .map(|namespace| namespace.id())
+            .collect();
+
+        // a small buffer forces the messages of the namespaces to interleave
+        let (alice, bob) = tokio::io::duplex(64);
+        let (mut alice_reader, mut alice_writer) = tokio::io::split(alice);
+        let (mut bob_reader, mut bob_writer) = tokio::io::split(bob);
+        let alice_task = async {
+            let res = run_alice_many(
+                &mut alice_writer,
+                &mut alice_reader,
+                &alice_handle,
+                &ids,
+                bob_node_pubkey,
+            )
+            .await;
+            // signal the end of the exchange to bob
+            alice_writer.shutdown().await.ok();
+            res
+        };
+        let bob_task = run_bob_many(
+            &mut bob_writer,
+            &mut bob_reader,
+            bob_handle.clone(),
+            alice_node_pubkey,
+            &policy,
+            |_namespace, _peer| std::future::ready(AcceptOutcome::Allow),
+        );
+        let (alice_res, bob_res) = tokio::join!(alice_task, bob_task);
+        let (alice_res, bob_res) = (alice_res?, bob_res?);
+        assert_eq!(alice_res.len(), 4);
+        assert_eq!(bob_res.len(), 4);
+        for ((namespace, alice_res), (bob_namespace, bob_res)) in alice_res.iter().zip(&bob_res) {
+            assert_eq!(namespace, bob_namespace);
+            if *namespace == denied.id() {
+                assert!(matches!(
+                    alice_res,
+                    Err(ConnectError::RemoteAbort(AbortReason::NotAllowed))
+                ));
+                assert!(matches!(
+                    bob_res,
+                    Err(AcceptError::Abort {
+                        reason: AbortReason::NotAllowed,
+                        ..
+                    })
+                ));
+            } else {
+                let (alice_res, bob_res) = (alice_res.as_ref().unwrap(), bob_res.as_ref().unwrap());
+                assert_eq!(alice_res.num_recv, 2);
+                assert_eq!(bob_res.num_sent, 2);
+                assert_eq!(alice_res.num_sent, bob_res.num_recv);
+            }
+        }
+
+        let mut alice_store = alice_handle.shutdown().await?;
+        let mut bob_store = bob_handle.shutdown().await?;
+        for (i, namespace) in namespaces.iter().enumerate() {
+            assert_eq!(get_messages(&mut alice_store, namespace.id()).len(), i + 3);
+            assert_eq!(get_messages(&mut bob_store, namespace.id()).len(), i + 3);
+        }
+        assert_eq!(get_messages(&mut bob_store, denied.id()).len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_many_rejects_mismatched_init() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(11);
+        let alice_node_pubkey = SecretKey::generate(&mut rng).public();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let other = NamespaceSecret::new(&mut rng);
+
+        let mut bob_store = store::Store::memory();
+        for namespace in [&namespace, &other] {
+            bob_store.new_replica(namespace.clone())?;
+            bob_store.close_replica(namespace.id());
+        }
+        let bob_handle = SyncHandle::spawn(bob_store, None, "bob".to_string());
+        for namespace in [&namespace, &other] {
+            bob_handle
+                .open(namespace.id(), OpenOpts::default().sync())
+                .await?;
+        }
+
+        // the frame is tagged with `namespace`, but the init message requests `other`
+        let message = bob_handle.sync_initial_message(other.id()).await?;
+        let init = super::Message::Init {
+            namespace: other.id(),
+            message,
+            version: PROTOCOL_VERSION,
+        };
+        let (alice, bob) = tokio::io::duplex(1024);
+        let (_alice_reader, alice_writer) = tokio::io::split(alice);
+        let (mut bob_reader, mut bob_writer) = tokio::io::split(bob);
+        let mut alice_writer = FramedWrite::new(alice_writer, MuxCodec);
+        alice_writer
+            .send(MuxFrame::message(namespace.id(), init))
+            .await?;
+        alice_writer.get_mut().shutdown().await?;
+
+        let res = run_bob_many(
+            &mut bob_writer,
+            &mut bob_reader,
+            bob_handle.clone(),
+            alice_node_pubkey,
+            &AcceptPolicy::default(),
+            |_namespace, _peer| std::future::ready(AcceptOutcome::Allow),
+        )
+        .await;
+        assert!(matches!(res, Err(AcceptError::Sync { .. })));
+        bob_handle.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sync_many_limits_namespaces_per_stream() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(12);
+        let alice_node_pubkey = SecretKey::generate(&mut rng).public();
+        let namespace = NamespaceSecret::new(&mut rng);
+
+        let mut bob_store = store::Store::memory();
+        bob_store.new_replica(namespace.clone())?;
+        bob_store.close_replica(namespace.id());
+        let bob_handle = SyncHandle::spawn(bob_store, None, "bob".to_string());
+        bob_handle
+            .open(namespace.id(), OpenOpts::default().sync())
+            .await?;
+        let message = bob_handle.sync_initial_message(namespace.id()).await?;
+
+        // alice requests one namespace more than allowed, which bob all rejects
+        let (alice, bob) = tokio::io::duplex(1024);
+        let (mut alice_reader, alice_writer) = tokio::io::split(alice);
+        let (mut bob_reader, mut bob_writer) = tokio::io::split(bob);
+        let alice_task = async {
+            let mut alice_writer = FramedWrite::new(alice_writer, MuxCodec);
+            for _ in 0..=MAX_NAMESPACES_PER_STREAM {
+                let namespace = NamespaceSecret::new(&mut rng).id();
+                let init = super::Message::Init {
+                    namespace,
+                    message: message.clone(),
+                    version: PROTOCOL_VERSION,
+                };
+                if alice_writer
+                    .send(MuxFrame::message(namespace, init))
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+            alice_writer.get_mut().shutdown().await.ok();
+        };
+        let mut sink = tokio::io::sink();
+        let drain_task = tokio::io::copy(&mut alice_reader, &mut sink);
+        let bob_task = async {
+            let res = run_bob_many(
+                &mut bob_writer,
+                &mut bob_reader,
+                bob_handle.clone(),
+                alice_node_pubkey,
+                &AcceptPolicy::default(),
+                |_namespace, _peer| {
+                    std::future::ready(AcceptOutcome::Reject(AbortReason::NotFound))
+                },
+            )
+            .await;
+            // signal the end of the exchange to alice
+            bob_writer.shutdown().await.ok();
+            res
+        };
+        let ((), _, res) = tokio::join!(alice_task, drain_task, bob_task);
+        assert!(matches!(res, Err(AcceptError::Sync { .. })));
+        bob_handle.shutdown().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn test_sync_already_in_sync() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(7);
+        let alice_node_pubkey = SecretKey::generate(&mut rng).public();
+        let bob_node_pubkey = SecretKey::generate(&mut rng).public();
+        let namespace = NamespaceSecret::new(&mut rng);
+
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+        let mut alice_replica = alice_store.new_replica(namespace.clone())?;
+        insert_messages(&mut rng, &mut alice_replica, 2, 3, |author, i| {
+            (format!("key/{i}"), format!("{author}: {i}"))
+        });
+        let entries = alice_store
+            .get_many(namespace.id(), Query::all())?
+            .collect::<Result<Vec<_>>>()?;
+        let mut bob_replica = bob_store.new_replica(namespace.clone())?;
+        for entry in entries {
+            bob_replica.insert_remote_entry(entry, [0u8; 32], crate::ContentStatus::Complete)?;
+        }
+        alice_store.close_replica(namespace.id());
+        bob_store.close_replica(namespace.id());
+
+        let alice_handle = SyncHandle::spawn(alice_store, None, "alice".to_string());
+        let bob_handle = SyncHandle::spawn(bob_store, None, "bob".to_string());
+        for handle in [&alice_handle, &bob_handle] {
+            handle
+                .open(namespace.id(), OpenOpts::default().sync())
+                .await?;
+        }
+
+        let (alice, bob) = tokio::io::duplex(64);
+        let alice_role = SyncRole::Initiator {
+            namespace: namespace.id(),
+            peer: bob_node_pubkey,
+        };
+        let bob_role = SyncRole::Acceptor {
+            peer: alice_node_pubkey,
+        };
+        let (alice_outcome, bob_outcome) = tokio::try_join!(
+            run_sync_stream(&alice_handle, alice, alice_role),
+            run_sync_stream(&bob_handle, bob, bob_role),
+        )?;
+        assert!(alice_outcome.already_in_sync);
+        assert!(bob_outcome.already_in_sync);
+        assert_eq!(alice_outcome.num_recv + alice_outcome.num_sent, 0);
+        assert_eq!(bob_outcome.num_recv + bob_outcome.num_sent, 0);
+
+        alice_handle.shutdown().await?;
+        bob_handle.shutdown().await?;
+        Ok(())
+    }
+
     #[tokio::test]
     #[traced_test]
     async fn test_sync_many_authors_memory() -> Result<()> {
@@ -9,7 +9,8 @@ use iroh_blobs::net_protocol::{Blobs, ProtectCb};
 use iroh_gossip::net::Gossip;
 
 use crate::{
-    engine::{DefaultAuthorStorage, Engine},
+    engine::{DefaultAuthorStorage, Engine, HeadsGossip, RetryPolicy, SyncFailureObserver},
+    net::AcceptPolicy,
     store::Store,
 };
 
@@ -30,6 +31,9 @@ impl<S: iroh_blobs::store::Store> ProtocolHandler for Docs<S> {
 }
 
 /// Docs protocol.
+///
+/// Register it with the router for both [`crate::ALPN`] and [`crate::ALPN_MULTI`], to accept
+/// single and multiplexed sync connections.
 #[derive(Debug, Clone)]
 pub struct Docs<S> {
     engine: Arc<Engine<S>>,
@@ -46,7 +50,10 @@ impl Docs<()> {
     /// Create a new [`Builder`] for the docs protocol, using a persistent replica and author storage
     /// in the given directory.
     pub fn persistent(path: PathBuf) -> Builder {
-        Builder { path: Some(path) }
+        Builder {
+            path: Some(path),
+            ..Default::default()
+        }
     }
 }
 
@@ -92,12 +99,58 @@ impl<S: iroh_blobs::store::Store> Docs<S> {
 }
 
 /// Builder for the docs protocol.
-#[derive(Debug, Default)]
+#[derive(derive_more::Debug, Default)]
 pub struct Builder {
     path: Option<PathBuf>,
+    max_concurrent_downloads: Option<usize>,
+    accept_policy: Option<AcceptPolicy>,
+    #[debug("SyncFailureObserver")]
+    sync_failure_observer: Option<SyncFailureObserver>,
+    retry_policy: Option<RetryPolicy>,
+    heads_gossip: Option<HeadsGossip>,
 }
 
 impl Builder {
+    /// Limit the number of content downloads in flight per document.
+    ///
+    /// Further downloads are queued until one of the running downloads finished. By default
+    /// the number of downloads is not limited.
+    pub fn max_concurrent_downloads(mut self, max: usize) -> Self {
+        self.max_concurrent_downloads = Some(max);
+        self
+    }
+
+    /// Only accept incoming sync requests for namespaces allowed by `policy`.
+    ///
+    /// By default, requests are accepted for all namespaces which are open for sync.
+    pub fn accept_policy(mut self, policy: AcceptPolicy) -> Self {
+        self.accept_policy = Some(policy);
+        self
+    }
+
+    /// Call `observer` whenever a sync with a peer failed.
+    pub fn sync_failure_observer(mut self, observer: SyncFailureObserver) -> Self {
+        self.sync_failure_observer = Some(observer);
+        self
+    }
+
+    /// Retry failed syncs with peers according to `policy`.
+    ///
+    /// By default, failed syncs are not retried.
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Exchange author heads via gossip and only sync with neighbors which have news, see
+    /// [`HeadsGossip`].
+    ///
+    /// By default, a full sync is started with each new neighbor.
+    pub fn heads_gossip(mut self, config: HeadsGossip) -> Self {
+        self.heads_gossip = Some(config);
+        self
+    }
+
     /// Build a [`Docs`] protocol given a [`Blobs`] and [`Gossip`] protocol.
     pub async fn spawn<S: iroh_blobs::store::Store>(
         self,
@@ -122,6 +175,21 @@ impl Builder {
             blobs.rt().clone(),
         )
         .await?;
+        if let Some(max) = self.max_concurrent_downloads {
+            engine.set_max_concurrent_downloads(Some(max)).await?;
+        }
+        if let Some(policy) = self.accept_policy {
+            engine.set_accept_policy(policy).await?;
+        }
+        if let Some(observer) = self.sync_failure_observer {
+            engine.set_sync_failure_observer(Some(observer)).await?;
+        }
+        if let Some(policy) = self.retry_policy {
+            engine.set_retry_policy(Some(policy)).await?;
+        }
+        if let Some(config) = self.heads_gossip {
+            engine.set_heads_gossip(Some(config)).await?;
+        }
         Ok(Docs::new(engine))
     }
 }
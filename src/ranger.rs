@@ -1,9 +1,14 @@
 //! Implementation of Set Reconcilliation based on
 //! "Range-Based Set Reconciliation" by Aljoscha Meyer.
 
-use std::{cmp::Ordering, fmt::Debug};
+use std::{cell::Cell, cmp::Ordering, fmt::Debug, marker::PhantomData};
 
-use serde::{Deserialize, Serialize};
+use serde::{
+    de::{self, DeserializeSeed, EnumAccess, SeqAccess, VariantAccess, Visitor},
+    ser::SerializeStruct,
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+use tracing::{debug_span, trace_span};
 
 use crate::ContentStatus;
 
@@ -33,6 +38,15 @@ pub trait RangeEntry: Debug + Clone {
 
     /// Get the fingerprint for this entry.
     fn as_fingerprint(&self) -> Fingerprint;
+
+    /// Get the size of this entry when encoded in a message, in bytes.
+    ///
+    /// See [`SyncConfig::max_message_size`]. The default implementation returns the in-memory
+    /// size of the entry, which does not account for heap allocations; implementors should
+    /// override it with the actual encoded size.
+    fn encoded_len(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
 }
 
 /// A trait constraining types that are valid entry keys.
@@ -67,18 +81,22 @@ pub struct Range<K> {
 }
 
 impl<K> Range<K> {
+    /// The start of the range, included in the range.
     pub fn x(&self) -> &K {
         &self.x
     }
 
+    /// The end of the range, excluded from the range unless it equals the start.
     pub fn y(&self) -> &K {
         &self.y
     }
 
+    /// Create a new range from `x` to `y`.
     pub fn new(x: K, y: K) -> Self {
         Range { x, y }
     }
 
+    /// Map the bounds of the range.
     pub fn map<X>(self, f: impl FnOnce(K, K) -> (X, X)) -> Range<X> {
         let (x, y) = f(self.x, self.y);
         Range { x, y }
@@ -86,10 +104,12 @@ impl<K> Range<K> {
 }
 
 impl<K: Ord> Range<K> {
+    /// Whether this range covers all elements.
     pub fn is_all(&self) -> bool {
         self.x() == self.y()
     }
 
+    /// Whether `t` lies within this range.
     pub fn contains(&self, t: &K) -> bool {
         match self.x().cmp(self.y()) {
             Ordering::Equal => true,
@@ -99,13 +119,91 @@ impl<K: Ord> Range<K> {
     }
 }
 
+/// Set operations on ranges.
+///
+/// The result of an operation on two ranges is not always a single range, so all operations
+/// return a list of disjoint ranges. An empty list denotes the empty set, which can not be
+/// expressed as a single [`Range`].
+impl<K: Ord + Clone> Range<K> {
+    /// Returns the ranges of keys contained in both `self` and `other`.
+    pub fn intersect(&self, other: &Self) -> Vec<Self> {
+        self.combine(other, |a, b| a && b)
+    }
+
+    /// Returns the ranges of keys contained in `self` or `other`.
+    pub fn union(&self, other: &Self) -> Vec<Self> {
+        self.combine(other, |a, b| a || b)
+    }
+
+    /// Returns the ranges of keys contained in `self` but not in `other`.
+    pub fn difference(&self, other: &Self) -> Vec<Self> {
+        self.combine(other, |a, b| a && !b)
+    }
+
+    /// Whether the range contains the keys below both of its bounds.
+    ///
+    /// This is the case for wrapping ranges and for the range of all keys. Such ranges always
+    /// contain the keys above both of their bounds as well.
+    fn contains_below(&self) -> bool {
+        self.x() >= self.y()
+    }
+
+    /// Combine two ranges with a set operation given as boolean function on membership.
+    fn combine(&self, other: &Self, op: impl Fn(bool, bool) -> bool) -> Vec<Self> {
+        // The bounds of both ranges split the key space into elementary intervals, in which
+        // membership in either range does not change. The interval below the smallest bound
+        // is open to the left, the interval starting at the largest bound is open to the right.
+        let mut bounds = vec![&self.x, &self.y, &other.x, &other.y];
+        bounds.sort();
+        bounds.dedup();
+
+        // Collect the maximal runs of included intervals, with `None` for open ends.
+        let mut segments: Vec<(Option<&K>, Option<&K>)> = Vec::new();
+        let mut start = op(self.contains_below(), other.contains_below()).then_some(None::<&K>);
+        for bound in bounds {
+            let included = op(self.contains(bound), other.contains(bound));
+            match (start, included) {
+                (Some(s), false) => {
+                    segments.push((s, Some(bound)));
+                    start = None;
+                }
+                (None, true) => start = Some(Some(bound)),
+                _ => {}
+            }
+        }
+        if let Some(s) = start {
+            segments.push((s, None));
+        }
+
+        // Both operands contain either both open ends of the key space or none, and so does the
+        // result. The two open segments are joined into a single wrapping range.
+        let mut ranges = Vec::new();
+        let (mut below, mut above) = (None, None);
+        for segment in segments {
+            match segment {
+                (None, None) => return vec![Range::new(self.x.clone(), self.x.clone())],
+                (None, Some(y)) => below = Some(y),
+                (Some(x), None) => above = Some(x),
+                (Some(x), Some(y)) => ranges.push(Range::new(x.clone(), y.clone())),
+            }
+        }
+        match (above, below) {
+            (Some(x), Some(y)) => ranges.push(Range::new(x.clone(), y.clone())),
+            (None, None) => {}
+            _ => unreachable!("set operations keep both open ends of the key space together"),
+        }
+        ranges
+    }
+}
+
 impl<K> From<(K, K)> for Range<K> {
     fn from((x, y): (K, K)) -> Self {
         Range { x, y }
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Serialize, Deserialize)]
+/// Fingerprint of a set of entries, the XOR of the fingerprints of all entries in the set.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Fingerprint(pub [u8; 32]);
 
 impl Debug for Fingerprint {
@@ -120,6 +218,7 @@ impl Fingerprint {
         Fingerprint(*blake3::hash(&[]).as_bytes())
     }
 
+    /// The fingerprint of a single entry.
     pub fn new<T: RangeEntry>(val: T) -> Self {
         val.as_fingerprint()
     }
@@ -210,6 +309,34 @@ impl<E: RangeEntry> Message<E> {
         Ok(Message { parts: vec![part] })
     }
 
+    /// Construct the initial message, offering `values` before the fingerprint.
+    fn init_with_values<S: Store<E>>(
+        store: &mut S,
+        config: &SyncConfig,
+        values: Vec<(E, ContentStatus)>,
+    ) -> Result<Self, S::Error> {
+        let mut message = Self::init(store)?;
+        if values.is_empty() {
+            return Ok(message);
+        }
+        let x = store.get_first()?;
+        let mut parts = Vec::new();
+        // values beyond the size limit are left out, the fingerprint covers them anyway
+        push_range_item(
+            &mut parts,
+            &mut 0,
+            config,
+            Range::new(x.clone(), x),
+            values,
+            true,
+            |_| Ok(None),
+        )?;
+        // items are processed before fingerprints anyway, keep them first for readability
+        parts.append(&mut message.parts);
+        message.parts = parts;
+        Ok(message)
+    }
+
     pub fn parts(&self) -> &[MessagePart<E>] {
         &self.parts
     }
@@ -223,6 +350,514 @@ impl<E: RangeEntry> Message<E> {
     }
 }
 
+impl<E> Message<E>
+where
+    E: RangeEntry + Serialize,
+    E::Key: Serialize,
+{
+    /// Encode the message with postcard in the compact wire encoding.
+    ///
+    /// See [`CompactMessage`] for details.
+    pub fn encode_compact(&self) -> Result<Vec<u8>, postcard::Error> {
+        postcard::to_stdvec(&CompactMessage(self))
+    }
+}
+
+/// Limits enforced when decoding a [`Message`] received from a remote peer.
+///
+/// The limits are checked while decoding, so that decoding stops as soon as a limit is exceeded
+/// instead of first allocating all parts and values of an oversized message.
+#[derive(Debug, Clone, Copy)]
+pub struct MessageLimits {
+    /// Maximum number of parts in a message.
+    pub max_parts: usize,
+    /// Maximum number of values in a message, summed over all parts.
+    pub max_values: usize,
+}
+
+impl Default for MessageLimits {
+    fn default() -> Self {
+        Self {
+            max_parts: 1 << 16,
+            max_values: 1 << 20,
+        }
+    }
+}
+
+/// Error returned when decoding a [`Message`] with [`MessageLimits`] failed.
+#[derive(Debug, thiserror::Error)]
+pub enum MessageDecodeError {
+    /// The message has more parts than allowed.
+    #[error("message has more than {limit} parts")]
+    TooManyParts {
+        /// The configured limit.
+        limit: usize,
+    },
+    /// The message has more values than allowed.
+    #[error("message has more than {limit} values")]
+    TooManyValues {
+        /// The configured limit.
+        limit: usize,
+    },
+    /// The message is malformed.
+    #[error("failed to decode message: {0}")]
+    Decode(#[from] postcard::Error),
+}
+
+impl<E> Message<E>
+where
+    E: RangeEntry + for<'de> Deserialize<'de>,
+    E::Key: for<'de> Deserialize<'de>,
+{
+    /// Decode a postcard-encoded message, enforcing `limits`.
+    pub fn decode(bytes: &[u8], limits: &MessageLimits) -> Result<Self, MessageDecodeError> {
+        let mut de = postcard::Deserializer::from_bytes(bytes);
+        Self::decode_from(&mut de, limits)
+    }
+
+    /// Decode a message from a postcard deserializer, enforcing `limits`.
+    pub(crate) fn decode_from<'de>(
+        de: &mut postcard::Deserializer<'de, postcard::de_flavors::Slice<'de>>,
+        limits: &MessageLimits,
+    ) -> Result<Self, MessageDecodeError> {
+        Self::decode_with(de, limits, false)
+    }
+
+    /// Decode a message encoded with [`Message::encode_compact`], enforcing `limits`.
+    pub fn decode_compact(
+        bytes: &[u8],
+        limits: &MessageLimits,
+    ) -> Result<Self, MessageDecodeError> {
+        let mut de = postcard::Deserializer::from_bytes(bytes);
+        Self::decode_compact_from(&mut de, limits)
+    }
+
+    /// Decode a compact message from a postcard deserializer, enforcing `limits`.
+    pub(crate) fn decode_compact_from<'de>(
+        de: &mut postcard::Deserializer<'de, postcard::de_flavors::Slice<'de>>,
+        limits: &MessageLimits,
+    ) -> Result<Self, MessageDecodeError> {
+        Self::decode_with(de, limits, true)
+    }
+
+    fn decode_with<'de>(
+        de: &mut postcard::Deserializer<'de, postcard::de_flavors::Slice<'de>>,
+        limits: &MessageLimits,
+        compact: bool,
+    ) -> Result<Self, MessageDecodeError> {
+        let budget = DecodeBudget {
+            limits: *limits,
+            values: Cell::new(0),
+            exceeded: Cell::new(None),
+        };
+        let seed = MessageSeed {
+            budget: &budget,
+            compact,
+            _entry: PhantomData,
+        };
+        seed.deserialize(de)
+            .map_err(|err| budget.exceeded.take().unwrap_or(err.into()))
+    }
+}
+
+/// Tracks the values decoded so far and the limit that was exceeded, if any.
+struct DecodeBudget {
+    limits: MessageLimits,
+    values: Cell<usize>,
+    exceeded: Cell<Option<MessageDecodeError>>,
+}
+
+impl DecodeBudget {
+    fn check_parts<Err: de::Error>(&self, count: usize) -> Result<(), Err> {
+        let limit = self.limits.max_parts;
+        if count > limit {
+            self.exceeded
+                .set(Some(MessageDecodeError::TooManyParts { limit }));
+            return Err(Err::custom("too many parts"));
+        }
+        Ok(())
+    }
+
+    fn check_values<Err: de::Error>(&self, additional: usize) -> Result<(), Err> {
+        let limit = self.limits.max_values;
+        if self.values.get().saturating_add(additional) > limit {
+            self.exceeded
+                .set(Some(MessageDecodeError::TooManyValues { limit }));
+            return Err(Err::custom("too many values"));
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a [`Message`], checking the number of parts and values against a [`DecodeBudget`].
+///
+/// The seeds mirror the derived [`Deserialize`] impls of the message types, or the
+/// [`CompactMessage`] encoding if `compact` is set.
+struct MessageSeed<'a, E> {
+    budget: &'a DecodeBudget,
+    compact: bool,
+    _entry: PhantomData<E>,
+}
+
+impl<'a, E> MessageSeed<'a, E> {
+    fn reborrow<T>(&self) -> MessageSeed<'a, T> {
+        MessageSeed {
+            budget: self.budget,
+            compact: self.compact,
+            _entry: PhantomData,
+        }
+    }
+}
+
+impl<'de, E> DeserializeSeed<'de> for MessageSeed<'_, E>
+where
+    E: RangeEntry + Deserialize<'de>,
+    E::Key: Deserialize<'de>,
+{
+    type Value = Message<E>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("Message", &["parts"], self)
+    }
+}
+
+impl<'de, E> Visitor<'de> for MessageSeed<'_, E>
+where
+    E: RangeEntry + Deserialize<'de>,
+    E::Key: Deserialize<'de>,
+{
+    type Value = Message<E>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("struct Message")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let parts = seq
+            .next_element_seed(PartsSeed(self.reborrow::<E>()))?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        Ok(Message { parts })
+    }
+}
+
+struct PartsSeed<'a, E>(MessageSeed<'a, E>);
+
+impl<'de, E> DeserializeSeed<'de> for PartsSeed<'_, E>
+where
+    E: RangeEntry + Deserialize<'de>,
+    E::Key: Deserialize<'de>,
+{
+    type Value = Vec<MessagePart<E>>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, E> Visitor<'de> for PartsSeed<'_, E>
+where
+    E: RangeEntry + Deserialize<'de>,
+    E::Key: Deserialize<'de>,
+{
+    type Value = Vec<MessagePart<E>>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a sequence of message parts")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let budget = self.0.budget;
+        budget.check_parts(seq.size_hint().unwrap_or(0))?;
+        let mut parts = Vec::new();
+        while let Some(part) = seq.next_element_seed(PartSeed(self.0.reborrow::<E>()))? {
+            parts.push(part);
+            budget.check_parts(parts.len())?;
+        }
+        Ok(parts)
+    }
+}
+
+struct PartSeed<'a, E>(MessageSeed<'a, E>);
+
+impl<'de, E> DeserializeSeed<'de> for PartSeed<'_, E>
+where
+    E: RangeEntry + Deserialize<'de>,
+    E::Key: Deserialize<'de>,
+{
+    type Value = MessagePart<E>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_enum("MessagePart", &["RangeFingerprint", "RangeItem"], self)
+    }
+}
+
+impl<'de, E> Visitor<'de> for PartSeed<'_, E>
+where
+    E: RangeEntry + Deserialize<'de>,
+    E::Key: Deserialize<'de>,
+{
+    type Value = MessagePart<E>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("enum MessagePart")
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        let (index, variant): (u32, _) = data.variant()?;
+        match index {
+            0 => Ok(MessagePart::RangeFingerprint(variant.newtype_variant()?)),
+            1 => Ok(MessagePart::RangeItem(
+                variant.newtype_variant_seed(ItemSeed(self.0))?,
+            )),
+            _ => Err(de::Error::invalid_value(
+                de::Unexpected::Unsigned(index as u64),
+                &"variant index 0 <= i < 2",
+            )),
+        }
+    }
+}
+
+struct ItemSeed<'a, E>(MessageSeed<'a, E>);
+
+impl<'de, E> DeserializeSeed<'de> for ItemSeed<'_, E>
+where
+    E: RangeEntry + Deserialize<'de>,
+    E::Key: Deserialize<'de>,
+{
+    type Value = RangeItem<E>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        let fields: &'static [&'static str] = match self.0.compact {
+            false => &["range", "values", "have_local"],
+            true => &["range", "values", "statuses", "have_local"],
+        };
+        deserializer.deserialize_struct("RangeItem", fields, self)
+    }
+}
+
+impl<'de, E> Visitor<'de> for ItemSeed<'_, E>
+where
+    E: RangeEntry + Deserialize<'de>,
+    E::Key: Deserialize<'de>,
+{
+    type Value = RangeItem<E>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("struct RangeItem")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let range = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let values = if self.0.compact {
+            let entries = seq
+                .next_element_seed(ValuesSeed(self.0.reborrow::<E>()))?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+            let statuses: StatusEncoding = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(2, &self))?;
+            let statuses = statuses.decode(entries.len()).map_err(de::Error::custom)?;
+            entries.into_iter().zip(statuses).collect()
+        } else {
+            seq.next_element_seed(ValuesSeed(self.0.reborrow::<(E, ContentStatus)>()))?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?
+        };
+        let have_local = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(2 + self.0.compact as usize, &self))?;
+        Ok(RangeItem {
+            range,
+            values,
+            have_local,
+        })
+    }
+}
+
+/// Decodes the values of a [`RangeItem`], either `(E, ContentStatus)` pairs or, in the compact
+/// encoding, only the entries.
+struct ValuesSeed<'a, T>(MessageSeed<'a, T>);
+
+impl<'de, T> DeserializeSeed<'de> for ValuesSeed<'_, T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = Vec<T>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, T> Visitor<'de> for ValuesSeed<'_, T>
+where
+    T: Deserialize<'de>,
+{
+    type Value = Vec<T>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("a sequence of values")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let budget = self.0.budget;
+        budget.check_values(seq.size_hint().unwrap_or(0))?;
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            budget.check_values(1)?;
+            budget.values.set(budget.values.get() + 1);
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+/// Encoding of the [`ContentStatus`] of each value of a [`RangeItem`] in a [`CompactMessage`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum StatusEncoding {
+    /// One status per value.
+    PerValue(Vec<ContentStatus>),
+    /// Runs of equal statuses, as pairs of status and run length.
+    RunLength(Vec<(ContentStatus, u64)>),
+}
+
+impl StatusEncoding {
+    /// Encode the statuses, run-length encoded if that is shorter.
+    fn encode<'a>(statuses: impl ExactSizeIterator<Item = &'a ContentStatus>) -> Self {
+        let len = statuses.len();
+        let mut runs: Vec<(ContentStatus, u64)> = Vec::new();
+        let mut per_value = Vec::with_capacity(len);
+        for status in statuses {
+            match runs.last_mut() {
+                Some((last, count)) if last == status => *count += 1,
+                _ => runs.push((*status, 1)),
+            }
+            per_value.push(*status);
+        }
+        // a run takes at least two bytes, a single status one byte.
+        if runs.len() * 2 < len {
+            Self::RunLength(runs)
+        } else {
+            Self::PerValue(per_value)
+        }
+    }
+
+    /// Decode the statuses for `len` values.
+    ///
+    /// Fails if the encoded statuses do not cover exactly `len` values. The run lengths are
+    /// checked before expanding them, so a crafted message can not trigger a large allocation.
+    fn decode(self, len: usize) -> Result<Vec<ContentStatus>, &'static str> {
+        const MISMATCH: &str = "number of content statuses does not match number of values";
+        match self {
+            Self::PerValue(statuses) if statuses.len() == len => Ok(statuses),
+            Self::PerValue(_) => Err(MISMATCH),
+            Self::RunLength(runs) => {
+                let total = runs
+                    .iter()
+                    .try_fold(0u64, |total, (_, count)| total.checked_add(*count));
+                if total != Some(len as u64) {
+                    return Err(MISMATCH);
+                }
+                let mut statuses = Vec::with_capacity(len);
+                for (status, count) in runs {
+                    statuses.extend(std::iter::repeat(status).take(count as usize));
+                }
+                Ok(statuses)
+            }
+        }
+    }
+}
+
+/// Serializes a [`Message`] in the compact wire encoding.
+///
+/// The compact encoding differs from the derived [`Serialize`] impl only for [`RangeItem`]s:
+/// instead of a `(value, content status)` pair for each value, it contains the list of values
+/// followed by their content statuses, which are run-length encoded if that is shorter. As most
+/// messages contain many values with the same status, this saves close to one byte per value.
+/// For example, a message with 1000 entries that all have [`ContentStatus::Missing`] shrinks by
+/// 995 bytes.
+///
+/// Decode with [`Message::decode_compact`].
+#[derive(Debug)]
+pub struct CompactMessage<'a, E: RangeEntry>(pub &'a Message<E>);
+
+impl<E> Serialize for CompactMessage<'_, E>
+where
+    E: RangeEntry + Serialize,
+    E::Key: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut message = serializer.serialize_struct("Message", 1)?;
+        message.serialize_field("parts", &CompactParts(&self.0.parts))?;
+        message.end()
+    }
+}
+
+struct CompactParts<'a, E: RangeEntry>(&'a [MessagePart<E>]);
+
+impl<E> Serialize for CompactParts<'_, E>
+where
+    E: RangeEntry + Serialize,
+    E::Key: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(CompactPart))
+    }
+}
+
+struct CompactPart<'a, E: RangeEntry>(&'a MessagePart<E>);
+
+impl<E> Serialize for CompactPart<'_, E>
+where
+    E: RangeEntry + Serialize,
+    E::Key: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            MessagePart::RangeFingerprint(part) => {
+                serializer.serialize_newtype_variant("MessagePart", 0, "RangeFingerprint", part)
+            }
+            MessagePart::RangeItem(item) => serializer.serialize_newtype_variant(
+                "MessagePart",
+                1,
+                "RangeItem",
+                &CompactItem(item),
+            ),
+        }
+    }
+}
+
+struct CompactItem<'a, E: RangeEntry>(&'a RangeItem<E>);
+
+impl<E> Serialize for CompactItem<'_, E>
+where
+    E: RangeEntry + Serialize,
+    E::Key: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let RangeItem {
+            range,
+            values,
+            have_local,
+        } = self.0;
+        let mut item = serializer.serialize_struct("RangeItem", 4)?;
+        item.serialize_field("range", range)?;
+        item.serialize_field("values", &CompactValues(values))?;
+        let statuses = StatusEncoding::encode(values.iter().map(|(_, status)| status));
+        item.serialize_field("statuses", &statuses)?;
+        item.serialize_field("have_local", have_local)?;
+        item.end()
+    }
+}
+
+struct CompactValues<'a, E>(&'a [(E, ContentStatus)]);
+
+impl<E: Serialize> Serialize for CompactValues<'_, E> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().map(|(value, _)| value))
+    }
+}
+
 pub trait Store<E: RangeEntry>: Sized {
     type Error: Debug + Send + Sync + Into<anyhow::Error> + 'static;
 
@@ -303,6 +938,19 @@ pub trait Store<E: RangeEntry>: Sized {
         Message::init(self)
     }
 
+    /// Generates the initial message, offering `values` to the other participant up front.
+    ///
+    /// The values are sent as items which do not request the items of the other participant in
+    /// return. They are inserted by the other participant before it compares the fingerprint of
+    /// the whole set, which is sent as in [`Self::initial_message`].
+    fn initial_message_with_values(
+        &mut self,
+        config: &SyncConfig,
+        values: Vec<(E, ContentStatus)>,
+    ) -> Result<Message<E>, Self::Error> {
+        Message::init_with_values(self, config, values)
+    }
+
     /// Processes an incoming message and produces a response.
     /// If terminated, returns `None`
     ///
@@ -330,6 +978,8 @@ pub trait Store<E: RangeEntry>: Sized {
         F3: Fn(&Self, &E) -> ContentStatus,
     {
         let mut out = Vec::new();
+        // encoded size of the values in `out`
+        let mut size = 0;
 
         // TODO: can these allocs be avoided?
         let mut items = Vec::new();
@@ -346,12 +996,21 @@ pub trait Store<E: RangeEntry>: Sized {
         }
 
         // Process item messages
+        let span = debug_span!("process_items", num_items = items.len()).entered();
         for RangeItem {
             range,
             values,
             have_local,
         } in items
         {
+            let _span = trace_span!(
+                "range_item",
+                x = ?range.x(),
+                y = ?range.y(),
+                num_values = values.len(),
+                have_local,
+            )
+            .entered();
             let diff: Option<Vec<_>> = if have_local {
                 None
             } else {
@@ -399,17 +1058,23 @@ pub trait Store<E: RangeEntry>: Sized {
 
             if let Some(diff) = diff {
                 if !diff.is_empty() {
-                    out.push(MessagePart::RangeItem(RangeItem {
-                        range,
-                        values: diff,
-                        have_local: true,
-                    }));
+                    push_range_item(&mut out, &mut size, config, range, diff, true, |range| {
+                        self.get_fingerprint(range).map(Some)
+                    })?;
                 }
             }
         }
 
+        span.exit();
+
         // Process fingerprint messages
+        let _span = debug_span!(
+            "process_fingerprints",
+            num_fingerprints = fingerprints.len()
+        )
+        .entered();
         for RangeFingerprint { range, fingerprint } in fingerprints {
+            let _span = trace_span!("range_fingerprint", x = ?range.x(), y = ?range.y()).entered();
             let local_fingerprint = self.get_fingerprint(&range)?;
             // Case1 Match, nothing to do
             if local_fingerprint == fingerprint {
@@ -429,13 +1094,17 @@ pub trait Store<E: RangeEntry>: Sized {
                         (entry, content_status)
                     })
                     .collect();
-                out.push(MessagePart::RangeItem(RangeItem {
-                    range,
-                    values,
-                    have_local: false,
-                }));
+                push_range_item(&mut out, &mut size, config, range, values, false, |range| {
+                    self.get_fingerprint(range).map(Some)
+                })?;
             } else {
                 // Case3 Recurse
+                let _span = trace_span!(
+                    "split_range",
+                    num_local_values,
+                    split_factor = config.split_factor
+                )
+                .entered();
                 // Create partition
                 // m0 = x < m1 < .. < mk = y, with k>= 2
                 // such that [ml, ml+1) is nonempty
@@ -528,11 +1197,15 @@ pub trait Store<E: RangeEntry>: Sized {
                                 })
                             })
                             .collect::<Result<_, _>>()?;
-                        out.push(MessagePart::RangeItem(RangeItem {
+                        push_range_item(
+                            &mut out,
+                            &mut size,
+                            config,
                             range,
                             values,
-                            have_local: false,
-                        }));
+                            false,
+                            |range| self.get_fingerprint(range).map(Some),
+                        )?;
                     }
                 }
                 debug_assert!(non_empty > 1);
@@ -663,12 +1336,130 @@ impl<E: RangeEntry, S: Store<E>> Store<E> for &mut S {
     }
 }
 
+/// Push a [`RangeItem`] for `range` to `out`.
+///
+/// If there are more than [`SyncConfig::max_values_per_message`] values, the range is split into
+/// consecutive subranges, each covering at most that many values.
+///
+/// `size` is the encoded size of the values already in the message, and is increased by the
+/// size of the pushed values. Once the values would exceed [`SyncConfig::max_message_size`],
+/// the rest of the range is pushed as a fingerprint computed by `fingerprint`, or left out if it
+/// returns `None`. The first value of a message is always pushed, so that every message makes
+/// progress.
+fn push_range_item<E: RangeEntry, Err>(
+    out: &mut Vec<MessagePart<E>>,
+    size: &mut usize,
+    config: &SyncConfig,
+    range: Range<E::Key>,
+    mut values: Vec<(E, ContentStatus)>,
+    have_local: bool,
+    mut fingerprint: impl FnMut(&Range<E::Key>) -> Result<Option<Fingerprint>, Err>,
+) -> Result<(), Err> {
+    let max = config.max_values_per_message.max(1);
+    // order the values as they appear in the (possibly wrapping) range, starting from x
+    values.sort_by(|(a, _), (b, _)| {
+        (a.key() < range.x(), a.key()).cmp(&(b.key() < range.x(), b.key()))
+    });
+    let Range { mut x, y } = range;
+    loop {
+        let mut take = 0;
+        for (entry, _) in values.iter().take(max) {
+            let len = entry.encoded_len();
+            if *size > 0 && *size + len > config.max_message_size {
+                break;
+            }
+            *size += len;
+            take += 1;
+        }
+        if take == values.len() {
+            break;
+        }
+        if take == 0 {
+            let range = Range::new(x, y);
+            if let Some(fingerprint) = fingerprint(&range)? {
+                out.push(MessagePart::RangeFingerprint(RangeFingerprint {
+                    range,
+                    fingerprint,
+                }));
+            }
+            return Ok(());
+        }
+        let rest = values.split_off(take);
+        let next_x = rest[0].0.key().clone();
+        out.push(MessagePart::RangeItem(RangeItem {
+            range: Range::new(x, next_x.clone()),
+            values,
+            have_local,
+        }));
+        x = next_x;
+        values = rest;
+    }
+    out.push(MessagePart::RangeItem(RangeItem {
+        range: Range::new(x, y),
+        values,
+        have_local,
+    }));
+    Ok(())
+}
+
+/// Configuration for the set reconciliation protocol.
 #[derive(Debug, Clone, Copy)]
 pub struct SyncConfig {
     /// Up to how many values to send immediately, before sending only a fingerprint.
     max_set_size: usize,
     /// `k` in the protocol, how many splits to generate. at least 2
     split_factor: usize,
+    /// Maximum number of values in a single [`RangeItem`].
+    ///
+    /// Larger item sets are split into multiple items over consecutive subranges, which bounds
+    /// the size of each item independent of `max_set_size`.
+    max_values_per_message: usize,
+    /// Maximum encoded size of the values in a single message, in bytes.
+    ///
+    /// Values which do not fit are deferred to later rounds by sending a fingerprint for their
+    /// range instead.
+    max_message_size: usize,
+    /// Maximum number of entries to receive in a single sync session.
+    max_entries_received: Option<usize>,
+}
+
+impl SyncConfig {
+    /// Set the maximum number of entries to receive from the remote in a single sync session.
+    ///
+    /// See [`crate::sync::Replica::sync_process_message`] for how the limit is enforced.
+    pub fn with_max_entries_received(mut self, max: usize) -> Self {
+        self.max_entries_received = Some(max);
+        self
+    }
+
+    /// The maximum number of entries to receive in a single sync session, if limited.
+    pub fn max_entries_received(&self) -> Option<usize> {
+        self.max_entries_received
+    }
+
+    /// Set the maximum number of values in a single [`RangeItem`].
+    pub fn with_max_values_per_message(mut self, max: usize) -> Self {
+        self.max_values_per_message = max;
+        self
+    }
+
+    /// The maximum number of values in a single [`RangeItem`].
+    pub fn max_values_per_message(&self) -> usize {
+        self.max_values_per_message
+    }
+
+    /// Set the maximum encoded size of the values in a single message, in bytes.
+    ///
+    /// A message always carries at least one value, even if it is larger than the limit.
+    pub fn with_max_message_size(mut self, max: usize) -> Self {
+        self.max_message_size = max;
+        self
+    }
+
+    /// The maximum encoded size of the values in a single message, in bytes.
+    pub fn max_message_size(&self) -> usize {
+        self.max_message_size
+    }
 }
 
 impl Default for SyncConfig {
@@ -676,12 +1467,15 @@ impl Default for SyncConfig {
         SyncConfig {
             max_set_size: 1,
             split_factor: 2,
+            max_values_per_message: 1024,
+            max_message_size: 4 * 1024 * 1024,
+            max_entries_received: None,
         }
     }
 }
 
-/// The outcome of a [`Store::put`] operation.
-#[derive(Debug)]
+/// The outcome of inserting an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum InsertOutcome {
     /// The entry was not inserted because a newer entry for its key or a
     /// prefix of its key exists.
@@ -738,6 +1532,10 @@ mod tests {
             hasher.update(format!("{:?}", self.1).as_bytes());
             Fingerprint(hasher.finalize().into())
         }
+
+        fn encoded_len(&self) -> usize {
+            format!("{:?}{:?}", self.0, self.1).len()
+        }
     }
 
     impl RangeKey for &'static str {
@@ -1153,7 +1951,14 @@ mod tests {
         }
 
         // run sync with a validate callback returning false, so no new entries are stored on either side
-        let mut res = sync_exchange_messages(alice, bob, &validate_alice, &validate_bob, 100);
+        let mut res = sync_exchange_messages(
+            alice,
+            bob,
+            &validate_alice,
+            &validate_bob,
+            &Default::default(),
+            100,
+        );
         res.assert_alice_set("unchanged", &alice_set);
         res.assert_bob_set("unchanged", &bob_set);
 
@@ -1264,7 +2069,33 @@ mod tests {
     {
         let alice_validate_cb: ValidateCb<K, V> = Box::new(|_, _, _| true);
         let bob_validate_cb: ValidateCb<K, V> = Box::new(|_, _, _| true);
-        sync_with_validate_cb_and_assert(alice_set, bob_set, &alice_validate_cb, &bob_validate_cb)
+        sync_with_validate_cb_and_assert(
+            alice_set,
+            bob_set,
+            &alice_validate_cb,
+            &bob_validate_cb,
+            &Default::default(),
+        )
+    }
+
+    fn sync_with_config<K, V>(
+        alice_set: &[(K, V)],
+        bob_set: &[(K, V)],
+        config: &SyncConfig,
+    ) -> SyncResult<K, V>
+    where
+        K: RangeKey + Default,
+        V: RangeValue,
+    {
+        let alice_validate_cb: ValidateCb<K, V> = Box::new(|_, _, _| true);
+        let bob_validate_cb: ValidateCb<K, V> = Box::new(|_, _, _| true);
+        sync_with_validate_cb_and_assert(
+            alice_set,
+            bob_set,
+            &alice_validate_cb,
+            &bob_validate_cb,
+            config,
+        )
     }
 
     fn insert_if_larger<K: RangeKey, V: RangeValue>(map: &mut BTreeMap<K, V>, key: K, value: V) {
@@ -1292,6 +2123,7 @@ mod tests {
         bob_set: &[(K, V)],
         alice_validate_cb: F1,
         bob_validate_cb: F2,
+        config: &SyncConfig,
     ) -> SyncResult<K, V>
     where
         K: RangeKey + Default,
@@ -1330,7 +2162,8 @@ mod tests {
             expected_set.into_iter().collect::<Vec<_>>()
         };
 
-        let mut res = sync_exchange_messages(alice, bob, alice_validate_cb, bob_validate_cb, 100);
+        let mut res =
+            sync_exchange_messages(alice, bob, alice_validate_cb, bob_validate_cb, config, 100);
 
         let alice_now: Vec<_> = res.alice.all().unwrap().collect::<Result<_, _>>().unwrap();
         if alice_now != expected_set {
@@ -1352,6 +2185,11 @@ mod tests {
             panic!("bob_now does not match expected");
         }
 
+        // Values deferred because of the message size limit may be sent again.
+        if config.max_message_size() < SyncConfig::default().max_message_size() {
+            return res;
+        }
+
         // Check that values were never sent twice
         let mut alice_sent = BTreeMap::new();
         for msg in &res.alice_to_bob {
@@ -1391,6 +2229,7 @@ mod tests {
         mut bob: SimpleStore<K, V>,
         alice_validate_cb: F1,
         bob_validate_cb: F2,
+        config: &SyncConfig,
         max_rounds: usize,
     ) -> SyncResult<K, V>
     where
@@ -1412,7 +2251,7 @@ mod tests {
 
             if let Some(msg) = bob
                 .process_message(
-                    &Default::default(),
+                    config,
                     msg,
                     &bob_validate_cb,
                     |_, _, _| (),
@@ -1423,7 +2262,7 @@ mod tests {
                 bob_to_alice.push(msg.clone());
                 next_to_bob = alice
                     .process_message(
-                        &Default::default(),
+                        config,
                         msg,
                         &alice_validate_cb,
                         |_, _, _| (),
@@ -1579,6 +2418,100 @@ mod tests {
         let _res = sync(&alice, &bob);
     }
 
+    #[test]
+    fn simple_store_sync_max_values_per_message() {
+        let config = SyncConfig::default().with_max_values_per_message(3);
+        let alice = mk_test_vec((0..20).map(|i| format!("{i:02}")));
+        let mut alice_store = SimpleStore::<String, ()>::default();
+        for e in alice.iter().cloned() {
+            alice_store.put(e).unwrap();
+        }
+
+        // an empty peer receives all values at once, split into multiple items
+        let init = SimpleStore::<String, ()>::default()
+            .initial_message()
+            .unwrap();
+        let reply = alice_store
+            .process_message(
+                &config,
+                init,
+                |_, _, _| true,
+                |_, _, _| (),
+                |_, _| ContentStatus::Complete,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.parts().len(), 7);
+        assert_eq!(reply.value_count(), 20);
+        for part in reply.parts() {
+            assert!(part.values().unwrap().len() <= 3);
+        }
+
+        let bob = mk_test_vec(["03", "07", "11", "21", "22"]);
+        let res = sync_with_config(&alice, &bob, &config);
+        for msg in res.alice_to_bob.iter().chain(res.bob_to_alice.iter()) {
+            for part in msg.parts() {
+                assert!(part.values().map_or(0, |v| v.len()) <= 3);
+            }
+        }
+    }
+
+    #[test]
+    fn simple_store_sync_max_message_size() {
+        // each value is encoded with 6 bytes, so at most 3 values fit into a message
+        let config = SyncConfig::default().with_max_message_size(20);
+        let alice = mk_test_vec((0..20).map(|i| format!("{i:02}")));
+        let mut alice_store = SimpleStore::<String, ()>::default();
+        for e in alice.iter().cloned() {
+            alice_store.put(e).unwrap();
+        }
+
+        // an empty peer receives only the values that fit, and a fingerprint for the rest
+        let init = SimpleStore::<String, ()>::default()
+            .initial_message()
+            .unwrap();
+        let reply = alice_store
+            .process_message(
+                &config,
+                init,
+                |_, _, _| true,
+                |_, _, _| (),
+                |_, _| ContentStatus::Complete,
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(reply.value_count(), 3);
+        assert!(matches!(
+            reply.parts().last(),
+            Some(MessagePart::RangeFingerprint(_))
+        ));
+
+        let bob = mk_test_vec(["03", "07", "11", "21", "22"]);
+        let res = sync_with_config(&alice, &bob, &config);
+        for msg in res.alice_to_bob.iter().chain(res.bob_to_alice.iter()) {
+            let size: usize = msg.values().map(|(e, _)| e.encoded_len()).sum();
+            assert!(size <= 20);
+        }
+    }
+
+    #[proptest]
+    fn simple_store_sync_small_messages(
+        #[strategy(test_vec_string_unit())] alice: Vec<(String, ())>,
+        #[strategy(test_vec_string_unit())] bob: Vec<(String, ())>,
+    ) {
+        let config = SyncConfig::default().with_max_message_size(32);
+        let _res = sync_with_config(&alice, &bob, &config);
+    }
+
+    #[proptest]
+    fn simple_store_sync_small_items(
+        #[strategy(test_vec_string_unit())] alice: Vec<(String, ())>,
+        #[strategy(test_vec_string_unit())] bob: Vec<(String, ())>,
+    ) {
+        let config = SyncConfig::default().with_max_values_per_message(2);
+        let _res = sync_with_config(&alice, &bob, &config);
+    }
+
     #[proptest]
     fn simple_store_sync(
         #[strategy(test_vec_string_unit())] alice: Vec<(String, ())>,
@@ -1595,6 +2528,63 @@ mod tests {
         let _res = sync(&alice, &bob);
     }
 
+    #[proptest]
+    fn range_set_operations(
+        #[strategy(test_range())] a: Range<String>,
+        #[strategy(test_range())] b: Range<String>,
+        #[strategy(prop::collection::vec(test_key(), 0..20))] keys: Vec<String>,
+    ) {
+        // sample the key space around the bounds, and some random keys
+        let keys = [a.x(), a.y(), b.x(), b.y()]
+            .into_iter()
+            .flat_map(|k| [k.clone(), format!("{k}0")])
+            .chain(keys)
+            .chain([String::new()]);
+        let intersection = a.intersect(&b);
+        let union = a.union(&b);
+        let difference = a.difference(&b);
+        for k in keys {
+            let count = |ranges: &[Range<String>]| ranges.iter().filter(|r| r.contains(&k)).count();
+            prop_assert!(count(&intersection) <= 1);
+            prop_assert!(count(&union) <= 1);
+            prop_assert!(count(&difference) <= 1);
+            prop_assert_eq!(
+                count(&intersection) == 1,
+                a.contains(&k) && b.contains(&k),
+                "intersect {:?} {:?}: {:?}",
+                a,
+                b,
+                intersection
+            );
+            prop_assert_eq!(count(&union) == 1, a.contains(&k) || b.contains(&k));
+            prop_assert_eq!(count(&difference) == 1, a.contains(&k) && !b.contains(&k));
+        }
+    }
+
+    #[test]
+    fn range_set_operations_examples() {
+        let r = |x: &str, y: &str| Range::new(x.to_string(), y.to_string());
+        // overlapping plain ranges
+        assert_eq!(r("a", "d").intersect(&r("b", "f")), vec![r("b", "d")]);
+        assert_eq!(r("a", "d").union(&r("b", "f")), vec![r("a", "f")]);
+        assert_eq!(r("a", "d").difference(&r("b", "f")), vec![r("a", "b")]);
+        // disjoint plain ranges
+        assert_eq!(r("a", "b").intersect(&r("c", "d")), vec![]);
+        assert_eq!(
+            r("a", "b").union(&r("c", "d")),
+            vec![r("a", "b"), r("c", "d")]
+        );
+        // wrapping ranges
+        assert_eq!(
+            r("x", "c").intersect(&r("b", "z")),
+            vec![r("b", "c"), r("x", "z")]
+        );
+        let union = r("x", "c").union(&r("b", "y"));
+        assert!(union.len() == 1 && union[0].is_all());
+        assert_eq!(r("m", "m").difference(&r("c", "f")), vec![r("f", "c")]);
+        assert_eq!(r("c", "f").difference(&r("a", "a")), vec![]);
+    }
+
     /// A generic fn to make a test for the get_range fn of a store.
     #[allow(clippy::type_complexity)]
     fn store_get_ranges_test<S, E>(
@@ -1633,4 +2623,34 @@ mod tests {
         let (expected, actual) = store_get_ranges_test::<SimpleStore<_, _>, _>(contents, range);
         prop_assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn test_status_encoding() {
+        use ContentStatus::*;
+        let cases: [&[ContentStatus]; 4] = [
+            &[],
+            &[Missing; 100],
+            &[Complete, Missing, Incomplete, Missing],
+            &[
+                Complete, Complete, Complete, Missing, Missing, Missing, Missing,
+            ],
+        ];
+        for statuses in cases {
+            let encoded = StatusEncoding::encode(statuses.iter());
+            assert_eq!(encoded.clone().decode(statuses.len()).unwrap(), statuses);
+            assert!(encoded.decode(statuses.len() + 1).is_err());
+        }
+        assert!(matches!(
+            StatusEncoding::encode([Missing; 100].iter()),
+            StatusEncoding::RunLength(runs) if runs == [(Missing, 100)]
+        ));
+        assert!(matches!(
+            StatusEncoding::encode([Complete, Missing, Incomplete, Missing].iter()),
+            StatusEncoding::PerValue(_)
+        ));
+
+        // crafted run lengths must not overflow or allocate
+        let crafted = StatusEncoding::RunLength(vec![(Missing, u64::MAX), (Complete, 2)]);
+        assert!(crafted.decode(1).is_err());
+    }
 }
@@ -60,16 +60,21 @@ impl<D: iroh_blobs::store::Store> Handler<D> {
             }
             Del(msg) => chan.rpc(msg, this, Self::doc_del).await,
             SetHash(msg) => chan.rpc(msg, this, Self::doc_set_hash).await,
+            SetMany(msg) => chan.rpc(msg, this, Self::doc_set_many).await,
+            SetHashMany(msg) => chan.rpc(msg, this, Self::doc_set_hash_many).await,
             Get(msg) => chan.server_streaming(msg, this, Self::doc_get_many).await,
             GetExact(msg) => chan.rpc(msg, this, Self::doc_get_exact).await,
             StartSync(msg) => chan.rpc(msg, this, Self::doc_start_sync).await,
             Leave(msg) => chan.rpc(msg, this, Self::doc_leave).await,
+            PauseSync(msg) => chan.rpc(msg, this, Self::doc_pause_sync).await,
+            ResumeSync(msg) => chan.rpc(msg, this, Self::doc_resume_sync).await,
             Share(msg) => chan.rpc(msg, this, Self::doc_share).await,
             Subscribe(msg) => {
                 chan.try_server_streaming(msg, this, Self::doc_subscribe)
                     .await
             }
             SetDownloadPolicy(msg) => chan.rpc(msg, this, Self::doc_set_download_policy).await,
+            SetSyncEventMode(msg) => chan.rpc(msg, this, Self::doc_set_sync_event_mode).await,
             GetDownloadPolicy(msg) => chan.rpc(msg, this, Self::doc_get_download_policy).await,
             GetSyncPeers(msg) => chan.rpc(msg, this, Self::doc_get_sync_peers).await,
 
@@ -11,6 +11,7 @@ use std::{
 use anyhow::{anyhow, Context as _, Result};
 use bytes::Bytes;
 use derive_more::{Display, FromStr};
+use futures_buffered::BufferedStreamExt;
 use futures_lite::{Stream, StreamExt};
 use iroh::NodeAddr;
 use iroh_blobs::{export::ExportProgress, store::ExportMode, Hash};
@@ -28,13 +29,14 @@ use crate::{
             CloseRequest, CreateRequest, DelRequest, DelResponse, DocListRequest,
             DocSubscribeRequest, DropRequest, ExportFileRequest, GetDownloadPolicyRequest,
             GetExactRequest, GetManyRequest, GetSyncPeersRequest, ImportFileRequest, ImportRequest,
-            LeaveRequest, OpenRequest, RpcService, SetDownloadPolicyRequest, SetHashRequest,
-            SetRequest, ShareRequest, StartSyncRequest, StatusRequest,
+            LeaveRequest, OpenRequest, PauseSyncRequest, ResumeSyncRequest, RpcService,
+            SetDownloadPolicyRequest, SetHashManyRequest, SetHashRequest, SetManyRequest,
+            SetRequest, SetSyncEventModeRequest, ShareRequest, StartSyncRequest, StatusRequest,
         },
         AddrInfoOptions,
     },
     store::{DownloadPolicy, Query},
-    AuthorId, Capability, CapabilityKind, DocTicket, NamespaceId, PeerIdBytes,
+    AuthorId, Capability, CapabilityKind, DocTicket, NamespaceId, PeerIdBytes, SyncEventMode,
 };
 #[doc(inline)]
 pub use crate::{
@@ -247,6 +249,42 @@ impl<C: Connector<RpcService>> Doc<C> {
         Ok(())
     }
 
+    /// Sets the content of many keys of the same author with a single request.
+    ///
+    /// The entries are inserted in order. Returns the content hashes of the values, in the
+    /// order of `entries`.
+    pub async fn set_many(
+        &self,
+        author_id: AuthorId,
+        entries: Vec<(Bytes, Bytes)>,
+    ) -> Result<Vec<Hash>> {
+        self.ensure_open()?;
+        let res = self
+            .rpc(SetManyRequest {
+                doc_id: self.id(),
+                author_id,
+                entries,
+            })
+            .await??;
+        Ok(res.hashes)
+    }
+
+    /// Sets many entries of the same author via their key, hash, and size with a single request.
+    pub async fn set_hash_many(
+        &self,
+        author_id: AuthorId,
+        entries: Vec<(Bytes, Hash, u64)>,
+    ) -> Result<()> {
+        self.ensure_open()?;
+        self.rpc(SetHashManyRequest {
+            doc_id: self.id(),
+            author_id,
+            entries,
+        })
+        .await??;
+        Ok(())
+    }
+
     /// Adds an entry from an absolute file path
     pub async fn import_file(
         &self,
@@ -347,6 +385,47 @@ impl<C: Connector<RpcService>> Doc<C> {
         Ok(flatten(stream).map(|res| res.map(|res| res.entry.into())))
     }
 
+    /// Returns all entries matching the query together with their content.
+    ///
+    /// The content is read from `blobs`, with up to [`ContentOpts::concurrency`] reads in flight.
+    /// Entries are yielded in the order of the query. If the content of an entry is not
+    /// available completely, an error is yielded unless [`ContentOpts::skip_missing`] is set, in
+    /// which case the entry is skipped.
+    pub async fn get_many_with_content<BC>(
+        &self,
+        query: impl Into<Query>,
+        blobs: &iroh_blobs::rpc::client::blobs::Client<BC>,
+        opts: ContentOpts,
+    ) -> Result<impl Stream<Item = Result<(Entry, Bytes)>>>
+    where
+        BC: Connector<iroh_blobs::rpc::proto::RpcService>,
+    {
+        let entries = self.get_many(query).await?;
+        let blobs = blobs.clone();
+        let stream = entries
+            .map(move |entry| {
+                let blobs = blobs.clone();
+                async move {
+                    let entry = match entry {
+                        Ok(entry) => entry,
+                        Err(err) => return Some(Err(err)),
+                    };
+                    let hash = entry.content_hash();
+                    match blobs.has(hash).await {
+                        Ok(true) => {}
+                        Ok(false) if opts.skip_missing => return None,
+                        Ok(false) => return Some(Err(anyhow!("content {hash} is missing"))),
+                        Err(err) => return Some(Err(err)),
+                    }
+                    let content = blobs.read_to_bytes(hash).await;
+                    Some(content.map(|content| (entry, content)))
+                }
+            })
+            .buffered_ordered(opts.concurrency.max(1))
+            .filter_map(|res| res);
+        Ok(stream)
+    }
+
     /// Returns a single entry.
     pub async fn get_one(&self, query: impl Into<Query>) -> Result<Option<Entry>> {
         self.get_many(query).await?.next().await.transpose()
@@ -388,6 +467,23 @@ impl<C: Connector<RpcService>> Doc<C> {
         Ok(())
     }
 
+    /// Pauses the live sync for this document.
+    ///
+    /// No entries are received from peers until [`Self::resume_sync`] is called. The document
+    /// stays subscribed to its gossip swarm while paused.
+    pub async fn pause_sync(&self) -> Result<()> {
+        self.ensure_open()?;
+        let _res = self.rpc(PauseSyncRequest { doc_id: self.id() }).await??;
+        Ok(())
+    }
+
+    /// Resumes the live sync for this document and catches up with the known peers.
+    pub async fn resume_sync(&self) -> Result<()> {
+        self.ensure_open()?;
+        let _res = self.rpc(ResumeSyncRequest { doc_id: self.id() }).await??;
+        Ok(())
+    }
+
     /// Subscribes to events for this document.
     pub async fn subscribe(&self) -> anyhow::Result<impl Stream<Item = anyhow::Result<LiveEvent>>> {
         self.ensure_open()?;
@@ -419,6 +515,21 @@ impl<C: Connector<RpcService>> Doc<C> {
         Ok(())
     }
 
+    /// Sets how events are emitted for entries inserted while syncing this document.
+    ///
+    /// With [`SyncEventMode::Silent`], subscribers receive a single [`LiveEvent::SyncBatchApplied`]
+    /// per sync instead of an event for each entry. Content is downloaded as usual. The mode is
+    /// reset when the document is closed.
+    pub async fn set_sync_event_mode(&self, mode: SyncEventMode) -> Result<()> {
+        self.ensure_open()?;
+        self.rpc(SetSyncEventModeRequest {
+            doc_id: self.id(),
+            mode,
+        })
+        .await??;
+        Ok(())
+    }
+
     /// Returns the download policy for this document
     pub async fn get_download_policy(&self) -> Result<DownloadPolicy> {
         let res = self
@@ -486,6 +597,24 @@ pub enum ImportProgress {
     Abort(serde_error::Error),
 }
 
+/// Options for [`Doc::get_many_with_content`].
+#[derive(Debug, Clone, Copy)]
+pub struct ContentOpts {
+    /// Maximum number of concurrent blob reads.
+    pub concurrency: usize,
+    /// Skip entries whose content is not available locally instead of yielding an error.
+    pub skip_missing: bool,
+}
+
+impl Default for ContentOpts {
+    fn default() -> Self {
+        Self {
+            concurrency: 16,
+            skip_missing: false,
+        }
+    }
+}
+
 /// Intended capability for document share tickets
 #[derive(Serialize, Deserialize, Debug, Clone, Display, FromStr)]
 pub enum ShareMode {
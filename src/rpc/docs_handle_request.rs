@@ -23,9 +23,11 @@ use super::{
         GetManyResponse, GetSyncPeersRequest, GetSyncPeersResponse, ImportFileRequest,
         ImportFileResponse, ImportRequest as DocImportRequest, ImportResponse as DocImportResponse,
         LeaveRequest, LeaveResponse, ListResponse as DocListResponse, OpenRequest, OpenResponse,
-        SetDownloadPolicyRequest, SetDownloadPolicyResponse, SetHashRequest, SetHashResponse,
-        SetRequest, SetResponse, ShareRequest, ShareResponse, StartSyncRequest, StartSyncResponse,
-        StatusRequest, StatusResponse,
+        PauseSyncRequest, PauseSyncResponse, ResumeSyncRequest, ResumeSyncResponse,
+        SetDownloadPolicyRequest, SetDownloadPolicyResponse, SetHashManyRequest,
+        SetHashManyResponse, SetHashRequest, SetHashResponse, SetManyRequest, SetManyResponse,
+        SetRequest, SetResponse, SetSyncEventModeRequest, SetSyncEventModeResponse, ShareRequest,
+        ShareResponse, StartSyncRequest, StartSyncResponse, StatusRequest, StatusResponse,
     },
     Handler, RpcError, RpcResult,
 };
@@ -283,6 +285,28 @@ impl<D: iroh_blobs::store::Store> Handler<D> {
         Ok(LeaveResponse {})
     }
 
+    pub(super) async fn doc_pause_sync(
+        self,
+        req: PauseSyncRequest,
+    ) -> RpcResult<PauseSyncResponse> {
+        let PauseSyncRequest { doc_id } = req;
+        self.pause_sync(doc_id)
+            .await
+            .map_err(|e| RpcError::new(&*e))?;
+        Ok(PauseSyncResponse {})
+    }
+
+    pub(super) async fn doc_resume_sync(
+        self,
+        req: ResumeSyncRequest,
+    ) -> RpcResult<ResumeSyncResponse> {
+        let ResumeSyncRequest { doc_id } = req;
+        self.resume_sync(doc_id)
+            .await
+            .map_err(|e| RpcError::new(&*e))?;
+        Ok(ResumeSyncResponse {})
+    }
+
     pub(super) async fn doc_set(self, req: SetRequest) -> RpcResult<SetResponse> {
         let blobs_store = self.blob_store();
         let SetRequest {
@@ -309,6 +333,33 @@ impl<D: iroh_blobs::store::Store> Handler<D> {
         Ok(SetResponse { entry })
     }
 
+    pub(super) async fn doc_set_many(self, req: SetManyRequest) -> RpcResult<SetManyResponse> {
+        let blobs_store = self.blob_store();
+        let SetManyRequest {
+            doc_id,
+            author_id,
+            entries,
+        } = req;
+        // keep the temp tags until the entries are inserted
+        let mut tags = Vec::with_capacity(entries.len());
+        let mut inserts = Vec::with_capacity(entries.len());
+        for (key, value) in entries {
+            let len = value.len();
+            let tag = blobs_store
+                .import_bytes(value, BlobFormat::Raw)
+                .await
+                .map_err(|e| RpcError::new(&e))?;
+            inserts.push((key, *tag.hash(), len as u64));
+            tags.push(tag);
+        }
+        let hashes = inserts.iter().map(|(_key, hash, _len)| *hash).collect();
+        self.sync
+            .insert_local_many(doc_id, author_id, inserts)
+            .await
+            .map_err(|e| RpcError::new(&*e))?;
+        Ok(SetManyResponse { hashes })
+    }
+
     pub(super) async fn doc_del(self, req: DelRequest) -> RpcResult<DelResponse> {
         let DelRequest {
             doc_id,
@@ -338,6 +389,22 @@ impl<D: iroh_blobs::store::Store> Handler<D> {
         Ok(SetHashResponse {})
     }
 
+    pub(super) async fn doc_set_hash_many(
+        self,
+        req: SetHashManyRequest,
+    ) -> RpcResult<SetHashManyResponse> {
+        let SetHashManyRequest {
+            doc_id,
+            author_id,
+            entries,
+        } = req;
+        self.sync
+            .insert_local_many(doc_id, author_id, entries)
+            .await
+            .map_err(|e| RpcError::new(&*e))?;
+        Ok(SetHashManyResponse {})
+    }
+
     pub(super) fn doc_get_many(
         self,
         req: GetManyRequest,
@@ -385,6 +452,16 @@ impl<D: iroh_blobs::store::Store> Handler<D> {
         Ok(SetDownloadPolicyResponse {})
     }
 
+    pub(super) async fn doc_set_sync_event_mode(
+        self,
+        req: SetSyncEventModeRequest,
+    ) -> RpcResult<SetSyncEventModeResponse> {
+        self.set_sync_event_mode(req.doc_id, req.mode)
+            .await
+            .map_err(|e| RpcError::new(&*e))?;
+        Ok(SetSyncEventModeResponse {})
+    }
+
     pub(super) async fn doc_get_download_policy(
         self,
         req: GetDownloadPolicyRequest,
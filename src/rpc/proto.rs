@@ -19,7 +19,7 @@ use crate::{
     engine::LiveEvent,
     store::{DownloadPolicy, Query},
     Author, AuthorId, Capability, CapabilityKind, DocTicket, Entry, NamespaceId, PeerIdBytes,
-    SignedEntry,
+    SignedEntry, SyncEventMode,
 };
 
 /// The RPC service type for the docs protocol.
@@ -54,6 +54,10 @@ pub enum Request {
     Set(SetRequest),
     #[rpc(response = RpcResult<SetHashResponse>)]
     SetHash(SetHashRequest),
+    #[rpc(response = RpcResult<SetManyResponse>)]
+    SetMany(SetManyRequest),
+    #[rpc(response = RpcResult<SetHashManyResponse>)]
+    SetHashMany(SetHashManyRequest),
     #[server_streaming(response = RpcResult<GetManyResponse>)]
     Get(GetManyRequest),
     #[rpc(response = RpcResult<GetExactResponse>)]
@@ -68,6 +72,10 @@ pub enum Request {
     StartSync(StartSyncRequest),
     #[rpc(response = RpcResult<LeaveResponse>)]
     Leave(LeaveRequest),
+    #[rpc(response = RpcResult<PauseSyncResponse>)]
+    PauseSync(PauseSyncRequest),
+    #[rpc(response = RpcResult<ResumeSyncResponse>)]
+    ResumeSync(ResumeSyncRequest),
     #[rpc(response = RpcResult<ShareResponse>)]
     Share(ShareRequest),
     #[try_server_streaming(create_error = RpcError, item_error = RpcError, item = DocSubscribeResponse)]
@@ -76,6 +84,8 @@ pub enum Request {
     GetDownloadPolicy(GetDownloadPolicyRequest),
     #[rpc(response = RpcResult<SetDownloadPolicyResponse>)]
     SetDownloadPolicy(SetDownloadPolicyRequest),
+    #[rpc(response = RpcResult<SetSyncEventModeResponse>)]
+    SetSyncEventMode(SetSyncEventModeRequest),
     #[rpc(response = RpcResult<GetSyncPeersResponse>)]
     GetSyncPeers(GetSyncPeersRequest),
     #[server_streaming(response = RpcResult<AuthorListResponse>)]
@@ -107,6 +117,8 @@ pub enum Response {
     Import(RpcResult<ImportResponse>),
     Set(RpcResult<SetResponse>),
     SetHash(RpcResult<SetHashResponse>),
+    SetMany(RpcResult<SetManyResponse>),
+    SetHashMany(RpcResult<SetHashManyResponse>),
     Get(RpcResult<GetManyResponse>),
     GetExact(RpcResult<GetExactResponse>),
     ImportFile(ImportFileResponse),
@@ -115,9 +127,12 @@ pub enum Response {
     Share(RpcResult<ShareResponse>),
     StartSync(RpcResult<StartSyncResponse>),
     Leave(RpcResult<LeaveResponse>),
+    PauseSync(RpcResult<PauseSyncResponse>),
+    ResumeSync(RpcResult<ResumeSyncResponse>),
     Subscribe(RpcResult<DocSubscribeResponse>),
     GetDownloadPolicy(RpcResult<GetDownloadPolicyResponse>),
     SetDownloadPolicy(RpcResult<SetDownloadPolicyResponse>),
+    SetSyncEventMode(RpcResult<SetSyncEventModeResponse>),
     GetSyncPeers(RpcResult<GetSyncPeersResponse>),
     StreamCreated(RpcResult<StreamCreated>),
     AuthorList(RpcResult<AuthorListResponse>),
@@ -257,6 +272,28 @@ pub struct LeaveRequest {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct LeaveResponse {}
 
+/// Pause the live sync for a doc.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PauseSyncRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`PauseSyncRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PauseSyncResponse {}
+
+/// Resume the live sync for a paused doc.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResumeSyncRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+}
+
+/// Response to [`ResumeSyncRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResumeSyncResponse {}
+
 /// Stop the live sync for a doc, and optionally delete the document.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DropRequest {
@@ -290,6 +327,24 @@ pub struct SetResponse {
     pub entry: SignedEntry,
 }
 
+/// Set many entries of the same author in a document
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetManyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Author of the entries.
+    pub author_id: AuthorId,
+    /// Keys and values of the entries.
+    pub entries: Vec<(Bytes, Bytes)>,
+}
+
+/// Response to [`SetManyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetManyResponse {
+    /// The content hashes of the values, in the order of the request.
+    pub hashes: Vec<Hash>,
+}
+
 /// A request to the node to add the data at the given filepath as an entry to the document
 ///
 /// Will produce a stream of [`ImportProgress`] messages.
@@ -378,6 +433,21 @@ pub struct SetHashRequest {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SetHashResponse {}
 
+/// Set many entries of the same author in a document via their hashes
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetHashManyRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Author of the entries.
+    pub author_id: AuthorId,
+    /// Keys, hashes and sizes of the entries.
+    pub entries: Vec<(Bytes, Hash, u64)>,
+}
+
+/// Response to [`SetHashManyRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetHashManyResponse {}
+
 /// Get entries from a document
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetManyRequest {
@@ -427,6 +497,19 @@ pub struct SetDownloadPolicyRequest {
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SetDownloadPolicyResponse {}
 
+/// Set how events are emitted for entries inserted while syncing
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetSyncEventModeRequest {
+    /// The document id
+    pub doc_id: NamespaceId,
+    /// Sync event mode
+    pub mode: SyncEventMode,
+}
+
+/// Response to [`SetSyncEventModeRequest`]
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SetSyncEventModeResponse {}
+
 /// Get a download policy
 #[derive(Serialize, Deserialize, Debug)]
 pub struct GetDownloadPolicyRequest {
@@ -3,9 +3,10 @@ use std::num::NonZeroUsize;
 
 use anyhow::Result;
 use bytes::Bytes;
+use iroh_blobs::Hash;
 use serde::{Deserialize, Serialize};
 
-use crate::{AuthorId, Entry, NamespaceId};
+use crate::{AuthorId, Entry, NamespaceId, SignedEntry};
 
 pub mod fs;
 mod pubkeys;
@@ -25,11 +26,27 @@ pub enum OpenError {
     /// The replica does not exist.
     #[error("Replica not found")]
     NotFound,
+    /// The store did not get to open the replica within the timeout set in
+    /// [`crate::actor::OpenOpts::timeout`].
+    #[error("Timed out waiting for the store to open the replica")]
+    Timeout,
     /// Other error while opening the replica.
     #[error("{0}")]
     Other(#[from] anyhow::Error),
 }
 
+/// Error returned from writes to a [`Store`] opened with [`Store::open_read_only`].
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("store is opened read-only")]
+pub struct ReadOnlyStore;
+
+/// Error returned from [`Store::flush_with_timeout`] if the commit did not complete in time.
+///
+/// Writes to the store fail with this error as well, until the stuck commit completed.
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("flushing the store did not complete within {0:?}")]
+pub struct FlushTimeout(pub std::time::Duration);
+
 /// Store that gives read access to download policies for a document.
 pub trait DownloadPolicyStore {
     /// Get the download policy for a document.
@@ -48,6 +65,110 @@ impl DownloadPolicyStore for crate::store::Store {
     }
 }
 
+/// Summary of the entry timestamps of a namespace, see [`Store::timestamp_summary`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimestampSummary {
+    /// Number of entries.
+    pub count: u64,
+    /// The smallest entry timestamp, or `None` if there are no entries.
+    pub min: Option<u64>,
+    /// The largest entry timestamp, or `None` if there are no entries.
+    pub max: Option<u64>,
+    /// Number of entries with a timestamp later than the reference time.
+    pub count_in_future: u64,
+    /// How far the latest entry is ahead of the reference time, in microseconds.
+    ///
+    /// Zero if no entry is in the future.
+    pub max_future_delta: u64,
+}
+
+/// Statistics of a [`Store::rebuild_namespace`] operation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RebuildStats {
+    /// Number of entries that were inserted.
+    pub inserted: usize,
+    /// Number of inserted entries that were removed again by newer entries.
+    pub removed: usize,
+    /// Number of entries that were not inserted because a newer entry existed.
+    pub not_inserted: usize,
+    /// Number of entries that failed to validate and were skipped.
+    pub invalid: usize,
+}
+
+/// Selects the fields of the entries returned from [`Store::get_projected`].
+///
+/// Fields which are not selected are `None` in the [`ProjectedEntry`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Projection {
+    /// Include the author of the entry.
+    pub author: bool,
+    /// Include the key of the entry.
+    pub key: bool,
+    /// Include the content hash of the entry.
+    pub content_hash: bool,
+    /// Include the content length of the entry.
+    pub content_len: bool,
+    /// Include the timestamp of the entry.
+    pub timestamp: bool,
+}
+
+impl Projection {
+    /// Select all fields.
+    pub fn all() -> Self {
+        Self {
+            author: true,
+            key: true,
+            content_hash: true,
+            content_len: true,
+            timestamp: true,
+        }
+    }
+
+    /// Select only the key and the content hash.
+    pub fn key_and_hash() -> Self {
+        Self {
+            key: true,
+            content_hash: true,
+            ..Default::default()
+        }
+    }
+
+    /// Select only the key and the timestamp.
+    pub fn key_and_timestamp() -> Self {
+        Self {
+            key: true,
+            timestamp: true,
+            ..Default::default()
+        }
+    }
+
+    /// Project an entry onto the selected fields.
+    pub fn project(&self, entry: &SignedEntry) -> ProjectedEntry {
+        ProjectedEntry {
+            author: self.author.then(|| entry.author_bytes()),
+            key: self.key.then(|| entry.id().key_bytes()),
+            content_hash: self.content_hash.then(|| entry.content_hash()),
+            content_len: self.content_len.then(|| entry.content_len()),
+            timestamp: self.timestamp.then(|| entry.timestamp()),
+        }
+    }
+}
+
+/// The fields of an entry selected by a [`Projection`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectedEntry {
+    /// The author of the entry.
+    pub author: Option<AuthorId>,
+    /// The key of the entry.
+    pub key: Option<Bytes>,
+    /// The content hash of the entry.
+    pub content_hash: Option<Hash>,
+    /// The content length of the entry.
+    pub content_len: Option<u64>,
+    /// The timestamp of the entry.
+    pub timestamp: Option<u64>,
+}
+
 /// Outcome of [`Store::import_namespace`]
 #[derive(Debug, Clone, Copy)]
 pub enum ImportNamespaceOutcome {
@@ -66,6 +187,26 @@ pub enum DownloadPolicy {
     NothingExcept(Vec<FilterKind>),
     /// Download every key unless it matches one of the filters.
     EverythingExcept(Vec<FilterKind>),
+    /// Apply prefix-scoped overrides on top of a base policy.
+    ///
+    /// The override with the longest prefix matching a key decides whether the key is
+    /// downloaded. If several overrides share that prefix, the first one in the list wins. Keys
+    /// matching no override are decided by the base policy.
+    WithOverrides {
+        /// The policy for keys not matching any override.
+        base: Box<DownloadPolicy>,
+        /// The prefix-scoped overrides.
+        overrides: Vec<PrefixOverride>,
+    },
+}
+
+/// Download decision for all keys starting with a prefix, see [`DownloadPolicy::WithOverrides`].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PrefixOverride {
+    /// The prefix of keys this override applies to.
+    pub prefix: Bytes,
+    /// Whether keys with this prefix shall be downloaded.
+    pub download: bool,
 }
 
 impl Default for DownloadPolicy {
@@ -81,6 +222,12 @@ pub enum FilterKind {
     Prefix(Bytes),
     /// Matches if the contained bytes and the key are the same.
     Exact(Bytes),
+    /// Matches if the key, interpreted as a `/`-separated path, matches the contained glob pattern.
+    ///
+    /// `*` matches any number of characters within a path segment, `?` matches a single
+    /// character, and a `**` segment matches any number of segments. Keys which are not valid
+    /// UTF-8 never match.
+    Glob(String),
 }
 
 impl std::fmt::Display for FilterKind {
@@ -89,6 +236,7 @@ impl std::fmt::Display for FilterKind {
         let (kind, bytes) = match self {
             FilterKind::Prefix(bytes) => ("prefix", bytes),
             FilterKind::Exact(bytes) => ("exact", bytes),
+            FilterKind::Glob(pattern) => return write!(f, "glob:utf8:{pattern}"),
         };
         let (encoding, repr) = match String::from_utf8(bytes.to_vec()) {
             Ok(repr) => ("utf8", repr),
@@ -103,19 +251,15 @@ impl std::str::FromStr for FilterKind {
 
     fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
         let Some((kind, rest)) = s.split_once(':') else {
-            anyhow::bail!("missing filter kind, either \"prefix:\" or \"exact:\"")
+            anyhow::bail!("missing filter kind, either \"prefix:\", \"exact:\" or \"glob:\"")
         };
         let Some((encoding, rest)) = rest.split_once(':') else {
             anyhow::bail!("missing encoding: either \"hex:\" or \"utf8:\"")
         };
 
-        let is_exact = match kind {
-            "exact" => true,
-            "prefix" => false,
-            other => {
-                anyhow::bail!("expected filter kind \"prefix:\" or \"exact:\", found {other}")
-            }
-        };
+        if !matches!(kind, "exact" | "prefix" | "glob") {
+            anyhow::bail!("expected filter kind \"prefix:\", \"exact:\" or \"glob:\", found {kind}")
+        }
 
         let decoded = match encoding {
             "utf8" => Bytes::from(rest.to_owned()),
@@ -128,10 +272,13 @@ impl std::str::FromStr for FilterKind {
             }
         };
 
-        if is_exact {
-            Ok(FilterKind::Exact(decoded))
-        } else {
-            Ok(FilterKind::Prefix(decoded))
+        match kind {
+            "exact" => Ok(FilterKind::Exact(decoded)),
+            "prefix" => Ok(FilterKind::Prefix(decoded)),
+            _ => match String::from_utf8(decoded.to_vec()) {
+                Ok(pattern) => Ok(FilterKind::Glob(pattern)),
+                Err(_) => anyhow::bail!("glob pattern is not valid utf8"),
+            },
         }
     }
 }
@@ -142,10 +289,90 @@ impl FilterKind {
         match self {
             FilterKind::Prefix(prefix) => key.as_ref().starts_with(prefix),
             FilterKind::Exact(expected) => expected == key.as_ref(),
+            FilterKind::Glob(pattern) => match std::str::from_utf8(key.as_ref()) {
+                Ok(key) => {
+                    let pattern: Vec<&str> = pattern.split('/').collect();
+                    let key: Vec<&str> = key.split('/').collect();
+                    glob_match_path(&pattern, &key)
+                }
+                Err(_) => false,
+            },
         }
     }
 }
 
+/// Match path segments against glob pattern segments, where a `**` segment matches any number
+/// of path segments.
+///
+/// This is a greedy match which only backtracks to the last `**`, so it takes at most
+/// `O(pattern.len() * path.len())` segment matches.
+fn glob_match_path(pattern: &[&str], path: &[&str]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // Position in the pattern after the last `**`, and the path position it was matched at.
+    let mut star: Option<(usize, usize)> = None;
+    while s < path.len() {
+        match pattern.get(p) {
+            Some(&"**") => {
+                p += 1;
+                star = Some((p, s));
+                continue;
+            }
+            Some(segment) if glob_match_segment(segment, path[s]) => {
+                p += 1;
+                s += 1;
+                continue;
+            }
+            _ => {}
+        }
+        // Let the last `**` consume one more segment and retry from there.
+        match star {
+            Some((star_p, star_s)) => {
+                p = star_p;
+                s = star_s + 1;
+                star = Some((star_p, s));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].iter().all(|segment| *segment == "**")
+}
+
+/// Match a single path segment against a glob pattern segment with `*` and `?` wildcards.
+///
+/// Like [`glob_match_path`], this only backtracks to the last `*`.
+fn glob_match_segment(pattern: &str, segment: &str) -> bool {
+    // Byte positions in the pattern and segment.
+    let (mut p, mut s) = (0, 0);
+    // Position in the pattern after the last `*`, and the segment position it was matched at.
+    let mut star: Option<(usize, usize)> = None;
+    while let Some(c) = segment[s..].chars().next() {
+        match pattern[p..].chars().next() {
+            Some('*') => {
+                p += 1;
+                star = Some((p, s));
+                continue;
+            }
+            Some(expected) if expected == '?' || expected == c => {
+                p += expected.len_utf8();
+                s += c.len_utf8();
+                continue;
+            }
+            _ => {}
+        }
+        // Let the last `*` consume one more character and retry from there.
+        match star {
+            Some((star_p, star_s)) => {
+                let skipped = segment[star_s..].chars().next().map_or(0, char::len_utf8);
+                p = star_p;
+                s = star_s + skipped;
+                star = Some((star_p, s));
+            }
+            None => return false,
+        }
+    }
+    pattern[p..].chars().all(|c| c == '*')
+}
+
 impl DownloadPolicy {
     /// Check if an entry should be downloaded according to this policy.
     pub fn matches(&self, entry: &Entry) -> bool {
@@ -157,6 +384,44 @@ impl DownloadPolicy {
             DownloadPolicy::EverythingExcept(patterns) => {
                 patterns.iter().all(|pattern| !pattern.matches(key))
             }
+            DownloadPolicy::WithOverrides { base, overrides } => {
+                let mut decision: Option<&PrefixOverride> = None;
+                for rule in overrides
+                    .iter()
+                    .filter(|rule| key.starts_with(&rule.prefix))
+                {
+                    if decision.map_or(true, |best| rule.prefix.len() > best.prefix.len()) {
+                        decision = Some(rule);
+                    }
+                }
+                match decision {
+                    Some(rule) => rule.download,
+                    None => base.matches(entry),
+                }
+            }
+        }
+    }
+
+    /// Add a prefix-scoped override to this policy.
+    ///
+    /// Wraps the policy in [`DownloadPolicy::WithOverrides`] unless it already is one.
+    pub fn with_override(self, prefix: impl Into<Bytes>, download: bool) -> Self {
+        let rule = PrefixOverride {
+            prefix: prefix.into(),
+            download,
+        };
+        match self {
+            DownloadPolicy::WithOverrides {
+                base,
+                mut overrides,
+            } => {
+                overrides.push(rule);
+                DownloadPolicy::WithOverrides { base, overrides }
+            }
+            base => DownloadPolicy::WithOverrides {
+                base: Box::new(base),
+                overrides: vec![rule],
+            },
         }
     }
 }
@@ -167,6 +432,7 @@ pub struct QueryBuilder<K> {
     kind: K,
     filter_author: AuthorFilter,
     filter_key: KeyFilter,
+    filter_content_hash: Option<Hash>,
     limit: Option<u64>,
     offset: u64,
     include_empty: bool,
@@ -189,12 +455,53 @@ impl<K> QueryBuilder<K> {
         self.filter_key = KeyFilter::Prefix(key.as_ref().to_vec().into());
         self
     }
+    /// Filter by a half-open key range: keys from `from` (inclusive) to `to` (exclusive).
+    ///
+    /// Keys are compared bytewise. The range applies across authors, unless an author filter
+    /// is set.
+    pub fn key_range(mut self, from: impl AsRef<[u8]>, to: impl AsRef<[u8]>) -> Self {
+        self.filter_key = KeyFilter::Range {
+            start: Bytes::copy_from_slice(from.as_ref()),
+            end: Bytes::copy_from_slice(to.as_ref()),
+        };
+        self
+    }
     /// Filter by author.
-    pub fn author(mut self, author: AuthorId) -> Self {
-        self.filter_author = AuthorFilter::Exact(author);
+    ///
+    /// Accepts an [`AuthorId`] or [`crate::AuthorPublicKey`], no author secret is needed.
+    pub fn author(mut self, author: impl Into<AuthorId>) -> Self {
+        self.filter_author = AuthorFilter::Exact(author.into());
+        self
+    }
+    /// Exclude the entries of an author.
+    ///
+    /// Can be called repeatedly to exclude several authors. Replaces a filter set with
+    /// [`Self::author`], and vice versa.
+    pub fn exclude_author(mut self, author: impl Into<AuthorId>) -> Self {
+        match &mut self.filter_author {
+            AuthorFilter::Exclude(authors) => authors.push(author.into()),
+            filter => *filter = AuthorFilter::Exclude(vec![author.into()]),
+        }
+        self
+    }
+    /// Exclude the entries of all `authors`.
+    ///
+    /// See [`Self::exclude_author`] for details.
+    pub fn exclude_authors(self, authors: impl IntoIterator<Item = impl Into<AuthorId>>) -> Self {
+        authors.into_iter().fold(self, Self::exclude_author)
+    }
+    /// Filter by content hash.
+    ///
+    /// There is no index on content hashes, so this scans all entries selected by the other
+    /// filters.
+    pub fn content_hash(mut self, hash: Hash) -> Self {
+        self.filter_content_hash = Some(hash);
         self
     }
     /// Set the maximum number of entries to be returned.
+    ///
+    /// For [`Query::single_latest_per_key`] queries, limit and offset are applied after the
+    /// reduction to the latest entry per key, so paginating never skips or repeats a key.
     pub fn limit(mut self, limit: u64) -> Self {
         self.limit = Some(limit);
         self
@@ -210,6 +517,7 @@ impl<K> QueryBuilder<K> {
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FlatQuery {
     sort_by: SortBy,
+    since_local_seq: Option<u64>,
 }
 
 /// Query that only returns the latest entry for a key which has entries from multiple authors.
@@ -226,6 +534,16 @@ impl QueryBuilder<FlatQuery> {
         self
     }
 
+    /// Only return entries with a local sequence number greater than `seq`.
+    ///
+    /// Local sequence numbers are assigned to entries when they are inserted into the store,
+    /// see [`fs::Store::get_local_seq`]. The entries are sorted by their local sequence number,
+    /// the sort field set with [`Self::sort_by`] is ignored but the direction is respected.
+    pub fn since_local_seq(mut self, seq: u64) -> Self {
+        self.kind.since_local_seq = Some(seq);
+        self
+    }
+
     /// Build the query.
     pub fn build(self) -> Query {
         Query::from(self)
@@ -254,6 +572,7 @@ impl From<QueryBuilder<SingleLatestPerKeyQuery>> for Query {
             kind: QueryKind::SingleLatestPerKey(builder.kind),
             filter_author: builder.filter_author,
             filter_key: builder.filter_key,
+            filter_content_hash: builder.filter_content_hash,
             limit: builder.limit,
             offset: builder.offset,
             include_empty: builder.include_empty,
@@ -268,6 +587,7 @@ impl From<QueryBuilder<FlatQuery>> for Query {
             kind: QueryKind::Flat(builder.kind),
             filter_author: builder.filter_author,
             filter_key: builder.filter_key,
+            filter_content_hash: builder.filter_content_hash,
             limit: builder.limit,
             offset: builder.offset,
             include_empty: builder.include_empty,
@@ -283,6 +603,7 @@ pub struct Query {
     kind: QueryKind,
     filter_author: AuthorFilter,
     filter_key: KeyFilter,
+    filter_content_hash: Option<Hash>,
     limit: Option<u64>,
     offset: u64,
     include_empty: bool,
@@ -301,10 +622,17 @@ impl Query {
     }
 
     /// Create a [`Query::all`] query filtered by a single author.
-    pub fn author(author: AuthorId) -> QueryBuilder<FlatQuery> {
+    pub fn author(author: impl Into<AuthorId>) -> QueryBuilder<FlatQuery> {
         Self::all().author(author)
     }
 
+    /// Create a [`Query::all`] query for the entries of all authors except `author`.
+    ///
+    /// See [`QueryBuilder::exclude_author`] for details.
+    pub fn exclude_author(author: impl Into<AuthorId>) -> QueryBuilder<FlatQuery> {
+        Self::all().exclude_author(author)
+    }
+
     /// Create a [`Query::all`] query filtered by a single key.
     pub fn key_exact(key: impl AsRef<[u8]>) -> QueryBuilder<FlatQuery> {
         Self::all().key_exact(key)
@@ -315,6 +643,28 @@ impl Query {
         Self::all().key_prefix(prefix)
     }
 
+    /// Create a [`Query::all`] query filtered by a half-open key range.
+    ///
+    /// See [`QueryBuilder::key_range`] for details.
+    pub fn key_range(from: impl AsRef<[u8]>, to: impl AsRef<[u8]>) -> QueryBuilder<FlatQuery> {
+        Self::all().key_range(from, to)
+    }
+
+    /// Create a [`Query::all`] query for entries referencing the content with `hash`.
+    ///
+    /// See [`QueryBuilder::content_hash`] for details.
+    pub fn content_hash(hash: Hash) -> QueryBuilder<FlatQuery> {
+        Self::all().content_hash(hash)
+    }
+
+    /// Create a [`Query::all`] query for entries with a local sequence number greater than
+    /// `seq`.
+    ///
+    /// See [`QueryBuilder::since_local_seq`] for details.
+    pub fn since_local_seq(seq: u64) -> QueryBuilder<FlatQuery> {
+        Self::all().since_local_seq(seq)
+    }
+
     /// Get the limit for this query (max. number of entries to emit).
     pub fn limit(&self) -> Option<u64> {
         self.limit
@@ -324,6 +674,20 @@ impl Query {
     pub fn offset(&self) -> u64 {
         self.offset
     }
+
+    /// Reverse the sort direction of this query.
+    pub(crate) fn reverse(&mut self) {
+        self.sort_direction = match self.sort_direction {
+            SortDirection::Asc => SortDirection::Desc,
+            SortDirection::Desc => SortDirection::Asc,
+        };
+    }
+
+    /// Test if the content hash of an entry is matched by the content hash filter of this query.
+    pub(crate) fn matches_content_hash(&self, content_hash: &Hash) -> bool {
+        self.filter_content_hash
+            .map_or(true, |hash| *content_hash == hash)
+    }
 }
 
 /// Sort direction
@@ -364,6 +728,13 @@ pub enum KeyFilter {
     Exact(Bytes),
     /// All keys that start with the provided value.
     Prefix(Bytes),
+    /// All keys from `start` (inclusive) to `end` (exclusive), compared bytewise.
+    Range {
+        /// First key of the range.
+        start: Bytes,
+        /// End of the range, which is not part of it.
+        end: Bytes,
+    },
 }
 
 impl<T: AsRef<[u8]>> From<T> for KeyFilter {
@@ -379,6 +750,26 @@ impl KeyFilter {
             Self::Any => true,
             Self::Exact(k) => &k[..] == key,
             Self::Prefix(p) => key.starts_with(p),
+            Self::Range { start, end } => &start[..] <= key && key < &end[..],
+        }
+    }
+}
+
+/// A direct child of a key prefix, see [`fs::Store::list_children`].
+#[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
+pub enum ChildEntry {
+    /// Keys continuing after the prefix with further segments share this common prefix, which
+    /// ends with the delimiter.
+    Prefix(Bytes),
+    /// A key without a delimiter after the prefix.
+    Key(Bytes),
+}
+
+impl ChildEntry {
+    /// The common prefix or key of this child.
+    pub fn as_bytes(&self) -> &Bytes {
+        match self {
+            Self::Prefix(bytes) | Self::Key(bytes) => bytes,
         }
     }
 }
@@ -391,6 +782,8 @@ pub enum AuthorFilter {
     Any,
     /// Matches exactly the provided author.
     Exact(AuthorId),
+    /// Matches all authors except the provided ones.
+    Exclude(Vec<AuthorId>),
 }
 
 impl AuthorFilter {
@@ -399,6 +792,7 @@ impl AuthorFilter {
         match self {
             Self::Any => true,
             Self::Exact(a) => a == author,
+            Self::Exclude(authors) => !authors.contains(author),
         }
     }
 }
@@ -423,4 +817,130 @@ mod tests {
         );
         assert_eq!(filter.to_string(), REPR)
     }
+
+    #[test]
+    fn test_filter_kind_glob_encode_decode() {
+        const REPR: &str = "glob:utf8:photos/*/thumb.jpg";
+        let filter: FilterKind = REPR.parse().expect("should decode");
+        assert_eq!(filter, FilterKind::Glob("photos/*/thumb.jpg".to_string()));
+        assert_eq!(filter.to_string(), REPR);
+        assert!("glob:hex:ff".parse::<FilterKind>().is_err());
+    }
+
+    #[test]
+    fn test_filter_kind_glob_single_segment() {
+        let filter = FilterKind::Glob("photos/*/thumb.jpg".to_string());
+        assert!(filter.matches("photos/2024/thumb.jpg"));
+        assert!(filter.matches("photos//thumb.jpg"));
+        assert!(!filter.matches("photos/2024/05/thumb.jpg"));
+        assert!(!filter.matches("photos/2024/thumb.png"));
+
+        let filter = FilterKind::Glob("img?.*".to_string());
+        assert!(filter.matches("img1.png"));
+        assert!(!filter.matches("img12.png"));
+        assert!(!filter.matches("dir/img1.png"));
+    }
+
+    #[test]
+    fn test_filter_kind_glob_multi_segment() {
+        let filter = FilterKind::Glob("photos/**/thumb.jpg".to_string());
+        assert!(filter.matches("photos/thumb.jpg"));
+        assert!(filter.matches("photos/2024/thumb.jpg"));
+        assert!(filter.matches("photos/2024/05/thumb.jpg"));
+        assert!(!filter.matches("videos/2024/thumb.jpg"));
+
+        let filter = FilterKind::Glob("**".to_string());
+        assert!(filter.matches("a/b/c"));
+
+        let filter = FilterKind::Glob("a/**/b/**/c".to_string());
+        assert!(filter.matches("a/b/c"));
+        assert!(filter.matches("a/x/b/y/b/z/c"));
+        assert!(!filter.matches("a/x/b/y/c/z"));
+    }
+
+    #[test]
+    fn test_filter_kind_glob_long_key() {
+        // with backtracking to every star, these take exponential time
+        let key = "a".repeat(10_000);
+        let filter = FilterKind::Glob("*a*a*a*a*a*a*a*a*a*a*b".to_string());
+        assert!(!filter.matches(&key));
+        let filter = FilterKind::Glob("*a*a*a*a*a*a*a*a*a*a*".to_string());
+        assert!(filter.matches(&key));
+
+        let key = vec!["a"; 2_000].join("/");
+        let filter = FilterKind::Glob("**/a/**/a/**/a/**/a/**/a/**/b".to_string());
+        assert!(!filter.matches(&key));
+        let filter = FilterKind::Glob("**/*a/**/a*/**/?/**".to_string());
+        assert!(filter.matches(&key));
+
+        let filter = FilterKind::Glob("*ä?*ö".to_string());
+        assert!(filter.matches("xäüyö"));
+        assert!(!filter.matches("xäö"));
+    }
+
+    fn entry(key: &[u8]) -> Entry {
+        let id = crate::RecordIdentifier::new(
+            NamespaceId::from(&[1u8; 32]),
+            AuthorId::from(&[2u8; 32]),
+            key,
+        );
+        Entry::new(id, crate::Record::new(Hash::new(key), 1, 1))
+    }
+
+    #[test]
+    fn test_download_policy_overrides() {
+        // everything except thumbnails, but within thumbnails/small download everything
+        let policy = DownloadPolicy::default()
+            .with_override("thumbnails/", false)
+            .with_override("thumbnails/small/", true);
+        assert!(policy.matches(&entry(b"photos/a.jpg")));
+        assert!(!policy.matches(&entry(b"thumbnails/a.jpg")));
+        assert!(!policy.matches(&entry(b"thumbnails/large/a.jpg")));
+        assert!(policy.matches(&entry(b"thumbnails/small/a.jpg")));
+
+        // the most specific override wins regardless of order
+        let policy = DownloadPolicy::NothingExcept(vec![FilterKind::Prefix("docs/".into())])
+            .with_override("docs/drafts/private/", true)
+            .with_override("docs/drafts/", false);
+        assert!(!policy.matches(&entry(b"photos/a.jpg")));
+        assert!(policy.matches(&entry(b"docs/a.txt")));
+        assert!(!policy.matches(&entry(b"docs/drafts/a.txt")));
+        assert!(policy.matches(&entry(b"docs/drafts/private/a.txt")));
+
+        // for overrides with the same prefix, the first one wins
+        let policy = DownloadPolicy::default()
+            .with_override("a/", false)
+            .with_override("a/", true);
+        assert!(!policy.matches(&entry(b"a/b")));
+    }
+
+    #[test]
+    fn test_download_policy_serialization_compat() {
+        // policies stored before overrides existed still decode
+        let stored = postcard::to_stdvec(&DownloadPolicy::NothingExcept(vec![FilterKind::Exact(
+            "a".into(),
+        )]))
+        .unwrap();
+        assert_eq!(stored, [0, 1, 1, 1, b'a']);
+        let policy: DownloadPolicy = postcard::from_bytes(&stored).unwrap();
+        assert_eq!(
+            policy,
+            DownloadPolicy::NothingExcept(vec![FilterKind::Exact("a".into())])
+        );
+
+        let policy = DownloadPolicy::default()
+            .with_override("thumbnails/", false)
+            .with_override("thumbnails/small/", true);
+        let bytes = postcard::to_stdvec(&policy).unwrap();
+        let decoded: DownloadPolicy = postcard::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded, policy);
+    }
+
+    #[test]
+    fn test_filter_kind_glob_non_utf8_key() {
+        let filter = FilterKind::Glob("**".to_string());
+        assert!(!filter.matches([0xff, 0xfe]));
+        let filter = FilterKind::Glob("photos/*".to_string());
+        assert!(!filter.matches(b"photos/\xff"));
+    }
 }
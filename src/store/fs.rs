@@ -2,31 +2,46 @@
 
 use std::{
     cmp::Ordering,
-    collections::HashSet,
+    collections::{hash_map, HashMap, HashSet},
+    io::{self, Read, Write},
     iter::{Chain, Flatten},
-    num::NonZeroU64,
+    num::{NonZeroU64, NonZeroUsize},
     ops::Bound,
     path::Path,
+    sync::Arc,
+    time::Duration,
 };
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, ensure, Result};
+use bytes::Bytes;
 use ed25519_dalek::{SignatureError, VerifyingKey};
 use iroh_blobs::Hash;
 use rand_core::CryptoRngCore;
-use redb::{Database, DatabaseError, ReadableMultimapTable, ReadableTable, ReadableTableMetadata};
+use redb::{
+    backends::InMemoryBackend, Database, DatabaseError, ReadableMultimapTable, ReadableTable,
+    ReadableTableMetadata, StorageBackend,
+};
 use tracing::warn;
 
 use super::{
-    pubkeys::MemPublicKeyStore, DownloadPolicy, ImportNamespaceOutcome, OpenError, PublicKeyStore,
-    Query,
+    pubkeys::MemPublicKeyStore, ChildEntry, DownloadPolicy, FlushTimeout, ImportNamespaceOutcome,
+    KeyFilter, OpenError, Projection, PublicKeyStore, Query, ReadOnlyStore, RebuildStats,
+    SortDirection, TimestampSummary,
 };
+#[cfg(feature = "metrics")]
+use crate::metrics::LatencyTimer;
 use crate::{
     actor::MAX_COMMIT_DELAY,
     keys::Author,
-    ranger::{Fingerprint, Range, RangeEntry},
-    sync::{Entry, EntrySignature, Record, RecordIdentifier, Replica, SignedEntry},
+    ranger::{Fingerprint, InsertOutcome, Range, RangeEntry, SyncConfig},
+    sync::{
+        cmp_entries, is_reserved_key, AllSubscribers, ConflictResolver, ContentChunks,
+        ContentStatus, CounterValue, Entry, EntrySignature, Event, HashConflictResolver, Record,
+        RecordIdentifier, Replica, SignedEntry, NAMESPACE_META_KEY, WRITE_TOKEN_PREFIX,
+        WRITE_TOKEN_REVOCATION_PREFIX,
+    },
     AuthorHeads, AuthorId, Capability, CapabilityKind, NamespaceId, NamespaceSecret, PeerIdBytes,
-    ReplicaInfo,
+    ReplicaInfo, WriteToken, WriteTokenId,
 };
 
 mod bounds;
@@ -39,21 +54,99 @@ pub(crate) mod tables;
 pub use self::ranges::RecordsRange;
 use self::{
     bounds::{ByKeyBounds, RecordsBounds},
-    query::QueryIterator,
-    ranges::RangeExt,
+    query::{value_is_empty, AuthorGroupsIterator, ProjectedQueryIterator, QueryIterator},
+    ranges::{RangeExt, RecordsByKeyRange},
     tables::{
-        LatestPerAuthorKey, LatestPerAuthorValue, ReadOnlyTables, RecordsId, RecordsTable,
-        RecordsValue, Tables, TransactionAndTables,
+        LatestPerAuthorKey, LatestPerAuthorValue, ReadOnlyTables, RecordsByKeyId, RecordsId,
+        RecordsTable, RecordsValue, Tables, TransactionAndTables, DEFAULT_AUTHOR_SETTING,
     },
 };
 
 /// Manages the replicas and authors for an instance.
-#[derive(Debug)]
+#[derive(derive_more::Debug)]
 pub struct Store {
-    db: Database,
+    db: Arc<Database>,
     transaction: CurrentTransaction,
     open_replicas: HashSet<NamespaceId>,
-    pubkeys: MemPublicKeyStore,
+    #[debug("PublicKeyStore")]
+    pubkeys: Arc<dyn PublicKeyStore + Send + Sync>,
+    trust_local: bool,
+    sync_config: SyncConfig,
+    conflict_resolvers: HashMap<NamespaceId, Arc<dyn ConflictResolver>>,
+    trusted_namespaces: HashSet<NamespaceId>,
+    content_status: lru::LruCache<(NamespaceId, Hash), ContentStatus>,
+    auto_flush: AutoFlush,
+    read_only: bool,
+    flusher: Option<Flusher>,
+    pending_flush: Option<PendingFlush>,
+    pub(crate) all_subscribers: AllSubscribers,
+}
+
+type FlushReply = std::sync::mpsc::SyncSender<std::result::Result<(), redb::CommitError>>;
+
+/// Long-lived thread which commits write transactions for [`Store::flush_with_timeout`].
+///
+/// The thread exits once the store, and with it the sender, is dropped.
+#[derive(Debug)]
+struct Flusher {
+    tx: std::sync::mpsc::Sender<(TransactionAndTables, FlushReply)>,
+}
+
+impl Flusher {
+    fn spawn() -> Result<Self> {
+        let (tx, rx) = std::sync::mpsc::channel::<(TransactionAndTables, FlushReply)>();
+        std::thread::Builder::new()
+            .name("docs-flush".into())
+            .spawn(move || {
+                while let Ok((w, reply)) = rx.recv() {
+                    reply.send(w.commit()).ok();
+                }
+            })?;
+        Ok(Self { tx })
+    }
+}
+
+/// A commit which did not complete within the timeout of [`Store::flush_with_timeout`].
+#[derive(Debug)]
+struct PendingFlush {
+    rx: std::sync::mpsc::Receiver<std::result::Result<(), redb::CommitError>>,
+    timeout: Duration,
+}
+
+/// When the [`Store`] commits its write transaction on its own.
+///
+/// Writes are batched into transactions, which are committed on [`Store::flush`], on reads of
+/// committed data, or with the next write once the transaction is older than 500ms. Setting
+/// either field commits the transaction earlier, once the limit is reached.
+///
+/// The limits are checked on each write. When the store is driven by a
+/// [`crate::actor::SyncHandle`], the store is in addition flushed every `every_interval`, so that
+/// writes become durable without further writes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AutoFlush {
+    /// Commit the transaction once this many entries were written to it.
+    pub every_writes: Option<usize>,
+    /// Commit the transaction once it is older than this interval.
+    pub every_interval: Option<Duration>,
+}
+
+/// Wait at most `timeout` for the commit behind `rx`, and track it in `pending` if it did not
+/// complete in time.
+fn wait_for_flush(
+    pending: &mut Option<PendingFlush>,
+    rx: std::sync::mpsc::Receiver<std::result::Result<(), redb::CommitError>>,
+    timeout: Duration,
+) -> Result<()> {
+    match rx.recv_timeout(timeout) {
+        Ok(res) => Ok(res?),
+        Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+            *pending = Some(PendingFlush { rx, timeout });
+            Err(FlushTimeout(timeout).into())
+        }
+        Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+            Err(anyhow!("flush thread panicked"))
+        }
+    }
 }
 
 impl Drop for Store {
@@ -107,6 +200,28 @@ impl Store {
         Self::new_impl(db)
     }
 
+    /// Open a copy of the database file at `path` for reading only.
+    ///
+    /// The file is copied into memory when opening, so it is neither locked nor modified, and it
+    /// can be opened while another process has it open. Changes made to the file after opening
+    /// are not visible. All writes to the store fail with [`ReadOnlyStore`], which for
+    /// [`Replica`] methods is wrapped in [`crate::InsertError::Store`].
+    pub fn open_read_only(path: impl AsRef<Path>) -> Result<Self> {
+        let data = std::fs::read(path)?;
+        let backend = InMemoryBackend::new();
+        backend.set_len(data.len() as u64)?;
+        backend.write(0, &data)?;
+        let db = Database::builder().create_with_backend(backend)?;
+        let mut store = Self::new_impl(db)?;
+        store.read_only = true;
+        Ok(store)
+    }
+
+    /// Whether this store was opened with [`Self::open_read_only`].
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     fn new_impl(db: redb::Database) -> Result<Self> {
         // Setup all tables
         let write_tx = db.begin_write()?;
@@ -117,13 +232,121 @@ impl Store {
         migrations::run_migrations(&db)?;
 
         Ok(Store {
-            db,
+            db: Arc::new(db),
             transaction: Default::default(),
             open_replicas: Default::default(),
-            pubkeys: Default::default(),
+            pubkeys: Arc::new(MemPublicKeyStore::default()),
+            trust_local: true,
+            sync_config: Default::default(),
+            conflict_resolvers: Default::default(),
+            trusted_namespaces: Default::default(),
+            content_status: lru::LruCache::new(CONTENT_STATUS_CAPACITY),
+            auto_flush: Default::default(),
+            read_only: false,
+            flusher: None,
+            pending_flush: None,
+            all_subscribers: Default::default(),
         })
     }
 
+    /// Set whether to trust entries inserted locally without verifying their signatures.
+    ///
+    /// Entries inserted locally are signed by ourselves, so verifying their signatures is
+    /// wasted work in most cases. This is enabled by default. Disable it to verify the
+    /// signatures of all inserted entries. Entries received from other peers are always verified,
+    /// regardless of this setting.
+    pub fn set_trust_local(&mut self, trust_local: bool) {
+        self.trust_local = trust_local;
+    }
+
+    /// Whether entries inserted locally are trusted without verifying their signatures.
+    ///
+    /// See [`Self::set_trust_local`].
+    pub fn trust_local(&self) -> bool {
+        self.trust_local
+    }
+
+    /// Set whether to trust that entries of a namespace are signed by the namespace.
+    ///
+    /// If enabled, only the author signature of entries of `namespace` is verified, see
+    /// [`SignedEntry::verify_author_only`]. This halves the cost of verifying entries received
+    /// during sync, but any valid author can then write to the namespace. Only enable this for
+    /// read-only mirrors of namespaces which sync with trusted peers. Disabled by default.
+    pub fn set_trust_namespace(&mut self, namespace: NamespaceId, trust: bool) {
+        if trust {
+            self.trusted_namespaces.insert(namespace);
+        } else {
+            self.trusted_namespaces.remove(&namespace);
+        }
+    }
+
+    /// Whether the namespace signatures of entries of `namespace` are not verified.
+    ///
+    /// See [`Self::set_trust_namespace`].
+    pub fn trusts_namespace(&self, namespace: &NamespaceId) -> bool {
+        self.trusted_namespaces.contains(namespace)
+    }
+
+    /// Set the configuration used when syncing replicas of this store with other peers.
+    pub fn set_sync_config(&mut self, config: SyncConfig) {
+        self.sync_config = config;
+    }
+
+    /// The configuration used when syncing replicas of this store with other peers.
+    pub fn sync_config(&self) -> SyncConfig {
+        self.sync_config
+    }
+
+    /// Set the [`ConflictResolver`] for a replica, or reset it to the default with `None`.
+    ///
+    /// The resolver decides which entry wins if two entries for the same key have equal
+    /// timestamps, both when inserting entries and when selecting the latest entry per key in
+    /// queries. All peers of a replica must use the same resolver, otherwise their replicas
+    /// will diverge.
+    ///
+    /// Resolvers are not persisted. They are lost when the store is dropped, and have to be set
+    /// again after opening the store.
+    pub fn set_conflict_resolver(
+        &mut self,
+        namespace: NamespaceId,
+        resolver: Option<Arc<dyn ConflictResolver>>,
+    ) {
+        match resolver {
+            Some(resolver) => self.conflict_resolvers.insert(namespace, resolver),
+            None => self.conflict_resolvers.remove(&namespace),
+        };
+    }
+
+    pub(crate) fn conflict_resolver(
+        &self,
+        namespace: &NamespaceId,
+    ) -> Option<Arc<dyn ConflictResolver>> {
+        self.conflict_resolvers.get(namespace).cloned()
+    }
+
+    /// Replace the store used to resolve author and namespace public keys.
+    ///
+    /// Public keys are resolved whenever signatures of entries received from other peers are
+    /// verified. By default, resolved keys are cached in memory for the lifetime of the store,
+    /// which can grow large for documents with many authors. This allows to plug in a bounded
+    /// cache or an external key store instead.
+    pub fn replace_author_key_store(
+        &mut self,
+        key_store: impl PublicKeyStore + Send + Sync + 'static,
+    ) {
+        self.pubkeys = Arc::new(key_store);
+    }
+
+    /// Set when the store commits its write transaction on its own, see [`AutoFlush`].
+    pub fn set_auto_flush(&mut self, auto_flush: AutoFlush) {
+        self.auto_flush = auto_flush;
+    }
+
+    /// When the store commits its write transaction on its own.
+    pub fn auto_flush(&self) -> AutoFlush {
+        self.auto_flush
+    }
+
     /// Flush the current transaction, if any.
     ///
     /// This is the cheapest way to ensure that the data is persisted.
@@ -134,6 +357,62 @@ impl Store {
         Ok(())
     }
 
+    /// Flush the current transaction, if any, and wait at most `timeout` for the commit.
+    ///
+    /// The commit runs on a separate thread. If it does not complete within `timeout`, this
+    /// returns a [`FlushTimeout`] error, while the commit keeps running in the background.
+    /// Until the stuck commit completed, all operations which need a write transaction fail
+    /// right away with [`FlushTimeout`] instead of blocking, and further calls to this method
+    /// wait at most `timeout` for the stuck commit.
+    pub fn flush_with_timeout(&mut self, timeout: Duration) -> Result<()> {
+        if let Some(pending) = self.pending_flush.take() {
+            wait_for_flush(&mut self.pending_flush, pending.rx, timeout)?;
+        }
+        let CurrentTransaction::Write(w) = std::mem::take(&mut self.transaction) else {
+            return Ok(());
+        };
+        if self.flusher.is_none() {
+            self.flusher = Some(Flusher::spawn()?);
+        }
+        let flusher = self.flusher.as_ref().expect("just set");
+        let (reply, rx) = std::sync::mpsc::sync_channel(1);
+        flusher
+            .tx
+            .send((w, reply))
+            .map_err(|_| anyhow!("flush thread stopped"))?;
+        wait_for_flush(&mut self.pending_flush, rx, timeout)
+    }
+
+    /// Fail with [`FlushTimeout`] if a commit from [`Self::flush_with_timeout`] is still
+    /// running, so that callers do not block on opening a write transaction.
+    fn check_pending_flush(&mut self) -> Result<()> {
+        let Some(pending) = &self.pending_flush else {
+            return Ok(());
+        };
+        match pending.rx.try_recv() {
+            Ok(res) => {
+                self.pending_flush = None;
+                Ok(res?)
+            }
+            Err(std::sync::mpsc::TryRecvError::Empty) => Err(FlushTimeout(pending.timeout).into()),
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                self.pending_flush = None;
+                Err(anyhow!("flush thread panicked"))
+            }
+        }
+    }
+
+    /// Flush the current transaction, if any, without blocking the current thread.
+    ///
+    /// The commit runs on the blocking thread pool of the tokio runtime, so this must be called
+    /// from within a tokio runtime. See [`Self::flush`].
+    pub async fn flush_async(&mut self) -> Result<()> {
+        if let CurrentTransaction::Write(w) = std::mem::take(&mut self.transaction) {
+            tokio::task::spawn_blocking(move || w.commit()).await??;
+        }
+        Ok(())
+    }
+
     /// Get a read-only snapshot of the database.
     ///
     /// This has the side effect of committing any open write transaction,
@@ -175,6 +454,24 @@ impl Store {
         Ok(tables)
     }
 
+    /// Get a cloneable read-only handle for the entries of a namespace.
+    ///
+    /// Each read on the handle opens its own database read transaction, so reads from many
+    /// threads can run concurrently and are not serialized through this store. Writes still have
+    /// to go through the [`Store`].
+    ///
+    /// This has the side effect of committing any open write transaction. Reads through the
+    /// handle only ever see committed data: writes made to the store after this call become
+    /// visible once the store commits them, e.g. through [`Self::flush`].
+    pub fn read_handle(&mut self, namespace: NamespaceId) -> Result<ReadHandle> {
+        self.flush()?;
+        Ok(ReadHandle {
+            db: self.db.clone(),
+            namespace,
+            resolver: self.conflict_resolver(&namespace),
+        })
+    }
+
     /// Get access to the tables to read from them.
     ///
     /// The underlying transaction is a write transaction, but with a non-mut
@@ -186,6 +483,7 @@ impl Store {
     /// As such, there is also no guarantee that the data you see is
     /// already persisted.
     fn tables(&mut self) -> Result<&Tables> {
+        self.check_pending_flush()?;
         let guard = &mut self.transaction;
         let tables = match std::mem::take(guard) {
             CurrentTransaction::None => {
@@ -223,6 +521,10 @@ impl Store {
     /// To ensure that the data is persisted, acquire a snapshot of the database
     /// or call flush.
     fn modify<T>(&mut self, f: impl FnOnce(&mut Tables) -> Result<T>) -> Result<T> {
+        if self.read_only {
+            return Err(ReadOnlyStore.into());
+        }
+        self.check_pending_flush()?;
         let guard = &mut self.transaction;
         let tables = match std::mem::take(guard) {
             CurrentTransaction::None => {
@@ -249,16 +551,54 @@ impl Store {
             CurrentTransaction::Write(ref mut tables) => tables.with_tables_mut(f)?,
             _ => unreachable!(),
         };
+        self.auto_flush_if_due()?;
         Ok(res)
     }
+
+    /// Count an entry written to the current write transaction.
+    fn count_entry_write(&mut self) -> Result<()> {
+        if let CurrentTransaction::Write(tables) = &mut self.transaction {
+            tables.writes += 1;
+        }
+        self.auto_flush_if_due()
+    }
+
+    /// Commit the current write transaction if one of the [`AutoFlush`] limits is reached.
+    fn auto_flush_if_due(&mut self) -> Result<()> {
+        let CurrentTransaction::Write(tables) = &self.transaction else {
+            return Ok(());
+        };
+        let AutoFlush {
+            every_writes,
+            every_interval,
+        } = self.auto_flush;
+        if every_writes.is_some_and(|n| tables.writes >= n)
+            || every_interval.is_some_and(|interval| tables.since.elapsed() >= interval)
+        {
+            tracing::debug!("committing transaction because of auto flush");
+            self.flush()?;
+        }
+        Ok(())
+    }
 }
 
 type PeersIter = std::vec::IntoIter<PeerIdBytes>;
 
 impl Store {
     /// Create a new replica for `namespace` and persist in this store.
+    ///
+    /// If the replica already exists, this behaves like [`Self::open_or_create_replica`].
     pub fn new_replica(&mut self, namespace: NamespaceSecret) -> Result<Replica> {
+        self.open_or_create_replica(namespace)
+    }
+
+    /// Open the replica for `namespace`, or create it if it does not exist yet.
+    ///
+    /// If the replica exists with only a read capability, the capability is upgraded to a write
+    /// capability with the passed secret.
+    pub fn open_or_create_replica(&mut self, namespace: NamespaceSecret) -> Result<Replica<'_>> {
         let id = namespace.id();
+        // Importing merges the write capability into an existing capability.
         self.import_namespace(namespace.into())?;
         self.open_replica(&id).map_err(Into::into)
     }
@@ -270,6 +610,17 @@ impl Store {
         Ok(author)
     }
 
+    /// Get the timestamp of the latest entry of each author in a namespace.
+    pub fn author_heads(&mut self, namespace: NamespaceId) -> Result<AuthorHeads> {
+        let latest = self.get_latest_for_each_author(namespace)?;
+        let mut heads = AuthorHeads::default();
+        for e in latest {
+            let (author, timestamp, _key) = e?;
+            heads.insert(author, timestamp);
+        }
+        Ok(heads)
+    }
+
     /// Check if a [`AuthorHeads`] contains entry timestamps that we do not have locally.
     ///
     /// Returns the number of authors that the other peer has updates for.
@@ -278,15 +629,7 @@ impl Store {
         namespace: NamespaceId,
         heads: &AuthorHeads,
     ) -> Result<Option<NonZeroU64>> {
-        let our_heads = {
-            let latest = self.get_latest_for_each_author(namespace)?;
-            let mut heads = AuthorHeads::default();
-            for e in latest {
-                let (author, timestamp, _key) = e?;
-                heads.insert(author, timestamp);
-            }
-            heads
-        };
+        let our_heads = self.author_heads(namespace)?;
         let has_news_for_us = heads.has_news_for(&our_heads);
         Ok(has_news_for_us)
     }
@@ -305,23 +648,93 @@ impl Store {
         &mut self,
         namespace_id: &NamespaceId,
     ) -> Result<ReplicaInfo, OpenError> {
+        let info = ReplicaInfo::new(self.load_capability(namespace_id)?);
+        self.open_replicas.insert(info.capability.id());
+        Ok(info)
+    }
+
+    fn load_capability(&mut self, namespace_id: &NamespaceId) -> Result<Capability, OpenError> {
         let tables = self.tables()?;
-        let info = match tables.namespaces.get(namespace_id.as_bytes()) {
+        match tables.namespaces.get(namespace_id.as_bytes()) {
             Ok(Some(db_value)) => {
                 let (raw_kind, raw_bytes) = db_value.value();
-                let namespace = Capability::from_raw(raw_kind, raw_bytes)?;
-                ReplicaInfo::new(namespace)
+                Ok(Capability::from_raw(raw_kind, raw_bytes)?)
             }
-            Ok(None) => return Err(OpenError::NotFound),
-            Err(err) => return Err(OpenError::Other(err.into())),
-        };
-        self.open_replicas.insert(info.capability.id());
-        Ok(info)
+            Ok(None) => Err(OpenError::NotFound),
+            Err(err) => Err(OpenError::Other(err.into())),
+        }
     }
 
     /// Close a replica.
     pub fn close_replica(&mut self, id: NamespaceId) {
-        self.open_replicas.remove(&id);
+        if self.open_replicas.remove(&id) {
+            self.all_subscribers
+                .send_with(id, || Event::Closed { namespace: id });
+        }
+    }
+
+    /// Subscribe to the events of all replicas of this store.
+    ///
+    /// Events are sent together with the namespace of the replica they were emitted for. This
+    /// includes replicas opened after subscribing. [`Event::Closed`] is sent whenever a replica
+    /// is closed, the subscription stays active for the other replicas.
+    ///
+    /// Like with [`crate::ReplicaInfo::subscribe`], the receiver must be received from in
+    /// a loop, otherwise inserts will hang. Senders whose receiver was dropped are removed.
+    pub fn subscribe_all(&mut self, sender: async_channel::Sender<(NamespaceId, Event)>) {
+        self.all_subscribers.subscribe(sender)
+    }
+
+    /// Explicitly unsubscribe a sender added with [`Self::subscribe_all`].
+    pub fn unsubscribe_all(&mut self, sender: &async_channel::Sender<(NamespaceId, Event)>) {
+        self.all_subscribers.unsubscribe(sender)
+    }
+
+    /// Record a change of the availability of content referenced in a namespace.
+    ///
+    /// If `status` differs from the last status recorded for `hash` in `namespace`, an
+    /// [`Event::ContentStatusChanged`] is sent to the subscribers added with
+    /// [`Self::subscribe_all`]. Returns whether the status changed. Use
+    /// [`crate::Replica::notify_content_status`] to reach the subscribers of an open replica as
+    /// well.
+    ///
+    /// The store remembers the statuses of the most recently notified hashes only. Once a status
+    /// is forgotten, notifying it again emits another event.
+    pub fn notify_content_status(
+        &mut self,
+        namespace: NamespaceId,
+        hash: Hash,
+        status: ContentStatus,
+    ) -> bool {
+        let previous = self.content_status.put((namespace, hash), status);
+        if previous == Some(status) {
+            return false;
+        }
+        self.all_subscribers
+            .send_with(namespace, || Event::ContentStatusChanged {
+                namespace,
+                hash,
+                status,
+            });
+        true
+    }
+
+    /// Get the content status last recorded with [`Self::notify_content_status`].
+    pub fn content_status(&self, namespace: NamespaceId, hash: Hash) -> Option<ContentStatus> {
+        self.content_status.peek(&(namespace, hash)).copied()
+    }
+
+    /// Forget the content statuses recorded for `namespace`.
+    fn forget_content_status(&mut self, namespace: NamespaceId) {
+        let keys: Vec<_> = self
+            .content_status
+            .iter()
+            .filter(|((ns, _), _)| *ns == namespace)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in keys {
+            self.content_status.pop(&key);
+        }
     }
 
     /// List all replica namespaces in this store.
@@ -337,6 +750,60 @@ impl Store {
         Ok(iter)
     }
 
+    /// Set a local alias for a namespace.
+    ///
+    /// The alias is a human-readable name for the namespace which is only stored locally and
+    /// not synced. Each namespace has at most one alias, so setting an alias replaces the
+    /// previous alias of the namespace. Fails if the namespace is not in the store, or if the
+    /// alias is already used for another namespace.
+    pub fn set_namespace_alias(&mut self, namespace: NamespaceId, alias: &str) -> Result<()> {
+        anyhow::ensure!(!alias.is_empty(), "alias must not be empty");
+        self.modify(|tables| {
+            anyhow::ensure!(
+                tables.namespaces.get(namespace.as_bytes())?.is_some(),
+                "namespace not found"
+            );
+            if let Some(existing) = tables.namespace_aliases.get(alias)? {
+                let existing = NamespaceId::from(existing.value());
+                anyhow::ensure!(
+                    existing == namespace,
+                    "alias {alias} is already used for namespace {}",
+                    existing.fmt_short()
+                );
+                return Ok(());
+            }
+            tables
+                .namespace_aliases
+                .retain(|_alias, id| id != namespace.as_bytes())?;
+            tables
+                .namespace_aliases
+                .insert(alias, namespace.as_bytes())?;
+            Ok(())
+        })
+    }
+
+    /// Get the namespace with a local alias set with [`Self::set_namespace_alias`].
+    pub fn get_namespace_by_alias(&mut self, alias: &str) -> Result<Option<NamespaceId>> {
+        let tables = self.tables()?;
+        let namespace = tables
+            .namespace_aliases
+            .get(alias)?
+            .map(|id| NamespaceId::from(id.value()));
+        Ok(namespace)
+    }
+
+    /// Get the local alias of a namespace, if set.
+    pub fn get_namespace_alias(&mut self, namespace: NamespaceId) -> Result<Option<String>> {
+        let tables = self.tables()?;
+        for entry in tables.namespace_aliases.iter()? {
+            let (alias, id) = entry?;
+            if id.value() == namespace.as_bytes() {
+                return Ok(Some(alias.value().to_string()));
+            }
+        }
+        Ok(None)
+    }
+
     /// Get an author key from the store.
     pub fn get_author(&mut self, author_id: &AuthorId) -> Result<Option<Author>> {
         let tables = self.tables()?;
@@ -358,13 +825,51 @@ impl Store {
     }
 
     /// Delete an author.
+    ///
+    /// If the author is the default author, the default author is unset.
     pub fn delete_author(&mut self, author: AuthorId) -> Result<()> {
         self.modify(|tables| {
             tables.authors.remove(author.as_bytes())?;
+            let is_default = tables
+                .settings
+                .get(DEFAULT_AUTHOR_SETTING)?
+                .is_some_and(|value| value.value() == author.as_bytes());
+            if is_default {
+                tables.settings.remove(DEFAULT_AUTHOR_SETTING)?;
+            }
             Ok(())
         })
     }
 
+    /// Set the default author of this store.
+    ///
+    /// The default author is persisted with the store. Fails if the author is not in the store.
+    pub fn set_default_author(&mut self, author: AuthorId) -> Result<()> {
+        self.modify(|tables| {
+            anyhow::ensure!(
+                tables.authors.get(author.as_bytes())?.is_some(),
+                "author not found"
+            );
+            tables
+                .settings
+                .insert(DEFAULT_AUTHOR_SETTING, author.as_bytes().as_slice())?;
+            Ok(())
+        })
+    }
+
+    /// Get the default author of this store, if set.
+    pub fn get_default_author(&mut self) -> Result<Option<AuthorId>> {
+        let tables = self.tables()?;
+        let Some(value) = tables.settings.get(DEFAULT_AUTHOR_SETTING)? else {
+            return Ok(None);
+        };
+        let bytes: &[u8; 32] = value
+            .value()
+            .try_into()
+            .map_err(|_| anyhow!("invalid default author"))?;
+        Ok(Some(AuthorId::from(bytes)))
+    }
+
     /// List all author keys in this store.
     pub fn list_authors(&mut self) -> Result<impl Iterator<Item = Result<Author>>> {
         let tables = self.snapshot()?;
@@ -423,11 +928,149 @@ impl Store {
             let _ = tables
                 .records_by_key
                 .retain_in(bounds.as_ref(), |_k, _v| false);
+            let bounds = RecordsBounds::namespace(*namespace);
+            tables
+                .records_local_seq
+                .retain_in(bounds.as_ref(), |_k, _v| false)?;
+            tables
+                .content_chunks
+                .retain_in(bounds.as_ref(), |_k, _v| false)?;
+            let bounds = (namespace.as_bytes(), &[0u8; 32])..=(namespace.as_bytes(), &[255u8; 32]);
+            tables.namespace_meta.retain_in(bounds, |_k, _v| false)?;
+            let bounds = (namespace.as_bytes(), 0)..=(namespace.as_bytes(), u64::MAX);
+            tables
+                .records_by_local_seq
+                .retain_in(bounds, |_k, _v| false)?;
+            tables.namespace_local_seq.remove(namespace.as_bytes())?;
             tables.namespaces.remove(namespace.as_bytes())?;
             tables.namespace_peers.remove_all(namespace.as_bytes())?;
             tables.download_policy.remove(namespace.as_bytes())?;
+            tables
+                .namespace_aliases
+                .retain(|_alias, id| id != namespace.as_bytes())?;
             Ok(())
-        })
+        })?;
+        self.forget_content_status(*namespace);
+        Ok(())
+    }
+
+    /// Remove all entries of a namespace, but keep the namespace itself.
+    ///
+    /// This is a local hard clear: the entries are removed from the store without inserting
+    /// tombstones. The local replica thus diverges from its peers, which still have the entries
+    /// and will sync them back. Use [`crate::Replica::delete_prefix`] to delete entries for all
+    /// peers instead.
+    ///
+    /// The capability, peers and download policy of the namespace are kept, and the replica may
+    /// be open. Returns the number of removed entries.
+    pub fn clear_namespace(&mut self, namespace: NamespaceId) -> Result<usize> {
+        let removed = self.modify(|tables| remove_entries(tables, namespace))?;
+        self.forget_content_status(namespace);
+        Ok(removed)
+    }
+
+    /// Remove all entries of a namespace and rebuild it from `entries`.
+    ///
+    /// This recovers a namespace from a backup of its entries, e.g. one taken with
+    /// [`Self::iter_all_entries`]. All existing entries of the namespace are removed first. Then
+    /// each entry is validated and inserted with the regular insertion semantics, so that entries
+    /// which are superseded by newer entries or prefix deletions are dropped and the result is
+    /// the same as if the entries had been synced. Entries of other namespaces and entries which
+    /// fail to validate are skipped. No events are emitted.
+    ///
+    /// The replica must not be open. Peers and the download policy of the namespace are kept.
+    pub fn rebuild_namespace(
+        &mut self,
+        namespace: NamespaceSecret,
+        entries: impl IntoIterator<Item = SignedEntry>,
+    ) -> Result<RebuildStats> {
+        let id = namespace.id();
+        if self.open_replicas.contains(&id) {
+            return Err(anyhow!("replica is not closed"));
+        }
+        self.import_namespace(namespace.into())?;
+        self.modify(|tables| remove_entries(tables, id))?;
+        let mut replica = self.open_replica(&id)?;
+        let stats = replica.rebuild_from_entries(entries);
+        drop(replica);
+        // The replica was opened internally only, so do not emit a close event.
+        self.open_replicas.remove(&id);
+        stats
+    }
+
+    /// Export all entries of a namespace, including empty entries, to a single-file archive.
+    ///
+    /// The archive starts with a header holding a magic number, the format version and the
+    /// [`NamespaceId`]. It is followed by the entries, each prefixed with its length. All entries
+    /// are exported, including those under reserved keys, like namespace metadata, write tokens
+    /// and counters. Only entries are exported, neither their content nor the namespace secret,
+    /// so the archive is safe to share with readers. Import the archive with
+    /// [`Self::import_archive`].
+    pub fn export_archive(&mut self, namespace: NamespaceId, mut writer: impl Write) -> Result<()> {
+        self.load_capability(&namespace)?;
+        writer.write_all(&ARCHIVE_MAGIC)?;
+        writer.write_all(&[ARCHIVE_VERSION])?;
+        writer.write_all(namespace.as_bytes())?;
+        let tables = self.snapshot()?;
+        let bounds = RecordsBounds::namespace(namespace);
+        let mut buf = Vec::new();
+        for entry in RecordsRange::with_bounds(&tables.records, bounds)? {
+            buf.clear();
+            postcard::to_io(&entry?, &mut buf)?;
+            let len = u32::try_from(buf.len())?;
+            writer.write_all(&len.to_le_bytes())?;
+            writer.write_all(&buf)?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Import the entries of an archive created with [`Self::export_archive`].
+    ///
+    /// The namespace is imported with read access if it is not yet in the store. The entries are
+    /// validated and inserted with the regular insertion semantics and merged with the existing
+    /// entries of the namespace. Entries which fail to validate are skipped. No events are
+    /// emitted.
+    ///
+    /// The replica must not be open. Returns the [`NamespaceId`] of the archive.
+    pub fn import_archive(&mut self, mut reader: impl Read) -> Result<NamespaceId> {
+        let mut header = [0u8; ARCHIVE_MAGIC.len() + 1 + 32];
+        reader.read_exact(&mut header)?;
+        let (magic, rest) = header.split_at(ARCHIVE_MAGIC.len());
+        ensure!(magic == ARCHIVE_MAGIC, "not a namespace archive");
+        let version = rest[0];
+        ensure!(
+            version == ARCHIVE_VERSION,
+            "unsupported archive version {version}"
+        );
+        let namespace = NamespaceId::from(<&[u8; 32]>::try_from(&rest[1..])?);
+
+        let mut entries = Vec::new();
+        let mut buf = Vec::new();
+        while let Some(len) = read_frame_len(&mut reader)? {
+            buf.clear();
+            (&mut reader).take(len as u64).read_to_end(&mut buf)?;
+            ensure!(buf.len() == len as usize, "truncated archive");
+            entries.push(postcard::from_bytes::<SignedEntry>(&buf)?);
+        }
+
+        if self.open_replicas.contains(&namespace) {
+            return Err(anyhow!("replica is not closed"));
+        }
+        self.import_namespace(Capability::Read(namespace))?;
+        let mut replica = self.open_replica(&namespace)?;
+        let stats = replica.rebuild_from_entries(entries);
+        drop(replica);
+        // The replica was opened internally only, so do not emit a close event.
+        self.open_replicas.remove(&namespace);
+        let stats = stats?;
+        if stats.invalid > 0 {
+            warn!(
+                invalid = stats.invalid,
+                "skipped invalid entries in archive"
+            );
+        }
+        Ok(namespace)
     }
 
     /// Get an iterator over entries of a replica.
@@ -436,50 +1079,682 @@ impl Store {
         namespace: NamespaceId,
         query: impl Into<Query>,
     ) -> Result<QueryIterator> {
+        let resolver = self.conflict_resolver(&namespace);
         let tables = self.snapshot_owned()?;
-        QueryIterator::new(tables, namespace, query.into())
+        QueryIterator::new(tables, namespace, query.into(), resolver)
     }
 
-    /// Get an entry by key and author.
-    pub fn get_exact(
+    /// Get an iterator over the entries of a namespace which match a query, grouped by author.
+    ///
+    /// Yields each author with matching entries once, together with an iterator over the
+    /// author's entries. Authors are ordered by id in the sort direction of the query. The groups
+    /// are read lazily, but all from the same snapshot of the store. Limit and offset of the
+    /// query apply within each group.
+    ///
+    /// Only flat queries can be grouped. Fails for [`Query::single_latest_per_key`] queries, as
+    /// selecting the latest entry per key within each group would yield different entries than
+    /// [`Self::get_many`].
+    pub fn get_many_grouped_by_author(
         &mut self,
         namespace: NamespaceId,
-        author: AuthorId,
-        key: impl AsRef<[u8]>,
-        include_empty: bool,
-    ) -> Result<Option<SignedEntry>> {
-        get_exact(
-            &self.tables()?.records,
-            namespace,
-            author,
-            key,
-            include_empty,
-        )
+        query: impl Into<Query>,
+    ) -> Result<AuthorGroupsIterator> {
+        let resolver = self.conflict_resolver(&namespace);
+        let tables = self.snapshot_owned()?;
+        AuthorGroupsIterator::new(tables, namespace, query.into(), resolver)
     }
 
-    /// Get all content hashes of all replicas in the store.
-    pub fn content_hashes(&mut self) -> Result<ContentHashesIterator> {
-        let tables = self.snapshot_owned()?;
-        ContentHashesIterator::all(&tables.records)
+    /// Get an iterator over the entries of a namespace which match a query, yielding only the
+    /// fields selected by `projection`.
+    ///
+    /// This is useful for large scans where only a few fields of each entry are needed, as the
+    /// yielded items do not hold on to the signatures and unselected fields.
+    pub fn get_projected(
+        &mut self,
+        namespace: NamespaceId,
+        query: impl Into<Query>,
+        projection: Projection,
+    ) -> Result<ProjectedQueryIterator> {
+        Ok(self.get_many(namespace, query)?.with_projection(projection))
     }
 
-    /// Get the latest entry for each author in a namespace.
-    pub fn get_latest_for_each_author(&mut self, namespace: NamespaceId) -> Result<LatestIterator> {
-        LatestIterator::new(&self.tables()?.latest_per_author, namespace)
+    /// Get the first entry matching the query, respecting its sort order.
+    ///
+    /// This stops iterating after the first matching entry.
+    pub fn first(
+        &mut self,
+        namespace: NamespaceId,
+        query: impl Into<Query>,
+    ) -> Result<Option<SignedEntry>> {
+        self.get_many(namespace, query)?.next().transpose()
     }
 
-    /// Register a peer that has been useful to sync a document.
-    pub fn register_useful_peer(
+    /// Get the last entry matching the query, respecting its sort order.
+    ///
+    /// Unless the query has a limit or offset, this iterates the query in reverse and stops after
+    /// the first matching entry instead of scanning all entries.
+    pub fn last(
         &mut self,
         namespace: NamespaceId,
-        peer: crate::PeerIdBytes,
-    ) -> Result<()> {
-        let peer = &peer;
-        let namespace = namespace.as_bytes();
-        // calculate nanos since UNIX_EPOCH for a time measurement
-        let nanos = std::time::UNIX_EPOCH
-            .elapsed()
-            .map(|duration| duration.as_nanos() as u64)?;
+        query: impl Into<Query>,
+    ) -> Result<Option<SignedEntry>> {
+        let mut query = query.into();
+        if query.limit().is_none() && query.offset() == 0 {
+            query.reverse();
+            return self.first(namespace, query);
+        }
+        let mut last = None;
+        for entry in self.get_many(namespace, query)? {
+            last = Some(entry?);
+        }
+        Ok(last)
+    }
+
+    /// List the direct children of `prefix`, grouping keys by the next `delimiter`.
+    ///
+    /// Keys containing the delimiter after the prefix are returned once as a
+    /// [`ChildEntry::Prefix`] up to and including the delimiter, all other keys are returned as
+    /// [`ChildEntry::Key`]. Children are sorted by key, and only keys with a non-empty entry of
+    /// any author are included. Once a common prefix is found, the keys below it are skipped
+    /// rather than iterated.
+    pub fn list_children(
+        &mut self,
+        namespace: NamespaceId,
+        prefix: impl AsRef<[u8]>,
+        delimiter: u8,
+    ) -> Result<Vec<ChildEntry>> {
+        let prefix = prefix.as_ref();
+        let namespace = namespace.as_bytes();
+        let tables = self.tables()?;
+        let mut children: Vec<ChildEntry> = Vec::new();
+        let mut cursor = prefix.to_vec();
+        'seek: loop {
+            let start = (namespace, &cursor[..], &[0u8; 32]);
+            for item in tables.records_by_key.range(start..)? {
+                let (id, _) = item?;
+                let (ns, key, author) = id.value();
+                if ns != namespace || !key.starts_with(prefix) {
+                    break 'seek;
+                }
+                if is_reserved_key(key) {
+                    continue;
+                }
+                let (child, is_prefix) =
+                    match key[prefix.len()..].iter().position(|b| *b == delimiter) {
+                        Some(pos) => (&key[..prefix.len() + pos + 1], true),
+                        None => (key, false),
+                    };
+                // entries of several authors for the same key
+                if children.last().is_some_and(|last| last.as_bytes() == child) {
+                    continue;
+                }
+                let record = tables.records.get((ns, author, key))?;
+                if record.map_or(true, |record| value_is_empty(&record.value())) {
+                    continue;
+                }
+                if !is_prefix {
+                    children.push(ChildEntry::Key(Bytes::copy_from_slice(child)));
+                    continue;
+                }
+                children.push(ChildEntry::Prefix(Bytes::copy_from_slice(child)));
+                // continue after the last key below the common prefix
+                cursor = child.to_vec();
+                while cursor.last() == Some(&u8::MAX) {
+                    cursor.pop();
+                }
+                match cursor.last_mut() {
+                    Some(last) => *last += 1,
+                    None => break 'seek,
+                }
+                continue 'seek;
+            }
+            break;
+        }
+        Ok(children)
+    }
+
+    /// Get an entry by key and author.
+    ///
+    /// Entries under reserved keys, such as namespace metadata, are not returned.
+    pub fn get_exact(
+        &mut self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        key: impl AsRef<[u8]>,
+        include_empty: bool,
+    ) -> Result<Option<SignedEntry>> {
+        if is_reserved_key(key.as_ref()) {
+            return Ok(None);
+        }
+        let tables = self.tables()?;
+        get_exact(
+            &tables.records,
+            &tables.content_chunks,
+            namespace,
+            author,
+            key,
+            include_empty,
+        )
+    }
+
+    /// Get many entries by author and key.
+    ///
+    /// All lookups are performed in a single transaction. The returned entries are in the order
+    /// of `keys`, with `None` for keys without an entry or under a reserved key.
+    pub fn get_exact_many(
+        &mut self,
+        namespace: NamespaceId,
+        keys: impl IntoIterator<Item = (AuthorId, Bytes)>,
+        include_empty: bool,
+    ) -> Result<Vec<Option<SignedEntry>>> {
+        let tables = self.tables()?;
+        keys.into_iter()
+            .map(|(author, key)| {
+                if is_reserved_key(&key) {
+                    return Ok(None);
+                }
+                get_exact(
+                    &tables.records,
+                    &tables.content_chunks,
+                    namespace,
+                    author,
+                    key,
+                    include_empty,
+                )
+            })
+            .collect()
+    }
+
+    /// Store the manifest of chunked entries of a namespace.
+    ///
+    /// Entries of `namespace` whose content hash and length match the manifest will afterwards
+    /// have their [`SignedEntry::content_chunks`] populated when read from the store. This is how
+    /// chunked entries received from other peers become resolvable, once the manifest blob was
+    /// downloaded. The manifest is kept for each matching entry and removed together with the
+    /// entry. Finding the matching entries scans the entries of the namespace.
+    ///
+    /// Returns the number of entries the manifest was stored for, or an error if `manifest` is
+    /// not a valid chunk list.
+    pub fn put_content_chunks(&mut self, namespace: NamespaceId, manifest: &[u8]) -> Result<usize> {
+        ContentChunks::from_manifest(manifest)?;
+        let hash = Hash::new(manifest);
+        let len = manifest.len() as u64;
+        self.modify(|tables| {
+            let bounds = RecordsBounds::namespace(namespace);
+            let mut ids = Vec::new();
+            for item in tables.records.range(bounds.as_ref())? {
+                let (id, value) = item?;
+                let (_timestamp, _namespace_sig, _author_sig, entry_len, entry_hash) =
+                    value.value();
+                if entry_len == len && entry_hash == hash.as_bytes() {
+                    let (_namespace, author, key) = id.value();
+                    ids.push((*author, key.to_vec()));
+                }
+            }
+            for (author, key) in ids.iter() {
+                tables
+                    .content_chunks
+                    .insert((namespace.as_bytes(), author, key.as_slice()), manifest)?;
+            }
+            Ok(ids.len())
+        })
+    }
+
+    /// Set the metadata of a namespace, signed by `author`.
+    ///
+    /// See [`Replica::set_namespace_meta`] for details.
+    pub fn set_namespace_meta(
+        &mut self,
+        namespace: NamespaceId,
+        author: &Author,
+        meta: Bytes,
+    ) -> Result<()> {
+        let capability = self.load_capability(&namespace)?;
+        let instance = StoreInstance::new(namespace, self);
+        let mut replica = Replica::new(instance, Box::new(ReplicaInfo::new(capability)));
+        replica.set_namespace_meta(author, meta)?;
+        Ok(())
+    }
+
+    /// Get the latest metadata of a namespace.
+    ///
+    /// If several authors have set metadata, the metadata with the latest timestamp is returned.
+    /// Returns `None` if no metadata was set, or if the bytes of the latest metadata were synced
+    /// from another peer but are not yet available, see [`Self::put_namespace_meta`].
+    pub fn get_namespace_meta(&mut self, namespace: NamespaceId) -> Result<Option<Bytes>> {
+        let tables = self.snapshot_owned()?;
+        let key = Bytes::from_static(NAMESPACE_META_KEY);
+        let bounds = ByKeyBounds::new(namespace, &KeyFilter::Exact(key));
+        let mut range =
+            RecordsByKeyRange::with_bounds(tables.records_by_key, tables.records, bounds)?;
+        let mut latest: Option<SignedEntry> = None;
+        while let Some(entry) = range.next_filtered(&SortDirection::Asc, |_| true) {
+            let entry = entry?;
+            if entry.is_empty() {
+                continue;
+            }
+            if latest
+                .as_ref()
+                .map_or(true, |latest| entry.record() > latest.record())
+            {
+                latest = Some(entry);
+            }
+        }
+        let Some(latest) = latest else {
+            return Ok(None);
+        };
+        let meta = tables
+            .namespace_meta
+            .get((namespace.as_bytes(), latest.author().as_bytes()))?;
+        // The stored bytes may belong to an older entry of the author.
+        Ok(meta
+            .map(|meta| Bytes::copy_from_slice(meta.value()))
+            .filter(|meta| Hash::new(meta) == latest.content_hash()))
+    }
+
+    /// Store the bytes of namespace metadata.
+    ///
+    /// Metadata entries of `namespace` whose content hash and length match `meta` will
+    /// afterwards be returned from [`Self::get_namespace_meta`]. This is how metadata received
+    /// from other peers becomes readable, once its blob was downloaded.
+    ///
+    /// Returns the number of metadata entries the bytes were stored for.
+    pub fn put_namespace_meta(&mut self, namespace: NamespaceId, meta: &[u8]) -> Result<usize> {
+        let hash = Hash::new(meta);
+        let len = meta.len() as u64;
+        self.modify(|tables| {
+            let bounds = ByKeyBounds::new(namespace, &KeyFilter::Exact(NAMESPACE_META_KEY.into()));
+            let mut authors = Vec::new();
+            for item in tables.records_by_key.range(bounds.as_ref())? {
+                let (id, _) = item?;
+                let (_namespace, _key, author) = id.value();
+                authors.push(*author);
+            }
+            let mut count = 0;
+            for author in authors {
+                let id = (namespace.as_bytes(), &author, NAMESPACE_META_KEY);
+                let Some(value) = tables.records.get(id)? else {
+                    continue;
+                };
+                let (_timestamp, _namespace_sig, _author_sig, entry_len, entry_hash) =
+                    value.value();
+                if entry_len == len && entry_hash == hash.as_bytes() {
+                    drop(value);
+                    tables
+                        .namespace_meta
+                        .insert((namespace.as_bytes(), &author), meta)?;
+                    count += 1;
+                }
+            }
+            Ok(count)
+        })
+    }
+
+    /// Get the value of the counter at `key`, which is the sum of the contributions of all
+    /// authors, see [`CounterValue`].
+    ///
+    /// Returns 0 if no author contributed to the counter.
+    pub fn get_counter(&mut self, namespace: NamespaceId, key: impl AsRef<[u8]>) -> Result<i64> {
+        let tables = self.snapshot_owned()?;
+        let prefix = Bytes::from(CounterValue::entry_key_prefix(key.as_ref()));
+        let bounds = ByKeyBounds::new(namespace, &KeyFilter::Prefix(prefix));
+        let mut range =
+            RecordsByKeyRange::with_bounds(tables.records_by_key, tables.records, bounds)?;
+        // only count the latest contribution of each author.
+        let mut latest: HashMap<AuthorId, (Record, CounterValue)> = HashMap::new();
+        while let Some(entry) = range.next_filtered(&SortDirection::Asc, |_| true) {
+            let entry = entry?;
+            if entry.is_empty() {
+                continue;
+            }
+            let Some(value) = CounterValue::from_entry_key(entry.key()) else {
+                continue;
+            };
+            match latest.entry(entry.author()) {
+                hash_map::Entry::Occupied(mut e) if entry.record() > &e.get().0 => {
+                    e.insert((entry.record().clone(), value));
+                }
+                hash_map::Entry::Occupied(_) => {}
+                hash_map::Entry::Vacant(e) => {
+                    e.insert((entry.record().clone(), value));
+                }
+            }
+        }
+        Ok(CounterValue::aggregate(
+            latest.into_values().map(|(_record, value)| value),
+        ))
+    }
+
+    /// Get the write tokens presented by `author` in `namespace`, and whether they are revoked.
+    ///
+    /// Unlike other reads, this works on a shared reference and reads from the current
+    /// transaction, so that tokens can be looked up while validating entries during sync.
+    pub(crate) fn get_write_tokens(
+        &self,
+        namespace: NamespaceId,
+        author: AuthorId,
+    ) -> Result<Vec<(WriteToken, bool)>> {
+        match &self.transaction {
+            CurrentTransaction::Write(tx) => {
+                let tables = tx.tables();
+                get_write_tokens(&tables.records, &tables.records_by_key, namespace, author)
+            }
+            CurrentTransaction::Read(tables) => {
+                get_write_tokens(&tables.records, &tables.records_by_key, namespace, author)
+            }
+            CurrentTransaction::None => {
+                let tables = ReadOnlyTables::new(self.db.begin_read()?)?;
+                get_write_tokens(&tables.records, &tables.records_by_key, namespace, author)
+            }
+        }
+    }
+
+    /// Check whether a revocation for the write token `id` exists in `namespace`.
+    ///
+    /// Like [`Self::get_write_tokens`], this reads from the current transaction.
+    pub(crate) fn is_write_token_revoked(
+        &self,
+        namespace: NamespaceId,
+        id: WriteTokenId,
+    ) -> Result<bool> {
+        match &self.transaction {
+            CurrentTransaction::Write(tx) => {
+                let tables = tx.tables();
+                is_write_token_revoked(&tables.records, &tables.records_by_key, namespace, id)
+            }
+            CurrentTransaction::Read(tables) => {
+                is_write_token_revoked(&tables.records, &tables.records_by_key, namespace, id)
+            }
+            CurrentTransaction::None => {
+                let tables = ReadOnlyTables::new(self.db.begin_read()?)?;
+                is_write_token_revoked(&tables.records, &tables.records_by_key, namespace, id)
+            }
+        }
+    }
+
+    /// Get the local sequence number of an entry.
+    ///
+    /// Each entry is assigned a sequence number when it is inserted into the store, be it
+    /// locally or through sync. Sequence numbers increase monotonically per namespace and are
+    /// never reused. See [`Query::since_local_seq`] to query entries by sequence number.
+    pub fn get_local_seq(
+        &mut self,
+        namespace: NamespaceId,
+        author: AuthorId,
+        key: impl AsRef<[u8]>,
+    ) -> Result<Option<u64>> {
+        let tables = self.tables()?;
+        let id = (namespace.as_bytes(), author.as_bytes(), key.as_ref());
+        Ok(tables.records_local_seq.get(id)?.map(|seq| seq.value()))
+    }
+
+    /// Get all content hashes of all replicas in the store.
+    ///
+    /// For chunked entries, this includes both the hash of the manifest and the hashes of all
+    /// chunks, see [`ContentChunks`].
+    pub fn content_hashes(&mut self) -> Result<ContentHashesIterator> {
+        let tables = self.snapshot_owned()?;
+        ContentHashesIterator::all(&tables.records, &tables.content_chunks)
+    }
+
+    /// Get an iterator over all entries of all namespaces in the store.
+    ///
+    /// This includes empty entries (tombstones). The iterator is lazy and will keep a database
+    /// snapshot open until it is dropped.
+    pub fn iter_all_entries(&mut self) -> Result<AllEntriesIterator> {
+        let tables = self.snapshot_owned()?;
+        AllEntriesIterator::new(tables)
+    }
+
+    /// Find entries of a namespace whose content length disagrees with the actual blob length.
+    ///
+    /// `resolve_len` is called with the content hash of each non-empty entry and should return
+    /// the length of the blob, or `None` if the blob is not available. Entries for which the
+    /// length cannot be resolved are skipped.
+    ///
+    /// The entries are only reported, not repaired: fixing them requires inserting a new signed
+    /// entry.
+    pub fn verify_content_lengths(
+        &mut self,
+        namespace: NamespaceId,
+        resolve_len: impl Fn(Hash) -> Option<u64>,
+    ) -> Result<Vec<RecordIdentifier>> {
+        let tables = self.snapshot()?;
+        let bounds = RecordsBounds::namespace(namespace);
+        let mut mismatched = Vec::new();
+        for entry in RecordsRange::with_bounds(&tables.records, bounds)? {
+            let entry = entry?;
+            if entry.is_empty() {
+                continue;
+            }
+            match resolve_len(entry.content_hash()) {
+                Some(len) if len != entry.content_len() => mismatched.push(entry.id().clone()),
+                _ => {}
+            }
+        }
+        Ok(mismatched)
+    }
+
+    /// Summarize the entry timestamps of a namespace relative to `now`.
+    ///
+    /// `now` is a timestamp in microseconds since the Unix epoch, as used for entries. This helps
+    /// to spot peers whose clocks are ahead and which write entries with future timestamps.
+    pub fn timestamp_summary(
+        &mut self,
+        namespace: NamespaceId,
+        now: u64,
+    ) -> Result<TimestampSummary> {
+        let tables = self.snapshot()?;
+        let bounds = RecordsBounds::namespace(namespace);
+        let mut summary = TimestampSummary::default();
+        for entry in RecordsRange::with_bounds(&tables.records, bounds)? {
+            let timestamp = entry?.timestamp();
+            summary.count += 1;
+            summary.min = Some(summary.min.map_or(timestamp, |min| min.min(timestamp)));
+            summary.max = Some(summary.max.map_or(timestamp, |max| max.max(timestamp)));
+            if timestamp > now {
+                summary.count_in_future += 1;
+                summary.max_future_delta = summary.max_future_delta.max(timestamp - now);
+            }
+        }
+        Ok(summary)
+    }
+
+    /// Write the canonical form of the live entries of a namespace to `writer`.
+    ///
+    /// The canonical form is the 32 byte namespace id, followed by all non-empty entries in
+    /// ascending order of author and key. Each entry is written as the 32 byte author id, the
+    /// key length as u64 little-endian, the key, the timestamp and the content length as u64
+    /// little-endian, and the 32 byte content hash. Signatures are not included.
+    ///
+    /// Two stores which are fully in sync for the namespace write identical bytes.
+    ///
+    /// Returns the number of entries written.
+    pub fn write_canonical(
+        &mut self,
+        namespace: NamespaceId,
+        mut writer: impl Write,
+    ) -> Result<u64> {
+        let tables = self.snapshot()?;
+        let bounds = RecordsBounds::namespace(namespace);
+        writer.write_all(namespace.as_bytes())?;
+        let mut count = 0;
+        for entry in RecordsRange::with_bounds(&tables.records, bounds)? {
+            let entry = entry?;
+            if entry.is_empty() {
+                continue;
+            }
+            writer.write_all(entry.author_bytes().as_bytes())?;
+            writer.write_all(&(entry.key().len() as u64).to_le_bytes())?;
+            writer.write_all(entry.key())?;
+            writer.write_all(&entry.timestamp().to_le_bytes())?;
+            writer.write_all(&entry.content_len().to_le_bytes())?;
+            writer.write_all(entry.content_hash().as_bytes())?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Get the BLAKE3 hash of the canonical form of a namespace, see [`Self::write_canonical`].
+    ///
+    /// Unlike the fingerprints used for set reconciliation, which combine entries with XOR, this
+    /// hashes an explicit serialization of all live entries, so it can be used to
+    /// content-address the state of a namespace. Two stores which are fully in sync for the
+    /// namespace produce the same digest.
+    pub fn namespace_digest(&mut self, namespace: NamespaceId) -> Result<Hash> {
+        let mut hasher = blake3::Hasher::new();
+        self.write_canonical(namespace, &mut hasher)?;
+        Ok(hasher.finalize().into())
+    }
+
+    /// Get the fingerprint over all entries of a single author in a namespace.
+    ///
+    /// Records are keyed by namespace and then author, so this only walks the author's own
+    /// entries. Together with [`AuthorHeads`] this allows two peers to cheaply check whether
+    /// they agree on an author's data before reconciling.
+    pub fn author_fingerprint(
+        &mut self,
+        namespace: NamespaceId,
+        author: AuthorId,
+    ) -> Result<Fingerprint> {
+        let tables = self.snapshot()?;
+        let bounds = RecordsBounds::author_prefix(namespace, author, Bytes::new());
+        let mut fp = Fingerprint::empty();
+        for entry in RecordsRange::with_bounds(&tables.records, bounds)? {
+            fp ^= entry?.as_fingerprint();
+        }
+        Ok(fp)
+    }
+
+    /// Physically remove all entries of `author` in `namespace` from this store.
+    ///
+    /// This only affects the local store: no tombstones are written and no events are emitted,
+    /// so peers still holding the entries will sync them back. Use
+    /// [`Replica::remove_author_entries`] with [`RemoveMode::Tombstone`] to delete the entries
+    /// for all peers.
+    ///
+    /// Returns the number of entries removed.
+    ///
+    /// [`RemoveMode::Tombstone`]: crate::RemoveMode::Tombstone
+    pub fn remove_entries_by_author(
+        &mut self,
+        namespace: NamespaceId,
+        author: AuthorId,
+    ) -> Result<usize> {
+        self.modify(|tables| {
+            let bounds = RecordsBounds::author_prefix(namespace, author, Bytes::new());
+            let removed = tables
+                .records
+                .extract_from_if(bounds.as_ref(), |_k, _v| true)?
+                .map(|res| {
+                    let (id, _value) = res?;
+                    let (_namespace, _author, key) = id.value();
+                    anyhow::Ok(key.to_vec())
+                })
+                .collect::<Result<Vec<_>>>()?;
+            for key in removed.iter() {
+                let key = key.as_slice();
+                tables
+                    .records_by_key
+                    .remove((namespace.as_bytes(), key, author.as_bytes()))?;
+                remove_local_seq(tables, (namespace.as_bytes(), author.as_bytes(), key))?;
+                tables
+                    .content_chunks
+                    .remove((namespace.as_bytes(), author.as_bytes(), key))?;
+            }
+            tables
+                .latest_per_author
+                .remove((namespace.as_bytes(), author.as_bytes()))?;
+            Ok(removed.len())
+        })
+    }
+
+    /// Partition a namespace into `splits` buckets and get the fingerprint of each bucket.
+    ///
+    /// The buckets hold roughly the same number of entries, and together cover the whole
+    /// namespace, with the last range wrapping around to the start like in set reconciliation.
+    /// The fingerprints are computed the same way as during sync, so they can be compared
+    /// against the fingerprints a remote peer computes for the same ranges. Fewer than `splits`
+    /// buckets are returned if the namespace has fewer entries.
+    pub fn range_fingerprints(
+        &mut self,
+        namespace: NamespaceId,
+        splits: usize,
+    ) -> Result<Vec<(Range<RecordIdentifier>, Fingerprint)>> {
+        anyhow::ensure!(splits > 0, "splits must not be zero");
+        let tables = self.snapshot()?;
+        let len = tables
+            .records
+            .range(RecordsBounds::namespace(namespace).as_ref())?
+            .count();
+        if len == 0 {
+            let first = RecordIdentifier::new(namespace, AuthorId::from([0u8; 32]), []);
+            return Ok(vec![(
+                Range::new(first.clone(), first),
+                Fingerprint::empty(),
+            )]);
+        }
+        let buckets = splits.min(len);
+        let mut starts = Vec::with_capacity(buckets);
+        let mut fingerprints = vec![Fingerprint::empty(); buckets];
+        let records =
+            RecordsRange::with_bounds(&tables.records, RecordsBounds::namespace(namespace))?;
+        for (i, entry) in records.enumerate() {
+            let entry = entry?;
+            let bucket = i * buckets / len;
+            if bucket == starts.len() {
+                starts.push(entry.id().clone());
+            }
+            fingerprints[bucket] ^= entry.as_fingerprint();
+        }
+        let ends = starts.iter().skip(1).chain(starts.first()).cloned();
+        let ranges = starts
+            .iter()
+            .cloned()
+            .zip(ends)
+            .map(|(x, y)| Range::new(x, y));
+        Ok(ranges.zip(fingerprints).collect())
+    }
+
+    /// Get an iterator over the entries of a namespace within a [`Range`] of record identifiers.
+    ///
+    /// Entries are yielded in the order of their identifiers, i.e. by author and then by key.
+    /// If the start of the range is greater than its end, the range wraps around: it contains
+    /// the entries from the start to the end of the namespace, followed by the entries from the
+    /// beginning of the namespace to the end of the range. If start and end are equal, the
+    /// range contains all entries of the namespace.
+    ///
+    /// Unlike [`Self::get_many`], this also yields empty entries and entries under reserved keys.
+    pub fn get_range(
+        &mut self,
+        namespace: NamespaceId,
+        range: Range<RecordIdentifier>,
+    ) -> Result<RangeIterator<'_>> {
+        anyhow::ensure!(
+            range.x().namespace() == namespace && range.y().namespace() == namespace,
+            "range is not within the namespace"
+        );
+        let tables = self.tables()?;
+        range_iter(&tables.records, namespace, &range)
+    }
+
+    /// Get the latest entry for each author in a namespace.
+    pub fn get_latest_for_each_author(&mut self, namespace: NamespaceId) -> Result<LatestIterator> {
+        LatestIterator::new(&self.tables()?.latest_per_author, namespace)
+    }
+
+    /// Register a peer that has been useful to sync a document.
+    pub fn register_useful_peer(
+        &mut self,
+        namespace: NamespaceId,
+        peer: crate::PeerIdBytes,
+    ) -> Result<()> {
+        let peer = &peer;
+        let namespace = namespace.as_bytes();
+        // calculate nanos since UNIX_EPOCH for a time measurement
+        let nanos = std::time::UNIX_EPOCH
+            .elapsed()
+            .map(|duration| duration.as_nanos() as u64)?;
         self.modify(|tables| {
             // ensure the document exists
             anyhow::ensure!(
@@ -614,48 +1889,203 @@ fn parse_capability((raw_kind, raw_bytes): (u8, &[u8; 32])) -> Result<Capability
 
 fn get_exact(
     record_table: &impl ReadableTable<RecordsId<'static>, RecordsValue<'static>>,
+    chunks_table: &impl ReadableTable<RecordsId<'static>, &'static [u8]>,
     namespace: NamespaceId,
     author: AuthorId,
     key: impl AsRef<[u8]>,
     include_empty: bool,
 ) -> Result<Option<SignedEntry>> {
+    #[cfg(feature = "metrics")]
+    let _timer = LatencyTimer::new(|metrics| &metrics.store_get_exact_latency);
     let id = (namespace.as_bytes(), author.as_bytes(), key.as_ref());
     let record = record_table.get(id)?;
-    Ok(record
+    let Some(entry) = record
         .map(|r| into_entry(id, r.value()))
-        .filter(|entry| include_empty || !entry.is_empty()))
+        .filter(|entry| include_empty || !entry.is_empty())
+    else {
+        return Ok(None);
+    };
+    Ok(Some(with_content_chunks(chunks_table, entry)?))
 }
 
-/// A wrapper around [`Store`] for a specific [`NamespaceId`]
-#[derive(Debug)]
-pub struct StoreInstance<'a> {
-    namespace: NamespaceId,
-    pub(crate) store: &'a mut Store,
-}
+/// Number of content statuses remembered by [`Store::notify_content_status`].
+const CONTENT_STATUS_CAPACITY: NonZeroUsize = match NonZeroUsize::new(4096) {
+    Some(capacity) => capacity,
+    None => unreachable!(),
+};
 
-impl<'a> StoreInstance<'a> {
-    pub(crate) fn new(namespace: NamespaceId, store: &'a mut Store) -> Self {
-        StoreInstance { namespace, store }
+/// Magic number at the start of a namespace archive, see [`Store::export_archive`].
+const ARCHIVE_MAGIC: [u8; 8] = *b"IDOCARCH";
+/// Current version of the namespace archive format.
+const ARCHIVE_VERSION: u8 = 1;
+
+/// Read the length prefix of the next entry in a namespace archive.
+///
+/// Returns `None` at the end of the archive.
+fn read_frame_len(reader: &mut impl Read) -> Result<Option<u32>> {
+    let mut buf = [0u8; 4];
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) if read == 0 => return Ok(None),
+            Ok(0) => bail!("truncated archive"),
+            Ok(n) => read += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        }
     }
+    Ok(Some(u32::from_le_bytes(buf)))
 }
 
-impl PublicKeyStore for StoreInstance<'_> {
-    fn public_key(&self, id: &[u8; 32]) -> std::result::Result<VerifyingKey, SignatureError> {
-        self.store.public_key(id)
+/// Attach the content chunks to a chunked entry, if its manifest is known.
+pub(super) fn with_content_chunks(
+    chunks_table: &impl ReadableTable<RecordsId<'static>, &'static [u8]>,
+    entry: SignedEntry,
+) -> Result<SignedEntry> {
+    let Some(manifest) = chunks_table.get(entry.id().as_byte_tuple())? else {
+        return Ok(entry);
+    };
+    let chunks = ContentChunks::from_manifest(manifest.value())?;
+    // A mismatch means the entry was replaced with different content, keep it unresolved.
+    Ok(entry.clone().with_content_chunks(chunks).unwrap_or(entry))
+}
+
+fn get_write_tokens(
+    records: &impl ReadableTable<RecordsId<'static>, RecordsValue<'static>>,
+    records_by_key: &impl ReadableTable<RecordsByKeyId<'static>, ()>,
+    namespace: NamespaceId,
+    author: AuthorId,
+) -> Result<Vec<(WriteToken, bool)>> {
+    let start = (namespace.as_bytes(), author.as_bytes(), WRITE_TOKEN_PREFIX);
+    let mut tokens = Vec::new();
+    for item in records.range(start..)? {
+        let (key, value) = item?;
+        let (entry_namespace, entry_author, key) = key.value();
+        if entry_namespace != namespace.as_bytes()
+            || entry_author != author.as_bytes()
+            || !key.starts_with(WRITE_TOKEN_PREFIX)
+        {
+            break;
+        }
+        let (_timestamp, _namespace_sig, _author_sig, len, _hash) = value.value();
+        if len == 0 {
+            continue;
+        }
+        // Tokens are validated on insert, skip anything that does not parse.
+        let Ok(token) = WriteToken::from_bytes(&key[WRITE_TOKEN_PREFIX.len()..]) else {
+            continue;
+        };
+        let revoked = is_write_token_revoked(records, records_by_key, namespace, token.id())?;
+        tokens.push((token, revoked));
     }
+    Ok(tokens)
 }
 
-impl super::DownloadPolicyStore for StoreInstance<'_> {
-    fn get_download_policy(&mut self, namespace: &NamespaceId) -> Result<DownloadPolicy> {
-        self.store.get_download_policy(namespace)
+fn is_write_token_revoked(
+    records: &impl ReadableTable<RecordsId<'static>, RecordsValue<'static>>,
+    records_by_key: &impl ReadableTable<RecordsByKeyId<'static>, ()>,
+    namespace: NamespaceId,
+    id: WriteTokenId,
+) -> Result<bool> {
+    let key = [WRITE_TOKEN_REVOCATION_PREFIX, id.as_bytes()].concat();
+    let start = (namespace.as_bytes(), key.as_slice(), &[u8::MIN; 32]);
+    let end = (namespace.as_bytes(), key.as_slice(), &[u8::MAX; 32]);
+    for item in records_by_key.range(start..=end)? {
+        let (by_key, _) = item?;
+        let (_namespace, _key, author) = by_key.value();
+        let record = records.get((namespace.as_bytes(), author, key.as_slice()))?;
+        if record.is_some_and(|record| record.value().3 != 0) {
+            return Ok(true);
+        }
     }
+    Ok(false)
 }
 
-impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
-    type Error = anyhow::Error;
-    type RangeIterator<'x>
-        = Chain<RecordsRange<'x>, Flatten<std::option::IntoIter<RecordsRange<'x>>>>
-    where
+/// A cloneable read-only handle to the entries of a namespace.
+///
+/// Created with [`Store::read_handle`]. Every read opens an independent read transaction,
+/// which means that the handle can be cloned and used from many threads at once.
+#[derive(derive_more::Debug, Clone)]
+pub struct ReadHandle {
+    #[debug("Database")]
+    db: Arc<Database>,
+    namespace: NamespaceId,
+    resolver: Option<Arc<dyn ConflictResolver>>,
+}
+
+impl ReadHandle {
+    /// The namespace this handle reads from.
+    pub fn namespace(&self) -> NamespaceId {
+        self.namespace
+    }
+
+    /// Get an entry by key and author.
+    ///
+    /// Entries under reserved keys, such as namespace metadata, are not returned.
+    pub fn get_exact(
+        &self,
+        author: AuthorId,
+        key: impl AsRef<[u8]>,
+        include_empty: bool,
+    ) -> Result<Option<SignedEntry>> {
+        if is_reserved_key(key.as_ref()) {
+            return Ok(None);
+        }
+        let tables = self.snapshot()?;
+        get_exact(
+            &tables.records,
+            &tables.content_chunks,
+            self.namespace,
+            author,
+            key,
+            include_empty,
+        )
+    }
+
+    /// Get an iterator over entries of the namespace.
+    ///
+    /// The iterator holds its own read transaction open until it is dropped.
+    pub fn get_many(&self, query: impl Into<Query>) -> Result<QueryIterator> {
+        let tables = self.snapshot()?;
+        QueryIterator::new(tables, self.namespace, query.into(), self.resolver.clone())
+    }
+
+    fn snapshot(&self) -> Result<ReadOnlyTables> {
+        let tx = self.db.begin_read()?;
+        Ok(ReadOnlyTables::new(tx)?)
+    }
+}
+
+/// A wrapper around [`Store`] for a specific [`NamespaceId`]
+#[derive(Debug)]
+pub struct StoreInstance<'a> {
+    namespace: NamespaceId,
+    pub(crate) store: &'a mut Store,
+}
+
+impl<'a> StoreInstance<'a> {
+    pub(crate) fn new(namespace: NamespaceId, store: &'a mut Store) -> Self {
+        StoreInstance { namespace, store }
+    }
+}
+
+impl PublicKeyStore for StoreInstance<'_> {
+    fn public_key(&self, id: &[u8; 32]) -> std::result::Result<VerifyingKey, SignatureError> {
+        self.store.public_key(id)
+    }
+}
+
+impl super::DownloadPolicyStore for StoreInstance<'_> {
+    fn get_download_policy(&mut self, namespace: &NamespaceId) -> Result<DownloadPolicy> {
+        self.store.get_download_policy(namespace)
+    }
+}
+
+impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
+    type Error = anyhow::Error;
+    type RangeIterator<'x>
+        = InstanceRangeIterator<'x>
+    where
         'a: 'x;
     type ParentIterator<'x>
         = ParentIterator
@@ -679,9 +2109,15 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
     }
 
     fn get(&mut self, id: &RecordIdentifier) -> Result<Option<SignedEntry>> {
-        self.store
-            .as_mut()
-            .get_exact(id.namespace(), id.author(), id.key(), true)
+        let tables = self.store.as_mut().tables()?;
+        get_exact(
+            &tables.records,
+            &tables.content_chunks,
+            id.namespace(),
+            id.author(),
+            id.key(),
+            true,
+        )
     }
 
     fn len(&mut self) -> Result<usize> {
@@ -697,8 +2133,11 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
     }
 
     fn get_fingerprint(&mut self, range: &Range<RecordIdentifier>) -> Result<Fingerprint> {
+        #[cfg(feature = "metrics")]
+        let _timer = LatencyTimer::new(|metrics| &metrics.store_get_fingerprint_latency);
         // TODO: optimize
-        let elements = self.get_range(range.clone())?;
+        let tables = self.store.as_mut().tables()?;
+        let elements = range_iter(&tables.records, self.namespace, range)?;
 
         let mut fp = Fingerprint::empty();
         for el in elements {
@@ -728,6 +2167,21 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
             );
             tables.records.insert(key, value)?;
 
+            // keep the manifest of chunked entries, and drop the manifest of a replaced entry
+            // unless it still matches the content
+            if let Some(chunks) = e.content_chunks() {
+                let manifest = ContentChunks::new(chunks.iter().copied()).to_manifest();
+                tables.content_chunks.insert(key, manifest.as_slice())?;
+            } else {
+                let outdated = tables
+                    .content_chunks
+                    .get(key)?
+                    .is_some_and(|manifest| Hash::new(manifest.value()) != hash);
+                if outdated {
+                    tables.content_chunks.remove(key)?;
+                }
+            }
+
             // insert into by key index table
             let key = (
                 &id.namespace().to_bytes(),
@@ -740,45 +2194,43 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
             let key = (&e.id().namespace().to_bytes(), &e.id().author().to_bytes());
             let value = (e.timestamp(), e.id().key());
             tables.latest_per_author.insert(key, value)?;
+
+            // assign a fresh local sequence number
+            let namespace = id.namespace().to_bytes();
+            let author = id.author().to_bytes();
+            let seq = tables
+                .namespace_local_seq
+                .get(&namespace)?
+                .map(|seq| seq.value())
+                .unwrap_or_default()
+                + 1;
+            tables.namespace_local_seq.insert(&namespace, seq)?;
+            let key = (&namespace, &author, id.key());
+            let prev_seq = tables
+                .records_local_seq
+                .insert(key, seq)?
+                .map(|seq| seq.value());
+            if let Some(prev_seq) = prev_seq {
+                tables.records_by_local_seq.remove((&namespace, prev_seq))?;
+            }
+            tables
+                .records_by_local_seq
+                .insert((&namespace, seq), (&author, id.key()))?;
             Ok(())
-        })
+        })?;
+        self.store.as_mut().count_entry_write()
     }
 
     fn get_range(&mut self, range: Range<RecordIdentifier>) -> Result<Self::RangeIterator<'_>> {
+        #[cfg(feature = "metrics")]
+        let timer = LatencyTimer::new(|metrics| &metrics.store_get_range_latency);
         let tables = self.store.as_mut().tables()?;
-        let iter = match range.x().cmp(range.y()) {
-            // identity range: iter1 = all, iter2 = none
-            Ordering::Equal => {
-                // iterator for all entries in replica
-                let bounds = RecordsBounds::namespace(self.namespace);
-                let iter = RecordsRange::with_bounds(&tables.records, bounds)?;
-                chain_none(iter)
-            }
-            // regular range: iter1 = x <= t < y, iter2 = none
-            Ordering::Less => {
-                // iterator for entries from range.x to range.y
-                let start = Bound::Included(range.x().to_byte_tuple());
-                let end = Bound::Excluded(range.y().to_byte_tuple());
-                let bounds = RecordsBounds::new(start, end);
-                let iter = RecordsRange::with_bounds(&tables.records, bounds)?;
-                chain_none(iter)
-            }
-            // split range: iter1 = start <= t < y, iter2 = x <= t <= end
-            Ordering::Greater => {
-                // iterator for entries from start to range.y
-                let end = Bound::Excluded(range.y().to_byte_tuple());
-                let bounds = RecordsBounds::from_start(&self.namespace, end);
-                let iter = RecordsRange::with_bounds(&tables.records, bounds)?;
-
-                // iterator for entries from range.x to end
-                let start = Bound::Included(range.x().to_byte_tuple());
-                let bounds = RecordsBounds::to_end(&self.namespace, start);
-                let iter2 = RecordsRange::with_bounds(&tables.records, bounds)?;
-
-                iter.chain(Some(iter2).into_iter().flatten())
-            }
-        };
-        Ok(iter)
+        let iter = range_iter(&tables.records, self.namespace, &range)?;
+        Ok(InstanceRangeIterator {
+            inner: iter,
+            #[cfg(feature = "metrics")]
+            timer: Some(timer),
+        })
     }
 
     fn entry_remove(&mut self, id: &RecordIdentifier) -> Result<Option<SignedEntry>> {
@@ -788,6 +2240,8 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
                 let id = (namespace, key, author);
                 tables.records_by_key.remove(id)?;
                 let id = (namespace, author, key);
+                remove_local_seq(tables, id)?;
+                tables.content_chunks.remove(id)?;
                 let value = tables.records.remove(id)?;
                 value.map(|value| into_entry(id, value.value()))
             };
@@ -799,7 +2253,7 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
         let tables = self.store.as_mut().tables()?;
         let bounds = RecordsBounds::namespace(self.namespace);
         let iter = RecordsRange::with_bounds(&tables.records, bounds)?;
-        Ok(chain_none(iter))
+        Ok(chain_none(iter).into())
     }
 
     fn prefixes_of(
@@ -814,29 +2268,196 @@ impl<'a> crate::ranger::Store<SignedEntry> for StoreInstance<'a> {
         let tables = self.store.as_mut().tables()?;
         let bounds = RecordsBounds::author_prefix(id.namespace(), id.author(), id.key_bytes());
         let iter = RecordsRange::with_bounds(&tables.records, bounds)?;
-        Ok(chain_none(iter))
+        Ok(chain_none(iter).into())
     }
 
     fn remove_prefix_filtered(
         &mut self,
         id: &RecordIdentifier,
         predicate: impl Fn(&Record) -> bool,
+    ) -> Result<usize> {
+        self.remove_prefix_filtered_entries(id, |entry| predicate(entry.record()))
+    }
+
+    fn put(&mut self, entry: SignedEntry) -> Result<InsertOutcome> {
+        #[cfg(feature = "metrics")]
+        let _timer = LatencyTimer::new(|metrics| &metrics.store_put_latency);
+        // Same as the default implementation, but consults the conflict resolver of the replica
+        // on equal timestamps. Without a resolver, the greater content hash wins, as in the
+        // ordering of [`Record`].
+        let resolver = self.store.conflict_resolver(&self.namespace);
+        let resolver: Option<&dyn ConflictResolver> =
+            Some(resolver.as_deref().unwrap_or(&HashConflictResolver));
+        for prefix_entry in self.prefixes_of(entry.id())? {
+            let prefix_entry = prefix_entry?;
+            if cmp_entries(resolver, entry.entry(), prefix_entry.entry()).is_le() {
+                return Ok(InsertOutcome::NotInserted);
+            }
+        }
+        let removed = self.remove_prefix_filtered_entries(entry.id(), |existing| {
+            cmp_entries(resolver, entry.entry(), existing).is_ge()
+        })?;
+        self.entry_put(entry)?;
+        Ok(InsertOutcome::Inserted { removed })
+    }
+}
+
+impl StoreInstance<'_> {
+    fn remove_prefix_filtered_entries(
+        &mut self,
+        id: &RecordIdentifier,
+        predicate: impl Fn(&Entry) -> bool,
     ) -> Result<usize> {
         let bounds = RecordsBounds::author_prefix(id.namespace(), id.author(), id.key_bytes());
         self.store.as_mut().modify(|tables| {
-            let cb = |_k: RecordsId, v: RecordsValue| {
+            let cb = |k: RecordsId, v: RecordsValue| {
+                let (namespace, author, key) = k;
                 let (timestamp, _namespace_sig, _author_sig, len, hash) = v;
+                let id = RecordIdentifier::new(*namespace, *author, key);
                 let record = Record::new(hash.into(), len, timestamp);
 
-                predicate(&record)
+                predicate(&Entry::new(id, record))
             };
-            let iter = tables.records.extract_from_if(bounds.as_ref(), cb)?;
-            let count = iter.count();
-            Ok(count)
+            let removed = tables
+                .records
+                .extract_from_if(bounds.as_ref(), cb)?
+                .map(|res| {
+                    let (id, _value) = res?;
+                    let (namespace, author, key) = id.value();
+                    anyhow::Ok((*namespace, *author, key.to_vec()))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            for (namespace, author, key) in removed.iter() {
+                remove_local_seq(tables, (namespace, author, key))?;
+                tables
+                    .content_chunks
+                    .remove((namespace, author, key.as_slice()))?;
+            }
+            Ok(removed.len())
         })
     }
 }
 
+fn remove_local_seq(tables: &mut Tables, id: RecordsId) -> Result<()> {
+    if let Some(seq) = tables.records_local_seq.remove(id)? {
+        let (namespace, _author, _key) = id;
+        tables
+            .records_by_local_seq
+            .remove((namespace, seq.value()))?;
+    }
+    Ok(())
+}
+
+/// Remove all entries of a namespace from all tables which index them.
+///
+/// Returns the number of removed entries.
+fn remove_entries(tables: &mut Tables, namespace: NamespaceId) -> Result<usize> {
+    let mut removed = 0;
+    let bounds = RecordsBounds::namespace(namespace);
+    tables.records.retain_in(bounds.as_ref(), |_k, _v| {
+        removed += 1;
+        false
+    })?;
+    let bounds = ByKeyBounds::namespace(namespace);
+    tables
+        .records_by_key
+        .retain_in(bounds.as_ref(), |_k, _v| false)?;
+    let bounds = RecordsBounds::namespace(namespace);
+    tables
+        .records_local_seq
+        .retain_in(bounds.as_ref(), |_k, _v| false)?;
+    tables
+        .content_chunks
+        .retain_in(bounds.as_ref(), |_k, _v| false)?;
+    let bounds = (namespace.as_bytes(), 0)..=(namespace.as_bytes(), u64::MAX);
+    tables
+        .records_by_local_seq
+        .retain_in(bounds, |_k, _v| false)?;
+    let bounds = (namespace.as_bytes(), &[u8::MIN; 32])..=(namespace.as_bytes(), &[u8::MAX; 32]);
+    tables.latest_per_author.retain_in(bounds, |_k, _v| false)?;
+    tables.namespace_local_seq.remove(namespace.as_bytes())?;
+    Ok(removed)
+}
+
+/// Iterator over the entries in a [`Range`], see [`Store::get_range`].
+pub type RangeIterator<'a> =
+    Chain<RecordsRange<'a>, Flatten<std::option::IntoIter<RecordsRange<'a>>>>;
+
+/// Iterator over a range of entries of a [`StoreInstance`].
+///
+/// With the `metrics` feature, iterators from [`crate::ranger::Store::get_range`] record the time
+/// from their creation until they are exhausted or dropped as `store_get_range_latency`.
+#[derive(derive_more::Debug)]
+#[debug("InstanceRangeIterator")]
+pub struct InstanceRangeIterator<'a> {
+    inner: RangeIterator<'a>,
+    #[cfg(feature = "metrics")]
+    timer: Option<LatencyTimer>,
+}
+
+impl<'a> From<RangeIterator<'a>> for InstanceRangeIterator<'a> {
+    fn from(inner: RangeIterator<'a>) -> Self {
+        Self {
+            inner,
+            #[cfg(feature = "metrics")]
+            timer: None,
+        }
+    }
+}
+
+impl Iterator for InstanceRangeIterator<'_> {
+    type Item = Result<SignedEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.inner.next();
+        #[cfg(feature = "metrics")]
+        if next.is_none() {
+            self.timer.take();
+        }
+        next
+    }
+}
+
+fn range_iter<'a>(
+    records: &'a impl ReadableTable<RecordsId<'static>, RecordsValue<'static>>,
+    namespace: NamespaceId,
+    range: &Range<RecordIdentifier>,
+) -> Result<RangeIterator<'a>> {
+    let iter = match range.x().cmp(range.y()) {
+        // identity range: iter1 = all, iter2 = none
+        Ordering::Equal => {
+            // iterator for all entries in replica
+            let bounds = RecordsBounds::namespace(namespace);
+            let iter = RecordsRange::with_bounds(records, bounds)?;
+            chain_none(iter)
+        }
+        // regular range: iter1 = x <= t < y, iter2 = none
+        Ordering::Less => {
+            // iterator for entries from range.x to range.y
+            let start = Bound::Included(range.x().to_byte_tuple());
+            let end = Bound::Excluded(range.y().to_byte_tuple());
+            let bounds = RecordsBounds::new(start, end);
+            let iter = RecordsRange::with_bounds(records, bounds)?;
+            chain_none(iter)
+        }
+        // split range: iter1 = start <= t < y, iter2 = x <= t <= end
+        Ordering::Greater => {
+            // iterator for entries from start to range.y
+            let end = Bound::Excluded(range.y().to_byte_tuple());
+            let bounds = RecordsBounds::from_start(&namespace, end);
+            let iter = RecordsRange::with_bounds(records, bounds)?;
+
+            // iterator for entries from range.x to end
+            let start = Bound::Included(range.x().to_byte_tuple());
+            let bounds = RecordsBounds::to_end(&namespace, start);
+            let iter2 = RecordsRange::with_bounds(records, bounds)?;
+
+            iter.chain(Some(iter2).into_iter().flatten())
+        }
+    };
+    Ok(iter)
+}
+
 fn chain_none<'a, I: Iterator<Item = T> + 'a, T>(
     iter: I,
 ) -> Chain<I, Flatten<std::option::IntoIter<I>>> {
@@ -857,7 +2478,13 @@ impl ParentIterator {
         author: AuthorId,
         key: Vec<u8>,
     ) -> anyhow::Result<Self> {
-        let parents = parents(&tables.records, namespace, author, key.clone());
+        let parents = parents(
+            &tables.records,
+            &tables.content_chunks,
+            namespace,
+            author,
+            key.clone(),
+        );
         Ok(Self {
             inner: parents.into_iter(),
         })
@@ -866,6 +2493,7 @@ impl ParentIterator {
 
 fn parents(
     table: &impl ReadableTable<RecordsId<'static>, RecordsValue<'static>>,
+    chunks_table: &impl ReadableTable<RecordsId<'static>, &'static [u8]>,
     namespace: NamespaceId,
     author: AuthorId,
     mut key: Vec<u8>,
@@ -873,7 +2501,11 @@ fn parents(
     let mut res = Vec::new();
 
     while !key.is_empty() {
-        let entry = get_exact(table, namespace, author, &key, false);
+        if is_reserved_key(&key) {
+            key.pop();
+            continue;
+        }
+        let entry = get_exact(table, chunks_table, namespace, author, &key, false);
         key.pop();
         match entry {
             Err(err) => res.push(Err(err)),
@@ -904,13 +2536,25 @@ impl Iterator for ParentIterator {
 pub struct ContentHashesIterator {
     #[debug(skip)]
     range: RecordsRange<'static>,
+    /// Manifests of chunked entries, whose chunk hashes are yielded after the entries.
+    #[debug(skip)]
+    manifests: redb::Range<'static, RecordsId<'static>, &'static [u8]>,
+    chunks: std::vec::IntoIter<(Hash, u64)>,
 }
 
 impl ContentHashesIterator {
     /// Create a new iterator over all content hashes.
-    pub fn all(table: &RecordsTable) -> anyhow::Result<Self> {
+    pub fn all(
+        table: &RecordsTable,
+        content_chunks: &redb::ReadOnlyTable<RecordsId<'static>, &'static [u8]>,
+    ) -> anyhow::Result<Self> {
         let range = RecordsRange::all_static(table)?;
-        Ok(Self { range })
+        let manifests = content_chunks.range::<RecordsId<'static>>(..)?;
+        Ok(Self {
+            range,
+            manifests,
+            chunks: Vec::new().into_iter(),
+        })
     }
 }
 
@@ -918,8 +2562,56 @@ impl Iterator for ContentHashesIterator {
     type Item = Result<Hash>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let v = self.range.next()?;
-        Some(v.map(|e| e.content_hash()))
+        if let Some(v) = self.range.next() {
+            return Some(v.map(|e| e.content_hash()));
+        }
+        loop {
+            if let Some((hash, _len)) = self.chunks.next() {
+                return Some(Ok(hash));
+            }
+            let manifest = match self.manifests.next()? {
+                Ok((_id, manifest)) => ContentChunks::from_manifest(manifest.value()),
+                Err(err) => return Some(Err(err.into())),
+            };
+            match manifest {
+                Ok(chunks) => self.chunks = chunks.chunks().to_vec().into_iter(),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+/// Iterator over all entries of all namespaces.
+///
+/// This represents a snapshot of the database at the time of creation, which is kept open
+/// until the iterator is dropped.
+#[derive(derive_more::Debug)]
+pub struct AllEntriesIterator {
+    #[debug(skip)]
+    range: RecordsRange<'static>,
+    #[debug(skip)]
+    content_chunks: redb::ReadOnlyTable<RecordsId<'static>, &'static [u8]>,
+}
+
+impl AllEntriesIterator {
+    fn new(tables: ReadOnlyTables) -> anyhow::Result<Self> {
+        let range = RecordsRange::all_static(&tables.records)?;
+        Ok(Self {
+            range,
+            content_chunks: tables.content_chunks,
+        })
+    }
+}
+
+impl Iterator for AllEntriesIterator {
+    type Item = Result<(NamespaceId, SignedEntry)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self
+            .range
+            .next()?
+            .and_then(|entry| with_content_chunks(&self.content_chunks, entry));
+        Some(entry.map(|entry| (entry.namespace(), entry)))
     }
 }
 
@@ -970,7 +2662,7 @@ fn into_entry(key: RecordsId, value: RecordsValue) -> SignedEntry {
 #[cfg(test)]
 mod tests {
     use super::{tables::LATEST_PER_AUTHOR_TABLE, *};
-    use crate::ranger::Store as _;
+    use crate::{ranger::Store as _, store::ProjectedEntry, ContentStatus, InsertError};
 
     #[test]
     fn test_ranges() -> Result<()> {
@@ -1076,60 +2768,1511 @@ mod tests {
         Ok(())
     }
 
-    fn copy_and_modify(
-        source: &Path,
-        modify: impl Fn(&redb::WriteTransaction) -> Result<()>,
-    ) -> Result<tempfile::NamedTempFile> {
+    #[test]
+    fn test_open_read_only() -> Result<()> {
         let dbfile = tempfile::NamedTempFile::new()?;
-        std::fs::copy(source, dbfile.path())?;
-        let db = Database::create(dbfile.path())?;
-        let write_tx = db.begin_write()?;
-        modify(&write_tx)?;
-        write_tx.commit()?;
-        drop(db);
-        Ok(dbfile)
+        let mut store = Store::persistent(dbfile.path())?;
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.hash_and_insert("a", &author, "hello")?;
+        replica.hash_and_insert("b", &author, "world")?;
+        drop(replica);
+        store.flush()?;
+
+        // the file can be opened while the writing store still holds it.
+        let mut ro_store = Store::open_read_only(dbfile.path())?;
+        assert!(ro_store.is_read_only());
+        let entries = ro_store
+            .get_many(namespace.id(), Query::all())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 2);
+        assert!(ro_store
+            .get_exact(namespace.id(), author.id(), "a", false)?
+            .is_some());
+
+        let mut replica = ro_store.open_replica(&namespace.id())?;
+        let res = replica.hash_and_insert("c", &author, "denied");
+        assert!(
+            matches!(res, Err(InsertError::Store(ref err)) if err.is::<ReadOnlyStore>()),
+            "{res:?}"
+        );
+        drop(replica);
+        let res = ro_store.new_author(&mut rand::thread_rng());
+        assert!(res.unwrap_err().is::<ReadOnlyStore>());
+        drop(ro_store);
+
+        // writes to the file after opening are not visible to a read-only store opened before.
+        let mut ro_store = Store::open_read_only(dbfile.path())?;
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert("c", &author, "later")?;
+        drop(replica);
+        store.flush()?;
+        assert!(ro_store
+            .get_exact(namespace.id(), author.id(), "c", false)?
+            .is_none());
+        drop(ro_store);
+
+        drop(store);
+        let mut store = Store::persistent(dbfile.path())?;
+        assert_eq!(
+            store
+                .get_many(namespace.id(), Query::all())?
+                .collect::<Result<Vec<_>>>()?
+                .len(),
+            3
+        );
+        Ok(())
     }
 
     #[test]
-    fn test_migration_001_populate_latest_table() -> Result<()> {
+    fn test_read_handle_parallel_reads() -> Result<()> {
         let dbfile = tempfile::NamedTempFile::new()?;
+        let mut store = Store::persistent(dbfile.path())?;
+
+        let author = store.new_author(&mut rand::thread_rng())?;
         let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        for i in 0..10 {
+            replica.hash_and_insert(format!("k{i}"), &author, format!("v{i}"))?;
+        }
+        let handle = store.read_handle(namespace.id())?;
 
-        // create a store and add some data
-        let expected = {
-            let mut store = Store::persistent(dbfile.path())?;
-            let author1 = store.new_author(&mut rand::thread_rng())?;
-            let author2 = store.new_author(&mut rand::thread_rng())?;
-            let mut replica = store.new_replica(namespace.clone())?;
-            replica.hash_and_insert(b"k1", &author1, b"v1")?;
-            replica.hash_and_insert(b"k2", &author2, b"v1")?;
-            replica.hash_and_insert(b"k3", &author1, b"v1")?;
+        let readers = (0..4)
+            .map(|_| {
+                let handle = handle.clone();
+                let author = author.id();
+                std::thread::spawn(move || -> Result<()> {
+                    for _ in 0..50 {
+                        let entries = handle.get_many(Query::all())?.collect::<Result<Vec<_>>>()?;
+                        assert!(entries.len() >= 10);
+                        let entry = handle.get_exact(author, b"k0", false)?.unwrap();
+                        assert_eq!(entry.key(), b"k0");
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
 
-            let expected = store
-                .get_latest_for_each_author(namespace.id())?
+        // write while the readers are running
+        let mut replica = store.open_replica(&namespace.id())?;
+        for i in 10..20 {
+            replica.hash_and_insert(format!("k{i}"), &author, format!("v{i}"))?;
+        }
+        store.flush()?;
+
+        for reader in readers {
+            reader.join().expect("reader panicked")?;
+        }
+
+        let entries = handle.get_many(Query::all())?.collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 20);
+        Ok(())
+    }
+
+    #[test]
+    fn test_auto_flush_every_writes() -> Result<()> {
+        let mut store = Store::memory();
+        store.set_auto_flush(AutoFlush {
+            every_writes: Some(2),
+            ..Default::default()
+        });
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        store.new_replica(namespace.clone())?;
+        let handle = store.read_handle(namespace.id())?;
+
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert("a", &author, "a")?;
+        assert!(handle.get_exact(author.id(), b"a", false)?.is_none());
+        replica.hash_and_insert("b", &author, "b")?;
+        assert!(handle.get_exact(author.id(), b"a", false)?.is_some());
+        assert!(handle.get_exact(author.id(), b"b", false)?.is_some());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flush_async() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.hash_and_insert("a", &author, "a")?;
+        let handle = store.read_handle(namespace.id())?;
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert("b", &author, "b")?;
+        assert!(handle.get_exact(author.id(), b"b", false)?.is_none());
+        store.flush_async().await?;
+        assert!(handle.get_exact(author.id(), b"b", false)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_exact_many() -> Result<()> {
+        let mut store = Store::memory();
+        let alice = store.new_author(&mut rand::thread_rng())?;
+        let bob = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.hash_and_insert("a", &alice, "alice a")?;
+        replica.hash_and_insert("b", &alice, "alice b")?;
+        replica.hash_and_insert("a", &bob, "bob a")?;
+        replica.hash_and_insert("c/1", &bob, "bob c")?;
+        replica.delete_prefix("c", &bob)?;
+        let chunks = ContentChunks::new([(Hash::new("x"), 1), (Hash::new("y"), 1)]);
+        replica.insert_chunked("d", &alice, chunks)?;
+
+        let keys = [
+            (bob.id(), "a"),
+            (alice.id(), "missing"),
+            (alice.id(), "a"),
+            (bob.id(), "c"),
+            (bob.id(), "c/1"),
+            (alice.id(), "d"),
+            (alice.id(), "a"),
+            (bob.id(), "b"),
+        ]
+        .map(|(author, key)| (author, Bytes::from(key)));
+        for include_empty in [false, true] {
+            let batched = store.get_exact_many(namespace.id(), keys.clone(), include_empty)?;
+            let single = keys
+                .iter()
+                .map(|(author, key)| store.get_exact(namespace.id(), *author, key, include_empty))
                 .collect::<Result<Vec<_>>>()?;
-            // drop everything to clear file locks.
-            store.close_replica(namespace.id());
-            // flush the store to disk
-            store.flush()?;
-            drop(store);
-            expected
-        };
-        assert_eq!(expected.len(), 2);
+            assert_eq!(batched.len(), keys.len());
+            for (batched, single) in batched.iter().zip(&single) {
+                assert_eq!(
+                    batched.as_ref().map(|e| e.entry()),
+                    single.as_ref().map(|e| e.entry())
+                );
+                assert_eq!(
+                    batched.as_ref().and_then(|e| e.content_chunks()),
+                    single.as_ref().and_then(|e| e.content_chunks())
+                );
+            }
+            let found = batched.iter().map(Option::is_some).collect::<Vec<_>>();
+            assert_eq!(
+                found,
+                [true, false, true, include_empty, false, true, true, false]
+            );
+        }
+        Ok(())
+    }
 
-        // create a copy of our db file with the latest table deleted.
-        let dbfile_before_migration = copy_and_modify(dbfile.path(), |tx| {
-            tx.delete_table(LATEST_PER_AUTHOR_TABLE)?;
-            Ok(())
-        })?;
+    #[test]
+    fn test_first_last() -> Result<()> {
+        let mut store = Store::memory();
+        let alice = store.new_author(&mut rand::thread_rng())?;
+        let bob = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        for key in ["a/1", "a/2", "b/1", "c"] {
+            replica.hash_and_insert(key, &alice, key)?;
+        }
+        for key in ["a/2", "b/1", "b/2", "d"] {
+            replica.hash_and_insert(key, &bob, key)?;
+        }
+        replica.delete_prefix("d", &bob)?;
+        drop(replica);
 
-        // open the copied db file, which will run the migration.
-        let mut store = Store::persistent(dbfile_before_migration.path())?;
-        let actual = store
-            .get_latest_for_each_author(namespace.id())?
-            .collect::<Result<Vec<_>>>()?;
+        let queries: Vec<Query> = vec![
+            Query::all().build(),
+            Query::all().include_empty().build(),
+            Query::author(bob.id()).build(),
+            Query::key_prefix("b").build(),
+            Query::all()
+                .sort_by(crate::store::SortBy::KeyAuthor, SortDirection::Desc)
+                .build(),
+            Query::all().since_local_seq(2).build(),
+            Query::all().offset(2).build(),
+            Query::all().limit(3).offset(1).build(),
+            Query::single_latest_per_key().build(),
+            Query::single_latest_per_key()
+                .sort_direction(SortDirection::Desc)
+                .include_empty()
+                .build(),
+            Query::key_prefix("x").build(),
+        ];
+        for query in queries {
+            let all = store
+                .get_many(namespace.id(), query.clone())?
+                .collect::<Result<Vec<_>>>()?;
+            let first = store.first(namespace.id(), query.clone())?;
+            let last = store.last(namespace.id(), query.clone())?;
+            assert_eq!(first.as_ref(), all.first(), "first of {query:?}");
+            assert_eq!(last.as_ref(), all.last(), "last of {query:?}");
+        }
+        Ok(())
+    }
 
-        assert_eq!(expected, actual);
+    #[test]
+    fn test_list_children() -> Result<()> {
+        let mut store = Store::memory();
+        let alice = store.new_author(&mut rand::thread_rng())?;
+        let bob = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        for key in [
+            "photos/2024/01/a.jpg",
+            "photos/2024/01/b.jpg",
+            "photos/2024/01/raw/a.raw",
+            "photos/2024/02/c.jpg",
+            "photos/2024/cover.jpg",
+            "photos/2024/trash/x.jpg",
+            "photos/2025/d.jpg",
+            "readme",
+        ] {
+            replica.hash_and_insert(key, &alice, key)?;
+        }
+        // the same key of a second author, and a prefix with only deleted entries
+        replica.hash_and_insert("photos/2024/cover.jpg", &bob, "cover")?;
+        replica.hash_and_insert("photos/2024/03/e.jpg", &bob, "e")?;
+        replica.delete_prefix("photos/2024/03", &bob)?;
+        replica.delete_prefix("photos/2024/trash/", &alice)?;
+        replica.hash_and_insert("photos/2024/trash/x.jpg", &bob, "x")?;
+        drop(replica);
+
+        let list = |store: &mut Store, prefix: &str, delimiter: u8| {
+            store.list_children(namespace.id(), prefix, delimiter)
+        };
+        let prefix = |p: &'static str| ChildEntry::Prefix(Bytes::from(p));
+        let key = |k: &'static str| ChildEntry::Key(Bytes::from(k));
+
+        assert_eq!(
+            list(&mut store, "photos/2024/", b'/')?,
+            [
+                prefix("photos/2024/01/"),
+                prefix("photos/2024/02/"),
+                key("photos/2024/cover.jpg"),
+                prefix("photos/2024/trash/"),
+            ]
+        );
+        assert_eq!(
+            list(&mut store, "", b'/')?,
+            [prefix("photos/"), key("readme")]
+        );
+        assert_eq!(
+            list(&mut store, "photos/", b'/')?,
+            [prefix("photos/2024/"), prefix("photos/2025/")]
+        );
+        assert_eq!(
+            list(&mut store, "photos/2024/01/", b'/')?,
+            [
+                key("photos/2024/01/a.jpg"),
+                key("photos/2024/01/b.jpg"),
+                prefix("photos/2024/01/raw/"),
+            ]
+        );
+        // the prefix is matched on bytes, not on segments
+        assert_eq!(
+            list(&mut store, "photos/202", b'/')?,
+            [prefix("photos/2024/"), prefix("photos/2025/")]
+        );
+        assert_eq!(
+            list(&mut store, "photos/2024/01/", b'.')?,
+            [
+                prefix("photos/2024/01/a."),
+                prefix("photos/2024/01/b."),
+                prefix("photos/2024/01/raw/a."),
+            ]
+        );
+        assert!(list(&mut store, "videos/", b'/')?.is_empty());
+        Ok(())
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_store_latency_metrics() -> Result<()> {
+        use iroh_metrics::core::{Core, Metric};
+
+        use crate::{metrics::Metrics, ranger::Store as _};
+
+        Core::try_init(|registry, metrics| {
+            metrics.insert(Metrics::new(registry));
+        })
+        .ok();
+        let core = Core::get().expect("metrics are initialized");
+        let count = |name: &str| {
+            let metrics = iroh_metrics::parse_prometheus_metrics(&core.encode());
+            metrics
+                .get(&format!("iroh_docs_{name}_count"))
+                .copied()
+                .unwrap_or_default()
+        };
+
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.hash_and_insert("a", &author, "a")?;
+        drop(replica);
+        store.get_exact(namespace.id(), author.id(), "a", false)?;
+        let mut instance = StoreInstance::new(namespace.id(), &mut store);
+        let range = Range::new(RecordIdentifier::default(), RecordIdentifier::default());
+        instance.get_range(range.clone())?.for_each(drop);
+        instance.get_fingerprint(&range)?;
+
+        for name in [
+            "store_put_latency",
+            "store_get_exact_latency",
+            "store_get_range_latency",
+            "store_get_fingerprint_latency",
+        ] {
+            assert!(count(name) >= 1.0, "no observation for {name}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_iter_all_entries() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace1 = NamespaceSecret::new(&mut rand::thread_rng());
+        let namespace2 = NamespaceSecret::new(&mut rand::thread_rng());
+
+        let mut replica = store.new_replica(namespace1.clone())?;
+        replica.hash_and_insert(b"a", &author, b"v1")?;
+        replica.hash_and_insert(b"b", &author, b"v2")?;
+        let mut replica = store.new_replica(namespace2.clone())?;
+        replica.hash_and_insert(b"c/1", &author, b"v3")?;
+        replica.delete_prefix(b"c", &author)?;
+
+        let mut entries = store
+            .iter_all_entries()?
+            .map(|res| res.map(|(namespace, entry)| (namespace, entry.key().to_vec())))
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort();
+        let mut expected = vec![
+            (namespace1.id(), b"a".to_vec()),
+            (namespace1.id(), b"b".to_vec()),
+            (namespace2.id(), b"c".to_vec()),
+        ];
+        expected.sort();
+        assert_eq!(entries, expected);
+
+        let tombstones = store
+            .iter_all_entries()?
+            .filter(|res| matches!(res, Ok((_, entry)) if entry.is_empty()))
+            .count();
+        assert_eq!(tombstones, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebuild_namespace() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let other = NamespaceSecret::new(&mut rand::thread_rng());
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.hash_and_insert(b"a", &author, b"v1")?;
+        replica.hash_and_insert(b"b", &author, b"v2")?;
+        replica.hash_and_insert(b"c/1", &author, b"v3")?;
+        replica.hash_and_insert(b"c/2", &author, b"v4")?;
+        store.close_replica(namespace.id());
+        // keep the versions that get superseded below in the backup
+        let mut backup = store
+            .iter_all_entries()?
+            .map(|res| res.map(|(_namespace, entry)| entry))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert(b"a", &author, b"v5")?;
+        replica.delete_prefix(b"c", &author)?;
+        store.close_replica(namespace.id());
+        let mut replica = store.new_replica(other.clone())?;
+        replica.hash_and_insert(b"d", &author, b"v6")?;
+        store.close_replica(other.id());
+        backup.extend(
+            store
+                .iter_all_entries()?
+                .map(|res| res.map(|(_namespace, entry)| entry))
+                .collect::<Result<Vec<_>>>()?,
+        );
+        // an entry with an invalid signature
+        let id = RecordIdentifier::new(namespace.id(), author.id(), b"e");
+        let record = Record::new_current(Hash::new(b"v7"), 2);
+        let entry = Entry::new(id, record).sign(&other, &author);
+        backup.push(entry);
+        backup.reverse();
+
+        let keys = |store: &mut Store| -> Result<Vec<(Vec<u8>, Hash)>> {
+            let mut keys = store
+                .iter_all_entries()?
+                .filter(|res| matches!(res, Ok((ns, _)) if *ns == namespace.id()))
+                .map(|res| res.map(|(_ns, entry)| (entry.key().to_vec(), entry.content_hash())))
+                .collect::<Result<Vec<_>>>()?;
+            keys.sort();
+            Ok(keys)
+        };
+        let expected = keys(&mut store)?;
+        assert_eq!(expected.len(), 3);
+
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert(b"f", &author, b"v8")?;
+        assert!(store
+            .rebuild_namespace(namespace.clone(), backup.clone())
+            .is_err());
+        store.close_replica(namespace.id());
+
+        let stats = store.rebuild_namespace(namespace.clone(), backup)?;
+        assert_eq!(keys(&mut store)?, expected);
+        // the entry of the other namespace and the badly signed entry
+        assert_eq!(stats.invalid, 2);
+        assert_eq!(stats.inserted - stats.removed, expected.len());
+        assert_eq!(
+            stats.inserted + stats.not_inserted + stats.invalid,
+            // 4 initial entries, 3 after the changes, 1 of the other namespace, 1 invalid
+            9
+        );
+        // other namespaces are untouched
+        assert!(store
+            .get_exact(other.id(), author.id(), b"d", false)?
+            .is_some());
+        // the rebuilt replica can be opened and written to
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert(b"g", &author, b"v9")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive_roundtrip() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let mut store = Store::memory();
+        let alice = store.new_author(&mut rng)?;
+        let bob = store.new_author(&mut rng)?;
+        let namespace = NamespaceSecret::new(&mut rng);
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.hash_and_insert(b"a", &alice, b"v1")?;
+        replica.hash_and_insert(b"b/1", &alice, b"v2")?;
+        replica.hash_and_insert(b"a", &bob, b"v3")?;
+        replica.delete_prefix(b"b", &alice)?;
+        replica.counter_add(b"visits", &alice, 2)?;
+        replica.set_namespace_meta(&bob, Bytes::from_static(b"title"))?;
+        store.close_replica(namespace.id());
+
+        // all entries, including those under reserved keys
+        let entries = |store: &mut Store| -> Result<Vec<SignedEntry>> {
+            store
+                .iter_all_entries()?
+                .filter_map(|entry| match entry {
+                    Ok((ns, entry)) => (ns == namespace.id()).then_some(Ok(entry)),
+                    Err(err) => Some(Err(err)),
+                })
+                .collect()
+        };
+        let expected = entries(&mut store)?;
+        // the tombstones are part of the export
+        assert_eq!(expected.len(), 6);
+        assert!(expected.iter().any(|entry| entry.is_empty()));
+
+        let mut archive = Vec::new();
+        store.export_archive(namespace.id(), &mut archive)?;
+        assert_eq!(&archive[..ARCHIVE_MAGIC.len()], &ARCHIVE_MAGIC);
+
+        let mut other = Store::memory();
+        let id = other.import_archive(&archive[..])?;
+        assert_eq!(id, namespace.id());
+        assert_eq!(entries(&mut other)?, expected);
+        assert_eq!(other.get_counter(id, b"visits")?, 2);
+        // the metadata entry is imported, the bytes follow with its blob
+        assert_eq!(other.put_namespace_meta(id, b"title")?, 1);
+        assert_eq!(
+            other.get_namespace_meta(id)?,
+            Some(Bytes::from_static(b"title"))
+        );
+        let kind = other
+            .list_namespaces()?
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .find(|(ns, _)| *ns == id)
+            .map(|(_, kind)| kind);
+        assert!(matches!(kind, Some(CapabilityKind::Read)));
+
+        // importing into a store with write access merges the entries
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert(b"c", &alice, b"v4")?;
+        store.close_replica(namespace.id());
+        let mut newer = Vec::new();
+        store.export_archive(namespace.id(), &mut newer)?;
+        other.import_archive(&newer[..])?;
+        assert_eq!(entries(&mut other)?, entries(&mut store)?);
+
+        // corrupt archives are rejected
+        assert!(other.import_archive(&b"not an archive"[..]).is_err());
+        assert!(other.import_archive(&archive[..archive.len() - 1]).is_err());
+        let mut bad_version = archive.clone();
+        bad_version[ARCHIVE_MAGIC.len()] = ARCHIVE_VERSION + 1;
+        assert!(other.import_archive(&bad_version[..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_content_lengths() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+
+        let data = b"hello world";
+        let hash = replica.hash_and_insert(b"good", &author, data)?;
+        replica.insert(b"bad", &author, hash, 3)?;
+        let unknown = Hash::new(b"unknown");
+        replica.insert(b"unknown", &author, unknown, 7)?;
+        replica.hash_and_insert(b"deleted/1", &author, data)?;
+        replica.delete_prefix(b"deleted", &author)?;
+
+        let mismatched = store
+            .verify_content_lengths(namespace.id(), |h| (h == hash).then_some(data.len() as u64))?;
+        assert_eq!(
+            mismatched,
+            vec![RecordIdentifier::new(namespace.id(), author.id(), b"bad")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_author_fingerprint() -> Result<()> {
+        let mut store = Store::memory();
+        let alice = store.new_author(&mut rand::thread_rng())?;
+        let bob = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.hash_and_insert(b"a/1", &alice, b"one")?;
+        replica.hash_and_insert(b"b/1", &bob, b"one")?;
+        store.close_replica(namespace.id());
+
+        let empty =
+            store.author_fingerprint(namespace.id(), Author::new(&mut rand::thread_rng()).id())?;
+        assert_eq!(empty, Fingerprint::empty());
+        let alice_fp = store.author_fingerprint(namespace.id(), alice.id())?;
+        let bob_fp = store.author_fingerprint(namespace.id(), bob.id())?;
+        assert_ne!(alice_fp, Fingerprint::empty());
+        assert_ne!(alice_fp, bob_fp);
+
+        // changing bob's entries leaves alice's fingerprint untouched
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert(b"b/2", &bob, b"two")?;
+        store.close_replica(namespace.id());
+        assert_eq!(
+            store.author_fingerprint(namespace.id(), alice.id())?,
+            alice_fp
+        );
+        let bob_fp2 = store.author_fingerprint(namespace.id(), bob.id())?;
+        assert_ne!(bob_fp2, bob_fp);
+
+        // and updating one of alice's entries only changes her fingerprint
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert(b"a/1", &alice, b"changed")?;
+        store.close_replica(namespace.id());
+        let alice_fp2 = store.author_fingerprint(namespace.id(), alice.id())?;
+        assert_ne!(alice_fp2, alice_fp);
+        assert_eq!(store.author_fingerprint(namespace.id(), bob.id())?, bob_fp2);
+        Ok(())
+    }
+
+    #[test_strategy::proptest(cases = 32)]
+    fn test_fingerprints_after_prefix_removals(
+        #[strategy(proptest::collection::vec((proptest::bool::ANY, "[ab]{1,3}", 0..2usize), 1..24))]
+        ops: Vec<(bool, String, usize)>,
+    ) {
+        let namespace = NamespaceSecret::from_bytes(&[1u8; 32]);
+        let authors = [
+            Author::from_bytes(&[2u8; 32]),
+            Author::from_bytes(&[3u8; 32]),
+        ];
+        let id =
+            |author: usize, key: &str| RecordIdentifier::new(namespace.id(), &authors[author], key);
+        let ranges = [
+            Range::new(id(0, ""), id(0, "")),
+            Range::new(id(0, ""), id(0, "b")),
+            Range::new(id(0, "ab"), id(1, "ab")),
+            Range::new(id(1, "b"), id(0, "ab")),
+        ];
+
+        let mut store = Store::memory();
+        let mut replica = store.new_replica(namespace.clone()).unwrap();
+        for (delete, key, author) in ops {
+            if delete {
+                replica.delete_prefix(&key, &authors[author]).unwrap();
+            } else {
+                replica
+                    .hash_and_insert(&key, &authors[author], &key)
+                    .unwrap();
+            }
+
+            // compare the fingerprints of the store with fingerprints computed from scratch
+            let entries = replica
+                .store
+                .store
+                .get_many(namespace.id(), Query::all().include_empty())
+                .unwrap()
+                .collect::<Result<Vec<_>>>()
+                .unwrap();
+            for range in &ranges {
+                let expected = entries
+                    .iter()
+                    .filter(|entry| range.contains(entry.id()))
+                    .fold(Fingerprint::empty(), |mut fp, entry| {
+                        fp ^= entry.as_fingerprint();
+                        fp
+                    });
+                let actual = replica.store.get_fingerprint(range).unwrap();
+                proptest::prop_assert_eq!(actual, expected, "range {:?}", range);
+            }
+        }
+    }
+
+    #[test]
+    fn test_range_fingerprints() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+
+        let buckets = store.range_fingerprints(namespace.id(), 16)?;
+        assert_eq!(buckets.len(), 1);
+        assert!(buckets[0].0.is_all());
+        assert_eq!(buckets[0].1, Fingerprint::empty());
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        for i in 0..100 {
+            replica.hash_and_insert(format!("key/{i}"), &author, b"value")?;
+        }
+        store.close_replica(namespace.id());
+
+        let mut wrapper = StoreInstance::new(namespace.id(), &mut store);
+        let first = wrapper.get_first()?;
+        let all = wrapper.get_fingerprint(&Range::new(first.clone(), first))?;
+        assert_ne!(all, Fingerprint::empty());
+
+        let buckets = store.range_fingerprints(namespace.id(), 16)?;
+        assert_eq!(buckets.len(), 16);
+        let mut combined = Fingerprint::empty();
+        for (range, fp) in &buckets {
+            combined ^= *fp;
+            // each bucket matches the fingerprint the sync protocol computes for its range
+            let mut wrapper = StoreInstance::new(namespace.id(), &mut store);
+            assert_eq!(wrapper.get_fingerprint(range)?, *fp);
+            let len = wrapper.get_range_len(range.clone())?;
+            assert!((6..=7).contains(&len), "bucket with {len} entries");
+        }
+        assert_eq!(combined, all);
+
+        // at most one bucket per entry
+        let buckets = store.range_fingerprints(namespace.id(), 1000)?;
+        assert_eq!(buckets.len(), 100);
+        assert!(store.range_fingerprints(namespace.id(), 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_notify_content_status() -> Result<()> {
+        let mut store = Store::memory();
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng()).id();
+        let hash = Hash::new(b"hello");
+        let (tx, rx) = async_channel::unbounded();
+        store.subscribe_all(tx.clone());
+
+        assert_eq!(store.content_status(namespace, hash), None);
+        assert!(store.notify_content_status(namespace, hash, ContentStatus::Missing));
+        assert!(store.notify_content_status(namespace, hash, ContentStatus::Complete));
+        // an unchanged status emits no event
+        assert!(!store.notify_content_status(namespace, hash, ContentStatus::Complete));
+        assert_eq!(
+            store.content_status(namespace, hash),
+            Some(ContentStatus::Complete)
+        );
+
+        let mut statuses = vec![];
+        while let Ok((ns, event)) = rx.try_recv() {
+            assert_eq!(ns, namespace);
+            match event {
+                Event::ContentStatusChanged {
+                    namespace: ev_ns,
+                    hash: ev_hash,
+                    status,
+                } => {
+                    assert_eq!(ev_ns, namespace);
+                    assert_eq!(ev_hash, hash);
+                    statuses.push(status);
+                }
+                event => panic!("unexpected event {event:?}"),
+            }
+        }
+        assert_eq!(
+            statuses,
+            vec![ContentStatus::Missing, ContentStatus::Complete]
+        );
+
+        // the least recently notified statuses are forgotten once the cache is full
+        store.unsubscribe_all(&tx);
+        for i in 0..CONTENT_STATUS_CAPACITY.get() {
+            let other = Hash::new(i.to_le_bytes());
+            store.notify_content_status(namespace, other, ContentStatus::Complete);
+        }
+        assert_eq!(store.content_status(namespace, hash), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_range() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let other = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        for key in ["a", "b", "c", "d", "e"] {
+            replica.hash_and_insert(key, &author, key)?;
+        }
+        drop(replica);
+        let mut replica = store.new_replica(other.clone())?;
+        replica.hash_and_insert("c", &author, "other")?;
+        drop(replica);
+
+        let id = |key: &str| RecordIdentifier::new(namespace.id(), author.id(), key);
+        let keys = |store: &mut Store, range| -> Result<Vec<Vec<u8>>> {
+            store
+                .get_range(namespace.id(), range)?
+                .map(|entry| entry.map(|entry| entry.key().to_vec()))
+                .collect()
+        };
+
+        // range in the middle of the keyspace
+        let range = Range::new(id("b"), id("d"));
+        assert_eq!(keys(&mut store, range)?, vec![b"b".to_vec(), b"c".to_vec()]);
+        // wrap-around range
+        let range = Range::new(id("d"), id("b"));
+        assert_eq!(
+            keys(&mut store, range)?,
+            vec![b"a".to_vec(), b"d".to_vec(), b"e".to_vec()]
+        );
+        // identity range contains the whole namespace
+        let range = Range::new(id("c"), id("c"));
+        assert_eq!(keys(&mut store, range)?.len(), 5);
+
+        // ranges of other namespaces are rejected
+        let range = Range::new(id("a"), id("c"));
+        assert!(store.get_range(other.id(), range).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_key_range() -> Result<()> {
+        let mut store = Store::memory();
+        let alice = store.new_author(&mut rand::thread_rng())?;
+        let bob = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        for key in ["l", "m", "m/1", "n", "p", "q", "r"] {
+            replica.hash_and_insert(key, &alice, key)?;
+            replica.hash_and_insert(key, &bob, key)?;
+        }
+        drop(replica);
+
+        let keys = |store: &mut Store, query: Query| -> Result<Vec<Vec<u8>>> {
+            store
+                .get_many(namespace.id(), query)?
+                .map(|entry| entry.map(|entry| entry.key().to_vec()))
+                .collect()
+        };
+        let expected = |keys: &[&str]| {
+            keys.iter()
+                .map(|key| key.as_bytes().to_vec())
+                .collect::<Vec<_>>()
+        };
+
+        // start is inclusive, end is exclusive, all authors are included
+        let query =
+            Query::key_range("m", "q").sort_by(crate::store::SortBy::KeyAuthor, SortDirection::Asc);
+        assert_eq!(
+            keys(&mut store, query.build())?,
+            expected(&["m", "m", "m/1", "m/1", "n", "n", "p", "p"])
+        );
+        let query = Query::key_range("m", "q");
+        assert_eq!(keys(&mut store, query.build())?.len(), 8);
+        let query = Query::single_latest_per_key().key_range("m", "q");
+        assert_eq!(
+            keys(&mut store, query.build())?,
+            expected(&["m", "m/1", "n", "p"])
+        );
+
+        // composes with an author filter
+        let query = Query::key_range("m", "q").author(alice.id());
+        assert_eq!(
+            keys(&mut store, query.build())?,
+            expected(&["m", "m/1", "n", "p"])
+        );
+        let query = Query::key_range("m", "q")
+            .author(bob.id())
+            .sort_by(crate::store::SortBy::KeyAuthor, SortDirection::Desc);
+        assert_eq!(
+            keys(&mut store, query.build())?,
+            expected(&["p", "n", "m/1", "m"])
+        );
+
+        // empty and inverted ranges yield nothing
+        assert!(keys(&mut store, Query::key_range("m", "m").build())?.is_empty());
+        assert!(keys(&mut store, Query::key_range("q", "m").build())?.is_empty());
+        let query = Query::key_range("q", "m").author(alice.id());
+        assert!(keys(&mut store, query.build())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_many_grouped_by_author() -> Result<()> {
+        let mut store = Store::memory();
+        let mut authors = (0..3)
+            .map(|_| store.new_author(&mut rand::thread_rng()))
+            .collect::<Result<Vec<_>>>()?;
+        authors.sort_by_key(|author| author.id());
+        let [a, b, c] = &authors[..] else {
+            unreachable!()
+        };
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        for key in ["x/2", "x/1", "y/1"] {
+            replica.hash_and_insert(key, a, key)?;
+            replica.hash_and_insert(key, c, key)?;
+        }
+        replica.hash_and_insert("y/2", b, "y/2")?;
+        drop(replica);
+
+        let grouped = |store: &mut Store, query: Query| -> Result<Vec<(AuthorId, Vec<String>)>> {
+            store
+                .get_many_grouped_by_author(namespace.id(), query)?
+                .map(|group| {
+                    let (author, entries) = group?;
+                    let keys = entries
+                        .map(|entry| {
+                            let entry = entry?;
+                            assert_eq!(entry.author(), author);
+                            Ok(String::from_utf8(entry.key().to_vec())?)
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    Ok((author, keys))
+                })
+                .collect()
+        };
+        let keys = |keys: &[&str]| keys.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            grouped(&mut store, Query::all().build())?,
+            vec![
+                (a.id(), keys(&["x/1", "x/2", "y/1"])),
+                (b.id(), keys(&["y/2"])),
+                (c.id(), keys(&["x/1", "x/2", "y/1"])),
+            ]
+        );
+
+        // the key filter applies within each group, authors without matches are skipped
+        let query =
+            Query::key_prefix("x/").sort_by(crate::store::SortBy::AuthorKey, SortDirection::Desc);
+        assert_eq!(
+            grouped(&mut store, query.build())?,
+            vec![
+                (c.id(), keys(&["x/2", "x/1"])),
+                (a.id(), keys(&["x/2", "x/1"])),
+            ]
+        );
+
+        // limits apply per group, author filters select the groups
+        let query = Query::all().limit(1).exclude_author(a.id());
+        assert_eq!(
+            grouped(&mut store, query.build())?,
+            vec![(b.id(), keys(&["y/2"])), (c.id(), keys(&["x/1"]))]
+        );
+
+        // all groups are read from the same snapshot
+        let mut groups = store.get_many_grouped_by_author(namespace.id(), Query::author(b.id()))?;
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert("z", b, "z")?;
+        drop(replica);
+        let (author, entries) = groups.next().unwrap()?;
+        assert_eq!(author, b.id());
+        assert_eq!(entries.count(), 1);
+        assert!(groups.next().is_none());
+
+        // latest-per-key queries select across authors, so they cannot be grouped
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert("x/1", b, "x/1")?;
+        drop(replica);
+        let latest = store
+            .get_many(
+                namespace.id(),
+                Query::single_latest_per_key().key_prefix("x/"),
+            )?
+            .map(|entry| entry.map(|entry| (entry.author(), entry.key().to_vec())))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            latest,
+            vec![(b.id(), b"x/1".to_vec()), (c.id(), b"x/2".to_vec())]
+        );
+        assert!(store
+            .get_many_grouped_by_author(namespace.id(), Query::single_latest_per_key())
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_exclude_author() -> Result<()> {
+        let mut store = Store::memory();
+        let mut authors = (0..3)
+            .map(|_| store.new_author(&mut rand::thread_rng()))
+            .collect::<Result<Vec<_>>>()?;
+        authors.sort_by_key(|author| author.id());
+        let [a, b, c] = &authors[..] else {
+            unreachable!()
+        };
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        for key in ["x/1", "x/2", "y/1"] {
+            for author in [a, b, c] {
+                replica.hash_and_insert(key, author, key)?;
+            }
+        }
+        drop(replica);
+
+        let entries = |store: &mut Store, query: Query| -> Result<Vec<(AuthorId, String)>> {
+            store
+                .get_many(namespace.id(), query)?
+                .map(|entry| {
+                    entry.map(|entry| {
+                        let key = String::from_utf8(entry.key().to_vec()).unwrap();
+                        (entry.author(), key)
+                    })
+                })
+                .collect()
+        };
+
+        // sorted by author, then key
+        let query = Query::exclude_author(b.id());
+        assert_eq!(
+            entries(&mut store, query.build())?,
+            vec![
+                (a.id(), "x/1".to_string()),
+                (a.id(), "x/2".to_string()),
+                (a.id(), "y/1".to_string()),
+                (c.id(), "x/1".to_string()),
+                (c.id(), "x/2".to_string()),
+                (c.id(), "y/1".to_string()),
+            ]
+        );
+
+        // sorted by key, then author, composed with a key prefix
+        let query = Query::key_prefix("x/")
+            .exclude_author(a.id())
+            .sort_by(crate::store::SortBy::KeyAuthor, SortDirection::Desc);
+        assert_eq!(
+            entries(&mut store, query.build())?,
+            vec![
+                (c.id(), "x/2".to_string()),
+                (b.id(), "x/2".to_string()),
+                (c.id(), "x/1".to_string()),
+                (b.id(), "x/1".to_string()),
+            ]
+        );
+
+        // several authors, and new entries since a local sequence number
+        let query = Query::all().exclude_authors([a.id(), c.id()]);
+        let expected = ["x/1", "x/2", "y/1"].map(|key| (b.id(), key.to_string()));
+        assert_eq!(entries(&mut store, query.build())?, expected);
+        let query = Query::since_local_seq(0).exclude_authors([a.id(), c.id()]);
+        assert_eq!(entries(&mut store, query.build())?, expected);
+
+        // an author filter replaces the exclusion
+        let query = Query::exclude_author(a.id()).author(a.id());
+        assert_eq!(entries(&mut store, query.build())?.len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_projected() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        for i in 0..5u8 {
+            let key = format!("key/{i}");
+            replica.hash_and_insert(&key, &author, [i])?;
+        }
+        drop(replica);
+
+        let full = store
+            .get_many(namespace.id(), Query::all())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(full.len(), 5);
+
+        let projected = store
+            .get_projected(namespace.id(), Query::all(), Projection::key_and_hash())?
+            .collect::<Result<Vec<_>>>()?;
+        let expected = full
+            .iter()
+            .map(|entry| ProjectedEntry {
+                key: Some(entry.id().key_bytes()),
+                content_hash: Some(entry.content_hash()),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(projected, expected);
+
+        let projected = store
+            .get_projected(namespace.id(), Query::all(), Projection::all())?
+            .collect::<Result<Vec<_>>>()?;
+        for (projected, entry) in projected.into_iter().zip(full.iter()) {
+            assert_eq!(projected.author, Some(entry.author_bytes()));
+            assert_eq!(projected.key.as_deref(), Some(entry.key()));
+            assert_eq!(projected.content_hash, Some(entry.content_hash()));
+            assert_eq!(projected.content_len, Some(entry.content_len()));
+            assert_eq!(projected.timestamp, Some(entry.timestamp()));
+        }
+
+        // the query is applied before the projection
+        let projected = store
+            .get_projected(
+                namespace.id(),
+                Query::all().key_prefix("key/3"),
+                Projection::key_and_timestamp(),
+            )?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(projected.len(), 1);
+        assert_eq!(projected[0].key.as_deref(), Some(&b"key/3"[..]));
+        assert_eq!(projected[0].timestamp, Some(full[3].timestamp()));
+        assert_eq!(projected[0].author, None);
+
+        // all ranges and filters of queries apply to projections
+        let queries: [Query; 4] = [
+            Query::content_hash(full[2].content_hash()).build(),
+            Query::single_latest_per_key().key_prefix("key/2").build(),
+            Query::all()
+                .sort_by(crate::store::SortBy::KeyAuthor, SortDirection::Desc)
+                .build(),
+            Query::since_local_seq(2).build(),
+        ];
+        for query in queries {
+            let expected = store
+                .get_many(namespace.id(), query.clone())?
+                .map(|entry| entry.map(|entry| Projection::all().project(&entry)))
+                .collect::<Result<Vec<_>>>()?;
+            assert!(!expected.is_empty());
+            let projected = store
+                .get_projected(namespace.id(), query, Projection::all())?
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(projected, expected);
+        }
+
+        let projected = store
+            .get_projected(namespace.id(), Query::all(), Projection::default())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(projected, vec![ProjectedEntry::default(); 5]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_timestamp_summary() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let now = 1_000_000_000;
+        let hash = Hash::new(b"hello");
+
+        let summary = store.timestamp_summary(namespace.id(), now)?;
+        assert_eq!(summary, TimestampSummary::default());
+
+        // insert entries directly, as the replica rejects timestamps too far in the future
+        let mut wrapper = StoreInstance::new(namespace.id(), &mut store);
+        for (key, timestamp) in [
+            ("past/1", now - 500),
+            ("past/2", now - 20),
+            ("now", now),
+            ("future/1", now + 30),
+            ("future/2", now + 7_000),
+        ] {
+            let record = Record::new(hash, 5, timestamp);
+            let entry = SignedEntry::from_parts(&namespace, &author, key, record);
+            wrapper.entry_put(entry)?;
+        }
+
+        let summary = store.timestamp_summary(namespace.id(), now)?;
+        assert_eq!(
+            summary,
+            TimestampSummary {
+                count: 5,
+                min: Some(now - 500),
+                max: Some(now + 7_000),
+                count_in_future: 2,
+                max_future_delta: 7_000,
+            }
+        );
+
+        let summary = store.timestamp_summary(namespace.id(), now + 10_000)?;
+        assert_eq!(summary.count_in_future, 0);
+        assert_eq!(summary.max_future_delta, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_replace_author_key_store() -> Result<()> {
+        #[derive(Debug, Clone, Default)]
+        struct RecordingKeyStore {
+            lookups: Arc<std::sync::Mutex<Vec<[u8; 32]>>>,
+        }
+
+        impl PublicKeyStore for RecordingKeyStore {
+            fn public_key(&self, id: &[u8; 32]) -> Result<VerifyingKey, SignatureError> {
+                self.lookups.lock().unwrap().push(*id);
+                VerifyingKey::from_bytes(id)
+            }
+        }
+
+        let mut store = Store::memory();
+        let key_store = RecordingKeyStore::default();
+        store.replace_author_key_store(key_store.clone());
+
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let entry = SignedEntry::from_parts(
+            &namespace,
+            &author,
+            "key",
+            Record::new_current(Hash::new(b"hello"), 5),
+        );
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.insert_remote_entry(entry, [1u8; 32], ContentStatus::Complete)?;
+        drop(replica);
+
+        let lookups = key_store.lookups.lock().unwrap().clone();
+        assert!(lookups.contains(namespace.id().as_bytes()));
+        assert!(lookups.contains(author.id().as_bytes()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_author() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let mut store = Store::persistent(dbfile.path())?;
+        assert_eq!(store.get_default_author()?, None);
+
+        // unknown authors can not be the default
+        let unknown = Author::new(&mut rand::thread_rng()).id();
+        assert!(store.set_default_author(unknown).is_err());
+        assert_eq!(store.get_default_author()?, None);
+
+        let author = store.new_author(&mut rand::thread_rng())?;
+        store.set_default_author(author.id())?;
+        assert_eq!(store.get_default_author()?, Some(author.id()));
+        drop(store);
+
+        // the default author survives reopening the store
+        let mut store = Store::persistent(dbfile.path())?;
+        assert_eq!(store.get_default_author()?, Some(author.id()));
+
+        // deleting another author keeps the default, deleting the default unsets it
+        let other = store.new_author(&mut rand::thread_rng())?;
+        store.delete_author(other.id())?;
+        assert_eq!(store.get_default_author()?, Some(author.id()));
+        store.delete_author(author.id())?;
+        assert_eq!(store.get_default_author()?, None);
+
+        // the in-memory store keeps the default author as well
+        let mut store = Store::memory();
+        assert_eq!(store.get_default_author()?, None);
+        let author = store.new_author(&mut rand::thread_rng())?;
+        store.set_default_author(author.id())?;
+        assert_eq!(store.get_default_author()?, Some(author.id()));
+        Ok(())
+    }
+
+    /// Storage backend which blocks in `sync_data` while stalled.
+    #[derive(Debug, Default)]
+    struct StallingBackend {
+        inner: InMemoryBackend,
+        stalled: Arc<std::sync::atomic::AtomicBool>,
+    }
+
+    impl StorageBackend for StallingBackend {
+        fn len(&self) -> std::result::Result<u64, io::Error> {
+            self.inner.len()
+        }
+
+        fn read(&self, offset: u64, len: usize) -> std::result::Result<Vec<u8>, io::Error> {
+            self.inner.read(offset, len)
+        }
+
+        fn set_len(&self, len: u64) -> std::result::Result<(), io::Error> {
+            self.inner.set_len(len)
+        }
+
+        fn sync_data(&self, eventual: bool) -> std::result::Result<(), io::Error> {
+            while self.stalled.load(std::sync::atomic::Ordering::SeqCst) {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            self.inner.sync_data(eventual)
+        }
+
+        fn write(&self, offset: u64, data: &[u8]) -> std::result::Result<(), io::Error> {
+            self.inner.write(offset, data)
+        }
+    }
+
+    #[test]
+    fn test_flush_with_timeout() -> Result<()> {
+        let backend = StallingBackend::default();
+        let stalled = backend.stalled.clone();
+        let db = Database::builder().create_with_backend(backend)?;
+        let mut store = Store::new_impl(db)?;
+
+        // flushing without a write transaction returns right away
+        stalled.store(true, std::sync::atomic::Ordering::SeqCst);
+        store.flush_with_timeout(Duration::from_millis(10))?;
+
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let err = store
+            .flush_with_timeout(Duration::from_millis(50))
+            .unwrap_err();
+        assert!(err.is::<FlushTimeout>());
+
+        // while the commit is stuck, writes fail instead of blocking
+        let err = store.new_author(&mut rand::thread_rng()).unwrap_err();
+        assert!(err.is::<FlushTimeout>());
+        let err = store
+            .flush_with_timeout(Duration::from_millis(10))
+            .unwrap_err();
+        assert!(err.is::<FlushTimeout>());
+
+        // once the backend recovers, the stuck commit completes and the store keeps working
+        stalled.store(false, std::sync::atomic::Ordering::SeqCst);
+        store.flush_with_timeout(Duration::from_secs(10))?;
+        let other = store.new_author(&mut rand::thread_rng())?;
+        store.flush_with_timeout(Duration::from_secs(10))?;
+        assert!(store.get_author(&author.id())?.is_some());
+        assert!(store.get_author(&other.id())?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespace_alias() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let mut store = Store::persistent(dbfile.path())?;
+        let notes = NamespaceSecret::new(&mut rand::thread_rng());
+        let todos = NamespaceSecret::new(&mut rand::thread_rng());
+        store.import_namespace(notes.clone().into())?;
+        store.import_namespace(todos.clone().into())?;
+        assert_eq!(store.get_namespace_by_alias("my-notes")?, None);
+
+        store.set_namespace_alias(notes.id(), "my-notes")?;
+        assert_eq!(store.get_namespace_by_alias("my-notes")?, Some(notes.id()));
+        assert_eq!(
+            store.get_namespace_alias(notes.id())?.as_deref(),
+            Some("my-notes")
+        );
+        assert_eq!(store.get_namespace_alias(todos.id())?, None);
+        // setting the same alias again is fine
+        store.set_namespace_alias(notes.id(), "my-notes")?;
+
+        // aliases are unique
+        assert!(store.set_namespace_alias(todos.id(), "my-notes").is_err());
+        assert_eq!(store.get_namespace_by_alias("my-notes")?, Some(notes.id()));
+        // unknown namespaces and empty aliases are rejected
+        let unknown = NamespaceSecret::new(&mut rand::thread_rng());
+        assert!(store.set_namespace_alias(unknown.id(), "unknown").is_err());
+        assert!(store.set_namespace_alias(todos.id(), "").is_err());
+
+        // setting a new alias replaces the previous one
+        store.set_namespace_alias(todos.id(), "todo")?;
+        store.set_namespace_alias(todos.id(), "todos")?;
+        assert_eq!(store.get_namespace_by_alias("todo")?, None);
+        assert_eq!(store.get_namespace_by_alias("todos")?, Some(todos.id()));
+        drop(store);
+
+        // aliases survive reopening the store
+        let mut store = Store::persistent(dbfile.path())?;
+        assert_eq!(store.get_namespace_by_alias("my-notes")?, Some(notes.id()));
+        assert_eq!(store.get_namespace_by_alias("todos")?, Some(todos.id()));
+
+        // removing a namespace frees its alias
+        store.remove_replica(&notes.id())?;
+        assert_eq!(store.get_namespace_by_alias("my-notes")?, None);
+        store.set_namespace_alias(todos.id(), "my-notes")?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_local_seq() -> Result<()> {
+        let mut store = Store::memory();
+        let author = store.new_author(&mut rand::thread_rng())?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.hash_and_insert(b"a", &author, b"1")?;
+        replica.hash_and_insert(b"b", &author, b"2")?;
+
+        let entries = store
+            .get_many(namespace.id(), Query::since_local_seq(0))?
+            .with_local_seq()
+            .map(|res| res.map(|(seq, entry)| (seq, entry.key().to_vec())))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries, vec![(1, b"a".to_vec()), (2, b"b".to_vec())]);
+        let cursor = entries.last().unwrap().0;
+
+        // nothing new since the cursor
+        let entries = store
+            .get_many(namespace.id(), Query::since_local_seq(cursor))?
+            .collect::<Result<Vec<_>>>()?;
+        assert!(entries.is_empty());
+
+        // updating an entry assigns a new sequence number and advances the cursor
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.hash_and_insert(b"a", &author, b"3")?;
+        replica.hash_and_insert(b"c", &author, b"4")?;
+        let entries = store
+            .get_many(namespace.id(), Query::since_local_seq(cursor))?
+            .with_local_seq()
+            .map(|res| res.map(|(seq, entry)| (seq, entry.key().to_vec())))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries, vec![(3, b"a".to_vec()), (4, b"c".to_vec())]);
+        assert_eq!(
+            store.get_local_seq(namespace.id(), author.id(), b"a")?,
+            Some(3)
+        );
+        assert_eq!(
+            store.get_local_seq(namespace.id(), author.id(), b"b")?,
+            Some(2)
+        );
+
+        // filters apply
+        let entries = store
+            .get_many(namespace.id(), Query::since_local_seq(0).key_prefix(b"b"))?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 1);
+
+        // a prefix delete removes the sequence numbers of the deleted entries
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.delete_prefix(b"c", &author)?;
+        let entries = store
+            .get_many(namespace.id(), Query::since_local_seq(0).include_empty())?
+            .with_local_seq()
+            .map(|res| res.map(|(seq, entry)| (seq, entry.key().to_vec())))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(
+            entries,
+            vec![(2, b"b".to_vec()), (3, b"a".to_vec()), (5, b"c".to_vec())]
+        );
+
+        // entries received from other peers get fresh local sequence numbers
+        let mut store2 = Store::memory();
+        let author2 = store2.new_author(&mut rand::thread_rng())?;
+        let mut replica2 = store2.new_replica(namespace.clone())?;
+        replica2.hash_and_insert(b"x", &author2, b"5")?;
+        let mut replica = store.open_replica(&namespace.id())?;
+        let mut remote_entries = store2
+            .get_many(namespace.id(), Query::all())?
+            .collect::<Result<Vec<_>>>()?;
+        let remote_entry = remote_entries.pop().unwrap();
+        replica.insert_remote_entry(remote_entry, [1u8; 32], ContentStatus::Complete)?;
+        assert_eq!(
+            store2.get_local_seq(namespace.id(), author2.id(), b"x")?,
+            Some(1)
+        );
+        let entries = store
+            .get_many(namespace.id(), Query::since_local_seq(5))?
+            .with_local_seq()
+            .map(|res| res.map(|(seq, entry)| (seq, entry.key().to_vec())))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries, vec![(6, b"x".to_vec())]);
+        Ok(())
+    }
+
+    fn copy_and_modify(
+        source: &Path,
+        modify: impl Fn(&redb::WriteTransaction) -> Result<()>,
+    ) -> Result<tempfile::NamedTempFile> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        std::fs::copy(source, dbfile.path())?;
+        let db = Database::create(dbfile.path())?;
+        let write_tx = db.begin_write()?;
+        modify(&write_tx)?;
+        write_tx.commit()?;
+        drop(db);
+        Ok(dbfile)
+    }
+
+    #[test]
+    fn test_migration_001_populate_latest_table() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+
+        // create a store and add some data
+        let expected = {
+            let mut store = Store::persistent(dbfile.path())?;
+            let author1 = store.new_author(&mut rand::thread_rng())?;
+            let author2 = store.new_author(&mut rand::thread_rng())?;
+            let mut replica = store.new_replica(namespace.clone())?;
+            replica.hash_and_insert(b"k1", &author1, b"v1")?;
+            replica.hash_and_insert(b"k2", &author2, b"v1")?;
+            replica.hash_and_insert(b"k3", &author1, b"v1")?;
+
+            let expected = store
+                .get_latest_for_each_author(namespace.id())?
+                .collect::<Result<Vec<_>>>()?;
+            // drop everything to clear file locks.
+            store.close_replica(namespace.id());
+            // flush the store to disk
+            store.flush()?;
+            drop(store);
+            expected
+        };
+        assert_eq!(expected.len(), 2);
+
+        // create a copy of our db file with the latest table deleted.
+        let dbfile_before_migration = copy_and_modify(dbfile.path(), |tx| {
+            tx.delete_table(LATEST_PER_AUTHOR_TABLE)?;
+            Ok(())
+        })?;
+
+        // open the copied db file, which will run the migration.
+        let mut store = Store::persistent(dbfile_before_migration.path())?;
+        let actual = store
+            .get_latest_for_each_author(namespace.id())?
+            .collect::<Result<Vec<_>>>()?;
+
+        assert_eq!(expected, actual);
+        Ok(())
+    }
+
+    #[test]
+    fn test_migration_005_populate_local_seq() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let namespace = NamespaceSecret::new(&mut rand::thread_rng());
+
+        {
+            let mut store = Store::persistent(dbfile.path())?;
+            let author = store.new_author(&mut rand::thread_rng())?;
+            let mut replica = store.new_replica(namespace.clone())?;
+            replica.hash_and_insert(b"k1", &author, b"v1")?;
+            replica.hash_and_insert(b"k2", &author, b"v1")?;
+            store.close_replica(namespace.id());
+            store.flush()?;
+        }
+
+        // create a copy of our db file with the local seq tables deleted.
+        let dbfile_before_migration = copy_and_modify(dbfile.path(), |tx| {
+            tx.delete_table(tables::RECORDS_LOCAL_SEQ_TABLE)?;
+            tx.delete_table(tables::RECORDS_BY_LOCAL_SEQ_TABLE)?;
+            tx.delete_table(tables::NAMESPACE_LOCAL_SEQ_TABLE)?;
+            Ok(())
+        })?;
+
+        // open the copied db file, which will run the migration.
+        let mut store = Store::persistent(dbfile_before_migration.path())?;
+        let seqs = store
+            .get_many(namespace.id(), Query::since_local_seq(0))?
+            .with_local_seq()
+            .map(|res| res.map(|(seq, _entry)| seq))
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(seqs, vec![1, 2]);
         Ok(())
     }
 
@@ -21,6 +21,15 @@ impl RecordsBounds {
             KeyFilter::Any => Bytes::new(),
             KeyFilter::Exact(key) => key,
             KeyFilter::Prefix(prefix) => prefix,
+            KeyFilter::Range { start, end } => {
+                let (ns, author) = (ns.to_bytes(), author.to_bytes());
+                // an empty range for end < start, redb rejects ranges with an end before the start
+                let end = std::cmp::max(start.clone(), end);
+                return Self(
+                    Bound::Included((ns, author, start)),
+                    Bound::Excluded((ns, author, end)),
+                );
+            }
         };
         let author = author.to_bytes();
         let ns = ns.to_bytes();
@@ -125,6 +134,13 @@ impl ByKeyBounds {
                 };
                 Self(start, end)
             }
+            KeyFilter::Range { start, end } => {
+                // an empty range for end < start, redb rejects ranges with an end before the start
+                let end = std::cmp::max(start, end);
+                let start = Bound::Included((ns.to_bytes(), start.clone(), [0u8; 32]));
+                let end = Bound::Excluded((ns.to_bytes(), end.clone(), [0u8; 32]));
+                Self(start, end)
+            }
         }
     }
 
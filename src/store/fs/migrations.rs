@@ -5,8 +5,8 @@ use redb::{Database, ReadableTable, ReadableTableMetadata, TableHandle, WriteTra
 use tracing::{debug, info};
 
 use super::tables::{
-    LATEST_PER_AUTHOR_TABLE, NAMESPACES_TABLE, NAMESPACES_TABLE_V1, RECORDS_BY_KEY_TABLE,
-    RECORDS_TABLE,
+    LATEST_PER_AUTHOR_TABLE, NAMESPACES_TABLE, NAMESPACES_TABLE_V1, NAMESPACE_LOCAL_SEQ_TABLE,
+    RECORDS_BY_KEY_TABLE, RECORDS_BY_LOCAL_SEQ_TABLE, RECORDS_LOCAL_SEQ_TABLE, RECORDS_TABLE,
 };
 use crate::{Capability, NamespaceSecret};
 
@@ -16,6 +16,7 @@ pub fn run_migrations(db: &Database) -> Result<()> {
     run_migration(db, migration_002_namespaces_populate_v2)?;
     run_migration(db, migration_003_namespaces_delete_v1)?;
     run_migration(db, migration_004_populate_by_key_index)?;
+    run_migration(db, migration_005_populate_local_seq)?;
     Ok(())
 }
 
@@ -136,3 +137,31 @@ fn migration_004_populate_by_key_index(tx: &WriteTransaction) -> Result<MigrateO
     }
     Ok(MigrateOutcome::Execute(len))
 }
+
+/// migration 005: assign local sequence numbers to all records (which did not exist before)
+fn migration_005_populate_local_seq(tx: &WriteTransaction) -> Result<MigrateOutcome> {
+    let mut local_seq_table = tx.open_table(RECORDS_LOCAL_SEQ_TABLE)?;
+    let mut by_local_seq_table = tx.open_table(RECORDS_BY_LOCAL_SEQ_TABLE)?;
+    let mut namespace_seq_table = tx.open_table(NAMESPACE_LOCAL_SEQ_TABLE)?;
+    let records_table = tx.open_table(RECORDS_TABLE)?;
+    if !local_seq_table.is_empty()? || records_table.is_empty()? {
+        return Ok(MigrateOutcome::Skip);
+    }
+
+    let iter = records_table.iter()?;
+    let mut seqs: HashMap<[u8; 32], u64> = HashMap::new();
+    let mut len = 0;
+    for next in iter {
+        let next = next?;
+        let (namespace, author, key) = next.0.value();
+        let seq = seqs.entry(*namespace).or_default();
+        *seq += 1;
+        local_seq_table.insert((namespace, author, key), *seq)?;
+        by_local_seq_table.insert((namespace, *seq), (author, key))?;
+        len += 1;
+    }
+    for (namespace, seq) in seqs {
+        namespace_seq_table.insert(&namespace, seq)?;
+    }
+    Ok(MigrateOutcome::Execute(len))
+}
@@ -1,17 +1,24 @@
-use anyhow::Result;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
 use iroh_blobs::Hash;
+use redb::ReadOnlyTable;
 
 use super::{
     bounds::{ByKeyBounds, RecordsBounds},
-    ranges::{RecordsByKeyRange, RecordsRange},
-    RecordsValue,
+    into_entry,
+    ranges::{RecordsByKeyRange, RecordsByLocalSeqRange, RecordsRange},
+    tables::RecordsId,
+    with_content_chunks, RecordsValue,
 };
 use crate::{
     store::{
         fs::tables::ReadOnlyTables,
         util::{IndexKind, LatestPerKeySelector, SelectorRes},
-        AuthorFilter, KeyFilter, Query,
+        AuthorFilter, KeyFilter, ProjectedEntry, Projection, Query, QueryKind, SortDirection,
     },
+    sync::{is_reserved_key, ConflictResolver},
     AuthorId, NamespaceId, SignedEntry,
 };
 
@@ -22,40 +29,63 @@ pub struct QueryIterator {
     query: Query,
     offset: u64,
     count: u64,
+    local_seq_table: ReadOnlyTable<RecordsId<'static>, u64>,
+    content_chunks_table: ReadOnlyTable<RecordsId<'static>, &'static [u8]>,
 }
 
 #[derive(Debug)]
 enum QueryRange {
     AuthorKey {
         range: RecordsRange<'static>,
+        author_filter: AuthorFilter,
         key_filter: KeyFilter,
     },
     KeyAuthor {
         range: RecordsByKeyRange,
         author_filter: AuthorFilter,
-        selector: Option<LatestPerKeySelector>,
+        selector: Option<Box<LatestPerKeySelector>>,
+    },
+    LocalSeq {
+        range: RecordsByLocalSeqRange,
+        author_filter: AuthorFilter,
+        key_filter: KeyFilter,
     },
 }
 
 impl QueryIterator {
-    pub fn new(tables: ReadOnlyTables, namespace: NamespaceId, query: Query) -> Result<Self> {
+    pub fn new(
+        tables: ReadOnlyTables,
+        namespace: NamespaceId,
+        query: Query,
+        resolver: Option<Arc<dyn ConflictResolver>>,
+    ) -> Result<Self> {
         let index_kind = IndexKind::from(&query);
         let range = match index_kind {
             IndexKind::AuthorKey { range, key_filter } => {
-                let (bounds, filter) = match range {
+                let (bounds, author_filter, key_filter) = match range {
                     // single author: both author and key are selected via the range. therefore
-                    // set `filter` to `Any`.
+                    // set the filters to `Any`.
                     AuthorFilter::Exact(author) => (
                         RecordsBounds::author_key(namespace, author, key_filter),
+                        AuthorFilter::Any,
                         KeyFilter::Any,
                     ),
                     // no author set => full table scan with the provided key filter
-                    AuthorFilter::Any => (RecordsBounds::namespace(namespace), key_filter),
+                    AuthorFilter::Any => (
+                        RecordsBounds::namespace(namespace),
+                        AuthorFilter::Any,
+                        key_filter,
+                    ),
+                    // excluded authors => full table scan, skipping the excluded authors
+                    filter @ AuthorFilter::Exclude(_) => {
+                        (RecordsBounds::namespace(namespace), filter, key_filter)
+                    }
                 };
                 let range = RecordsRange::with_bounds_static(&tables.records, bounds)?;
                 QueryRange::AuthorKey {
                     range,
-                    key_filter: filter,
+                    author_filter,
+                    key_filter,
                 }
             }
             IndexKind::KeyAuthor {
@@ -66,13 +96,31 @@ impl QueryIterator {
                 let bounds = ByKeyBounds::new(namespace, &range);
                 let range =
                     RecordsByKeyRange::with_bounds(tables.records_by_key, tables.records, bounds)?;
-                let selector = latest_per_key.then(LatestPerKeySelector::default);
+                let selector =
+                    latest_per_key.then(|| Box::new(LatestPerKeySelector::new(resolver)));
                 QueryRange::KeyAuthor {
                     author_filter,
                     range,
                     selector,
                 }
             }
+            IndexKind::LocalSeq {
+                since,
+                author_filter,
+                key_filter,
+            } => {
+                let range = RecordsByLocalSeqRange::since(
+                    tables.records_by_local_seq,
+                    tables.records,
+                    namespace,
+                    since,
+                )?;
+                QueryRange::LocalSeq {
+                    range,
+                    author_filter,
+                    key_filter,
+                }
+            }
         };
 
         Ok(Self {
@@ -80,40 +128,89 @@ impl QueryIterator {
             query,
             offset: 0,
             count: 0,
+            local_seq_table: tables.records_local_seq,
+            content_chunks_table: tables.content_chunks,
         })
     }
-}
 
-impl Iterator for QueryIterator {
-    type Item = Result<SignedEntry>;
+    /// Yield the local sequence number of each entry together with the entry.
+    ///
+    /// See [`crate::store::Store::get_local_seq`] for details on local sequence numbers.
+    pub fn with_local_seq(self) -> LocalSeqQueryIterator {
+        LocalSeqQueryIterator(self)
+    }
 
-    fn next(&mut self) -> Option<Result<SignedEntry>> {
+    /// Yield only the fields of each entry selected by `projection`.
+    pub fn with_projection(self, projection: Projection) -> ProjectedQueryIterator {
+        ProjectedQueryIterator {
+            inner: self,
+            projection,
+        }
+    }
+}
+
+impl QueryIterator {
+    /// Get the next item of the query, built from the raw record with `map`.
+    ///
+    /// If only the latest entry per key is selected, the entries have to be built to be compared,
+    /// and the items are built from the selected entries with `map_entry` instead.
+    fn next_with<T>(
+        &mut self,
+        map: impl for<'x> Fn(RecordsId<'x>, RecordsValue<'x>) -> T,
+        map_entry: impl Fn(SignedEntry) -> T,
+    ) -> Option<Result<T>> {
         // early-return if we reached the query limit.
         if let Some(limit) = self.query.limit() {
             if self.count >= limit {
                 return None;
             }
         }
+        let query = &self.query;
         loop {
             let next = match &mut self.range {
-                QueryRange::AuthorKey { range, key_filter } => {
-                    // get the next entry from the query range, filtered by the key and empty filters
-                    range.next_filtered(&self.query.sort_direction, |(_ns, _author, key), value| {
-                        key_filter.matches(key)
-                            && (self.query.include_empty || !value_is_empty(&value))
+                QueryRange::AuthorKey {
+                    range,
+                    author_filter,
+                    key_filter,
+                } => {
+                    // get the next entry from the query range, filtered by the author, key and
+                    // value filters
+                    range.next_filter_map(&query.sort_direction, |id, value| {
+                        let (_ns, author, key) = id;
+                        (author_filter.matches(&AuthorId::from(author))
+                            && key_filter.matches(key)
+                            && !is_reserved_key(key)
+                            && value_matches(query, &value))
+                        .then(|| map(id, value))
                     })
                 }
 
                 QueryRange::KeyAuthor {
                     range,
                     author_filter,
-                    selector,
+                    selector: None,
+                } => {
+                    // get the next entry from the query range, filtered by the author and value
+                    // filters
+                    range.next_filter_map(
+                        &query.sort_direction,
+                        |(_ns, key, author)| {
+                            author_filter.matches(&(AuthorId::from(author)))
+                                && !is_reserved_key(key)
+                        },
+                        |id, value| value_matches(query, &value).then(|| map(id, value)),
+                    )
+                }
+
+                QueryRange::KeyAuthor {
+                    range,
+                    author_filter,
+                    selector: Some(selector),
                 } => loop {
                     // get the next entry from the query range, filtered by the author filter
-                    let next = range
-                        .next_filtered(&self.query.sort_direction, |(_ns, _key, author)| {
-                            author_filter.matches(&(AuthorId::from(author)))
-                        });
+                    let next = range.next_filtered(&query.sort_direction, |(_ns, key, author)| {
+                        author_filter.matches(&(AuthorId::from(author))) && !is_reserved_key(key)
+                    });
 
                     // early-break if next contains Err
                     let next = match next.transpose() {
@@ -121,28 +218,49 @@ impl Iterator for QueryIterator {
                         Ok(next) => next,
                     };
 
-                    // push the entry into the selector. if active, only the latest entry
-                    // for each key will be emitted.
-                    let next = match selector {
-                        None => next,
-                        Some(selector) => match selector.push(next) {
-                            SelectorRes::Continue => continue,
-                            SelectorRes::Finished => None,
-                            SelectorRes::Some(res) => Some(res),
-                        },
+                    // push the entry into the selector, so that only the latest entry for each
+                    // key is emitted.
+                    let next = match selector.push(next) {
+                        SelectorRes::Continue => continue,
+                        SelectorRes::Finished => None,
+                        SelectorRes::Some(res) => Some(res),
                     };
 
-                    // skip the entry if empty and no empty entries requested
-                    if !self.query.include_empty && matches!(&next, Some(e) if e.is_empty()) {
-                        continue;
+                    // skip the selected entry if it does not match the value filters. these
+                    // apply after the selection, so that older entries do not show up instead.
+                    match next {
+                        Some(entry)
+                            if (!query.include_empty && entry.is_empty())
+                                || !query.matches_content_hash(&entry.content_hash()) =>
+                        {
+                            continue
+                        }
+                        next => break next.map(|entry| Ok(map_entry(entry))),
                     }
-
-                    break next.map(Result::Ok);
                 },
+                QueryRange::LocalSeq {
+                    range,
+                    author_filter,
+                    key_filter,
+                } => {
+                    // get the next entry from the query range, filtered by author, key and value
+                    // filters
+                    range.next_filter_map(
+                        &query.sort_direction,
+                        |(_ns, author, key)| {
+                            author_filter.matches(&(AuthorId::from(author)))
+                                && key_filter.matches(key)
+                                && !is_reserved_key(key)
+                        },
+                        |id, value| value_matches(query, &value).then(|| map(id, value)),
+                    )
+                }
             };
 
             // skip the entry if we didn't get past the requested offset yet.
-            if self.offset < self.query.offset() && matches!(next, Some(Ok(_))) {
+            // offset and limit count the entries after the latest-per-key selection above, so
+            // that pages of `single_latest_per_key` queries are stable.
+            if self.offset < query.offset() && matches!(next, Some(Ok(_))) {
                 self.offset += 1;
                 continue;
             }
@@ -153,7 +271,171 @@ impl Iterator for QueryIterator {
     }
 }
 
-fn value_is_empty(value: &RecordsValue) -> bool {
+impl Iterator for QueryIterator {
+    type Item = Result<SignedEntry>;
+
+    fn next(&mut self) -> Option<Result<SignedEntry>> {
+        let next = self.next_with(into_entry, |entry| entry)?;
+        Some(next.and_then(|entry| with_content_chunks(&self.content_chunks_table, entry)))
+    }
+}
+
+/// Whether a record value matches the empty and content hash filters of `query`.
+///
+/// There is no index on content hashes, so the content hash filter filters the scanned range.
+fn value_matches(query: &Query, value: &RecordsValue) -> bool {
+    let (_timestamp, _namespace_sig, _author_sig, _len, hash) = value;
+    (query.include_empty || !value_is_empty(value))
+        && query.matches_content_hash(&Hash::from(**hash))
+}
+
+pub(super) fn value_is_empty(value: &RecordsValue) -> bool {
     let (_timestamp, _namespace_sig, _author_sig, _len, hash) = value;
     *hash == Hash::EMPTY.as_bytes()
 }
+
+/// A query iterator that yields the local sequence number together with each entry.
+///
+/// Created with [`QueryIterator::with_local_seq`].
+#[derive(Debug)]
+pub struct LocalSeqQueryIterator(QueryIterator);
+
+impl Iterator for LocalSeqQueryIterator {
+    type Item = Result<(u64, SignedEntry)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = match self.0.next()? {
+            Ok(entry) => entry,
+            Err(err) => return Some(Err(err)),
+        };
+        let seq = match self.0.local_seq_table.get(entry.id().as_byte_tuple()) {
+            Ok(Some(seq)) => seq.value(),
+            Ok(None) => return Some(Err(anyhow!("missing local sequence number"))),
+            Err(err) => return Some(Err(err.into())),
+        };
+        Some(Ok((seq, entry)))
+    }
+}
+
+/// A query iterator that yields only the selected fields of each entry.
+///
+/// Created with [`QueryIterator::with_projection`].
+#[derive(Debug)]
+pub struct ProjectedQueryIterator {
+    inner: QueryIterator,
+    projection: Projection,
+}
+
+impl Iterator for ProjectedQueryIterator {
+    type Item = Result<ProjectedEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let projection = self.projection;
+        self.inner.next_with(
+            |id, value| project_record(&projection, id, value),
+            |entry| projection.project(&entry),
+        )
+    }
+}
+
+/// Project a raw record onto the fields selected by `projection`, without building the entry.
+fn project_record(projection: &Projection, id: RecordsId, value: RecordsValue) -> ProjectedEntry {
+    let (_namespace, author, key) = id;
+    let (timestamp, _namespace_sig, _author_sig, len, hash) = value;
+    ProjectedEntry {
+        author: projection.author.then(|| AuthorId::from(author)),
+        key: projection.key.then(|| Bytes::copy_from_slice(key)),
+        content_hash: projection.content_hash.then(|| Hash::from(*hash)),
+        content_len: projection.content_len.then_some(len),
+        timestamp: projection.timestamp.then_some(timestamp),
+    }
+}
+
+/// An iterator over the entries of a namespace which match a query, grouped by author.
+///
+/// Created with [`super::Store::get_many_grouped_by_author`].
+#[derive(Debug)]
+pub struct AuthorGroupsIterator {
+    tables: ReadOnlyTables,
+    namespace: NamespaceId,
+    query: Query,
+    resolver: Option<Arc<dyn ConflictResolver>>,
+    authors: std::vec::IntoIter<AuthorId>,
+}
+
+impl AuthorGroupsIterator {
+    pub(super) fn new(
+        tables: ReadOnlyTables,
+        namespace: NamespaceId,
+        query: Query,
+        resolver: Option<Arc<dyn ConflictResolver>>,
+    ) -> Result<Self> {
+        // The latest entry per key is selected across all authors. Running the query per author
+        // would select the latest entry per key of each author instead, and rescan the namespace
+        // for each group.
+        if matches!(query.kind, QueryKind::SingleLatestPerKey(_)) {
+            return Err(anyhow!(
+                "latest-per-key queries cannot be grouped by author, use a flat query instead"
+            ));
+        }
+        // the latest-per-author table holds an entry for each author with entries in the
+        // namespace, so the authors are found without scanning the records.
+        let start = (namespace.as_bytes(), &[u8::MIN; 32]);
+        let end = (namespace.as_bytes(), &[u8::MAX; 32]);
+        let mut authors = tables
+            .latest_per_author
+            .range(start..=end)?
+            .map(|item| item.map(|(key, _value)| AuthorId::from(key.value().1)))
+            .collect::<Result<Vec<_>, _>>()?;
+        authors.retain(|author| query.filter_author.matches(author));
+        if matches!(query.sort_direction, SortDirection::Desc) {
+            authors.reverse();
+        }
+        Ok(Self {
+            tables,
+            namespace,
+            query,
+            resolver,
+            authors: authors.into_iter(),
+        })
+    }
+}
+
+impl Iterator for AuthorGroupsIterator {
+    type Item = Result<(AuthorId, AuthorEntries)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for author in self.authors.by_ref() {
+            let mut query = self.query.clone();
+            query.filter_author = AuthorFilter::Exact(author);
+            let entries = self
+                .tables
+                .try_clone()
+                .map_err(anyhow::Error::from)
+                .and_then(|tables| {
+                    QueryIterator::new(tables, self.namespace, query, self.resolver.clone())
+                });
+            let mut entries = match entries {
+                Ok(entries) => entries.peekable(),
+                Err(err) => return Some(Err(err)),
+            };
+            // skip authors without matching entries
+            if entries.peek().is_some() {
+                return Some(Ok((author, AuthorEntries(entries))));
+            }
+        }
+        None
+    }
+}
+
+/// The entries of a single author, yielded by [`AuthorGroupsIterator`].
+#[derive(Debug)]
+pub struct AuthorEntries(std::iter::Peekable<QueryIterator>);
+
+impl Iterator for AuthorEntries {
+    type Item = Result<SignedEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
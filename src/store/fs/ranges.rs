@@ -5,9 +5,11 @@ use redb::{Key, Range, ReadOnlyTable, ReadableTable, Value};
 use super::{
     bounds::{ByKeyBounds, RecordsBounds},
     into_entry,
-    tables::{RecordsByKeyId, RecordsId, RecordsValue},
+    tables::{
+        RecordsByKeyId, RecordsByLocalSeqId, RecordsByLocalSeqValue, RecordsId, RecordsValue,
+    },
 };
-use crate::{store::SortDirection, SignedEntry};
+use crate::{store::SortDirection, NamespaceId, SignedEntry};
 
 /// An extension trait for [`Range`] that provides methods for mapped retrieval.
 pub trait RangeExt<K: Key, V: Value> {
@@ -84,17 +86,15 @@ impl<'a> RecordsRange<'a> {
         Ok(Self(range))
     }
 
-    //
-    /// Get the next item in the range.
+    /// Get the next item in the range, mapped from the raw record.
     ///
-    /// Omit items for which the `matcher` function returns false.
-    pub(super) fn next_filtered(
+    /// Omit items for which the `filter_map` function returns `None`.
+    pub(super) fn next_filter_map<T>(
         &mut self,
         direction: &SortDirection,
-        filter: impl for<'x> Fn(RecordsId<'x>, RecordsValue<'x>) -> bool,
-    ) -> Option<anyhow::Result<SignedEntry>> {
-        self.0
-            .next_filter_map(direction, |k, v| filter(k, v).then(|| into_entry(k, v)))
+        filter_map: impl for<'x> Fn(RecordsId<'x>, RecordsValue<'x>) -> Option<T>,
+    ) -> Option<anyhow::Result<T>> {
+        self.0.next_filter_map(direction, filter_map)
     }
 }
 
@@ -149,18 +149,90 @@ impl RecordsByKeyRange {
         direction: &SortDirection,
         filter: impl for<'x> Fn(RecordsByKeyId<'x>) -> bool,
     ) -> Option<anyhow::Result<SignedEntry>> {
-        let entry = self.by_key_range.next_try_filter_map(direction, |k, _v| {
+        self.next_filter_map(direction, filter, |id, value| Some(into_entry(id, value)))
+    }
+
+    /// Get the next item in the range, mapped from the raw record.
+    ///
+    /// Omit items for which the `filter` function returns false, before the record is read, and
+    /// items for which the `map` function returns `None`.
+    pub fn next_filter_map<T>(
+        &mut self,
+        direction: &SortDirection,
+        filter: impl for<'x> Fn(RecordsByKeyId<'x>) -> bool,
+        map: impl for<'x> Fn(RecordsId<'x>, RecordsValue<'x>) -> Option<T>,
+    ) -> Option<anyhow::Result<T>> {
+        self.by_key_range.next_try_filter_map(direction, |k, _v| {
             if !filter(k) {
                 return None;
             };
             let (namespace, key, author) = k;
             let records_id = (namespace, author, key);
-            let entry = self.records_table.get(&records_id).transpose()?;
-            let entry = entry
-                .map(|value| into_entry(records_id, value.value()))
-                .map_err(anyhow::Error::from);
-            Some(entry)
-        });
-        entry
+            match self.records_table.get(&records_id) {
+                Ok(Some(value)) => map(records_id, value.value()).map(Ok),
+                Ok(None) => None,
+                Err(err) => Some(Err(err.into())),
+            }
+        })
+    }
+}
+
+/// An iterator over the records of a namespace, ordered by local sequence number.
+#[derive(derive_more::Debug)]
+#[debug("RecordsByLocalSeqRange")]
+pub struct RecordsByLocalSeqRange {
+    records_table: ReadOnlyTable<RecordsId<'static>, RecordsValue<'static>>,
+    by_local_seq_range:
+        Range<'static, RecordsByLocalSeqId<'static>, RecordsByLocalSeqValue<'static>>,
+}
+
+impl RecordsByLocalSeqRange {
+    /// Create a range over all records of `namespace` with a local sequence number greater
+    /// than `since`.
+    pub fn since(
+        by_local_seq_table: ReadOnlyTable<
+            RecordsByLocalSeqId<'static>,
+            RecordsByLocalSeqValue<'static>,
+        >,
+        records_table: ReadOnlyTable<RecordsId<'static>, RecordsValue<'static>>,
+        namespace: NamespaceId,
+        since: u64,
+    ) -> anyhow::Result<Self> {
+        let namespace = namespace.as_bytes();
+        let by_local_seq_range = match since.checked_add(1) {
+            Some(start) => by_local_seq_table.range((namespace, start)..=(namespace, u64::MAX))?,
+            // nothing can be newer than u64::MAX, create an empty range
+            None => by_local_seq_table.range((namespace, u64::MAX)..(namespace, u64::MAX))?,
+        };
+        Ok(Self {
+            records_table,
+            by_local_seq_range,
+        })
+    }
+
+    /// Get the next item in the range, mapped from the raw record.
+    ///
+    /// Omit items for which the `filter` function returns false, before the record is read, and
+    /// items for which the `map` function returns `None`.
+    pub fn next_filter_map<T>(
+        &mut self,
+        direction: &SortDirection,
+        filter: impl for<'x> Fn(RecordsId<'x>) -> bool,
+        map: impl for<'x> Fn(RecordsId<'x>, RecordsValue<'x>) -> Option<T>,
+    ) -> Option<anyhow::Result<T>> {
+        self.by_local_seq_range.next_try_filter_map(
+            direction,
+            |(namespace, _seq), (author, key)| {
+                let records_id = (namespace, author, key);
+                if !filter(records_id) {
+                    return None;
+                }
+                match self.records_table.get(&records_id) {
+                    Ok(Some(value)) => map(records_id, value.value()).map(Ok),
+                    Ok(None) => None,
+                    Err(err) => Some(Err(err.into())),
+                }
+            },
+        )
     }
 }
@@ -1,7 +1,7 @@
 #![allow(missing_docs)]
 // Table Definitions
 
-use std::time::Instant;
+use std::{sync::Arc, time::Instant};
 
 use bytes::Bytes;
 use redb::{
@@ -73,6 +73,53 @@ pub type Nanos = u64;
 pub const DOWNLOAD_POLICY_TABLE: TableDefinition<&[u8; 32], &[u8]> =
     TableDefinition::new("download-policy-1");
 
+/// Table: Local sequence number per record
+/// Key:   `([u8; 32], [u8; 32], &[u8])` # (NamespaceId, AuthorId, Key)
+/// Value: `u64`                          # Local sequence number
+pub const RECORDS_LOCAL_SEQ_TABLE: TableDefinition<RecordsId, u64> =
+    TableDefinition::new("records-local-seq-1");
+
+/// Table: Records by local sequence number
+/// Key:   `([u8; 32], u64)`   # (NamespaceId, Local sequence number)
+/// Value: `([u8; 32], &[u8])` # (AuthorId, Key)
+pub const RECORDS_BY_LOCAL_SEQ_TABLE: TableDefinition<RecordsByLocalSeqId, RecordsByLocalSeqValue> =
+    TableDefinition::new("records-by-local-seq-1");
+pub type RecordsByLocalSeqId<'a> = (&'a [u8; 32], u64);
+pub type RecordsByLocalSeqValue<'a> = (&'a [u8; 32], &'a [u8]);
+
+/// Table: Last assigned local sequence number per namespace
+/// Key:   `[u8; 32]` # NamespaceId
+/// Value: `u64`      # Local sequence number
+pub const NAMESPACE_LOCAL_SEQ_TABLE: TableDefinition<&[u8; 32], u64> =
+    TableDefinition::new("namespace-local-seq-1");
+
+/// Table: Manifests of chunked entries
+/// Key:   `([u8; 32], [u8; 32], Vec<u8>)` # (NamespaceId, AuthorId, Key)
+/// Value: `&[u8]`                         # Manifest bytes
+pub const CONTENT_CHUNKS_TABLE: TableDefinition<RecordsId, &[u8]> =
+    TableDefinition::new("records-content-chunks-1");
+
+/// Table: Namespace metadata
+/// Key:   `([u8; 32], [u8; 32])` # (NamespaceId, AuthorId)
+/// Value: `&[u8]`                # Metadata bytes
+pub const NAMESPACE_META_TABLE: TableDefinition<(&[u8; 32], &[u8; 32]), &[u8]> =
+    TableDefinition::new("namespace-meta-1");
+
+/// Table: Local aliases of namespaces
+/// Key:   `&str`     # Alias
+/// Value: `[u8; 32]` # NamespaceId
+pub const NAMESPACE_ALIASES_TABLE: TableDefinition<&str, &[u8; 32]> =
+    TableDefinition::new("namespace-aliases-1");
+
+/// Table: Store-wide settings
+/// Key:   `&str`     # Setting name, see [`DEFAULT_AUTHOR_SETTING`]
+/// Value: `&[u8]`    # Setting value
+pub const SETTINGS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("settings-1");
+
+/// Setting: Default author of the store
+/// Value: `[u8; 32]` # AuthorId
+pub const DEFAULT_AUTHOR_SETTING: &str = "default-author";
+
 self_cell::self_cell! {
     struct TransactionAndTablesInner {
         owner: WriteTransaction,
@@ -86,6 +133,7 @@ pub struct TransactionAndTables {
     #[debug("TransactionAndTablesInner")]
     inner: TransactionAndTablesInner,
     pub(crate) since: Instant,
+    pub(crate) writes: usize,
 }
 
 impl TransactionAndTables {
@@ -93,6 +141,7 @@ impl TransactionAndTables {
         Ok(Self {
             inner: TransactionAndTablesInner::try_new(tx, |tx| Tables::new(tx))?,
             since: Instant::now(),
+            writes: 0,
         })
     }
 
@@ -122,6 +171,14 @@ pub struct Tables<'tx> {
     pub namespace_peers: MultimapTable<'tx, &'static [u8; 32], (Nanos, &'static PeerIdBytes)>,
     pub download_policy: Table<'tx, &'static [u8; 32], &'static [u8]>,
     pub authors: Table<'tx, &'static [u8; 32], &'static [u8; 32]>,
+    pub records_local_seq: Table<'tx, RecordsId<'static>, u64>,
+    pub records_by_local_seq:
+        Table<'tx, RecordsByLocalSeqId<'static>, RecordsByLocalSeqValue<'static>>,
+    pub namespace_local_seq: Table<'tx, &'static [u8; 32], u64>,
+    pub content_chunks: Table<'tx, RecordsId<'static>, &'static [u8]>,
+    pub namespace_meta: Table<'tx, (&'static [u8; 32], &'static [u8; 32]), &'static [u8]>,
+    pub settings: Table<'tx, &'static str, &'static [u8]>,
+    pub namespace_aliases: Table<'tx, &'static str, &'static [u8; 32]>,
 }
 
 impl<'tx> Tables<'tx> {
@@ -133,6 +190,13 @@ impl<'tx> Tables<'tx> {
         let namespace_peers = tx.open_multimap_table(NAMESPACE_PEERS_TABLE)?;
         let download_policy = tx.open_table(DOWNLOAD_POLICY_TABLE)?;
         let authors = tx.open_table(AUTHORS_TABLE)?;
+        let records_local_seq = tx.open_table(RECORDS_LOCAL_SEQ_TABLE)?;
+        let records_by_local_seq = tx.open_table(RECORDS_BY_LOCAL_SEQ_TABLE)?;
+        let namespace_local_seq = tx.open_table(NAMESPACE_LOCAL_SEQ_TABLE)?;
+        let content_chunks = tx.open_table(CONTENT_CHUNKS_TABLE)?;
+        let namespace_meta = tx.open_table(NAMESPACE_META_TABLE)?;
+        let settings = tx.open_table(SETTINGS_TABLE)?;
+        let namespace_aliases = tx.open_table(NAMESPACE_ALIASES_TABLE)?;
         Ok(Self {
             records,
             records_by_key,
@@ -141,6 +205,13 @@ impl<'tx> Tables<'tx> {
             namespace_peers,
             download_policy,
             authors,
+            records_local_seq,
+            records_by_local_seq,
+            namespace_local_seq,
+            content_chunks,
+            namespace_meta,
+            settings,
+            namespace_aliases,
         })
     }
 }
@@ -155,11 +226,28 @@ pub struct ReadOnlyTables {
     pub namespace_peers: ReadOnlyMultimapTable<&'static [u8; 32], (Nanos, &'static PeerIdBytes)>,
     pub download_policy: ReadOnlyTable<&'static [u8; 32], &'static [u8]>,
     pub authors: ReadOnlyTable<&'static [u8; 32], &'static [u8; 32]>,
-    tx: ReadTransaction,
+    pub records_local_seq: ReadOnlyTable<RecordsId<'static>, u64>,
+    pub records_by_local_seq:
+        ReadOnlyTable<RecordsByLocalSeqId<'static>, RecordsByLocalSeqValue<'static>>,
+    pub namespace_local_seq: ReadOnlyTable<&'static [u8; 32], u64>,
+    pub content_chunks: ReadOnlyTable<RecordsId<'static>, &'static [u8]>,
+    pub namespace_meta: ReadOnlyTable<(&'static [u8; 32], &'static [u8; 32]), &'static [u8]>,
+    pub settings: ReadOnlyTable<&'static str, &'static [u8]>,
+    pub namespace_aliases: ReadOnlyTable<&'static str, &'static [u8; 32]>,
+    tx: Arc<ReadTransaction>,
 }
 
 impl ReadOnlyTables {
     pub fn new(tx: ReadTransaction) -> Result<Self, redb::TableError> {
+        Self::open(Arc::new(tx))
+    }
+
+    /// Open the tables again, reading from the same snapshot of the database.
+    pub fn try_clone(&self) -> Result<Self, redb::TableError> {
+        Self::open(self.tx.clone())
+    }
+
+    fn open(tx: Arc<ReadTransaction>) -> Result<Self, redb::TableError> {
         let records = tx.open_table(RECORDS_TABLE)?;
         let records_by_key = tx.open_table(RECORDS_BY_KEY_TABLE)?;
         let namespaces = tx.open_table(NAMESPACES_TABLE)?;
@@ -167,6 +255,13 @@ impl ReadOnlyTables {
         let namespace_peers = tx.open_multimap_table(NAMESPACE_PEERS_TABLE)?;
         let download_policy = tx.open_table(DOWNLOAD_POLICY_TABLE)?;
         let authors = tx.open_table(AUTHORS_TABLE)?;
+        let records_local_seq = tx.open_table(RECORDS_LOCAL_SEQ_TABLE)?;
+        let records_by_local_seq = tx.open_table(RECORDS_BY_LOCAL_SEQ_TABLE)?;
+        let namespace_local_seq = tx.open_table(NAMESPACE_LOCAL_SEQ_TABLE)?;
+        let content_chunks = tx.open_table(CONTENT_CHUNKS_TABLE)?;
+        let namespace_meta = tx.open_table(NAMESPACE_META_TABLE)?;
+        let settings = tx.open_table(SETTINGS_TABLE)?;
+        let namespace_aliases = tx.open_table(NAMESPACE_ALIASES_TABLE)?;
         Ok(Self {
             records,
             records_by_key,
@@ -175,6 +270,13 @@ impl ReadOnlyTables {
             namespace_peers,
             download_policy,
             authors,
+            records_local_seq,
+            records_by_local_seq,
+            namespace_local_seq,
+            content_chunks,
+            namespace_meta,
+            settings,
+            namespace_aliases,
             tx,
         })
     }
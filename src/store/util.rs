@@ -1,7 +1,12 @@
 //! Utilities useful across different store impls.
 
-use super::{AuthorFilter, KeyFilter, Query, QueryKind, SortBy};
-use crate::SignedEntry;
+use std::sync::Arc;
+
+use super::{AuthorFilter, FlatQuery, KeyFilter, Query, QueryKind, SortBy};
+use crate::{
+    sync::{cmp_entries, ConflictResolver},
+    SignedEntry,
+};
 
 /// A helper for stores that have by-author and by-key indexes for records.
 #[derive(Debug)]
@@ -15,17 +20,32 @@ pub enum IndexKind {
         author_filter: AuthorFilter,
         latest_per_key: bool,
     },
+    LocalSeq {
+        since: u64,
+        author_filter: AuthorFilter,
+        key_filter: KeyFilter,
+    },
 }
 
 impl From<&Query> for IndexKind {
     fn from(query: &Query) -> Self {
         match &query.kind {
+            QueryKind::Flat(FlatQuery {
+                since_local_seq: Some(since),
+                ..
+            }) => IndexKind::LocalSeq {
+                since: *since,
+                author_filter: query.filter_author.clone(),
+                key_filter: query.filter_key.clone(),
+            },
             QueryKind::Flat(details) => match (&query.filter_author, details.sort_by) {
-                (AuthorFilter::Any, SortBy::KeyAuthor) => IndexKind::KeyAuthor {
-                    range: query.filter_key.clone(),
-                    author_filter: AuthorFilter::Any,
-                    latest_per_key: false,
-                },
+                (AuthorFilter::Any | AuthorFilter::Exclude(_), SortBy::KeyAuthor) => {
+                    IndexKind::KeyAuthor {
+                        range: query.filter_key.clone(),
+                        author_filter: query.filter_author.clone(),
+                        latest_per_key: false,
+                    }
+                }
                 _ => IndexKind::AuthorKey {
                     range: query.filter_author.clone(),
                     key_filter: query.filter_key.clone(),
@@ -42,9 +62,13 @@ impl From<&Query> for IndexKind {
 
 /// Helper to extract the latest entry per key from an iterator that yields [`SignedEntry`] items.
 ///
-/// Items must be pushed in key-sorted order.
+/// Items must be pushed in key-sorted order. Entries with equal timestamps are ordered with the
+/// [`ConflictResolver`], if set.
 #[derive(Debug, Default)]
-pub struct LatestPerKeySelector(Option<SignedEntry>);
+pub struct LatestPerKeySelector {
+    last: Option<SignedEntry>,
+    resolver: Option<Arc<dyn ConflictResolver>>,
+}
 
 pub enum SelectorRes {
     /// The iterator is finished.
@@ -56,31 +80,39 @@ pub enum SelectorRes {
 }
 
 impl LatestPerKeySelector {
+    /// Create a new selector.
+    pub fn new(resolver: Option<Arc<dyn ConflictResolver>>) -> Self {
+        Self {
+            last: None,
+            resolver,
+        }
+    }
+
     /// Push an entry into the selector.
     ///
     /// Entries must be sorted by key beforehand.
     pub fn push(&mut self, entry: Option<SignedEntry>) -> SelectorRes {
         let Some(entry) = entry else {
-            return match self.0.take() {
+            return match self.last.take() {
                 Some(entry) => SelectorRes::Some(entry),
                 None => SelectorRes::Finished,
             };
         };
-        match self.0.take() {
+        match self.last.take() {
             None => {
-                self.0 = Some(entry);
+                self.last = Some(entry);
                 SelectorRes::Continue
             }
             Some(last) if last.key() == entry.key() => {
-                if entry.timestamp() > last.timestamp() {
-                    self.0 = Some(entry);
+                if cmp_entries(self.resolver.as_deref(), entry.entry(), last.entry()).is_gt() {
+                    self.last = Some(entry);
                 } else {
-                    self.0 = Some(last);
+                    self.last = Some(last);
                 }
                 SelectorRes::Continue
             }
             Some(last) => {
-                self.0 = Some(entry);
+                self.last = Some(entry);
                 SelectorRes::Some(last)
             }
         }
@@ -7,8 +7,11 @@
 // This is going to change!
 
 use std::{
+    borrow::Cow,
     cmp::Ordering,
+    collections::{BTreeSet, HashMap, VecDeque},
     fmt::Debug,
+    num::NonZeroUsize,
     ops::{Deref, DerefMut},
     sync::Arc,
     time::{Duration, SystemTime},
@@ -21,13 +24,20 @@ use iroh_blobs::Hash;
 use iroh_metrics::{inc, inc_by};
 use serde::{Deserialize, Serialize};
 
-pub use crate::heads::AuthorHeads;
+pub use crate::heads::{AuthorHeads, HeadComparison};
 #[cfg(feature = "metrics")]
 use crate::metrics::Metrics;
+pub use crate::ranger::{
+    CompactMessage, Fingerprint, InsertOutcome, MessageDecodeError, MessageLimits, Range,
+    SyncConfig,
+};
 use crate::{
-    keys::{Author, AuthorId, AuthorPublicKey, NamespaceId, NamespacePublicKey, NamespaceSecret},
-    ranger::{self, Fingerprint, InsertOutcome, RangeEntry, RangeKey, RangeValue, Store},
-    store::{self, fs::StoreInstance, DownloadPolicyStore, PublicKeyStore},
+    keys::{
+        Author, AuthorId, AuthorPublicKey, BatchVerifier, NamespaceId, NamespacePublicKey,
+        NamespaceSecret, WriteToken, WriteTokenId,
+    },
+    ranger::{self, RangeEntry, RangeKey, RangeValue, Store},
+    store::{self, fs::StoreInstance, DownloadPolicyStore, RebuildStats},
 };
 
 /// Protocol message for the set reconciliation protocol.
@@ -35,6 +45,72 @@ use crate::{
 /// Can be serialized to bytes with [serde] to transfer between peers.
 pub type ProtocolMessage = crate::ranger::Message<SignedEntry>;
 
+/// Decode a postcard-encoded [`ProtocolMessage`] received from a remote peer.
+///
+/// Fails with an error instead of allocating if the message exceeds `limits`.
+pub fn decode_protocol_message(
+    bytes: &[u8],
+    limits: &MessageLimits,
+) -> Result<ProtocolMessage, MessageDecodeError> {
+    ProtocolMessage::decode(bytes, limits)
+}
+
+impl ProtocolMessage {
+    /// Returns a human readable summary of this message.
+    ///
+    /// The summary lists the number of range fingerprints, range items and values, followed by
+    /// the key bounds of each range. Keys are printed as UTF-8 if possible, and as hex otherwise.
+    ///
+    /// This is meant for debugging and allocates, so it should only be called if the output is
+    /// actually used, e.g. in lazily evaluated tracing fields.
+    pub fn describe(&self) -> String {
+        use std::fmt::Write;
+
+        let mut fingerprints = 0;
+        let mut items = 0;
+        let mut ranges = String::new();
+        for part in self.parts() {
+            // writing to a string does not fail
+            match part {
+                ranger::MessagePart::RangeFingerprint(part) => {
+                    fingerprints += 1;
+                    write!(
+                        &mut ranges,
+                        " fingerprint[{}..{}]",
+                        fmt_key_bound(part.range.x()),
+                        fmt_key_bound(part.range.y())
+                    )
+                    .ok();
+                }
+                ranger::MessagePart::RangeItem(part) => {
+                    items += 1;
+                    write!(
+                        &mut ranges,
+                        " item[{}..{}; values={}, have_local={}]",
+                        fmt_key_bound(part.range.x()),
+                        fmt_key_bound(part.range.y()),
+                        part.values.len(),
+                        part.have_local
+                    )
+                    .ok();
+                }
+            }
+        }
+        format!(
+            "fingerprints={fingerprints} items={items} values={}{ranges}",
+            self.value_count()
+        )
+    }
+}
+
+fn fmt_key_bound(id: &RecordIdentifier) -> String {
+    let key = match std::str::from_utf8(id.key()) {
+        Ok(key) => key.to_string(),
+        Err(_) => hex::encode(id.key()),
+    };
+    format!("{}/{}", id.author().fmt_short(), key)
+}
+
 /// Byte representation of a `PeerId` from `iroh-net`.
 // TODO: PeerId is in iroh-net which iroh-docs doesn't depend on. Add iroh-base crate with `PeerId`.
 pub type PeerIdBytes = [u8; 32];
@@ -43,11 +119,155 @@ pub type PeerIdBytes = [u8; 32];
 /// Value is 10 minutes.
 pub const MAX_TIMESTAMP_FUTURE_SHIFT: u64 = 10 * 60 * Duration::from_secs(1).as_millis() as u64;
 
+/// Reserved key under which each author stores the namespace metadata blob.
+///
+/// Entries with this key are excluded from regular queries.
+/// See [`Replica::set_namespace_meta`].
+pub const NAMESPACE_META_KEY: &[u8] = b"\0iroh-docs/namespace-meta";
+
+/// Maximum length of namespace metadata in bytes.
+///
+/// Peers only read downloaded blobs up to this length to resolve namespace metadata.
+pub const MAX_NAMESPACE_META_LEN: u64 = 64 * 1024;
+
+/// Reserved key prefix under which authors present their [`WriteToken`]s.
+///
+/// See [`Replica::insert_with_token`].
+pub const WRITE_TOKEN_PREFIX: &[u8] = b"\0iroh-docs/write-token/";
+
+/// Domain separation tag for author signatures of entries signed under a [`WriteToken`].
+const WRITE_TOKEN_ENTRY_DOMAIN: &[u8] = b"iroh-docs/write-token-entry/v1";
+
+/// Tag in place of the namespace signature of entries signed under a [`WriteToken`], followed by
+/// the 16 bytes of the token id.
+const WRITE_TOKEN_MARKER_TAG: &[u8; 48] =
+    b"iroh-docs/write-token-marker/v1\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0";
+
+/// Reserved key prefix under which revocations of [`WriteToken`]s are stored.
+///
+/// See [`Replica::revoke_write_token`].
+pub const WRITE_TOKEN_REVOCATION_PREFIX: &[u8] = b"\0iroh-docs/write-token-revoked/";
+
+/// Reserved key prefix under which the contributions of authors to counters are stored.
+///
+/// See [`CounterValue`] and [`Replica::counter_add`].
+pub const COUNTER_PREFIX: &[u8] = b"\0iroh-docs/counter/";
+
+/// Returns `true` if `key` is reserved for namespace metadata, write tokens or counters.
+pub(crate) fn is_reserved_key(key: &[u8]) -> bool {
+    key == NAMESPACE_META_KEY
+        || key.starts_with(WRITE_TOKEN_PREFIX)
+        || key.starts_with(WRITE_TOKEN_REVOCATION_PREFIX)
+        || key.starts_with(COUNTER_PREFIX)
+}
+
+/// Fails with [`InsertError::ReservedKey`] if `key` is reserved.
+///
+/// Entries under reserved keys are only written by the dedicated methods of [`Replica`].
+fn ensure_not_reserved(key: &[u8]) -> Result<(), InsertError> {
+    match is_reserved_key(key) {
+        true => Err(InsertError::ReservedKey),
+        false => Ok(()),
+    }
+}
+
+/// Returns `true` if the content of a remote entry should be downloaded.
+///
+/// Namespace metadata is always downloaded, unless it was removed or is longer than
+/// [`MAX_NAMESPACE_META_LEN`]. Write tokens and counters are stored in the key, there is no
+/// content to download for them.
+fn should_download(entry: &SignedEntry, download_policy: &store::DownloadPolicy) -> bool {
+    if entry.key() == NAMESPACE_META_KEY {
+        return !entry.is_empty() && entry.content_len() <= MAX_NAMESPACE_META_LEN;
+    }
+    !is_reserved_key(entry.key()) && download_policy.matches(entry.entry())
+}
+
+/// The contribution of a single author to a counter.
+///
+/// Counters are stored as one entry per author under the reserved [`COUNTER_PREFIX`]. As each
+/// author only replaces their own entry, concurrent additions of different authors are all
+/// kept, and the value of the counter is the sum of the contributions of all authors. Concurrent
+/// additions of the same author from different devices are resolved by last-writer-wins.
+///
+/// The contribution is encoded in the entry key: the prefix, the length of the counter key as
+/// u32 big-endian, the counter key, and the contribution as i64 big-endian.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CounterValue {
+    key: Bytes,
+    value: i64,
+}
+
+impl CounterValue {
+    /// Create a new contribution of `value` to the counter at `key`.
+    pub fn new(key: impl Into<Bytes>, value: i64) -> Self {
+        Self {
+            key: key.into(),
+            value,
+        }
+    }
+
+    /// Get the key of the counter.
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Get the contributed value.
+    pub fn value(&self) -> i64 {
+        self.value
+    }
+
+    /// Get the prefix of the entry keys of all contributions to the counter at `key`.
+    pub fn entry_key_prefix(key: &[u8]) -> Vec<u8> {
+        let len = u32::try_from(key.len()).expect("key too long");
+        [COUNTER_PREFIX, &len.to_be_bytes(), key].concat()
+    }
+
+    /// Encode this contribution as an entry key.
+    pub fn to_entry_key(&self) -> Vec<u8> {
+        [
+            Self::entry_key_prefix(&self.key).as_slice(),
+            &self.value.to_be_bytes(),
+        ]
+        .concat()
+    }
+
+    /// Decode a contribution from an entry key.
+    ///
+    /// Returns `None` if `entry_key` is not the key of a counter entry.
+    pub fn from_entry_key(entry_key: &[u8]) -> Option<Self> {
+        let rest = entry_key.strip_prefix(COUNTER_PREFIX)?;
+        let (len, rest) = rest.split_first_chunk::<4>()?;
+        let len = u32::from_be_bytes(*len) as usize;
+        if len.checked_add(8) != Some(rest.len()) {
+            return None;
+        }
+        let (key, value) = rest.split_at(len);
+        let value = i64::from_be_bytes(value.try_into().ok()?);
+        Some(Self::new(Bytes::copy_from_slice(key), value))
+    }
+
+    /// Sum up the contributions to a counter.
+    pub fn aggregate(values: impl IntoIterator<Item = CounterValue>) -> i64 {
+        values
+            .into_iter()
+            .fold(0i64, |sum, value| sum.saturating_add(value.value))
+    }
+}
+
 /// Callback that may be set on a replica to determine the availability status for a content hash.
 pub type ContentStatusCallback = Arc<dyn Fn(Hash) -> ContentStatus + Send + Sync + 'static>;
 
+/// Function that may be set on a replica to normalize keys before they are used in a
+/// [`RecordIdentifier`].
+///
+/// See [`ReplicaInfo::set_key_normalizer`].
+pub type KeyNormalizer = Arc<dyn Fn(&[u8]) -> Cow<'_, [u8]> + Send + Sync + 'static>;
+
 /// Event emitted by sync when entries are added.
-#[derive(Debug, Clone)]
+///
+/// Events can be serialized to forward them to subscribers in other processes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Event {
     /// A local entry has been added.
     LocalInsert {
@@ -69,6 +289,80 @@ pub enum Event {
         /// [`ContentStatus`] for this entry in the remote's replica.
         remote_content_status: ContentStatus,
     },
+    /// A sync in [`SyncEventMode::Silent`] inserted entries.
+    ///
+    /// Emitted once per sync instead of an [`Event::RemoteInsert`] for each entry.
+    SyncBatchApplied {
+        /// Document in which the entries were inserted.
+        namespace: NamespaceId,
+        /// Number of inserted entries.
+        count: usize,
+    },
+    /// The availability of content referenced in a document changed.
+    ///
+    /// Emitted by [`crate::store::Store::notify_content_status`] to the subscribers of all
+    /// replicas, and by [`Replica::notify_content_status`] to the subscribers of the replica as
+    /// well.
+    ContentStatusChanged {
+        /// Document for which the status was reported.
+        namespace: NamespaceId,
+        /// Hash of the content.
+        hash: Hash,
+        /// New status of the content.
+        status: ContentStatus,
+    },
+    /// The replica has been closed.
+    ///
+    /// This is the last event sent to subscribers, the subscription ends afterwards.
+    Closed {
+        /// Document which was closed.
+        namespace: NamespaceId,
+    },
+}
+
+/// How events are emitted for entries inserted while syncing with a remote peer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SyncEventMode {
+    /// Emit an [`Event::RemoteInsert`] for each inserted entry.
+    #[default]
+    PerEntry,
+    /// Do not emit events for inserted entries.
+    ///
+    /// Instead, a single [`Event::SyncBatchApplied`] is emitted with [`Replica::sync_batch_applied`]
+    /// once the sync finished. This is useful during a bulk initial sync, where the events for
+    /// each entry can overwhelm subscribers.
+    ///
+    /// Subscribers added with [`ReplicaInfo::subscribe_internal`] still receive an
+    /// [`Event::RemoteInsert`] for each entry, so that the engine keeps downloading content.
+    Silent,
+}
+
+/// Options for [`Replica::insert_with_opts`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InsertOpts {
+    /// Skip the insert if the latest entry for the key and author has the same content.
+    ///
+    /// Re-inserting unchanged content otherwise writes a new entry with a fresh timestamp,
+    /// which has to be synced to all peers.
+    pub skip_if_unchanged: bool,
+}
+
+impl InsertOpts {
+    /// Set [`Self::skip_if_unchanged`].
+    pub fn skip_if_unchanged(mut self) -> Self {
+        self.skip_if_unchanged = true;
+        self
+    }
+}
+
+/// Outcome of [`Replica::insert_with_opts`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertOptsOutcome {
+    /// The entry was passed on to the store, which reports whether it was inserted.
+    Applied(InsertOutcome),
+    /// The entry was not inserted because the latest entry for its key and author already has
+    /// the same content, see [`InsertOpts::skip_if_unchanged`].
+    Unchanged,
 }
 
 /// Whether an entry was inserted locally or by a remote peer.
@@ -96,6 +390,43 @@ pub enum ContentStatus {
     Missing,
 }
 
+/// Combine the content status of the parts of some content into the status of the whole.
+///
+/// The content is complete if all parts are complete, and missing if all parts are missing.
+/// Otherwise, some but not all of the content is available, and the content is incomplete.
+/// Content without any parts is missing.
+pub fn content_status_from_parts(statuses: &[ContentStatus]) -> ContentStatus {
+    if statuses.is_empty() {
+        ContentStatus::Missing
+    } else if statuses.iter().all(|s| *s == ContentStatus::Complete) {
+        ContentStatus::Complete
+    } else if statuses.iter().all(|s| *s == ContentStatus::Missing) {
+        ContentStatus::Missing
+    } else {
+        ContentStatus::Incomplete
+    }
+}
+
+/// Get the content status of `entry` from the status of its blobs.
+///
+/// For chunked entries whose chunks are known, the status of the manifest and all chunks is
+/// combined with [`content_status_from_parts`].
+pub(crate) fn entry_content_status(
+    entry: &SignedEntry,
+    content_status_cb: &ContentStatusCallback,
+) -> ContentStatus {
+    match entry.content_chunks() {
+        None => content_status_cb(entry.content_hash()),
+        Some(chunks) => {
+            let statuses = std::iter::once(entry.content_hash())
+                .chain(chunks.iter().map(|(hash, _len)| *hash))
+                .map(|hash| content_status_cb(hash))
+                .collect::<Vec<_>>();
+            content_status_from_parts(&statuses)
+        }
+    }
+}
+
 /// Outcome of a sync operation.
 #[derive(Debug, Clone, Default)]
 pub struct SyncOutcome {
@@ -105,6 +436,15 @@ pub struct SyncOutcome {
     pub num_recv: usize,
     /// Number of entries we sent.
     pub num_sent: usize,
+    /// Number of received entries which were inserted into our replica.
+    pub num_inserted: usize,
+    /// Number of received entries which failed validation and were dropped.
+    pub num_invalid: usize,
+    /// Whether both replicas were already in sync, i.e. the fingerprints of the initial message
+    /// matched and no further rounds were needed.
+    pub already_in_sync: bool,
+    /// Number of messages from the remote processed so far.
+    pub num_rounds: usize,
 }
 
 fn get_as_ptr<T>(value: &T) -> Option<usize> {
@@ -124,26 +464,149 @@ fn same_channel<T>(a: &async_channel::Sender<T>, b: &async_channel::Sender<T>) -
 }
 
 #[derive(Debug, Default)]
-struct Subscribers(Vec<async_channel::Sender<Event>>);
+struct Subscribers {
+    senders: Vec<async_channel::Sender<Event>>,
+    /// Senders which also receive the per-entry events of silent syncs.
+    internal: Vec<async_channel::Sender<Event>>,
+    /// The most recent events, kept for replaying them to new subscribers.
+    replay: VecDeque<Event>,
+    replay_capacity: usize,
+}
 impl Subscribers {
     pub fn subscribe(&mut self, sender: async_channel::Sender<Event>) {
-        self.0.push(sender)
+        self.senders.push(sender)
+    }
+    pub fn subscribe_with_replay(&mut self, sender: async_channel::Sender<Event>, n: usize) {
+        let skip = self.replay.len().saturating_sub(n);
+        for event in self.replay.iter().skip(skip) {
+            if sender.send_blocking(event.clone()).is_err() {
+                return;
+            }
+        }
+        self.senders.push(sender)
+    }
+    pub fn subscribe_internal(&mut self, sender: async_channel::Sender<Event>) {
+        self.internal.push(sender)
     }
     pub fn unsubscribe(&mut self, sender: &async_channel::Sender<Event>) {
-        self.0.retain(|s| !same_channel(s, sender));
+        self.senders.retain(|s| !same_channel(s, sender));
+        self.internal.retain(|s| !same_channel(s, sender));
+    }
+    pub fn set_replay_capacity(&mut self, capacity: usize) {
+        self.replay_capacity = capacity;
+        let excess = self.replay.len().saturating_sub(capacity);
+        self.replay.drain(..excess);
     }
     pub fn send(&mut self, event: Event) {
-        self.0
+        if self.replay_capacity > 0 {
+            if self.replay.len() == self.replay_capacity {
+                self.replay.pop_front();
+            }
+            self.replay.push_back(event.clone());
+        }
+        self.senders
+            .retain(|sender| sender.send_blocking(event.clone()).is_ok());
+        self.send_internal(event);
+    }
+    /// Send an event to the internal senders only.
+    fn send_internal(&mut self, event: Event) {
+        self.internal
             .retain(|sender| sender.send_blocking(event.clone()).is_ok())
     }
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.senders.len() + self.internal.len()
     }
     pub fn send_with(&mut self, f: impl FnOnce() -> Event) {
-        if !self.0.is_empty() {
+        if !self.senders.is_empty() || !self.internal.is_empty() || self.replay_capacity > 0 {
             self.send(f())
         }
     }
+    pub fn send_internal_with(&mut self, f: impl FnOnce() -> Event) {
+        if !self.internal.is_empty() {
+            self.send_internal(f())
+        }
+    }
+}
+
+/// Cache of entries read with [`Replica::get_exact`], including empty entries.
+///
+/// `None` values cache the absence of an entry. The cached records are also kept in an ordered
+/// index, so that the records under a key prefix can be invalidated without scanning the cache.
+#[derive(Debug)]
+struct ReadCache {
+    entries: lru::LruCache<RecordIdentifier, Option<SignedEntry>>,
+    index: BTreeSet<RecordIdentifier>,
+}
+
+impl ReadCache {
+    fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            entries: lru::LruCache::new(capacity),
+            index: Default::default(),
+        }
+    }
+
+    fn get(&mut self, id: &RecordIdentifier) -> Option<Option<SignedEntry>> {
+        self.entries.get(id).cloned()
+    }
+
+    fn put(&mut self, id: RecordIdentifier, entry: Option<SignedEntry>) {
+        self.index.insert(id.clone());
+        if let Some((evicted, _)) = self.entries.push(id.clone(), entry) {
+            if evicted != id {
+                self.index.remove(&evicted);
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.index.clear();
+    }
+
+    /// Invalidate all cached records of `author` whose key starts with `prefix`.
+    ///
+    /// Inserting an entry removes older entries of the same author whose key starts with the
+    /// inserted key, so those have to be invalidated as well.
+    fn invalidate(&mut self, namespace: NamespaceId, author: AuthorId, prefix: &[u8]) {
+        let start = RecordIdentifier::new(namespace, author, prefix);
+        let stale: Vec<_> = self
+            .index
+            .range(&start..)
+            .take_while(|id| id.as_ref().starts_with(start.as_ref()))
+            .cloned()
+            .collect();
+        for id in stale {
+            self.index.remove(&id);
+            self.entries.pop(&id);
+        }
+    }
+}
+
+/// Subscribers to the events of all replicas of a store.
+///
+/// Unlike [`Subscribers`], this is shared between replicas and therefore uses a lock.
+#[derive(Debug, Default)]
+pub(crate) struct AllSubscribers(
+    std::sync::Mutex<Vec<async_channel::Sender<(NamespaceId, Event)>>>,
+);
+impl AllSubscribers {
+    pub fn subscribe(&self, sender: async_channel::Sender<(NamespaceId, Event)>) {
+        self.0.lock().expect("poisoned").push(sender)
+    }
+    pub fn unsubscribe(&self, sender: &async_channel::Sender<(NamespaceId, Event)>) {
+        self.0
+            .lock()
+            .expect("poisoned")
+            .retain(|s| !same_channel(s, sender));
+    }
+    pub fn send_with(&self, namespace: NamespaceId, f: impl FnOnce() -> Event) {
+        let mut senders = self.0.lock().expect("poisoned");
+        if !senders.is_empty() {
+            let event = f();
+            senders.retain(|sender| sender.send_blocking((namespace, event.clone())).is_ok())
+        }
+    }
 }
 
 /// Kind of capability of the namespace.
@@ -151,6 +614,8 @@ impl Subscribers {
     Debug,
     Clone,
     Copy,
+    PartialEq,
+    Eq,
     Serialize,
     Deserialize,
     num_enum::IntoPrimitive,
@@ -262,6 +727,12 @@ pub struct ReplicaInfo {
     subscribers: Subscribers,
     #[debug("ContentStatusCallback")]
     content_status_cb: Option<ContentStatusCallback>,
+    #[debug("KeyNormalizer")]
+    key_normalizer: Option<KeyNormalizer>,
+    sync_event_mode: SyncEventMode,
+    max_content_len: Option<u64>,
+    generation: u64,
+    read_cache: Option<ReadCache>,
     closed: bool,
 }
 
@@ -273,6 +744,11 @@ impl ReplicaInfo {
             subscribers: Default::default(),
             // on_insert_sender: RwLock::new(None),
             content_status_cb: None,
+            key_normalizer: None,
+            sync_event_mode: Default::default(),
+            max_content_len: None,
+            generation: 0,
+            read_cache: None,
             closed: false,
         }
     }
@@ -286,6 +762,31 @@ impl ReplicaInfo {
         self.subscribers.subscribe(sender)
     }
 
+    /// Subscribe to insert events, including the per-entry events of silent syncs.
+    ///
+    /// Unlike other subscribers, `sender` receives an [`Event::RemoteInsert`] for each entry
+    /// inserted by a sync in [`SyncEventMode::Silent`]. This is meant for internal consumers
+    /// which have to see each entry, e.g. the engine, which queues content downloads for them.
+    pub fn subscribe_internal(&mut self, sender: async_channel::Sender<Event>) {
+        self.subscribers.subscribe_internal(sender)
+    }
+
+    /// Subscribe to insert events, and replay up to `n` of the most recent events first.
+    ///
+    /// Only events kept in the replay buffer can be replayed, see
+    /// [`Self::set_event_replay_capacity`]. This allows subscribing after a replica was opened
+    /// without missing the events of the first writes.
+    pub fn subscribe_with_replay(&mut self, sender: async_channel::Sender<Event>, n: usize) {
+        self.subscribers.subscribe_with_replay(sender, n)
+    }
+
+    /// Set the number of recent events kept for [`Self::subscribe_with_replay`].
+    ///
+    /// The default is zero, which disables the replay buffer.
+    pub fn set_event_replay_capacity(&mut self, capacity: usize) {
+        self.subscribers.set_replay_capacity(capacity)
+    }
+
     /// Explicitly unsubscribe a sender.
     ///
     /// Simply dropping the receiver is fine too. If you cloned a single sender to subscribe to
@@ -313,6 +814,91 @@ impl ReplicaInfo {
         }
     }
 
+    /// Set the key normalizer, or remove it by passing `None`.
+    ///
+    /// The normalizer is applied to keys passed to [`Replica::insert`], [`Replica::delete_prefix`],
+    /// [`Replica::record_id`] and the other local write methods of the replica, e.g. to strip
+    /// trailing slashes so that `foo/` and `foo` refer to the same entry. Keys under the reserved
+    /// prefixes are never normalized.
+    ///
+    /// Entries received from other peers are signed and cannot be normalized. All peers writing
+    /// to a namespace must therefore apply the same normalizer, otherwise they will create
+    /// entries for keys which the other peers consider distinct, and their replicas diverge.
+    pub fn set_key_normalizer(&mut self, normalizer: Option<KeyNormalizer>) {
+        self.key_normalizer = normalizer;
+    }
+
+    /// Apply the key normalizer to `key`, if one is set.
+    fn normalize_key<'k>(&self, key: &'k [u8]) -> Cow<'k, [u8]> {
+        match &self.key_normalizer {
+            Some(normalizer) if !is_reserved_key(key) => normalizer(key),
+            _ => Cow::Borrowed(key),
+        }
+    }
+
+    /// Set how events are emitted for entries inserted while syncing.
+    pub fn set_sync_event_mode(&mut self, mode: SyncEventMode) {
+        self.sync_event_mode = mode;
+    }
+
+    /// How events are emitted for entries inserted while syncing.
+    pub fn sync_event_mode(&self) -> SyncEventMode {
+        self.sync_event_mode
+    }
+
+    /// Set the maximum content length accepted for entries.
+    ///
+    /// Entries claiming a larger content length are rejected with
+    /// [`ValidationFailure::ContentTooLarge`], both when inserted locally and when received
+    /// while syncing. `None`, the default, accepts any length.
+    pub fn set_max_content_len(&mut self, max_content_len: Option<u64>) {
+        self.max_content_len = max_content_len;
+    }
+
+    /// The maximum content length accepted for entries, if any.
+    pub fn max_content_len(&self) -> Option<u64> {
+        self.max_content_len
+    }
+
+    /// The number of entries inserted into the replica since it was opened.
+    ///
+    /// This is bumped whenever an entry is actually written to the store, be it from a local
+    /// insert, a prefix deletion or a sync. Inserts that are superseded by existing entries do
+    /// not change the generation. The counter is kept in memory only and starts at zero each
+    /// time the replica is opened.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Enable an in-memory cache for [`Replica::get_exact`] holding up to `capacity` records.
+    ///
+    /// The least recently read records are evicted first. Cached records are invalidated when
+    /// they are overwritten or removed through the replica. Writes to the store which bypass the
+    /// replica are not seen by the cache. The cache is off by default, a `capacity` of zero
+    /// disables it again.
+    pub fn enable_read_cache(&mut self, capacity: usize) {
+        self.read_cache = NonZeroUsize::new(capacity).map(ReadCache::new);
+    }
+
+    /// Invalidate cached records after an entry of `author` with key `key` was inserted.
+    fn invalidate_read_cache(&mut self, author: AuthorId, key: &[u8]) {
+        let namespace = self.capability.id();
+        if let Some(cache) = self.read_cache.as_mut() {
+            cache.invalidate(namespace, author, key);
+        }
+    }
+
+    /// Mark the replica as closed and send [`Event::Closed`] to all subscribers.
+    ///
+    /// The subscribers are dropped afterwards.
+    pub(crate) fn close(&mut self) {
+        self.closed = true;
+        self.read_cache = None;
+        let namespace = self.capability.id();
+        self.subscribers.send(Event::Closed { namespace });
+        self.subscribers = Default::default();
+    }
+
     fn ensure_open(&self) -> Result<(), InsertError> {
         if self.closed() {
             Err(InsertError::Closed)
@@ -371,15 +957,176 @@ where
         hash: Hash,
         len: u64,
     ) -> Result<usize, InsertError> {
+        self.insert_with_timestamp(key, author, hash, len, system_time_now())
+    }
+
+    /// Insert a new record at the given key with an explicit `timestamp`.
+    ///
+    /// This is useful to import entries with known original timestamps, e.g. when migrating
+    /// from another system. The `timestamp` is in microseconds since the Unix epoch.
+    ///
+    /// The entry is only inserted if it is newer than the existing entry for the same key and
+    /// author, otherwise [`InsertError::NewerEntryExists`] is returned. Timestamps more than
+    /// [`MAX_TIMESTAMP_FUTURE_SHIFT`] in the future of our system time are rejected.
+    ///
+    /// Returns the number of entries removed as a consequence of this insertion,
+    /// or an error either if the entry failed to validate or if a store operation failed.
+    pub fn insert_with_timestamp(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        author: &Author,
+        hash: Hash,
+        len: u64,
+        timestamp: u64,
+    ) -> Result<usize, InsertError> {
+        let signed_entry = self.sign_local_entry(key, author, hash, len, timestamp)?;
+        self.insert_entry(signed_entry, InsertOrigin::Local)
+    }
+
+    /// Insert a new record at the given key, and report whether it was inserted.
+    ///
+    /// This is like [`Self::insert`], but an entry which is superseded by a newer entry for its
+    /// key or a prefix of its key is reported as [`InsertOutcome::NotInserted`] instead of
+    /// failing with [`InsertError::NewerEntryExists`].
+    pub fn insert_with_outcome(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        author: &Author,
+        hash: Hash,
+        len: u64,
+    ) -> Result<InsertOutcome, InsertError> {
+        let signed_entry = self.sign_local_entry(key, author, hash, len, system_time_now())?;
+        self.insert_entry_with_outcome(signed_entry, InsertOrigin::Local)
+    }
+
+    /// Insert a new record at the given key with [`InsertOpts`], and report whether it was
+    /// inserted.
+    ///
+    /// Like [`Self::insert_with_outcome`], but with [`InsertOpts::skip_if_unchanged`] set, no
+    /// entry is written if the latest entry of `author` at `key` already has the same `hash`
+    /// and `len`. This is reported as [`InsertOptsOutcome::Unchanged`].
+    pub fn insert_with_opts(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        author: &Author,
+        hash: Hash,
+        len: u64,
+        opts: InsertOpts,
+    ) -> Result<InsertOptsOutcome, InsertError> {
+        if opts.skip_if_unchanged {
+            self.info.ensure_open()?;
+            let id = self.record_id(key.as_ref(), author.id());
+            let existing = self
+                .get_exact(author.id(), id.key(), false)
+                .map_err(InsertError::Store)?;
+            if existing.is_some_and(|e| e.content_hash() == hash && e.content_len() == len) {
+                return Ok(InsertOptsOutcome::Unchanged);
+            }
+        }
+        self.insert_with_outcome(key, author, hash, len)
+            .map(InsertOptsOutcome::Applied)
+    }
+
+    fn sign_local_entry(
+        &self,
+        key: impl AsRef<[u8]>,
+        author: &Author,
+        hash: Hash,
+        len: u64,
+        timestamp: u64,
+    ) -> Result<SignedEntry, InsertError> {
         if len == 0 || hash == Hash::EMPTY {
             return Err(InsertError::EntryIsEmpty);
         }
         self.info.ensure_open()?;
-        let id = RecordIdentifier::new(self.id(), author.id(), key);
-        let record = Record::new_current(hash, len);
+        ensure_not_reserved(key.as_ref())?;
+        let id = self.record_id(key, author);
+        let record = Record::new(hash, len, timestamp);
+        let entry = Entry::new(id, record);
+        let secret = self.secret_key()?;
+        Ok(entry.sign(secret, author))
+    }
+
+    /// Refresh the timestamp of the entry of `author` at `key`, keeping its content.
+    ///
+    /// The entry is signed again with the current time, so that it wins over older copies held
+    /// by peers. If the timestamp of the existing entry is ahead of our clock, the new timestamp
+    /// is set just after it instead.
+    ///
+    /// Returns the new entry, or `None` if there is no entry for the key.
+    pub fn touch(
+        &mut self,
+        author: &Author,
+        key: impl AsRef<[u8]>,
+    ) -> Result<Option<SignedEntry>, InsertError> {
+        self.info.ensure_open()?;
+        let Some(existing) = self
+            .get_exact(author.id(), key, false)
+            .map_err(InsertError::Store)?
+        else {
+            return Ok(None);
+        };
+        let timestamp = system_time_now().max(existing.timestamp() + 1);
+        let mut entry = self.sign_local_entry(
+            existing.key(),
+            author,
+            existing.content_hash(),
+            existing.content_len(),
+            timestamp,
+        )?;
+        if let Some(chunks) = existing.content_chunks() {
+            entry = entry
+                .with_content_chunks(ContentChunks::new(chunks.iter().copied()))
+                .expect("manifest matches the record");
+        }
+        self.insert_entry(entry.clone(), InsertOrigin::Local)?;
+        Ok(Some(entry))
+    }
+
+    /// Insert a new chunked record at the given key.
+    ///
+    /// The record points to the manifest of `chunks`, see [`ContentChunks`] for details. The
+    /// manifest is kept in the store, so that [`SignedEntry::content_chunks`] is available on
+    /// entries read back from it. The manifest blob itself has to be added to the blob store by
+    /// the caller, so that peers can download it. The sync engine does this in its
+    /// `insert_chunked`.
+    ///
+    /// Fails with [`ValidationFailure::ContentTooLarge`] if the manifest is longer than
+    /// [`ContentChunks::MAX_MANIFEST_LEN`].
+    ///
+    /// Returns the number of entries removed as a consequence of this insertion,
+    /// or an error either if the entry failed to validate or if a store operation failed.
+    pub fn insert_chunked(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        author: &Author,
+        chunks: ContentChunks,
+    ) -> Result<usize, InsertError> {
+        if chunks.chunks().is_empty() {
+            return Err(InsertError::EntryIsEmpty);
+        }
+        self.info.ensure_open()?;
+        ensure_not_reserved(key.as_ref())?;
+        let manifest = chunks.to_manifest();
+        if manifest.len() as u64 > ContentChunks::MAX_MANIFEST_LEN {
+            return Err(ValidationFailure::ContentTooLarge {
+                len: manifest.len() as u64,
+                max: ContentChunks::MAX_MANIFEST_LEN,
+            }
+            .into());
+        }
+        let id = self.record_id(key, author);
+        let record = Record::new(
+            Hash::new(&manifest),
+            manifest.len() as u64,
+            system_time_now(),
+        );
         let entry = Entry::new(id, record);
         let secret = self.secret_key()?;
-        let signed_entry = entry.sign(secret, author);
+        let signed_entry = entry
+            .sign(secret, author)
+            .with_content_chunks(chunks)
+            .expect("manifest matches the record");
         self.insert_entry(signed_entry, InsertOrigin::Local)
     }
 
@@ -388,6 +1135,8 @@ where
     /// This inserts an empty entry with the key set to `prefix`, effectively clearing all other
     /// entries whose key starts with or is equal to the given `prefix`.
     ///
+    /// Fails with [`InsertError::ReservedKey`] if `prefix` is a reserved key.
+    ///
     /// Returns the number of entries deleted.
     pub fn delete_prefix(
         &mut self,
@@ -395,24 +1144,363 @@ where
         author: &Author,
     ) -> Result<usize, InsertError> {
         self.info.ensure_open()?;
-        let id = RecordIdentifier::new(self.id(), author.id(), prefix);
+        ensure_not_reserved(prefix.as_ref())?;
+        let id = self.record_id(prefix, author);
         let entry = Entry::new_empty(id);
         let signed_entry = entry.sign(self.secret_key()?, author);
         self.insert_entry(signed_entry, InsertOrigin::Local)
     }
 
-    /// Insert an entry into this replica which was received from a remote peer.
+    /// Delete all entries of `author` whose record identifier lies within `range`.
     ///
-    /// This will verify both the namespace and author signatures of the entry, emit an `on_insert`
-    /// event, and insert the entry into the replica store.
+    /// Deletions are per key, so this inserts one empty entry for each live entry in the range,
+    /// which costs a signature and a write for each, and each of them is synced to peers. Use
+    /// [`Self::delete_prefix`] instead where possible, which needs a single entry.
     ///
-    /// Returns the number of entries removed as a consequence of this insertion,
-    /// or an error if the entry failed to validate or if a store operation failed.
-    pub fn insert_remote_entry(
+    /// As an empty entry deletes all entries whose key starts with its key, deleting a key in
+    /// the range also deletes older entries of `author` which extend that key, even if they
+    /// are outside of the range. This only happens if the end of the range starts with a key
+    /// inside the range.
+    ///
+    /// The range follows the semantics of [`Range`]: if the end is not greater than the start,
+    /// the range wraps around the namespace.
+    ///
+    /// Returns the number of entries deleted.
+    pub fn delete_range(
         &mut self,
-        entry: SignedEntry,
-        received_from: PeerIdBytes,
-        content_status: ContentStatus,
+        range: Range<RecordIdentifier>,
+        author: &Author,
+    ) -> Result<usize, InsertError> {
+        self.info.ensure_open()?;
+        let namespace = self.id();
+        let mut keys = Vec::new();
+        for entry in self.store.get_range(range).map_err(InsertError::Store)? {
+            let entry = entry.map_err(InsertError::Store)?;
+            let id = entry.id();
+            if id.namespace() == namespace
+                && id.author() == author.id()
+                && !entry.is_empty()
+                && !is_reserved_key(id.key())
+            {
+                keys.push(id.key_bytes());
+            }
+        }
+        self.delete_keys(keys, author)
+    }
+
+    /// Insert a deletion for each of `keys`, skipping keys which extend an already deleted key.
+    fn delete_keys(&mut self, mut keys: Vec<Bytes>, author: &Author) -> Result<usize, InsertError> {
+        keys.sort();
+        let mut deleted = 0;
+        let mut last: Option<Bytes> = None;
+        for key in keys {
+            // the deletion of a key also deletes all keys which extend it.
+            if matches!(&last, Some(last) if key.starts_with(last)) {
+                continue;
+            }
+            deleted += self.delete_prefix(&key, author)?;
+            last = Some(key);
+        }
+        Ok(deleted)
+    }
+
+    /// Remove all entries of `author` from this replica.
+    ///
+    /// With [`RemoveMode::Tombstone`], a deletion is inserted for each entry of the author,
+    /// which removes the entry and is synced to peers like any other deletion. Entries with a
+    /// timestamp newer than the deletion are kept. Entries under reserved keys, like namespace
+    /// metadata, write tokens and counters, are kept as well. This needs write access to the
+    /// replica.
+    ///
+    /// With [`RemoveMode::Local`], the entries are dropped from the local store only. No events
+    /// are emitted and nothing is synced, so peers still holding the entries will send them
+    /// again on the next sync. This is useful to erase data locally on a replica which stops
+    /// syncing, or after the author's entries were deleted for everyone.
+    ///
+    /// Returns the number of entries removed.
+    pub fn remove_author_entries(
+        &mut self,
+        author: &Author,
+        mode: RemoveMode,
+    ) -> Result<usize, InsertError> {
+        match mode {
+            RemoveMode::Tombstone => {
+                self.info.ensure_open()?;
+                let id = self.record_id([], author);
+                let mut keys = Vec::new();
+                for entry in self.store.prefixed_by(&id).map_err(InsertError::Store)? {
+                    let entry = entry.map_err(InsertError::Store)?;
+                    if !entry.is_empty() && !is_reserved_key(entry.key()) {
+                        keys.push(entry.id().key_bytes());
+                    }
+                }
+                self.delete_keys(keys, author)
+            }
+            RemoveMode::Local => {
+                self.info.ensure_open()?;
+                let removed = self
+                    .store
+                    .store
+                    .remove_entries_by_author(self.id(), author.id())
+                    .map_err(InsertError::Store)?;
+                self.info.invalidate_read_cache(author.id(), &[]);
+                Ok(removed)
+            }
+        }
+    }
+
+    /// Preview which entries [`Self::delete_prefix`] would delete, without writing anything.
+    ///
+    /// Returns the identifiers of all entries of `author` whose key starts with or is equal to
+    /// `prefix` and which would be removed by a deletion at the current time. No secret is
+    /// needed, so this also works on read-only replicas.
+    pub fn delete_prefix_preview(
+        &mut self,
+        prefix: impl AsRef<[u8]>,
+        author: impl Into<AuthorId>,
+    ) -> Result<Vec<RecordIdentifier>, InsertError> {
+        self.info.ensure_open()?;
+        let id = self.record_id(prefix, author);
+        let record = Record::empty_current();
+        // Same checks as in `ranger::Store::put`: a newer entry for the prefix or one of its
+        // prefixes means that the deletion would not be inserted at all.
+        for parent in self.store.prefixes_of(&id).map_err(InsertError::Store)? {
+            if &record <= parent.map_err(InsertError::Store)?.value() {
+                return Ok(vec![]);
+            }
+        }
+        let mut ids = Vec::new();
+        for entry in self.store.prefixed_by(&id).map_err(InsertError::Store)? {
+            let entry = entry.map_err(InsertError::Store)?;
+            if entry.value() <= &record {
+                ids.push(entry.id().clone());
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Set the metadata of this namespace, signed by `author`.
+    ///
+    /// The metadata is stored as a blob, referenced by an entry under the reserved
+    /// [`NAMESPACE_META_KEY`]. The entry reconciles with other peers and its content is
+    /// downloaded like any other entry, but it is excluded from regular queries. The bytes are
+    /// kept in the store, so that they can be read with
+    /// [`crate::store::Store::get_namespace_meta`]. The blob itself has to be added to the blob
+    /// store by the caller, so that peers can download it. The sync engine does this in its
+    /// `set_namespace_meta`.
+    ///
+    /// Any metadata previously set by `author` is replaced. Setting empty metadata removes the
+    /// metadata of `author`. Fails with [`ValidationFailure::ContentTooLarge`] if `meta` is
+    /// longer than [`MAX_NAMESPACE_META_LEN`].
+    pub fn set_namespace_meta(&mut self, author: &Author, meta: Bytes) -> Result<(), InsertError> {
+        self.info.ensure_open()?;
+        if meta.len() as u64 > MAX_NAMESPACE_META_LEN {
+            return Err(ValidationFailure::ContentTooLarge {
+                len: meta.len() as u64,
+                max: MAX_NAMESPACE_META_LEN,
+            }
+            .into());
+        }
+        let id = RecordIdentifier::new(self.id(), author.id(), NAMESPACE_META_KEY);
+        let record = match meta.len() {
+            0 => Record::empty(system_time_now()),
+            len => Record::new(Hash::new(&meta), len as u64, system_time_now()),
+        };
+        let signed_entry = Entry::new(id, record).sign(self.secret_key()?, author);
+        self.insert_entry(signed_entry, InsertOrigin::Local)?;
+        if !meta.is_empty() {
+            let namespace = self.id();
+            self.store
+                .store
+                .put_namespace_meta(namespace, &meta)
+                .map_err(InsertError::Store)?;
+        }
+        Ok(())
+    }
+
+    /// Remove all entries of this replica from the local store, see
+    /// [`crate::store::Store::clear_namespace`].
+    ///
+    /// No tombstones are inserted, so peers will sync the entries back. The replica stays open,
+    /// and subscribers are kept. Returns the number of removed entries.
+    pub fn clear(&mut self) -> anyhow::Result<usize> {
+        self.info.ensure_open()?;
+        let namespace = self.id();
+        let removed = self.store.store.clear_namespace(namespace)?;
+        if let Some(cache) = self.info.read_cache.as_mut() {
+            cache.clear();
+        }
+        Ok(removed)
+    }
+
+    /// Store the manifest of chunked entries of this replica, see
+    /// [`crate::store::Store::put_content_chunks`].
+    ///
+    /// Returns the number of entries the manifest was stored for.
+    pub fn put_content_chunks(&mut self, manifest: &[u8]) -> anyhow::Result<usize> {
+        self.info.ensure_open()?;
+        let namespace = self.id();
+        let count = self.store.store.put_content_chunks(namespace, manifest)?;
+        if count > 0 {
+            if let Some(cache) = self.info.read_cache.as_mut() {
+                cache.clear();
+            }
+        }
+        Ok(count)
+    }
+
+    /// Add `delta` to the counter at `key`, see [`CounterValue`].
+    ///
+    /// This replaces the contribution of `author` to the counter with its sum with `delta`.
+    /// Contributions of other authors are kept, and are summed up when reading the counter with
+    /// [`crate::store::Store::get_counter`]. `delta` may be negative.
+    ///
+    /// Returns the new contribution of `author`.
+    pub fn counter_add(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        author: &Author,
+        delta: i64,
+    ) -> Result<i64, InsertError> {
+        self.info.ensure_open()?;
+        let key = key.as_ref();
+        let prefix =
+            RecordIdentifier::new(self.id(), author.id(), CounterValue::entry_key_prefix(key));
+        let mut current: Option<SignedEntry> = None;
+        let mut latest = 0;
+        for entry in self
+            .store
+            .prefixed_by(&prefix)
+            .map_err(InsertError::Store)?
+        {
+            let entry = entry.map_err(InsertError::Store)?;
+            latest = latest.max(entry.timestamp());
+            if !entry.is_empty()
+                && current
+                    .as_ref()
+                    .map_or(true, |current| entry.record() > current.record())
+            {
+                current = Some(entry);
+            }
+        }
+        let current = current
+            .and_then(|entry| CounterValue::from_entry_key(entry.key()))
+            .map_or(0, |value| value.value());
+        let value = current.saturating_add(delta);
+
+        // Clear the previous contribution with a prefix delete first. It has to be newer than
+        // the previous contribution even if that was written with a clock ahead of ours.
+        let timestamp = system_time_now().max(latest.saturating_add(1));
+        let tombstone = Entry::new(prefix, Record::empty(timestamp));
+        let signed_entry = tombstone.sign(self.secret_key()?, author);
+        self.insert_entry(signed_entry, InsertOrigin::Local)?;
+        let key = CounterValue::new(Bytes::copy_from_slice(key), value).to_entry_key();
+        let bytes = value.to_be_bytes();
+        let id = RecordIdentifier::new(self.id(), author.id(), key);
+        let record = Record::new(Hash::new(bytes), bytes.len() as u64, timestamp + 1);
+        let signed_entry = Entry::new(id, record).sign(self.secret_key()?, author);
+        self.insert_entry(signed_entry, InsertOrigin::Local)?;
+        Ok(value)
+    }
+
+    /// Insert a new record at the given key, authorized by a [`WriteToken`].
+    ///
+    /// This allows to write to a namespace without access to its [`NamespaceSecret`]. The token
+    /// must have been issued to `author` with [`WriteToken::issue`]. Before the first entry, the
+    /// token is presented in an entry under the reserved [`WRITE_TOKEN_PREFIX`], which is synced
+    /// to other peers so that they can validate entries signed under the token.
+    ///
+    /// Peers which do not support write tokens reject such entries as badly signed.
+    ///
+    /// Returns the number of entries removed as a consequence of this insertion,
+    /// or an error either if the entry failed to validate or if a store operation failed.
+    pub fn insert_with_token(
+        &mut self,
+        key: impl AsRef<[u8]>,
+        author: &Author,
+        token: &WriteToken,
+        hash: Hash,
+        len: u64,
+    ) -> Result<usize, InsertError> {
+        if len == 0 || hash == Hash::EMPTY {
+            return Err(InsertError::EntryIsEmpty);
+        }
+        self.info.ensure_open()?;
+        ensure_not_reserved(key.as_ref())?;
+        if token.namespace() != self.id() {
+            return Err(ValidationFailure::InvalidNamespace {
+                expected: self.id(),
+                got: token.namespace(),
+            }
+            .into());
+        }
+        let timestamp = system_time_now();
+
+        let token_bytes = token.to_bytes();
+        let token_key = [WRITE_TOKEN_PREFIX, &token_bytes].concat();
+        let token_id = self.record_id(&token_key, author);
+        if self
+            .store
+            .get(&token_id)
+            .map_err(InsertError::Store)?
+            .is_none()
+        {
+            let len = token_bytes.len() as u64;
+            let record = Record::new(Hash::new(&token_bytes), len, timestamp);
+            self.insert_entry_with_token(Entry::new(token_id, record), token, author)?;
+        }
+
+        let id = self.record_id(key, author);
+        let record = Record::new(hash, len, timestamp);
+        self.insert_entry_with_token(Entry::new(id, record), token, author)
+    }
+
+    fn insert_entry_with_token(
+        &mut self,
+        entry: Entry,
+        token: &WriteToken,
+        author: &Author,
+    ) -> Result<usize, InsertError> {
+        let signature = EntrySignature::from_entry_with_token(&entry, token.id(), author);
+        let signed_entry = SignedEntry::new(signature, entry);
+        // Local inserts are not verified, but tokens are not guaranteed to be valid.
+        let now = system_time_now();
+        validate_write_token(now, &self.store, &signed_entry, &PendingTokens::default())?;
+        self.insert_entry(signed_entry, InsertOrigin::Local)
+    }
+
+    /// Revoke a [`WriteToken`] issued for this namespace.
+    ///
+    /// The revocation is stored in an entry under the reserved [`WRITE_TOKEN_REVOCATION_PREFIX`],
+    /// signed by `author` and the namespace, and reconciles with other peers like any other entry.
+    /// Once a peer has the revocation, it rejects all entries signed under the token, which
+    /// includes entries signed before the revocation which the peer did not have yet.
+    pub fn revoke_write_token(
+        &mut self,
+        author: &Author,
+        id: WriteTokenId,
+    ) -> Result<usize, InsertError> {
+        self.info.ensure_open()?;
+        let key = [WRITE_TOKEN_REVOCATION_PREFIX, id.as_bytes()].concat();
+        let record_id = self.record_id(key, author);
+        let len = id.as_bytes().len() as u64;
+        let record = Record::new(Hash::new(id.as_bytes()), len, system_time_now());
+        let signed_entry = Entry::new(record_id, record).sign(self.secret_key()?, author);
+        self.insert_entry(signed_entry, InsertOrigin::Local)
+    }
+
+    /// Insert an entry into this replica which was received from a remote peer.
+    ///
+    /// This will verify both the namespace and author signatures of the entry, emit an `on_insert`
+    /// event, and insert the entry into the replica store.
+    ///
+    /// Returns the number of entries removed as a consequence of this insertion,
+    /// or an error if the entry failed to validate or if a store operation failed.
+    pub fn insert_remote_entry(
+        &mut self,
+        entry: SignedEntry,
+        received_from: PeerIdBytes,
+        content_status: ContentStatus,
     ) -> Result<usize, InsertError> {
         self.info.ensure_open()?;
         entry.validate_empty()?;
@@ -423,6 +1511,90 @@ where
         self.insert_entry(entry, origin)
     }
 
+    /// Insert the entry of an [`EntryTicket`] into this replica.
+    ///
+    /// The entry is inserted as a remote entry received from `received_from`, the peer that
+    /// shared the ticket, and is reported as an [`Event::RemoteInsert`]. The signatures of the
+    /// entry are always verified.
+    ///
+    /// Returns the number of entries removed as a consequence of this insertion.
+    pub fn insert_entry_ticket(
+        &mut self,
+        ticket: EntryTicket,
+        received_from: PeerIdBytes,
+        content_status: ContentStatus,
+    ) -> Result<usize, InsertError> {
+        if ticket.namespace != self.id() {
+            return Err(ValidationFailure::InvalidNamespace {
+                expected: self.id(),
+                got: ticket.namespace,
+            }
+            .into());
+        }
+        self.insert_remote_entry(ticket.entry, received_from, content_status)
+    }
+
+    /// Insert an entry which was signed elsewhere as a local entry.
+    ///
+    /// This is useful if entries are signed outside of this process, e.g. with a hardware
+    /// security module. The replica must be writable, and the signatures of the entry are
+    /// always verified. The entry is reported as an [`Event::LocalInsert`].
+    ///
+    /// Returns the number of entries removed as a consequence of this insertion.
+    pub fn insert_signed_local(&mut self, entry: SignedEntry) -> Result<usize, InsertError> {
+        self.info.ensure_open()?;
+        self.secret_key()?;
+        entry.validate_empty()?;
+        validate_entry_with(system_time_now(), &self.store, self.id(), &entry, true)?;
+        self.insert_entry(entry, InsertOrigin::Local)
+    }
+
+    /// Validate and insert `entries` without emitting events.
+    ///
+    /// Used by [`store::Store::rebuild_namespace`]. Signatures are always verified.
+    pub(crate) fn rebuild_from_entries(
+        &mut self,
+        entries: impl IntoIterator<Item = SignedEntry>,
+    ) -> anyhow::Result<RebuildStats> {
+        self.info.ensure_open()?;
+        let namespace = self.id();
+        let now = system_time_now();
+        // Entries signed under a write token can only be validated once the token is known,
+        // so insert revocations and tokens first. Otherwise insert older entries first.
+        let mut entries = entries.into_iter().collect::<Vec<_>>();
+        entries.sort_by_key(|entry| {
+            let rank = if entry.key().starts_with(WRITE_TOKEN_REVOCATION_PREFIX) {
+                0
+            } else if entry.key().starts_with(WRITE_TOKEN_PREFIX) {
+                1
+            } else {
+                2
+            };
+            (rank, entry.timestamp())
+        });
+
+        let mut stats = RebuildStats::default();
+        for entry in entries {
+            let valid = entry.validate_empty().is_ok()
+                && validate_entry_with(now, &self.store, namespace, &entry, true).is_ok();
+            if !valid {
+                stats.invalid += 1;
+                continue;
+            }
+            let (author, key) = (entry.author(), entry.key().to_vec());
+            match self.store.put(entry)? {
+                InsertOutcome::Inserted { removed } => {
+                    self.info.generation += 1;
+                    self.info.invalidate_read_cache(author, &key);
+                    stats.inserted += 1;
+                    stats.removed += removed;
+                }
+                InsertOutcome::NotInserted => stats.not_inserted += 1,
+            }
+        }
+        Ok(stats)
+    }
+
     /// Insert a signed entry into the database.
     ///
     /// Returns the number of entries removed as a consequence of this insertion.
@@ -431,21 +1603,44 @@ where
         entry: SignedEntry,
         origin: InsertOrigin,
     ) -> Result<usize, InsertError> {
+        match self.insert_entry_with_outcome(entry, origin)? {
+            InsertOutcome::Inserted { removed } => Ok(removed),
+            InsertOutcome::NotInserted => Err(InsertError::NewerEntryExists),
+        }
+    }
+
+    /// Insert a signed entry into the database, and emit an event if it was inserted.
+    fn insert_entry_with_outcome(
+        &mut self,
+        entry: SignedEntry,
+        origin: InsertOrigin,
+    ) -> Result<InsertOutcome, InsertError> {
         let namespace = self.id();
 
         #[cfg(feature = "metrics")]
         let len = entry.content_len();
 
         let store = &self.store;
-        validate_entry(system_time_now(), store, namespace, &entry, &origin)?;
+        let max_content_len = self.info.max_content_len;
+        validate_entry(
+            system_time_now(),
+            store,
+            namespace,
+            &entry,
+            &origin,
+            max_content_len,
+            false,
+            &PendingTokens::default(),
+        )?;
 
         let outcome = self.store.put(entry.clone()).map_err(InsertError::Store)?;
         tracing::debug!(?origin, hash = %entry.content_hash(), ?outcome, "insert");
 
-        let removed_count = match outcome {
-            InsertOutcome::Inserted { removed } => removed,
-            InsertOutcome::NotInserted => return Err(InsertError::NewerEntryExists),
-        };
+        if let InsertOutcome::NotInserted = outcome {
+            return Ok(outcome);
+        }
+        self.info.generation += 1;
+        self.info.invalidate_read_cache(entry.author(), entry.key());
 
         let insert_event = match origin {
             InsertOrigin::Local => {
@@ -470,7 +1665,7 @@ where
                     .store
                     .get_download_policy(&self.id())
                     .unwrap_or_default();
-                let should_download = download_policy.matches(entry.entry());
+                let should_download = should_download(&entry, &download_policy);
                 Event::RemoteInsert {
                     namespace,
                     entry,
@@ -481,9 +1676,13 @@ where
             }
         };
 
+        self.store
+            .store
+            .all_subscribers
+            .send_with(namespace, || insert_event.clone());
         self.info.subscribers.send(insert_event);
 
-        Ok(removed_count)
+        Ok(outcome)
     }
 
     /// Hashes the given data and inserts it.
@@ -504,8 +1703,87 @@ where
     }
 
     /// Get the identifier for an entry in this replica.
-    pub fn record_id(&self, key: impl AsRef<[u8]>, author: &Author) -> RecordIdentifier {
-        RecordIdentifier::new(self.info.capability.id(), author.id(), key)
+    ///
+    /// The author may be passed as [`Author`], [`AuthorId`] or [`AuthorPublicKey`], as no
+    /// secret is needed to compute the identifier. The key is normalized with the
+    /// [`KeyNormalizer`] of the replica, if one is set.
+    pub fn record_id(
+        &self,
+        key: impl AsRef<[u8]>,
+        author: impl Into<AuthorId>,
+    ) -> RecordIdentifier {
+        let key = self.info.normalize_key(key.as_ref());
+        RecordIdentifier::new(self.info.capability.id(), author, key)
+    }
+
+    /// Get an entry by key and author.
+    ///
+    /// Same as [`store::fs::Store::get_exact`], but served from memory if the read cache is
+    /// enabled and holds the record, see [`ReplicaInfo::enable_read_cache`].
+    pub fn get_exact(
+        &mut self,
+        author: impl Into<AuthorId>,
+        key: impl AsRef<[u8]>,
+        include_empty: bool,
+    ) -> anyhow::Result<Option<SignedEntry>> {
+        if is_reserved_key(key.as_ref()) {
+            return Ok(None);
+        }
+        let id = RecordIdentifier::new(self.id(), author, key);
+        let cached = self
+            .info
+            .read_cache
+            .as_mut()
+            .and_then(|cache| cache.get(&id));
+        let entry = match cached {
+            Some(entry) => entry,
+            None => {
+                let entry =
+                    self.store
+                        .store
+                        .get_exact(id.namespace(), id.author(), id.key(), true)?;
+                if let Some(cache) = self.info.read_cache.as_mut() {
+                    cache.put(id, entry.clone());
+                }
+                entry
+            }
+        };
+        Ok(entry.filter(|entry| include_empty || !entry.is_empty()))
+    }
+
+    /// Get the fingerprint of all entries in this replica.
+    ///
+    /// Two replicas of the same namespace with equal fingerprints contain the same entries,
+    /// which makes this a cheap way to check whether anything changed, or whether two peers
+    /// are in sync, without running the sync protocol.
+    pub fn fingerprint(&mut self) -> anyhow::Result<Fingerprint> {
+        self.info.ensure_open().map_err(anyhow::Error::from)?;
+        let x = self.store.get_first()?;
+        let range = ranger::Range::new(x.clone(), x);
+        self.store.get_fingerprint(&range)
+    }
+
+    /// Whether the fingerprint of all entries in this replica equals the `remote` fingerprint.
+    ///
+    /// With the fingerprint of a remote replica of the same namespace, see [`Self::fingerprint`],
+    /// this tells whether both are in sync, in which case a sync can be skipped.
+    pub fn matches_fingerprint(&mut self, remote: Fingerprint) -> anyhow::Result<bool> {
+        Ok(self.fingerprint()? == remote)
+    }
+
+    /// Compare the latest entry timestamp of each author in this replica with the heads of a
+    /// remote peer.
+    ///
+    /// This only reads from the local store, so it can be used to find out whether a sync
+    /// with the peer would transfer anything without connecting to it.
+    pub fn compare_heads(&mut self, remote: &AuthorHeads) -> anyhow::Result<HeadComparison> {
+        let ours = self
+            .store
+            .store
+            .get_latest_for_each_author(self.id())?
+            .map(|res| res.map(|(author, timestamp, _key)| (author, timestamp)))
+            .collect::<anyhow::Result<AuthorHeads>>()?;
+        Ok(ours.compare(remote))
     }
 
     /// Create the initial message for the set reconciliation flow with a remote peer.
@@ -514,9 +1792,67 @@ where
         self.store.initial_message().map_err(Into::into)
     }
 
+    /// Create the initial message for the set reconciliation flow, using the [`AuthorHeads`] of
+    /// the remote peer as a hint.
+    ///
+    /// For each author whose latest entry is newer than the head of the peer, the entries newer
+    /// than that head are sent along with the fingerprint of the initial message. The peer
+    /// inserts them before comparing fingerprints, so if they were all it was missing, the sync
+    /// finishes after a single message. Otherwise reconciliation continues as usual. If we have
+    /// no newer entries for any author, this is the same as [`Self::sync_initial_message`].
+    ///
+    /// Outdated heads only lead to entries being sent which the peer already has, they never
+    /// cause entries to be missed.
+    pub fn sync_initial_message_with_hint(
+        &mut self,
+        peer_heads: &AuthorHeads,
+    ) -> anyhow::Result<crate::ranger::Message<SignedEntry>> {
+        self.info.ensure_open().map_err(anyhow::Error::from)?;
+        let namespace = self.id();
+        let ahead = self
+            .store
+            .store
+            .get_latest_for_each_author(namespace)?
+            .filter_map(|res| match res {
+                Ok((author, timestamp, _key)) => match peer_heads.get(&author) {
+                    Some(theirs) if theirs >= timestamp => None,
+                    theirs => Some(Ok((author, theirs))),
+                },
+                Err(err) => Some(Err(err)),
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let cb = self.info.content_status_cb.clone();
+        let mut values = Vec::new();
+        for (author, theirs) in ahead {
+            let prefix = RecordIdentifier::new(namespace, author, []);
+            for entry in self.store.prefixed_by(&prefix)? {
+                let entry = entry?;
+                if theirs.is_some_and(|theirs| entry.timestamp() <= theirs) {
+                    continue;
+                }
+                let content_status = match cb.as_ref() {
+                    Some(cb) => entry_content_status(&entry, cb),
+                    None => ContentStatus::Missing,
+                };
+                values.push((entry, content_status));
+            }
+        }
+        let config = self.store.store.sync_config();
+        self.store.initial_message_with_values(&config, values)
+    }
+
     /// Process a set reconciliation message from a remote peer.
     ///
     /// Returns the next message to be sent to the peer, if any.
+    ///
+    /// If the store's [`SyncConfig::max_entries_received`] would be exceeded by the entries in
+    /// this message, the message is not processed and a [`SyncError::LimitExceeded`] is returned.
+    /// Entries inserted while processing earlier messages of the same sync session are kept.
+    ///
+    /// Inserted entries are counted in [`SyncOutcome::num_inserted`]. If the replica's
+    /// [`SyncEventMode`] is [`SyncEventMode::Silent`], no events are emitted for them; call
+    /// [`Self::sync_batch_applied`] once the sync finished instead.
     pub fn sync_process_message(
         &mut self,
         message: crate::ranger::Message<SignedEntry>,
@@ -526,9 +1862,29 @@ where
         self.info.ensure_open()?;
         let my_namespace = self.id();
         let now = system_time_now();
+        state.num_rounds += 1;
+        let _span = tracing::debug_span!(
+            "sync_round",
+            namespace = %my_namespace.fmt_short(),
+            peer = %hex::encode(&from_peer[..5]),
+            round = state.num_rounds,
+            num_values = message.value_count(),
+        )
+        .entered();
 
         // update state with incoming data.
-        state.num_recv += message.value_count();
+        let config = self.store.store.sync_config();
+        let num_recv = state.num_recv + message.value_count();
+        if let Some(limit) = config.max_entries_received() {
+            if num_recv > limit {
+                return Err(SyncError::LimitExceeded {
+                    count: num_recv,
+                    limit,
+                }
+                .into());
+            }
+        }
+        state.num_recv = num_recv;
         for (entry, _content_status) in message.values() {
             state
                 .heads_received
@@ -538,12 +1894,20 @@ where
         // let subscribers = std::rc::Rc::new(&mut self.subscribers);
         // l
         let cb = self.info.content_status_cb.clone();
+        let silent = self.info.sync_event_mode == SyncEventMode::Silent;
+        let max_content_len = self.info.max_content_len;
+        let num_invalid = std::cell::Cell::new(0);
         let download_policy = self
             .store
             .get_download_policy(&my_namespace)
             .unwrap_or_default();
+        let verified = BatchVerified::new(&self.store, my_namespace, &message);
+        // Entries signed under a write token can only be validated once the token is known. As
+        // entries arrive in no particular order, take the tokens and revocations of this message
+        // into account when validating its other entries.
+        let pending_tokens = PendingTokens::new(now, &self.store, my_namespace, &message);
         let reply = self.store.process_message(
-            &Default::default(),
+            &config,
             message,
             // validate callback: validate incoming entries, and send to on_insert channel
             |store, entry, content_status| {
@@ -551,13 +1915,30 @@ where
                     from: from_peer,
                     remote_content_status: content_status,
                 };
-                validate_entry(now, store, my_namespace, entry, &origin).is_ok()
+                let valid = validate_entry(
+                    now,
+                    store,
+                    my_namespace,
+                    entry,
+                    &origin,
+                    max_content_len,
+                    verified.contains(entry),
+                    &pending_tokens,
+                )
+                .is_ok();
+                if !valid {
+                    num_invalid.set(num_invalid.get() + 1);
+                }
+                valid
             },
             // on_insert callback: is called when an entry was actually inserted in the store
-            |_store, entry, content_status| {
+            |store, entry, content_status| {
+                state.num_inserted += 1;
+                self.info.generation += 1;
+                self.info.invalidate_read_cache(entry.author(), entry.key());
                 // We use `send_with` to only clone the entry if we have active subscriptions.
-                self.info.subscribers.send_with(|| {
-                    let should_download = download_policy.matches(entry.entry());
+                let event = || {
+                    let should_download = should_download(&entry, &download_policy);
                     Event::RemoteInsert {
                         from: from_peer,
                         namespace: my_namespace,
@@ -565,18 +1946,27 @@ where
                         should_download,
                         remote_content_status: content_status,
                     }
-                })
+                };
+                if silent {
+                    // internal subscribers still see each entry, to queue downloads
+                    self.info.subscribers.send_internal_with(event);
+                    return;
+                }
+                store.store.all_subscribers.send_with(my_namespace, event);
+                self.info.subscribers.send_with(event);
             },
             // content_status callback: get content status for outgoing entries
             |_store, entry| {
                 if let Some(cb) = cb.as_ref() {
-                    cb(entry.content_hash())
+                    entry_content_status(entry, cb)
                 } else {
                     ContentStatus::Missing
                 }
             },
         )?;
 
+        state.num_invalid += num_invalid.get();
+
         // update state with outgoing data.
         if let Some(ref reply) = reply {
             state.num_sent += reply.value_count();
@@ -585,11 +1975,55 @@ where
         Ok(reply)
     }
 
+    /// Emit a single [`Event::SyncBatchApplied`] for entries inserted by a silent sync.
+    ///
+    /// Does nothing unless the replica's [`SyncEventMode`] is [`SyncEventMode::Silent`] and
+    /// `count` is non-zero, because otherwise events were already emitted for each entry.
+    pub fn sync_batch_applied(&mut self, count: usize) {
+        if self.info.sync_event_mode != SyncEventMode::Silent || count == 0 {
+            return;
+        }
+        let namespace = self.id();
+        let event = || Event::SyncBatchApplied { namespace, count };
+        self.store.store.all_subscribers.send_with(namespace, event);
+        self.info.subscribers.send_with(event);
+    }
+
+    /// Record a change of the availability of content referenced in this replica.
+    ///
+    /// Like [`crate::store::Store::notify_content_status`], but the
+    /// [`Event::ContentStatusChanged`] is also sent to the subscribers of this replica.
+    pub fn notify_content_status(&mut self, hash: Hash, status: ContentStatus) -> bool {
+        let namespace = self.id();
+        if !self
+            .store
+            .store
+            .notify_content_status(namespace, hash, status)
+        {
+            return false;
+        }
+        self.info
+            .subscribers
+            .send_with(|| Event::ContentStatusChanged {
+                namespace,
+                hash,
+                status,
+            });
+        true
+    }
+
     /// Get the namespace identifier for this [`Replica`].
     pub fn id(&self) -> NamespaceId {
         self.info.capability.id()
     }
 
+    /// Get the current write generation of this [`Replica`].
+    ///
+    /// See [`ReplicaInfo::generation`].
+    pub fn generation(&self) -> u64 {
+        self.info.generation()
+    }
+
     /// Get the [`Capability`] of this [`Replica`].
     pub fn capability(&self) -> &Capability {
         &self.info.capability
@@ -614,28 +2048,295 @@ pub struct ReadOnly;
 /// * the entry's namespace matches the current replica
 /// * the entry's timestamp is not more than 10 minutes in the future of our system time
 /// * the entry is newer than an existing entry for the same key and author, if such exists.
-fn validate_entry<S: ranger::Store<SignedEntry> + PublicKeyStore>(
+///
+/// The signatures are not verified again if `verified` is true, e.g. because they were verified
+/// in a batch already.
+#[allow(clippy::too_many_arguments)]
+fn validate_entry(
     now: u64,
-    store: &S,
+    store: &StoreInstance,
     expected_namespace: NamespaceId,
     entry: &SignedEntry,
     origin: &InsertOrigin,
+    max_content_len: Option<u64>,
+    verified: bool,
+    pending_tokens: &PendingTokens,
 ) -> Result<(), ValidationFailure> {
-    // Verify the namespace
-    if entry.namespace() != expected_namespace {
-        return Err(ValidationFailure::InvalidNamespace);
+    if let Some(max) = max_content_len {
+        if entry.content_len() > max {
+            return Err(ValidationFailure::ContentTooLarge {
+                len: entry.content_len(),
+                max,
+            });
+        }
     }
-
-    // Verify signature for non-local entries.
-    if !matches!(origin, InsertOrigin::Local) && entry.verify(store).is_err() {
-        return Err(ValidationFailure::BadSignature);
+    // Namespace metadata is downloaded regardless of the download policy, so it is bounded.
+    if entry.key() == NAMESPACE_META_KEY && entry.content_len() > MAX_NAMESPACE_META_LEN {
+        return Err(ValidationFailure::ContentTooLarge {
+            len: entry.content_len(),
+            max: MAX_NAMESPACE_META_LEN,
+        });
     }
 
-    // Verify that the timestamp of the entry is not too far in the future.
-    if entry.timestamp() > now + MAX_TIMESTAMP_FUTURE_SHIFT {
-        return Err(ValidationFailure::TooFarInTheFuture);
+    // Verify signature for non-local entries, and for local entries if they are not trusted.
+    let verify = !verified
+        && match origin {
+            InsertOrigin::Local => !store.store.trust_local(),
+            InsertOrigin::Sync { .. } => true,
+        };
+    validate_entry_with_tokens(
+        now,
+        store,
+        expected_namespace,
+        entry,
+        verify,
+        pending_tokens,
+    )
+}
+
+/// Write tokens and revocations received in a sync message which may not be in the store yet.
+///
+/// Only tokens and revocations whose entries are valid themselves are included.
+#[derive(Debug, Default)]
+struct PendingTokens {
+    tokens: Vec<WriteToken>,
+    revoked: std::collections::HashSet<WriteTokenId>,
+}
+
+impl PendingTokens {
+    fn new(
+        now: u64,
+        store: &StoreInstance,
+        namespace: NamespaceId,
+        message: &crate::ranger::Message<SignedEntry>,
+    ) -> Self {
+        let mut pending = Self::default();
+        // Revocations are signed by the namespace, so they don't depend on any token.
+        for (entry, _content_status) in message.values() {
+            let Some(id) = entry.key().strip_prefix(WRITE_TOKEN_REVOCATION_PREFIX) else {
+                continue;
+            };
+            let Ok(id) = <[u8; 16]>::try_from(id) else {
+                continue;
+            };
+            if !entry.is_empty()
+                && validate_entry_with_tokens(now, store, namespace, entry, true, &pending).is_ok()
+            {
+                pending.revoked.insert(id.into());
+            }
+        }
+        for (entry, _content_status) in message.values() {
+            let Some(bytes) = entry.key().strip_prefix(WRITE_TOKEN_PREFIX) else {
+                continue;
+            };
+            let Ok(token) = WriteToken::from_bytes(bytes) else {
+                continue;
+            };
+            if !entry.is_empty()
+                && validate_entry_with_tokens(now, store, namespace, entry, true, &pending).is_ok()
+            {
+                pending.tokens.push(token);
+            }
+        }
+        pending
     }
-    Ok(())
+}
+
+/// Entries of a sync message whose signatures were verified in a batch.
+#[derive(Debug, Default)]
+struct BatchVerified(HashMap<RecordIdentifier, Vec<SignedEntry>>);
+
+impl BatchVerified {
+    /// Verify the signatures of the entries in each [`ranger::RangeItem`] of `message` in a batch.
+    ///
+    /// Entries signed under a [`WriteToken`] are left to be verified individually. If a batch
+    /// fails, none of its entries are marked as verified, so that verifying them individually
+    /// finds the invalid ones.
+    fn new(store: &StoreInstance, namespace: NamespaceId, message: &ProtocolMessage) -> Self {
+        let mut verified = Self::default();
+        // Only the author signatures are verified for trusted namespaces.
+        if store.store.trusts_namespace(&namespace) {
+            return verified;
+        }
+        let Ok(namespace_key) = namespace.public_key(store) else {
+            return verified;
+        };
+        for values in message.parts().iter().filter_map(|part| part.values()) {
+            let batch = values
+                .iter()
+                .filter(|(entry, _)| {
+                    entry.namespace() == namespace && entry.write_token_id().is_none()
+                })
+                .filter_map(|(entry, _)| Some((entry, entry.author().public_key(store).ok()?)))
+                .collect::<Vec<_>>();
+            if batch.len() < 2 {
+                continue;
+            }
+            let entries = batch
+                .iter()
+                .map(|(entry, author)| (entry.entry(), &namespace_key, author, entry.signature()))
+                .collect::<Vec<_>>();
+            match EntrySignature::verify_batch(&entries) {
+                Ok(()) => {
+                    for (entry, _) in batch {
+                        let id = entry.id().clone();
+                        verified.0.entry(id).or_default().push(entry.clone());
+                    }
+                }
+                Err(_) => tracing::debug!(
+                    num_entries = batch.len(),
+                    "batch verification failed, verifying entries individually"
+                ),
+            }
+        }
+        verified
+    }
+
+    fn contains(&self, entry: &SignedEntry) -> bool {
+        self.0
+            .get(entry.id())
+            .is_some_and(|entries| entries.contains(entry))
+    }
+}
+
+/// Validate an entry, verifying its signatures only if `verify` is true.
+fn validate_entry_with(
+    now: u64,
+    store: &StoreInstance,
+    expected_namespace: NamespaceId,
+    entry: &SignedEntry,
+    verify: bool,
+) -> Result<(), ValidationFailure> {
+    validate_entry_with_tokens(
+        now,
+        store,
+        expected_namespace,
+        entry,
+        verify,
+        &PendingTokens::default(),
+    )
+}
+
+fn validate_entry_with_tokens(
+    now: u64,
+    store: &StoreInstance,
+    expected_namespace: NamespaceId,
+    entry: &SignedEntry,
+    verify: bool,
+    pending_tokens: &PendingTokens,
+) -> Result<(), ValidationFailure> {
+    // Verify the namespace
+    if entry.namespace() != expected_namespace {
+        return Err(ValidationFailure::InvalidNamespace {
+            expected: expected_namespace,
+            got: entry.namespace(),
+        });
+    }
+
+    if verify && store.store.trusts_namespace(&expected_namespace) {
+        entry
+            .verify_author_only(store)
+            .map_err(|_| ValidationFailure::BadSignature)?;
+    } else if verify && entry.verify(store).is_err() {
+        // Entries which are not signed by the namespace may still be signed under a write token.
+        validate_write_token(now, store, entry, pending_tokens)?;
+    }
+
+    // Verify that the timestamp of the entry is not too far in the future.
+    if entry.timestamp() > now + MAX_TIMESTAMP_FUTURE_SHIFT {
+        return Err(ValidationFailure::TooFarInTheFuture {
+            timestamp: entry.timestamp(),
+            now,
+            limit: MAX_TIMESTAMP_FUTURE_SHIFT,
+        });
+    }
+    Ok(())
+}
+
+/// Validate an entry signed under a [`WriteToken`].
+///
+/// The token is identified by the id in the entry signature. It is either presented by the entry
+/// itself, if its key is below [`WRITE_TOKEN_PREFIX`], or must have been presented by the author
+/// before, possibly in the same sync message.
+///
+/// The entry timestamp is chosen by the author, so an expired token is rejected by our own time
+/// `now` as well, and not only if the entry timestamp is after the expiry.
+fn validate_write_token(
+    now: u64,
+    store: &StoreInstance,
+    entry: &SignedEntry,
+    pending_tokens: &PendingTokens,
+) -> Result<(), ValidationFailure> {
+    let author = entry
+        .author()
+        .public_key(store)
+        .map_err(|_| ValidationFailure::BadSignature)?;
+    let token_id = entry
+        .signature()
+        .verify_with_token(entry.entry(), &author)
+        .map_err(|_| ValidationFailure::BadSignature)?;
+
+    // Only the namespace owner may revoke tokens.
+    if entry.key().starts_with(WRITE_TOKEN_REVOCATION_PREFIX) {
+        return Err(ValidationFailure::BadSignature);
+    }
+
+    let namespace = entry.namespace();
+    let token = match entry.key().strip_prefix(WRITE_TOKEN_PREFIX) {
+        Some(bytes) => WriteToken::from_bytes(bytes)
+            .and_then(|token| {
+                let revoked = store.store.is_write_token_revoked(namespace, token.id())?;
+                Ok(Some((token, revoked)))
+            })
+            .map_err(|_| ValidationFailure::InvalidWriteToken)?,
+        None => {
+            let tokens = store
+                .store
+                .get_write_tokens(namespace, entry.author())
+                .map_err(|err| {
+                    tracing::warn!(?err, "failed to read write tokens");
+                    ValidationFailure::InvalidWriteToken
+                })?;
+            let pending = pending_tokens
+                .tokens
+                .iter()
+                .filter(|token| token.author() == entry.author())
+                .map(|token| (token.clone(), false));
+            tokens
+                .into_iter()
+                .chain(pending)
+                .find(|(token, _revoked)| token.id() == token_id)
+        }
+    };
+    let Some((token, revoked)) = token.filter(|(token, _revoked)| {
+        token.id() == token_id
+            && token.namespace() == namespace
+            && token.author() == entry.author()
+            && token.verify(store).is_ok()
+    }) else {
+        return Err(ValidationFailure::InvalidWriteToken);
+    };
+
+    if revoked || pending_tokens.revoked.contains(&token.id()) {
+        return Err(ValidationFailure::WriteTokenRevoked { token: token.id() });
+    }
+    if entry.timestamp() > token.expires_at() || now > token.expires_at() {
+        return Err(ValidationFailure::WriteTokenExpired {
+            timestamp: entry.timestamp(),
+            now,
+            expires_at: token.expires_at(),
+        });
+    }
+    Ok(())
+}
+
+/// How [`Replica::remove_author_entries`] removes entries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemoveMode {
+    /// Write a deletion, which removes the entries on all peers we sync with.
+    Tombstone,
+    /// Drop the entries from the local store only, they will be synced back from peers.
+    Local,
 }
 
 /// Error emitted when inserting entries into a [`Replica`] failed
@@ -653,6 +2354,9 @@ pub enum InsertError {
     /// Attempted to insert an empty entry.
     #[error("Attempted to insert an empty entry")]
     EntryIsEmpty,
+    /// Attempted to write to a key reserved for namespace metadata, write tokens or counters.
+    #[error("Attempted to write to a reserved key")]
+    ReservedKey,
     /// Replica is read only.
     #[error("Attempted to insert to read only replica")]
     #[from(ReadOnly)]
@@ -662,28 +2366,215 @@ pub enum InsertError {
     Closed,
 }
 
+/// Error emitted when a sync with a remote peer was aborted.
+#[derive(thiserror::Error, Debug)]
+pub enum SyncError {
+    /// The remote sent more entries than allowed by [`SyncConfig::max_entries_received`].
+    #[error("received {count} entries, exceeding the limit of {limit}")]
+    LimitExceeded {
+        /// Number of entries received in this sync session, including the rejected message.
+        count: usize,
+        /// The configured limit.
+        limit: usize,
+    },
+}
+
 /// Reason why entry validation failed
 #[derive(thiserror::Error, Debug)]
 pub enum ValidationFailure {
     /// Entry namespace does not match the current replica.
-    #[error("Entry namespace does not match the current replica")]
-    InvalidNamespace,
+    #[error("Entry namespace {got} does not match the replica namespace {expected}")]
+    InvalidNamespace {
+        /// Namespace of the replica.
+        expected: NamespaceId,
+        /// Namespace of the entry.
+        got: NamespaceId,
+    },
     /// Entry signature is invalid.
     #[error("Entry signature is invalid")]
     BadSignature,
     /// Entry timestamp is too far in the future.
-    #[error("Entry timestamp is too far in the future.")]
-    TooFarInTheFuture,
+    #[error(
+        "Entry timestamp {timestamp} is too far in the future (now: {now}, max shift: {limit})"
+    )]
+    TooFarInTheFuture {
+        /// Timestamp of the entry.
+        timestamp: u64,
+        /// Our system time when validating the entry.
+        now: u64,
+        /// Maximum shift into the future accepted, see [`MAX_TIMESTAMP_FUTURE_SHIFT`].
+        limit: u64,
+    },
     /// Entry has length 0 but not the empty hash, or the empty hash but not length 0.
-    #[error("Entry has length 0 but not the empty hash, or the empty hash but not length 0")]
-    InvalidEmptyEntry,
+    #[error("Entry has length {len} and hash {hash}, only one of them is empty")]
+    InvalidEmptyEntry {
+        /// Content hash of the entry.
+        hash: Hash,
+        /// Content length of the entry.
+        len: u64,
+    },
+    /// Entry is not signed by the namespace, and no valid write token exists for its author.
+    #[error("Entry is not signed by the namespace, and no valid write token exists")]
+    InvalidWriteToken,
+    /// Entry is signed under a write token which expired, either before the entry timestamp or
+    /// before our current time.
+    #[error("Write token expired at {expires_at}, entry timestamp {timestamp}, now {now}")]
+    WriteTokenExpired {
+        /// Timestamp of the entry.
+        timestamp: u64,
+        /// Our system time when validating the entry.
+        now: u64,
+        /// Expiry of the write token.
+        expires_at: u64,
+    },
+    /// Entry is signed under a write token which was revoked.
+    #[error("Entry is signed under the revoked write token {token}")]
+    WriteTokenRevoked {
+        /// Id of the revoked token.
+        token: WriteTokenId,
+    },
+    /// Content chunks do not match the content hash and length of the entry.
+    #[error("Content chunks do not match the content hash and length of the entry")]
+    ContentChunksMismatch,
+    /// Entry content length exceeds the maximum accepted by the replica.
+    #[error("Entry content length {len} exceeds the maximum of {max} accepted by the replica")]
+    ContentTooLarge {
+        /// Content length of the entry.
+        len: u64,
+        /// Maximum content length accepted by the replica.
+        max: u64,
+    },
+}
+
+/// An ordered list of content chunks, each identified by its hash and length.
+///
+/// A chunked entry does not point to its content directly. Instead, the content hash of the
+/// entry is the hash of the chunk manifest (see [`Self::to_manifest`]), and the content length
+/// is the length of the manifest. This keeps chunked entries fully compatible with peers that
+/// only know about single-hash entries: to them, the manifest is just another blob.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentChunks(Vec<(Hash, u64)>);
+
+impl ContentChunks {
+    /// Maximum length of a manifest in bytes.
+    ///
+    /// Peers only read downloaded blobs up to this length to resolve the chunks of an entry.
+    pub const MAX_MANIFEST_LEN: u64 = 1024 * 1024;
+
+    /// Create a new list of content chunks.
+    pub fn new(chunks: impl IntoIterator<Item = (Hash, u64)>) -> Self {
+        Self(chunks.into_iter().collect())
+    }
+
+    /// Get the chunks as a slice of `(hash, len)` pairs.
+    pub fn chunks(&self) -> &[(Hash, u64)] {
+        &self.0
+    }
+
+    /// Get the combined length of all chunks.
+    ///
+    /// Returns `None` if the combined length overflows a `u64`.
+    pub fn total_len(&self) -> Option<u64> {
+        self.0
+            .iter()
+            .try_fold(0u64, |total, (_, len)| total.checked_add(*len))
+    }
+
+    /// Encode the chunk list into its manifest bytes.
+    pub fn to_manifest(&self) -> Vec<u8> {
+        postcard::to_stdvec(&self.0).expect("serialization to vec cannot fail")
+    }
+
+    /// Decode a chunk list from manifest bytes.
+    pub fn from_manifest(manifest: &[u8]) -> anyhow::Result<Self> {
+        let chunks = Self(postcard::from_bytes(manifest)?);
+        anyhow::ensure!(
+            chunks.total_len().is_some(),
+            "combined length of chunks overflows"
+        );
+        Ok(chunks)
+    }
+
+    /// Get the hash of the manifest, which is the content hash of a chunked entry.
+    pub fn manifest_hash(&self) -> Hash {
+        Hash::new(self.to_manifest())
+    }
 }
 
 /// A signed entry.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignedEntry {
     signature: EntrySignature,
     entry: Entry,
+    /// Chunk list of a chunked entry, if known.
+    ///
+    /// This is derived data, authenticated by the content hash of the entry, and therefore
+    /// neither signed nor sent over the wire.
+    #[serde(skip)]
+    chunks: Option<ContentChunks>,
+}
+
+/// A single [`SignedEntry`] together with its namespace, to share an entry without a full sync.
+///
+/// The ticket serializes to a base32 string with [`fmt::Display`] and parses with [`FromStr`].
+/// A recipient inserts the entry with [`Replica::insert_entry_ticket`], which verifies its
+/// signatures.
+///
+/// [`fmt::Display`]: std::fmt::Display
+/// [`FromStr`]: std::str::FromStr
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, derive_more::Display)]
+#[display("{}", iroh_base::ticket::Ticket::serialize(self))]
+pub struct EntryTicket {
+    /// Namespace of the entry.
+    pub namespace: NamespaceId,
+    /// The signed entry.
+    pub entry: SignedEntry,
+}
+
+/// Wire format for [`EntryTicket`].
+///
+/// A single variant enum, so that postcard adds a discriminator for future variants.
+#[derive(Serialize, Deserialize)]
+enum EntryTicketWireFormat {
+    Variant0(EntryTicket),
+}
+
+impl EntryTicket {
+    /// Create a new entry ticket.
+    pub fn new(entry: SignedEntry) -> Self {
+        Self {
+            namespace: entry.namespace(),
+            entry,
+        }
+    }
+}
+
+impl iroh_base::ticket::Ticket for EntryTicket {
+    const KIND: &'static str = "entry";
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let data = EntryTicketWireFormat::Variant0(self.clone());
+        postcard::to_stdvec(&data).expect("postcard serialization failed")
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, iroh_base::ticket::Error> {
+        let res: EntryTicketWireFormat =
+            postcard::from_bytes(bytes).map_err(iroh_base::ticket::Error::Postcard)?;
+        let EntryTicketWireFormat::Variant0(res) = res;
+        if res.namespace != res.entry.namespace() {
+            return Err(iroh_base::ticket::Error::Verify(
+                "namespace does not match the entry",
+            ));
+        }
+        Ok(res)
+    }
+}
+
+impl std::str::FromStr for EntryTicket {
+    type Err = iroh_base::ticket::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        iroh_base::ticket::Ticket::deserialize(s)
+    }
 }
 
 impl From<SignedEntry> for Entry {
@@ -692,6 +2583,15 @@ impl From<SignedEntry> for Entry {
     }
 }
 
+// The resolved chunks are derived from the entry, so they are not compared.
+impl PartialEq for SignedEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.signature == other.signature && self.entry == other.entry
+    }
+}
+
+impl Eq for SignedEntry {}
+
 impl PartialOrd for SignedEntry {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
@@ -720,13 +2620,17 @@ impl Ord for Entry {
 
 impl SignedEntry {
     pub(crate) fn new(signature: EntrySignature, entry: Entry) -> Self {
-        SignedEntry { signature, entry }
+        SignedEntry {
+            signature,
+            entry,
+            chunks: None,
+        }
     }
 
     /// Create a new signed entry by signing an entry with the `namespace` and `author`.
     pub fn from_entry(entry: Entry, namespace: &NamespaceSecret, author: &Author) -> Self {
         let signature = EntrySignature::from_entry(&entry, namespace, author);
-        SignedEntry { signature, entry }
+        SignedEntry::new(signature, entry)
     }
 
     /// Create a new signed entries from its parts.
@@ -750,11 +2654,64 @@ impl SignedEntry {
         )
     }
 
+    /// Verify only the author signature of this entry.
+    ///
+    /// The namespace signature proves that the author was allowed to write to the namespace,
+    /// which this does not check. This needs a single public key resolution and signature
+    /// verification instead of two, and is only sound if the source of the entry is trusted to
+    /// send only entries of authors with write access, see [`crate::store::Store::set_trust_namespace`].
+    pub fn verify_author_only<S: store::PublicKeyStore>(
+        &self,
+        store: &S,
+    ) -> Result<(), SignatureError> {
+        self.signature
+            .verify_author(&self.entry, &self.entry.author().public_key(store)?)
+    }
+
+    /// Create an [`EntryTicket`] to share this entry out of band.
+    pub fn to_ticket_entry(&self) -> EntryTicket {
+        EntryTicket::new(self.clone())
+    }
+
+    /// Returns true if both signed entries contain the same [`Entry`], ignoring the signatures.
+    pub fn same_entry(&self, other: &Self) -> bool {
+        self.entry == other.entry
+    }
+
     /// Get the signature.
     pub fn signature(&self) -> &EntrySignature {
         &self.signature
     }
 
+    /// Get the id of the [`WriteToken`] this entry was signed under, if any.
+    ///
+    /// The namespace owner can revoke the token with [`Replica::revoke_write_token`].
+    pub fn write_token_id(&self) -> Option<WriteTokenId> {
+        self.signature.write_token_id()
+    }
+
+    /// Get the content chunks of a chunked entry.
+    ///
+    /// Returns `None` for single-hash entries, and for chunked entries whose manifest is not
+    /// known locally yet.
+    pub fn content_chunks(&self) -> Option<&[(Hash, u64)]> {
+        self.chunks.as_ref().map(|chunks| chunks.chunks())
+    }
+
+    /// Attach the content chunks to this entry.
+    ///
+    /// Fails if the manifest of `chunks` does not match the content hash and length of the entry.
+    pub fn with_content_chunks(mut self, chunks: ContentChunks) -> Result<Self, ValidationFailure> {
+        let manifest = chunks.to_manifest();
+        if Hash::new(&manifest) != self.content_hash()
+            || manifest.len() as u64 != self.content_len()
+        {
+            return Err(ValidationFailure::ContentChunksMismatch);
+        }
+        self.chunks = Some(chunks);
+        Ok(self)
+    }
+
     /// Validate that the entry has the empty hash if the length is 0, or a non-zero length.
     pub fn validate_empty(&self) -> Result<(), ValidationFailure> {
         self.entry().validate_empty()
@@ -812,6 +2769,11 @@ impl RangeEntry for SignedEntry {
         hasher.update(self.content_hash().as_bytes());
         Fingerprint(hasher.finalize().into())
     }
+
+    fn encoded_len(&self) -> usize {
+        postcard::serialize_with_flavor(self, postcard::ser_flavors::Size::default())
+            .expect("serialization to size cannot fail")
+    }
 }
 
 /// Signature over an entry.
@@ -851,6 +2813,64 @@ impl EntrySignature {
         }
     }
 
+    /// Create a new signature by signing an entry with an `author` holding the [`WriteToken`]
+    /// with id `token`.
+    ///
+    /// Without access to the namespace secret, the namespace signature is replaced by the id of
+    /// the token, and the author signs the entry together with the token id. The entry is only
+    /// valid together with the write token.
+    pub fn from_entry_with_token(entry: &Entry, token: WriteTokenId, author: &Author) -> Self {
+        let author_signature = author.sign(&Self::token_signed_bytes(entry, token));
+        EntrySignature {
+            author_signature,
+            namespace_signature: Self::token_marker(token),
+        }
+    }
+
+    /// Get the id of the [`WriteToken`] this signature was created under with
+    /// [`Self::from_entry_with_token`], or `None` if it was signed with the namespace secret.
+    pub fn write_token_id(&self) -> Option<WriteTokenId> {
+        let bytes = self.namespace_signature.to_bytes();
+        let (tag, id) = bytes.split_at(WRITE_TOKEN_MARKER_TAG.len());
+        if tag != WRITE_TOKEN_MARKER_TAG {
+            return None;
+        }
+        let id: [u8; 16] = id.try_into().expect("marker length is fixed");
+        Some(id.into())
+    }
+
+    /// Verify that this signature was created with [`Self::from_entry_with_token`] by signing
+    /// the `entry` with the secret key of the specified `author`.
+    ///
+    /// Returns the id of the write token the entry was signed under.
+    pub fn verify_with_token(
+        &self,
+        entry: &Entry,
+        author: &AuthorPublicKey,
+    ) -> Result<WriteTokenId, SignatureError> {
+        let token = self.write_token_id().ok_or_else(SignatureError::new)?;
+        author.verify(
+            &Self::token_signed_bytes(entry, token),
+            &self.author_signature,
+        )?;
+        Ok(token)
+    }
+
+    fn token_signed_bytes(entry: &Entry, token: WriteTokenId) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(WRITE_TOKEN_ENTRY_DOMAIN);
+        bytes.extend_from_slice(token.as_bytes());
+        entry.encode(&mut bytes);
+        bytes
+    }
+
+    fn token_marker(token: WriteTokenId) -> Signature {
+        let mut bytes = [0u8; 64];
+        bytes[..WRITE_TOKEN_MARKER_TAG.len()].copy_from_slice(WRITE_TOKEN_MARKER_TAG);
+        bytes[WRITE_TOKEN_MARKER_TAG.len()..].copy_from_slice(token.as_bytes());
+        Signature::from_bytes(&bytes)
+    }
+
     /// Verify that this signature was created by signing the `entry` with the
     /// secret keys of the specified `author` and `namespace`.
     pub fn verify(
@@ -866,6 +2886,44 @@ impl EntrySignature {
         Ok(())
     }
 
+    /// Verify that the author signature of this signature was created by signing the `entry`
+    /// with the secret key of the specified `author`. The namespace signature is not checked.
+    pub fn verify_author(
+        &self,
+        entry: &Entry,
+        author: &AuthorPublicKey,
+    ) -> Result<(), SignatureError> {
+        match self.write_token_id() {
+            Some(_) => self.verify_with_token(entry, author).map(|_| ()),
+            None => author.verify(&entry.to_vec(), &self.author_signature),
+        }
+    }
+
+    /// Verify the signatures of many entries at once, like [`Self::verify`] does for each.
+    ///
+    /// This is considerably faster than verifying the entries one by one, but fails as a whole
+    /// if any single signature is invalid. Verify the entries individually to find out which
+    /// ones are invalid.
+    pub fn verify_batch(
+        entries: &[(
+            &Entry,
+            &NamespacePublicKey,
+            &AuthorPublicKey,
+            &EntrySignature,
+        )],
+    ) -> Result<(), SignatureError> {
+        let messages = entries
+            .iter()
+            .map(|(entry, ..)| entry.to_vec())
+            .collect::<Vec<_>>();
+        let mut batch = BatchVerifier::default();
+        for ((_, namespace, author, signature), bytes) in entries.iter().zip(&messages) {
+            batch.push_namespace(bytes, &signature.namespace_signature, namespace);
+            batch.push_author(bytes, &signature.author_signature, author);
+        }
+        batch.verify()
+    }
+
     pub(crate) fn from_parts(namespace_sig: &[u8; 64], author_sig: &[u8; 64]) -> Self {
         let namespace_signature = Signature::from_bytes(namespace_sig);
         let author_signature = Signature::from_bytes(author_sig);
@@ -912,11 +2970,15 @@ impl Entry {
 
     /// Validate that the entry has the empty hash if the length is 0, or a non-zero length.
     pub fn validate_empty(&self) -> Result<(), ValidationFailure> {
+        let invalid = || ValidationFailure::InvalidEmptyEntry {
+            hash: self.content_hash(),
+            len: self.content_len(),
+        };
         match (self.content_hash() == Hash::EMPTY, self.content_len() == 0) {
             (true, true) => Ok(()),
             (false, false) => Ok(()),
-            (true, false) => Err(ValidationFailure::InvalidEmptyEntry),
-            (false, true) => Err(ValidationFailure::InvalidEmptyEntry),
+            (true, false) => Err(invalid()),
+            (false, true) => Err(invalid()),
         }
     }
 
@@ -984,9 +3046,29 @@ const AUTHOR_BYTES: std::ops::Range<usize> = 32..64;
 const KEY_BYTES: std::ops::RangeFrom<usize> = 64..;
 
 /// The identifier of a record.
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+///
+/// A record identifier is always at least 64 bytes long: the namespace id, the author id and
+/// the key. This is checked when decoding, so that the accessors never panic.
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(try_from = "Bytes")]
 pub struct RecordIdentifier(Bytes);
 
+/// Error returned when decoding a [`RecordIdentifier`] from bytes that are too short.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("record identifier must be at least 64 bytes, got {len}")]
+pub struct InvalidRecordIdentifier {
+    /// The length of the rejected bytes.
+    pub len: usize,
+}
+
+impl TryFrom<Bytes> for RecordIdentifier {
+    type Error = InvalidRecordIdentifier;
+
+    fn try_from(bytes: Bytes) -> Result<Self, Self::Error> {
+        Self::from_bytes(bytes)
+    }
+}
+
 impl Default for RecordIdentifier {
     fn default() -> Self {
         Self::new(NamespaceId::default(), AuthorId::default(), b"")
@@ -1031,6 +3113,31 @@ impl RecordIdentifier {
         Self(bytes.freeze())
     }
 
+    /// Decode a [`RecordIdentifier`] from its byte representation.
+    ///
+    /// The bytes are the namespace id, the author id and the key, as returned by
+    /// [`Self::as_bytes`]. Fails if the bytes are too short to contain both ids.
+    pub fn from_bytes(bytes: impl Into<Bytes>) -> Result<Self, InvalidRecordIdentifier> {
+        let bytes = bytes.into();
+        if bytes.len() < KEY_BYTES.start {
+            return Err(InvalidRecordIdentifier { len: bytes.len() });
+        }
+        Ok(Self(bytes))
+    }
+
+    /// Returns the namespace and author bytes.
+    fn id_bytes(&self) -> (&[u8; 32], &[u8; 32]) {
+        // the length is checked on construction, see [`Self::from_bytes`]
+        (
+            self.0[NAMESPACE_BYTES]
+                .try_into()
+                .expect("record identifier is at least 64 bytes"),
+            self.0[AUTHOR_BYTES]
+                .try_into()
+                .expect("record identifier is at least 64 bytes"),
+        )
+    }
+
     /// Serialize this [`RecordIdentifier`] into a mutable byte array.
     pub(crate) fn encode(&self, out: &mut Vec<u8>) {
         out.extend_from_slice(&self.0);
@@ -1043,20 +3150,19 @@ impl RecordIdentifier {
 
     /// Get this [`RecordIdentifier`] as a tuple of byte slices.
     pub fn as_byte_tuple(&self) -> (&[u8; 32], &[u8; 32], &[u8]) {
-        (
-            self.0[NAMESPACE_BYTES].try_into().unwrap(),
-            self.0[AUTHOR_BYTES].try_into().unwrap(),
-            &self.0[KEY_BYTES],
-        )
+        let (namespace, author) = self.id_bytes();
+        (namespace, author, self.key())
     }
 
     /// Get this [`RecordIdentifier`] as a tuple of bytes.
     pub fn to_byte_tuple(&self) -> ([u8; 32], [u8; 32], Bytes) {
-        (
-            self.0[NAMESPACE_BYTES].try_into().unwrap(),
-            self.0[AUTHOR_BYTES].try_into().unwrap(),
-            self.0.slice(KEY_BYTES),
-        )
+        let (namespace, author) = self.id_bytes();
+        (*namespace, *author, self.key_bytes())
+    }
+
+    /// Get this [`RecordIdentifier`] as a tuple of [`NamespaceId`], [`AuthorId`] and key.
+    pub fn parts(&self) -> (NamespaceId, AuthorId, Bytes) {
+        (self.namespace(), self.author(), self.key_bytes())
     }
 
     /// Get the key of this record.
@@ -1071,14 +3177,12 @@ impl RecordIdentifier {
 
     /// Get the [`NamespaceId`] of this record as byte array.
     pub fn namespace(&self) -> NamespaceId {
-        let value: &[u8; 32] = &self.0[NAMESPACE_BYTES].try_into().unwrap();
-        value.into()
+        self.id_bytes().0.into()
     }
 
     /// Get the [`AuthorId`] of this record as byte array.
     pub fn author(&self) -> AuthorId {
-        let value: &[u8; 32] = &self.0[AUTHOR_BYTES].try_into().unwrap();
-        value.into()
+        self.id_bytes().1.into()
     }
 }
 
@@ -1115,9 +3219,56 @@ pub struct Record {
 
 impl RangeValue for Record {}
 
+/// Decides which of two entries for the same key wins if their timestamps are equal.
+///
+/// Entries with a newer timestamp always win. Only on equal timestamps the resolver is
+/// consulted, and the entry it orders greater wins. Without a resolver, inserts keep the entry
+/// with the greater content hash, and queries for the latest entry per key yield the first of
+/// the entries in key and author order. Use [`HashConflictResolver`] to prefer the greater
+/// content hash in both cases.
+///
+/// A resolver can be set per replica with [`crate::store::Store::set_conflict_resolver`].
+/// Resolvers are kept in memory only and are not persisted, so they have to be set again
+/// whenever the store is opened. All peers of a replica must use the same resolver. Otherwise,
+/// peers keep different entries for the same key and their replicas diverge.
+pub trait ConflictResolver: std::fmt::Debug + Send + Sync {
+    /// Compare two entries with equal timestamps.
+    fn resolve(&self, a: &Entry, b: &Entry) -> Ordering;
+}
+
+/// A [`ConflictResolver`] which prefers the entry with the greater content hash.
+///
+/// This is the order used for inserts without a resolver. Setting it explicitly also applies it
+/// to queries for the latest entry per key.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HashConflictResolver;
+
+impl ConflictResolver for HashConflictResolver {
+    fn resolve(&self, a: &Entry, b: &Entry) -> Ordering {
+        a.content_hash().cmp(&b.content_hash())
+    }
+}
+
+/// Compare two entries by timestamp, and consult `resolver` if the timestamps are equal.
+///
+/// Without a resolver, entries with equal timestamps are equal.
+pub(crate) fn cmp_entries(
+    resolver: Option<&dyn ConflictResolver>,
+    a: &Entry,
+    b: &Entry,
+) -> Ordering {
+    a.timestamp()
+        .cmp(&b.timestamp())
+        .then_with(|| match resolver {
+            Some(resolver) => resolver.resolve(a, b),
+            None => Ordering::Equal,
+        })
+}
+
 /// Ordering for entry values.
 ///
-/// Compares first the timestamp, then the content hash.
+/// Compares first the timestamp, then the content hash. Replicas with a [`ConflictResolver`]
+/// use it instead of the content hash when inserting entries.
 impl Ord for Record {
     fn cmp(&self, other: &Self) -> Ordering {
         self.timestamp
@@ -1435,11 +3586,38 @@ mod tests {
             }
         }
         assert_eq!(expected.len(), n_replicas * n_entries);
+
+        // chunked entries protect both the manifest and the chunks
+        let author = store.new_author(&mut rng)?;
+        let mut replica = store.new_replica(NamespaceSecret::new(&mut rng))?;
+        let chunks = ContentChunks::new([(Hash::new("chunk 1"), 7), (Hash::new("chunk 2"), 7)]);
+        replica.insert_chunked("chunked", &author, chunks.clone())?;
+        drop(replica);
+        expected.insert(chunks.manifest_hash());
+        expected.extend(chunks.chunks().iter().map(|(hash, _len)| *hash));
+
         let actual = store.content_hashes()?.collect::<Result<HashSet<Hash>>>()?;
         assert_eq!(actual, expected);
         Ok(())
     }
 
+    #[test]
+    fn test_record_identifier_parts() {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng).id();
+        let author = Author::new(&mut rng).id();
+
+        let id = RecordIdentifier::new(namespace, author, b"/my/key");
+        let (ns, a, key) = id.parts();
+        assert_eq!(ns, namespace);
+        assert_eq!(a, author);
+        assert_eq!(key, Bytes::from_static(b"/my/key"));
+        assert_eq!(RecordIdentifier::new(ns, a, key), id);
+
+        let (_, _, key) = RecordIdentifier::new(namespace, author, []).parts();
+        assert!(key.is_empty());
+    }
+
     #[test]
     fn test_multikey() {
         let mut rng = rand::thread_rng();
@@ -1591,6 +3769,67 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_subscribe_all_memory() -> Result<()> {
+        let store = store::Store::memory();
+        test_subscribe_all(store)
+    }
+
+    #[test]
+    fn test_subscribe_all_fs() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let store = store::fs::Store::persistent(dbfile.path())?;
+        test_subscribe_all(store)
+    }
+
+    fn test_subscribe_all(mut store: Store) -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let author = store.new_author(&mut rng)?;
+        let namespace1 = NamespaceSecret::new(&mut rng);
+        let namespace2 = NamespaceSecret::new(&mut rng);
+
+        let (tx, rx) = async_channel::unbounded();
+        store.subscribe_all(tx.clone());
+        // a dropped receiver is removed without affecting other subscribers
+        let (dropped_tx, dropped_rx) = async_channel::bounded(1);
+        store.subscribe_all(dropped_tx);
+        drop(dropped_rx);
+
+        // replicas opened after subscribing are included
+        let mut replica = store.new_replica(namespace1.clone())?;
+        replica.hash_and_insert("one", &author, b"one")?;
+        drop(replica);
+        let mut replica = store.new_replica(namespace2.clone())?;
+        replica.hash_and_insert("two", &author, b"two")?;
+        drop(replica);
+        store.close_replica(namespace1.id());
+
+        let events = drain(rx.clone());
+        assert_eq!(events.len(), 3);
+        assert!(matches!(
+            &events[0],
+            (namespace, Event::LocalInsert { entry, .. })
+                if *namespace == namespace1.id() && entry.key() == b"one"
+        ));
+        assert!(matches!(
+            &events[1],
+            (namespace, Event::LocalInsert { entry, .. })
+                if *namespace == namespace2.id() && entry.key() == b"two"
+        ));
+        assert!(matches!(
+            &events[2],
+            (namespace, Event::Closed { namespace: closed })
+                if *namespace == namespace1.id() && *closed == namespace1.id()
+        ));
+
+        // no events after unsubscribing
+        store.unsubscribe_all(&tx);
+        let mut replica = store.open_replica(&namespace2.id())?;
+        replica.hash_and_insert("three", &author, b"three")?;
+        assert!(rx.try_recv().is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_replica_sync_memory() -> Result<()> {
         let alice_store = store::Store::memory();
@@ -1645,37 +3884,698 @@ mod tests {
     }
 
     #[test]
-    fn test_replica_timestamp_sync_memory() -> Result<()> {
+    fn test_replica_fingerprint_memory() -> Result<()> {
         let alice_store = store::Store::memory();
         let bob_store = store::Store::memory();
 
-        test_replica_timestamp_sync(alice_store, bob_store)?;
+        test_replica_fingerprint(alice_store, bob_store)?;
         Ok(())
     }
 
     #[test]
-    fn test_replica_timestamp_sync_fs() -> Result<()> {
+    fn test_replica_fingerprint_fs() -> Result<()> {
         let alice_dbfile = tempfile::NamedTempFile::new()?;
         let alice_store = store::fs::Store::persistent(alice_dbfile.path())?;
         let bob_dbfile = tempfile::NamedTempFile::new()?;
         let bob_store = store::fs::Store::persistent(bob_dbfile.path())?;
-        test_replica_timestamp_sync(alice_store, bob_store)?;
+        test_replica_fingerprint(alice_store, bob_store)?;
 
         Ok(())
     }
 
-    fn test_replica_timestamp_sync(mut alice_store: Store, mut bob_store: Store) -> Result<()> {
+    fn test_replica_fingerprint(mut alice_store: Store, mut bob_store: Store) -> Result<()> {
         let mut rng = rand::thread_rng();
         let author = Author::new(&mut rng);
-        let namespace = NamespaceSecret::new(&mut rng);
-        let mut alice = alice_store.new_replica(namespace.clone())?;
-        let mut bob = bob_store.new_replica(namespace.clone())?;
+        let myspace = NamespaceSecret::new(&mut rng);
+        let mut alice = alice_store.new_replica(myspace.clone())?;
+        let mut bob = bob_store.new_replica(myspace.clone())?;
 
-        let key = b"key";
-        let alice_value = b"alice";
-        let bob_value = b"bob";
-        let _alice_hash = alice.hash_and_insert(key, &author, alice_value)?;
-        // system time increased - sync should overwrite
+        let empty = alice.fingerprint()?;
+        assert_eq!(empty, Fingerprint::empty());
+        assert_eq!(bob.fingerprint()?, empty);
+
+        alice.hash_and_insert("foo", &author, b"foo")?;
+        let after_insert = alice.fingerprint()?;
+        assert_ne!(after_insert, empty);
+        assert_ne!(bob.fingerprint()?, after_insert);
+
+        bob.hash_and_insert("bar", &author, b"bar")?;
+        sync(&mut alice, &mut bob)?;
+        let fingerprint = alice.fingerprint()?;
+        assert_ne!(fingerprint, after_insert);
+        assert_eq!(bob.fingerprint()?, fingerprint);
+        assert!(alice.matches_fingerprint(bob.fingerprint()?)?);
+        assert!(bob.matches_fingerprint(fingerprint)?);
+
+        bob.hash_and_insert("baz", &author, b"baz")?;
+        assert!(!alice.matches_fingerprint(bob.fingerprint()?)?);
+        assert!(!bob.matches_fingerprint(fingerprint)?);
+
+        alice_store.flush()?;
+        bob_store.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_status_from_parts() {
+        use ContentStatus::*;
+        let cases: &[(&[ContentStatus], ContentStatus)] = &[
+            (&[], Missing),
+            (&[Complete], Complete),
+            (&[Incomplete], Incomplete),
+            (&[Missing], Missing),
+            (&[Complete, Complete], Complete),
+            (&[Complete, Incomplete], Incomplete),
+            (&[Complete, Missing], Incomplete),
+            (&[Incomplete, Incomplete], Incomplete),
+            (&[Incomplete, Missing], Incomplete),
+            (&[Missing, Missing], Missing),
+            (&[Missing, Complete, Complete], Incomplete),
+        ];
+        for (statuses, expected) in cases {
+            assert_eq!(
+                content_status_from_parts(statuses),
+                *expected,
+                "{statuses:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_entry_content_status() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let mut store = store::Store::memory();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(namespace.clone())?;
+        let chunks = ContentChunks::new([(Hash::new("a"), 1), (Hash::new("b"), 1)]);
+        replica.insert_chunked("chunked", &author, chunks.clone())?;
+        replica.hash_and_insert("single", &author, "a")?;
+        drop(replica);
+        let chunked = store
+            .get_exact(namespace.id(), author.id(), "chunked", false)?
+            .expect("exists");
+        let single = store
+            .get_exact(namespace.id(), author.id(), "single", false)?
+            .expect("exists");
+
+        let manifest = chunks.manifest_hash();
+        let status_of = |available: Vec<Hash>| -> ContentStatusCallback {
+            Arc::new(move |hash| match available.contains(&hash) {
+                true => ContentStatus::Complete,
+                false => ContentStatus::Missing,
+            })
+        };
+        let cb = status_of(vec![]);
+        assert_eq!(entry_content_status(&chunked, &cb), ContentStatus::Missing);
+        assert_eq!(entry_content_status(&single, &cb), ContentStatus::Missing);
+        let cb = status_of(vec![manifest, Hash::new("a")]);
+        assert_eq!(
+            entry_content_status(&chunked, &cb),
+            ContentStatus::Incomplete
+        );
+        assert_eq!(entry_content_status(&single, &cb), ContentStatus::Complete);
+        let cb = status_of(vec![manifest, Hash::new("a"), Hash::new("b")]);
+        assert_eq!(entry_content_status(&chunked, &cb), ContentStatus::Complete);
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunked_entry_sync_memory() -> Result<()> {
+        let alice_store = store::Store::memory();
+        let bob_store = store::Store::memory();
+
+        test_chunked_entry_sync(alice_store, bob_store)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_chunked_entry_sync_fs() -> Result<()> {
+        let alice_dbfile = tempfile::NamedTempFile::new()?;
+        let alice_store = store::fs::Store::persistent(alice_dbfile.path())?;
+        let bob_dbfile = tempfile::NamedTempFile::new()?;
+        let bob_store = store::fs::Store::persistent(bob_dbfile.path())?;
+        test_chunked_entry_sync(alice_store, bob_store)?;
+
+        Ok(())
+    }
+
+    fn test_chunked_entry_sync(mut alice_store: Store, mut bob_store: Store) -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let chunks = ContentChunks::new([
+            (Hash::new(b"chunk 1"), 7),
+            (Hash::new(b"chunk 2"), 7),
+            (Hash::new(b"chunk 3"), 7),
+        ]);
+        assert_eq!(chunks.total_len(), Some(21));
+        let manifest = chunks.to_manifest();
+        // manifests whose combined length overflows are rejected
+        let overflowing = ContentChunks::new([(Hash::new(b"a"), u64::MAX), (Hash::new(b"b"), 1)]);
+        assert_eq!(overflowing.total_len(), None);
+        assert!(ContentChunks::from_manifest(&overflowing.to_manifest()).is_err());
+        assert!(bob_store
+            .put_content_chunks(myspace.id(), &overflowing.to_manifest())
+            .is_err());
+
+        let mut alice = alice_store.new_replica(myspace.clone())?;
+        alice.insert_chunked("chunked", &author, chunks.clone())?;
+        alice.hash_and_insert("single", &author, b"single")?;
+        assert!(matches!(
+            alice.insert_chunked("empty", &author, ContentChunks::new([])),
+            Err(InsertError::EntryIsEmpty)
+        ));
+        let too_large = (0..ContentChunks::MAX_MANIFEST_LEN / 32)
+            .map(|i| (Hash::new(i.to_le_bytes()), 1))
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            alice.insert_chunked("too large", &author, ContentChunks::new(too_large)),
+            Err(InsertError::Validation(
+                ValidationFailure::ContentTooLarge { .. }
+            ))
+        ));
+
+        let mut bob = bob_store.new_replica(myspace.clone())?;
+        let (alice_out, bob_out) = sync(&mut alice, &mut bob)?;
+        assert_eq!(alice_out.num_sent, 2);
+        assert_eq!(bob_out.num_recv, 2);
+
+        // alice knows the chunks of her own entry
+        let entry = alice_store
+            .get_exact(myspace.id(), author.id(), b"chunked", false)?
+            .unwrap();
+        assert_eq!(entry.content_hash(), chunks.manifest_hash());
+        assert_eq!(entry.content_len(), manifest.len() as u64);
+        assert_eq!(entry.content_chunks(), Some(chunks.chunks()));
+
+        // bob received a valid entry pointing to the manifest, but has no manifest yet
+        let synced = bob_store
+            .get_exact(myspace.id(), author.id(), b"chunked", false)?
+            .unwrap();
+        synced.verify(&())?;
+        assert_eq!(synced.content_chunks(), None);
+        assert_eq!(synced, entry);
+
+        // once the manifest blob is available, the chunks resolve, only within the namespace
+        let otherspace = NamespaceSecret::new(&mut rng);
+        assert_eq!(bob_store.put_content_chunks(otherspace.id(), &manifest)?, 0);
+        assert_eq!(bob_store.put_content_chunks(myspace.id(), &manifest)?, 1);
+        let synced = bob_store
+            .get_exact(myspace.id(), author.id(), b"chunked", false)?
+            .unwrap();
+        assert_eq!(synced, entry);
+        assert_eq!(synced.content_chunks(), Some(chunks.chunks()));
+
+        // the chunks resolve in queries and full iteration as well
+        let queried = bob_store
+            .get_many(myspace.id(), Query::key_exact(b"chunked"))?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(queried, vec![entry.clone()]);
+        let all = bob_store
+            .iter_all_entries()?
+            .map(|item| item.map(|(_namespace, entry)| entry))
+            .collect::<Result<Vec<_>>>()?;
+        assert!(all.contains(&entry));
+
+        // rebuilding the namespace from its entries keeps the chunks
+        bob_store.close_replica(myspace.id());
+        let stats = bob_store.rebuild_namespace(myspace.clone(), all)?;
+        assert_eq!(stats.inserted, 2);
+        let rebuilt = bob_store
+            .get_exact(myspace.id(), author.id(), b"chunked", false)?
+            .unwrap();
+        assert_eq!(rebuilt.content_chunks(), Some(chunks.chunks()));
+
+        // the manifest is removed together with its entries, so that re-syncing the same entry
+        // after clearing the namespace does not resolve stale chunks
+        assert_eq!(bob_store.clear_namespace(myspace.id())?, 2);
+        {
+            let mut alice = alice_store.open_replica(&myspace.id())?;
+            let mut bob = bob_store.open_replica(&myspace.id())?;
+            sync(&mut alice, &mut bob)?;
+        }
+        let resynced = bob_store
+            .get_exact(myspace.id(), author.id(), b"chunked", false)?
+            .unwrap();
+        assert_eq!(resynced.content_chunks(), None);
+
+        // replacing the entry removes its manifest as well
+        assert_eq!(bob_store.put_content_chunks(myspace.id(), &manifest)?, 1);
+        bob_store.open_replica(&myspace.id())?.hash_and_insert(
+            "chunked",
+            &author,
+            b"not chunked",
+        )?;
+        let replaced = bob_store
+            .get_exact(myspace.id(), author.id(), b"chunked", false)?
+            .unwrap();
+        assert_eq!(replaced.content_chunks(), None);
+        assert_eq!(bob_store.put_content_chunks(myspace.id(), &manifest)?, 0);
+
+        // single-hash entries are unaffected
+        let single = bob_store
+            .get_exact(myspace.id(), author.id(), b"single", false)?
+            .unwrap();
+        assert_eq!(single.content_hash(), Hash::new(b"single"));
+        assert_eq!(single.content_chunks(), None);
+
+        // chunks that do not match the entry are rejected
+        let other = ContentChunks::new([(Hash::new(b"other"), 5)]);
+        assert!(matches!(
+            single.with_content_chunks(other),
+            Err(ValidationFailure::ContentChunksMismatch)
+        ));
+        assert!(bob_store
+            .put_content_chunks(myspace.id(), b"not a manifest")
+            .is_err());
+
+        alice_store.flush()?;
+        bob_store.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespace_meta_memory() -> Result<()> {
+        let alice_store = store::Store::memory();
+        let bob_store = store::Store::memory();
+
+        test_namespace_meta(alice_store, bob_store)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespace_meta_fs() -> Result<()> {
+        let alice_dbfile = tempfile::NamedTempFile::new()?;
+        let alice_store = store::fs::Store::persistent(alice_dbfile.path())?;
+        let bob_dbfile = tempfile::NamedTempFile::new()?;
+        let bob_store = store::fs::Store::persistent(bob_dbfile.path())?;
+        test_namespace_meta(alice_store, bob_store)?;
+
+        Ok(())
+    }
+
+    fn test_namespace_meta(mut alice_store: Store, mut bob_store: Store) -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let namespace = myspace.id();
+        let first = Bytes::from_static(b"title: first");
+        let second = Bytes::from_static(b"title: second");
+
+        let mut alice = alice_store.new_replica(myspace.clone())?;
+        alice.hash_and_insert("foo", &author, "bar")?;
+        alice.set_namespace_meta(&author, first)?;
+        drop(alice);
+        alice_store.set_namespace_meta(namespace, &author, second.clone())?;
+        assert_eq!(
+            alice_store.get_namespace_meta(namespace)?,
+            Some(second.clone())
+        );
+
+        // metadata is excluded from regular queries and lookups
+        let entries = alice_store
+            .get_many(namespace, Query::all().include_empty())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key(), b"foo");
+        let entries = alice_store
+            .get_many(namespace, Query::single_latest_per_key().include_empty())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            alice_store.get_exact(namespace, author.id(), NAMESPACE_META_KEY, true)?,
+            None
+        );
+
+        // metadata syncs to other replicas, and its content is downloaded
+        bob_store.new_replica(myspace.clone())?;
+        assert_eq!(bob_store.get_namespace_meta(namespace)?, None);
+        let mut bob = bob_store.open_replica(&namespace)?;
+        let (tx, rx) = async_channel::bounded(10);
+        bob.info.subscribe(tx);
+        let mut alice = alice_store.open_replica(&namespace)?;
+        sync(&mut alice, &mut bob)?;
+        drop(bob);
+        let mut meta_download = None;
+        while let Ok(event) = rx.try_recv() {
+            if let Event::RemoteInsert {
+                entry,
+                should_download,
+                ..
+            } = event
+            {
+                if entry.key() == NAMESPACE_META_KEY && should_download {
+                    meta_download = Some(entry.content_hash());
+                }
+            }
+        }
+        assert_eq!(meta_download, Some(Hash::new(&second)));
+        let entries = bob_store
+            .get_many(namespace, Query::all())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 1);
+
+        // the metadata is readable once the downloaded bytes are stored
+        assert_eq!(bob_store.get_namespace_meta(namespace)?, None);
+        let otherspace = NamespaceSecret::new(&mut rng);
+        assert_eq!(bob_store.put_namespace_meta(otherspace.id(), &second)?, 0);
+        assert_eq!(bob_store.put_namespace_meta(namespace, b"title: first")?, 0);
+        assert_eq!(bob_store.get_namespace_meta(namespace)?, None);
+        assert_eq!(bob_store.put_namespace_meta(namespace, &second)?, 1);
+        assert_eq!(bob_store.get_namespace_meta(namespace)?, Some(second));
+
+        // metadata over the size limit is rejected
+        let too_large = Bytes::from(vec![0u8; MAX_NAMESPACE_META_LEN as usize + 1]);
+        assert!(matches!(
+            bob_store.set_namespace_meta(namespace, &author, too_large),
+            Err(err) if matches!(
+                err.downcast_ref::<InsertError>(),
+                Some(InsertError::Validation(ValidationFailure::ContentTooLarge { .. }))
+            )
+        ));
+
+        // oversized metadata from other peers is rejected, and never downloaded
+        let id = RecordIdentifier::new(namespace, author.id(), NAMESPACE_META_KEY);
+        let record = Record::new(
+            Hash::new(b"huge"),
+            MAX_NAMESPACE_META_LEN + 1,
+            system_time_now(),
+        );
+        let huge = Entry::new(id, record).sign(&myspace, &author);
+        assert!(!should_download(&huge, &Default::default()));
+        let mut bob = bob_store.open_replica(&namespace)?;
+        assert!(matches!(
+            bob.insert_remote_entry(huge, [1u8; 32], ContentStatus::Missing),
+            Err(InsertError::Validation(
+                ValidationFailure::ContentTooLarge { .. }
+            ))
+        ));
+        drop(bob);
+
+        // empty metadata removes the metadata
+        bob_store.set_namespace_meta(namespace, &author, Bytes::new())?;
+        assert_eq!(bob_store.get_namespace_meta(namespace)?, None);
+
+        alice_store.flush()?;
+        bob_store.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reserved_keys_rejected() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let token = WriteToken::issue(&mut rng, &myspace, author.id(), u64::MAX);
+        let mut store = store::Store::memory();
+        let mut replica = store.new_replica(myspace.clone())?;
+
+        let counter_key = CounterValue::new(Bytes::from_static(b"visits"), 1).to_entry_key();
+        let token_key = [WRITE_TOKEN_PREFIX, b"token"].concat();
+        let revocation_key = [WRITE_TOKEN_REVOCATION_PREFIX, b"token"].concat();
+        for key in [
+            NAMESPACE_META_KEY,
+            &token_key,
+            &revocation_key,
+            &counter_key,
+        ] {
+            let hash = Hash::new(b"value");
+            assert!(matches!(
+                replica.insert(key, &author, hash, 5),
+                Err(InsertError::ReservedKey)
+            ));
+            assert!(matches!(
+                replica.insert_with_timestamp(key, &author, hash, 5, system_time_now()),
+                Err(InsertError::ReservedKey)
+            ));
+            assert!(matches!(
+                replica.insert_chunked(key, &author, ContentChunks::new([(hash, 5)])),
+                Err(InsertError::ReservedKey)
+            ));
+            assert!(matches!(
+                replica.insert_with_token(key, &author, &token, hash, 5),
+                Err(InsertError::ReservedKey)
+            ));
+            assert!(matches!(
+                replica.delete_prefix(key, &author),
+                Err(InsertError::ReservedKey)
+            ));
+        }
+
+        // the dedicated methods still write reserved entries
+        replica.counter_add("visits", &author, 1)?;
+        replica.set_namespace_meta(&author, Bytes::from_static(b"title"))?;
+        drop(replica);
+        assert_eq!(store.get_counter(myspace.id(), "visits")?, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_protocol_message_describe() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+
+        let mut alice = alice_store.new_replica(myspace.clone())?;
+        alice.hash_and_insert("ape", &author, "1")?;
+        alice.hash_and_insert("bee", &author, "2")?;
+        let mut bob = bob_store.new_replica(myspace.clone())?;
+        bob.hash_and_insert("cat", &author, "3")?;
+
+        let msg = alice.sync_initial_message()?;
+        assert!(msg
+            .describe()
+            .starts_with("fingerprints=1 items=0 values=0 fingerprint["));
+
+        let mut state = SyncOutcome::default();
+        let reply = bob
+            .sync_process_message(msg, [1u8; 32], &mut state)?
+            .expect("reply");
+        let fingerprints = reply
+            .parts()
+            .iter()
+            .filter(|part| part.is_range_fingerprint())
+            .count();
+        let items = reply
+            .parts()
+            .iter()
+            .filter(|part| part.is_range_item())
+            .count();
+        let description = reply.describe();
+        assert!(description.starts_with(&format!(
+            "fingerprints={fingerprints} items={items} values={} ",
+            reply.value_count()
+        )));
+        assert_eq!(
+            description.matches(" item[").count() + description.matches(" fingerprint[").count(),
+            reply.parts().len()
+        );
+        assert!(description.contains(&format!("{}/", author.id().fmt_short())));
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_protocol_message() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+
+        let mut alice = alice_store.new_replica(myspace.clone())?;
+        let mut bob = bob_store.new_replica(myspace.clone())?;
+        for i in 0..3 {
+            bob.hash_and_insert(format!("bee{i}"), &author, "2")?;
+        }
+        let msg = alice.sync_initial_message()?;
+        let reply = bob
+            .sync_process_message(msg, [1u8; 32], &mut SyncOutcome::default())?
+            .expect("reply");
+        assert!(reply.value_count() > 1);
+
+        // valid messages within the limits roundtrip
+        let bytes = postcard::to_stdvec(&reply)?;
+        let decoded = decode_protocol_message(&bytes, &MessageLimits::default())?;
+        assert_eq!(decoded, reply);
+
+        // valid messages exceeding the limits are rejected
+        let limits = MessageLimits {
+            max_parts: 0,
+            ..Default::default()
+        };
+        assert!(matches!(
+            decode_protocol_message(&bytes, &limits),
+            Err(MessageDecodeError::TooManyParts { limit: 0 })
+        ));
+        let limits = MessageLimits {
+            max_values: 1,
+            ..Default::default()
+        };
+        assert!(matches!(
+            decode_protocol_message(&bytes, &limits),
+            Err(MessageDecodeError::TooManyValues { limit: 1 })
+        ));
+
+        // crafted messages claiming huge lengths fail cleanly instead of allocating.
+        // postcard encodes sequence lengths and variant indices as varints.
+        let bytes = postcard::to_stdvec(&(u32::MAX,))?;
+        assert!(matches!(
+            decode_protocol_message(&bytes, &MessageLimits::default()),
+            Err(MessageDecodeError::Decode(_))
+        ));
+        let id = RecordIdentifier::new(myspace.id(), author.id(), "ape");
+        let range = Range::new(id.clone(), id);
+        // one part, of variant `RangeItem`, with a range and an enormous number of values
+        let bytes = postcard::to_stdvec(&(1u32, 1u32, range, u32::MAX))?;
+        assert!(matches!(
+            decode_protocol_message(&bytes, &MessageLimits::default()),
+            Err(MessageDecodeError::Decode(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_entry_ticket() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let author = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+
+        let mut alice = alice_store.new_replica(myspace.clone())?;
+        alice.hash_and_insert("shared", &author, "hello")?;
+        drop(alice);
+        let entry = alice_store
+            .get_exact(myspace.id(), author.id(), "shared", false)?
+            .expect("entry");
+
+        // the ticket roundtrips through its string form
+        let ticket = entry.to_ticket_entry();
+        let parsed: EntryTicket = ticket.to_string().parse()?;
+        assert_eq!(parsed, ticket);
+
+        let peer = [9u8; 32];
+        let mut bob = bob_store.new_replica(myspace.clone())?;
+        let (tx, rx) = async_channel::bounded(10);
+        bob.info.subscribe(tx);
+        let res = bob.insert_entry_ticket(parsed, peer, ContentStatus::Complete);
+        assert!(res.is_ok(), "{res:?}");
+        // the entry is reported as received from the peer that shared the ticket
+        assert!(matches!(
+            rx.try_recv(),
+            Ok(Event::RemoteInsert { from, should_download: true, remote_content_status: ContentStatus::Complete, .. })
+                if from == peer
+        ));
+        drop(bob);
+        let bob_entry = bob_store
+            .get_exact(myspace.id(), author.id(), "shared", false)?
+            .expect("entry inserted");
+        assert_eq!(bob_entry, entry);
+
+        // a tampered entry fails to verify
+        let mut tampered = entry.clone();
+        tampered.entry.record = Record::new(Hash::new("evil"), 4, entry.timestamp() + 1);
+        let mut bob = bob_store.open_replica(&myspace.id())?;
+        let res = bob.insert_entry_ticket(tampered.to_ticket_entry(), peer, ContentStatus::Missing);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::BadSignature))
+        ));
+
+        // tickets for another namespace are rejected
+        let mut other_store = store::Store::memory();
+        let other = NamespaceSecret::new(&mut rng);
+        let mut other_replica = other_store.new_replica(other.clone())?;
+        let res = other_replica.insert_entry_ticket(ticket, peer, ContentStatus::Missing);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::InvalidNamespace { expected, got }))
+                if expected == other.id() && got == myspace.id()
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_compact_protocol_message() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let author = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+
+        let mut alice = alice_store.new_replica(myspace.clone())?;
+        let mut bob = bob_store.new_replica(myspace.clone())?;
+        for i in 0..1000 {
+            bob.hash_and_insert(format!("bee{i}"), &author, "2")?;
+        }
+        // without a content status callback, all values are reported as missing
+        let msg = alice.sync_initial_message()?;
+        let reply = bob
+            .sync_process_message(msg, [1u8; 32], &mut SyncOutcome::default())?
+            .expect("reply");
+        assert_eq!(reply.value_count(), 1000);
+        assert!(reply
+            .values()
+            .all(|(_, status)| *status == ContentStatus::Missing));
+        assert_eq!(
+            reply.parts().iter().filter(|p| p.is_range_item()).count(),
+            1
+        );
+
+        let plain = postcard::to_stdvec(&reply)?;
+        let compact = reply.encode_compact()?;
+        let decoded = ProtocolMessage::decode_compact(&compact, &MessageLimits::default())?;
+        assert_eq!(decoded, reply);
+        // one byte per status is replaced by a single run of five bytes
+        assert_eq!(plain.len() - compact.len(), 995);
+
+        // limits apply to the compact encoding as well
+        let limits = MessageLimits {
+            max_values: 999,
+            ..Default::default()
+        };
+        assert!(matches!(
+            ProtocolMessage::decode_compact(&compact, &limits),
+            Err(MessageDecodeError::TooManyValues { limit: 999 })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replica_timestamp_sync_memory() -> Result<()> {
+        let alice_store = store::Store::memory();
+        let bob_store = store::Store::memory();
+
+        test_replica_timestamp_sync(alice_store, bob_store)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_replica_timestamp_sync_fs() -> Result<()> {
+        let alice_dbfile = tempfile::NamedTempFile::new()?;
+        let alice_store = store::fs::Store::persistent(alice_dbfile.path())?;
+        let bob_dbfile = tempfile::NamedTempFile::new()?;
+        let bob_store = store::fs::Store::persistent(bob_dbfile.path())?;
+        test_replica_timestamp_sync(alice_store, bob_store)?;
+
+        Ok(())
+    }
+
+    fn test_replica_timestamp_sync(mut alice_store: Store, mut bob_store: Store) -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut alice = alice_store.new_replica(namespace.clone())?;
+        let mut bob = bob_store.new_replica(namespace.clone())?;
+
+        let key = b"key";
+        let alice_value = b"alice";
+        let bob_value = b"bob";
+        let _alice_hash = alice.hash_and_insert(key, &author, alice_value)?;
+        // system time increased - sync should overwrite
         let bob_hash = bob.hash_and_insert(key, &author, bob_value)?;
         sync(&mut alice, &mut bob)?;
         assert_eq!(
@@ -1690,79 +4590,505 @@ mod tests {
         let mut alice = alice_store.new_replica(namespace.clone())?;
         let mut bob = bob_store.new_replica(namespace.clone())?;
 
-        let alice_value_2 = b"alice2";
-        // system time increased - sync should overwrite
-        let _bob_hash_2 = bob.hash_and_insert(key, &author, bob_value)?;
-        let alice_hash_2 = alice.hash_and_insert(key, &author, alice_value_2)?;
+        let alice_value_2 = b"alice2";
+        // system time increased - sync should overwrite
+        let _bob_hash_2 = bob.hash_and_insert(key, &author, bob_value)?;
+        let alice_hash_2 = alice.hash_and_insert(key, &author, alice_value_2)?;
+        sync(&mut alice, &mut bob)?;
+        assert_eq!(
+            get_content_hash(&mut alice_store, namespace.id(), author.id(), key)?,
+            Some(alice_hash_2)
+        );
+        assert_eq!(
+            get_content_hash(&mut alice_store, namespace.id(), author.id(), key)?,
+            Some(alice_hash_2)
+        );
+        alice_store.flush()?;
+        bob_store.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_future_timestamp() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let mut store = store::Store::memory();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        let key = b"hi";
+        let t = system_time_now();
+        let record = Record::from_data(b"1", t);
+        let entry0 = SignedEntry::from_parts(&namespace, &author, key, record);
+        replica.insert_entry(entry0.clone(), InsertOrigin::Local)?;
+
+        assert_eq!(
+            get_entry(&mut store, namespace.id(), author.id(), key)?,
+            entry0
+        );
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        let t = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT - 10000;
+        let record = Record::from_data(b"2", t);
+        let entry1 = SignedEntry::from_parts(&namespace, &author, key, record);
+        replica.insert_entry(entry1.clone(), InsertOrigin::Local)?;
+        assert_eq!(
+            get_entry(&mut store, namespace.id(), author.id(), key)?,
+            entry1
+        );
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        let t = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT;
+        let record = Record::from_data(b"2", t);
+        let entry2 = SignedEntry::from_parts(&namespace, &author, key, record);
+        replica.insert_entry(entry2.clone(), InsertOrigin::Local)?;
+        assert_eq!(
+            get_entry(&mut store, namespace.id(), author.id(), key)?,
+            entry2
+        );
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        let t = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT + 10000;
+        let record = Record::from_data(b"2", t);
+        let entry3 = SignedEntry::from_parts(&namespace, &author, key, record);
+        let res = replica.insert_entry(entry3, InsertOrigin::Local);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(
+                ValidationFailure::TooFarInTheFuture { timestamp, now, limit }
+            )) if timestamp == t && now < t && limit == MAX_TIMESTAMP_FUTURE_SHIFT
+        ));
+        assert_eq!(
+            get_entry(&mut store, namespace.id(), author.id(), key)?,
+            entry2
+        );
+        store.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_with_timestamp() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let mut store = store::Store::memory();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(namespace.clone())?;
+        let key = b"hi";
+
+        // import an entry from the past
+        let t = system_time_now() - 60 * 60 * 1_000_000;
+        let hash = Hash::new(b"old");
+        replica.insert_with_timestamp(key, &author, hash, 3, t)?;
+        let entry = get_entry(&mut store, namespace.id(), author.id(), key)?;
+        assert_eq!(entry.timestamp(), t);
+        assert_eq!(entry.content_hash(), hash);
+
+        // an even older entry does not replace the existing one
+        let mut replica = store.open_replica(&namespace.id())?;
+        let res = replica.insert_with_timestamp(key, &author, Hash::new(b"older"), 5, t - 1);
+        assert!(matches!(res, Err(InsertError::NewerEntryExists)));
+
+        // timestamps too far in the future are rejected
+        let t_future = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT + 10000;
+        let res = replica.insert_with_timestamp(key, &author, Hash::new(b"new"), 3, t_future);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(
+                ValidationFailure::TooFarInTheFuture { timestamp, .. }
+            )) if timestamp == t_future
+        ));
+        assert_eq!(
+            get_entry(&mut store, namespace.id(), author.id(), key)?,
+            entry
+        );
+        store.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_with_outcome() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let mut store = store::Store::memory();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(namespace.clone())?;
+        let (tx, rx) = async_channel::unbounded();
+        replica.info.subscribe(tx);
+
+        let outcome = replica.insert_with_outcome(b"a/b", &author, Hash::new(b"ab"), 2)?;
+        assert_eq!(outcome, InsertOutcome::Inserted { removed: 0 });
+        assert!(matches!(rx.try_recv(), Ok(Event::LocalInsert { .. })));
+
+        // a newer entry for a prefix removes the existing entry
+        let outcome = replica.insert_with_outcome(b"a", &author, Hash::new(b"a"), 1)?;
+        assert_eq!(outcome, InsertOutcome::Inserted { removed: 1 });
+        assert!(matches!(rx.try_recv(), Ok(Event::LocalInsert { .. })));
+
+        // an entry superseded by a newer entry is not inserted, and no event is emitted
+        let t_future = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT / 2;
+        replica.insert_with_timestamp(b"c", &author, Hash::new(b"c1"), 2, t_future)?;
+        assert!(rx.try_recv().is_ok());
+        let outcome = replica.insert_with_outcome(b"c", &author, Hash::new(b"c2"), 2)?;
+        assert_eq!(outcome, InsertOutcome::NotInserted);
+        assert!(rx.try_recv().is_err());
+        let outcome = replica.insert_with_outcome(b"a/c", &author, Hash::new(b"ac"), 2)?;
+        assert_eq!(outcome, InsertOutcome::Inserted { removed: 0 });
+        let res = replica.insert(b"c", &author, Hash::new(b"c2"), 2);
+        assert!(matches!(res, Err(InsertError::NewerEntryExists)));
+
+        let entry = get_entry(&mut store, namespace.id(), author.id(), b"c")?;
+        assert_eq!(entry.content_hash(), Hash::new(b"c1"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_skip_if_unchanged() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let mut store = store::Store::memory();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(namespace)?;
+        let (tx, rx) = async_channel::unbounded();
+        replica.info.subscribe(tx);
+        let skip = InsertOpts::default().skip_if_unchanged();
+
+        let outcome = replica.insert_with_opts(b"k", &author, Hash::new(b"v1"), 2, skip)?;
+        assert_eq!(
+            outcome,
+            InsertOptsOutcome::Applied(InsertOutcome::Inserted { removed: 0 })
+        );
+        assert!(rx.try_recv().is_ok());
+        let first = replica.get_exact(author.id(), b"k", false)?.unwrap();
+
+        // identical content is not written again
+        let outcome = replica.insert_with_opts(b"k", &author, Hash::new(b"v1"), 2, skip)?;
+        assert_eq!(outcome, InsertOptsOutcome::Unchanged);
+        assert!(rx.try_recv().is_err());
+        let entry = replica.get_exact(author.id(), b"k", false)?.unwrap();
+        assert_eq!(entry.timestamp(), first.timestamp());
+
+        // without the option, a new entry with a fresh timestamp is written
+        let opts = InsertOpts::default();
+        let outcome = replica.insert_with_opts(b"k", &author, Hash::new(b"v1"), 2, opts)?;
+        assert!(matches!(
+            outcome,
+            InsertOptsOutcome::Applied(InsertOutcome::Inserted { .. })
+        ));
+        assert!(rx.try_recv().is_ok());
+        let entry = replica.get_exact(author.id(), b"k", false)?.unwrap();
+        assert!(entry.timestamp() > first.timestamp());
+
+        // changed content or length is written
+        let outcome = replica.insert_with_opts(b"k", &author, Hash::new(b"v1"), 3, skip)?;
+        assert!(matches!(
+            outcome,
+            InsertOptsOutcome::Applied(InsertOutcome::Inserted { .. })
+        ));
+        let outcome = replica.insert_with_opts(b"k", &author, Hash::new(b"v2"), 3, skip)?;
+        assert!(matches!(
+            outcome,
+            InsertOptsOutcome::Applied(InsertOutcome::Inserted { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_replica_generation() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let mut store = store::Store::memory();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(namespace.clone())?;
+        assert_eq!(replica.generation(), 0);
+
+        replica.hash_and_insert(b"a/1", &author, b"1")?;
+        replica.hash_and_insert(b"a/2", &author, b"2")?;
+        assert_eq!(replica.generation(), 2);
+
+        // a prefix deletion inserts a single tombstone
+        replica.delete_prefix(b"a", &author)?;
+        assert_eq!(replica.generation(), 3);
+
+        // superseded inserts do not change the generation
+        let t_future = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT / 2;
+        replica.insert_with_timestamp(b"b", &author, Hash::new(b"b1"), 2, t_future)?;
+        assert_eq!(replica.generation(), 4);
+        let outcome = replica.insert_with_outcome(b"b", &author, Hash::new(b"b2"), 2)?;
+        assert_eq!(outcome, InsertOutcome::NotInserted);
+        let res = replica.insert(b"b", &author, Hash::new(b"b2"), 2);
+        assert!(matches!(res, Err(InsertError::NewerEntryExists)));
+        assert_eq!(replica.generation(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn test_compare_heads() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let mut store = store::Store::memory();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(namespace.clone())?;
+        let mut authors = [(); 3].map(|_| Author::new(&mut rng));
+        authors.sort_by_key(|author| author.id());
+        let [alice, bob, carol] = authors;
+        let (a, b, c) = (alice.id(), bob.id(), carol.id());
+
+        // an empty replica is in sync with empty heads
+        let cmp = replica.compare_heads(&AuthorHeads::default())?;
+        assert_eq!(
+            cmp,
+            HeadComparison {
+                we_ahead: vec![],
+                they_ahead: vec![],
+                in_sync: true
+            }
+        );
+
+        let t = system_time_now();
+        replica.insert_with_timestamp(b"x", &alice, Hash::new(b"x"), 1, t)?;
+        replica.insert_with_timestamp(b"y", &alice, Hash::new(b"y"), 1, t + 10)?;
+        replica.insert_with_timestamp(b"x", &bob, Hash::new(b"x"), 1, t + 5)?;
+
+        // all in sync
+        let remote = AuthorHeads::from_iter([(a, t + 10), (b, t + 5)]);
+        let cmp = replica.compare_heads(&remote)?;
+        assert!(cmp.in_sync);
+        assert!(cmp.we_ahead.is_empty() && cmp.they_ahead.is_empty());
+
+        // we are ahead for an older head and for an author unknown to the remote
+        let remote = AuthorHeads::from_iter([(a, t + 1)]);
+        let cmp = replica.compare_heads(&remote)?;
+        assert!(!cmp.in_sync);
+        assert_eq!(cmp.we_ahead, vec![a, b]);
+        assert!(cmp.they_ahead.is_empty());
+
+        // they are ahead for a newer head and for an author unknown to us
+        let remote = AuthorHeads::from_iter([(a, t + 10), (b, t + 6), (c, t)]);
+        let cmp = replica.compare_heads(&remote)?;
+        assert!(!cmp.in_sync);
+        assert!(cmp.we_ahead.is_empty());
+        assert_eq!(cmp.they_ahead, vec![b, c]);
+
+        // mixed
+        let remote = AuthorHeads::from_iter([(a, t + 9), (b, t + 6)]);
+        let cmp = replica.compare_heads(&remote)?;
+        assert!(!cmp.in_sync);
+        assert_eq!(cmp.we_ahead, vec![a]);
+        assert_eq!(cmp.they_ahead, vec![b]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_content_len() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+        let mut alice = alice_store.new_replica(namespace.clone())?;
+        let mut bob = bob_store.new_replica(namespace.clone())?;
+        bob.info.set_max_content_len(Some(1024));
+
+        alice.insert(b"small", &author, Hash::new(b"small"), 1024)?;
+        alice.insert(b"huge", &author, Hash::new(b"huge"), 1 << 50)?;
+        let huge = alice
+            .store
+            .store
+            .get_exact(namespace.id(), author.id(), b"huge", false)?
+            .expect("entry exists");
+
+        // remote entries with an oversized content length are rejected
+        let res = bob.insert_remote_entry(huge, [1u8; 32], ContentStatus::Missing);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::ContentTooLarge { len, max: 1024 }))
+                if len == 1 << 50
+        ));
+
+        // and skipped while syncing
+        let (_alice_out, bob_out) = sync(&mut alice, &mut bob)?;
+        assert_eq!(bob_out.num_inserted, 1);
+        drop(alice);
+        drop(bob);
+        assert!(bob_store
+            .get_exact(namespace.id(), author.id(), b"small", false)?
+            .is_some());
+        assert!(bob_store
+            .get_exact(namespace.id(), author.id(), b"huge", false)?
+            .is_none());
+
+        // local inserts enforce the limit as well
+        let mut bob = bob_store.open_replica(&namespace.id())?;
+        bob.info.set_max_content_len(Some(1024));
+        let res = bob.insert(b"local", &author, Hash::new(b"local"), 1025);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(
+                ValidationFailure::ContentTooLarge {
+                    len: 1025,
+                    max: 1024
+                }
+            ))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_empty_entry() {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let author = Author::new(&mut rng);
+        let id = RecordIdentifier::new(namespace.id(), author.id(), b"key");
+
+        let entry = Entry::new(id, Record::new(Hash::EMPTY, 4, system_time_now()));
+        let err = entry.validate_empty().unwrap_err();
+        assert!(matches!(
+            err,
+            ValidationFailure::InvalidEmptyEntry { hash, len: 4 } if hash == Hash::EMPTY
+        ));
+        assert!(err.to_string().contains("length 4"));
+    }
+
+    #[test]
+    fn test_read_cache() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+        let mut alice = alice_store.new_replica(namespace.clone())?;
+        let mut bob = bob_store.new_replica(namespace.clone())?;
+        alice.info.enable_read_cache(8);
+
+        let get_hash = |replica: &mut Replica, key: &[u8]| -> Result<Option<Hash>> {
+            let entry = replica.get_exact(author.id(), key, false)?;
+            Ok(entry.map(|entry| entry.content_hash()))
+        };
+
+        // overwriting a cached record
+        let v1 = alice.hash_and_insert(b"a/b", &author, b"v1")?;
+        assert_eq!(get_hash(&mut alice, b"a/b")?, Some(v1));
+        let v2 = alice.hash_and_insert(b"a/b", &author, b"v2")?;
+        assert_eq!(get_hash(&mut alice, b"a/b")?, Some(v2));
+
+        // missing records are cached too
+        assert_eq!(get_hash(&mut alice, b"c")?, None);
+        let c = alice.hash_and_insert(b"c", &author, b"c")?;
+        assert_eq!(get_hash(&mut alice, b"c")?, Some(c));
+
+        // prefix deletions invalidate all records below the prefix
+        alice.delete_prefix(b"a", &author)?;
+        assert_eq!(get_hash(&mut alice, b"a/b")?, None);
+        assert!(alice.get_exact(author.id(), b"a", true)?.is_some());
+
+        // entries received while syncing
+        let v3 = bob.hash_and_insert(b"a/b", &author, b"v3")?;
         sync(&mut alice, &mut bob)?;
-        assert_eq!(
-            get_content_hash(&mut alice_store, namespace.id(), author.id(), key)?,
-            Some(alice_hash_2)
-        );
-        assert_eq!(
-            get_content_hash(&mut alice_store, namespace.id(), author.id(), key)?,
-            Some(alice_hash_2)
-        );
-        alice_store.flush()?;
-        bob_store.flush()?;
+        assert_eq!(get_hash(&mut alice, b"a/b")?, Some(v3));
+
+        // local removal of all entries of an author
+        alice.remove_author_entries(&author, RemoveMode::Local)?;
+        assert_eq!(get_hash(&mut alice, b"a/b")?, None);
+        assert_eq!(get_hash(&mut alice, b"c")?, None);
+
+        // evicted records are dropped from the index
+        for i in 0..20u8 {
+            get_hash(&mut alice, &[b'k', i])?;
+        }
+        let cache = alice.info.read_cache.as_ref().unwrap();
+        assert_eq!(cache.entries.len(), 8);
+        assert_eq!(cache.index.len(), 8);
         Ok(())
     }
 
     #[test]
-    fn test_future_timestamp() -> Result<()> {
+    fn test_subscribe_with_replay() -> Result<()> {
         let mut rng = rand::thread_rng();
         let mut store = store::Store::memory();
         let author = Author::new(&mut rng);
         let namespace = NamespaceSecret::new(&mut rng);
-
         let mut replica = store.new_replica(namespace.clone())?;
-        let key = b"hi";
-        let t = system_time_now();
-        let record = Record::from_data(b"1", t);
-        let entry0 = SignedEntry::from_parts(&namespace, &author, key, record);
-        replica.insert_entry(entry0.clone(), InsertOrigin::Local)?;
 
-        assert_eq!(
-            get_entry(&mut store, namespace.id(), author.id(), key)?,
-            entry0
-        );
+        // without a replay buffer, nothing is replayed
+        replica.hash_and_insert(b"a", &author, b"a")?;
+        let (tx, rx) = async_channel::unbounded();
+        replica.info.subscribe_with_replay(tx, 10);
+        assert!(rx.try_recv().is_err());
 
-        let mut replica = store.new_replica(namespace.clone())?;
-        let t = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT - 10000;
-        let record = Record::from_data(b"2", t);
-        let entry1 = SignedEntry::from_parts(&namespace, &author, key, record);
-        replica.insert_entry(entry1.clone(), InsertOrigin::Local)?;
-        assert_eq!(
-            get_entry(&mut store, namespace.id(), author.id(), key)?,
-            entry1
-        );
+        replica.info.set_event_replay_capacity(2);
+        for key in [b"b", b"c", b"d"] {
+            replica.hash_and_insert(key, &author, key)?;
+        }
+        assert_eq!(rx.len(), 3);
+
+        // a late subscriber receives the missed events, limited by the capacity and `n`
+        let keys = |rx: &async_channel::Receiver<Event>| {
+            std::iter::from_fn(|| rx.try_recv().ok())
+                .map(|event| match event {
+                    Event::LocalInsert { entry, .. } => entry.key().to_vec(),
+                    event => panic!("unexpected event {event:?}"),
+                })
+                .collect::<Vec<_>>()
+        };
+        let (tx, rx) = async_channel::unbounded();
+        replica.info.subscribe_with_replay(tx, 10);
+        assert_eq!(keys(&rx), [b"c".to_vec(), b"d".to_vec()]);
+        let (tx, rx2) = async_channel::unbounded();
+        replica.info.subscribe_with_replay(tx, 1);
+        assert_eq!(keys(&rx2), [b"d".to_vec()]);
+
+        // and events after subscribing as usual
+        replica.hash_and_insert(b"e", &author, b"e")?;
+        assert_eq!(keys(&rx), [b"e".to_vec()]);
+        assert_eq!(keys(&rx2), [b"e".to_vec()]);
+        Ok(())
+    }
 
+    #[test]
+    fn test_insert_signed_local() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let mut store = store::Store::memory();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let other_namespace = NamespaceSecret::new(&mut rng);
         let mut replica = store.new_replica(namespace.clone())?;
-        let t = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT;
-        let record = Record::from_data(b"2", t);
-        let entry2 = SignedEntry::from_parts(&namespace, &author, key, record);
-        replica.insert_entry(entry2.clone(), InsertOrigin::Local)?;
-        assert_eq!(
-            get_entry(&mut store, namespace.id(), author.id(), key)?,
-            entry2
-        );
+        let (tx, rx) = async_channel::unbounded();
+        replica.info.subscribe(tx);
+        let sign = |namespace_id: NamespaceId, secret: &NamespaceSecret, key: &str| {
+            let id = RecordIdentifier::new(namespace_id, author.id(), key);
+            let entry = Entry::new(id, Record::new_current(Hash::new(key), 3));
+            SignedEntry::from_entry(entry, secret, &author)
+        };
 
-        let mut replica = store.new_replica(namespace.clone())?;
-        let t = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT + 10000;
-        let record = Record::from_data(b"2", t);
-        let entry3 = SignedEntry::from_parts(&namespace, &author, key, record);
-        let res = replica.insert_entry(entry3, InsertOrigin::Local);
+        // a correctly signed entry is inserted as a local entry
+        replica.insert_signed_local(sign(namespace.id(), &namespace, "foo"))?;
+        assert!(matches!(rx.try_recv(), Ok(Event::LocalInsert { .. })));
+        let entry = get_entry(&mut store, namespace.id(), author.id(), b"foo")?;
+        assert_eq!(entry.content_hash(), Hash::new("foo"));
+
+        // entries of another namespace, or signed with the wrong namespace key, are rejected
+        let mut replica = store.open_replica(&namespace.id())?;
+        let res = replica.insert_signed_local(sign(other_namespace.id(), &other_namespace, "bar"));
         assert!(matches!(
             res,
-            Err(InsertError::Validation(
-                ValidationFailure::TooFarInTheFuture
-            ))
+            Err(InsertError::Validation(ValidationFailure::InvalidNamespace { expected, got }))
+                if expected == namespace.id() && got == other_namespace.id()
         ));
-        assert_eq!(
-            get_entry(&mut store, namespace.id(), author.id(), key)?,
-            entry2
-        );
-        store.flush()?;
+        let res = replica.insert_signed_local(sign(namespace.id(), &other_namespace, "bar"));
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::BadSignature))
+        ));
+        drop(replica);
+        assert!(store
+            .get_exact(namespace.id(), author.id(), b"bar", true)?
+            .is_none());
+
+        // read-only replicas do not accept local entries
+        let mut store = store::Store::memory();
+        store.import_namespace(Capability::Read(namespace.id()))?;
+        let mut replica = store.open_replica(&namespace.id())?;
+        let res = replica.insert_signed_local(sign(namespace.id(), &namespace, "baz"));
+        assert!(matches!(res, Err(InsertError::ReadOnly)));
         Ok(())
     }
 
@@ -1780,6 +5106,325 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_public_author_record_id_and_query() -> Result<()> {
+        let mut store = store::Store::memory();
+        let mut rng = rand::thread_rng();
+        let alice = Author::new(&mut rng);
+        let bob = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(myspace.clone())?;
+        replica.hash_and_insert(b"a", &alice, b"alice")?;
+        replica.hash_and_insert(b"b", &bob, b"bob")?;
+
+        let alice_public = alice.public_key();
+        let expected = RecordIdentifier::new(myspace.id(), alice.id(), b"a");
+        assert_eq!(replica.record_id(b"a", &alice), expected);
+        assert_eq!(replica.record_id(b"a", alice.id()), expected);
+        assert_eq!(replica.record_id(b"a", alice_public), expected);
+        assert_eq!(RecordIdentifier::new(&myspace, &alice, b"a"), expected);
+        drop(replica);
+
+        for query in [
+            Query::author(alice_public).build(),
+            Query::all().author(alice_public).build(),
+            Query::author(alice.id()).build(),
+        ] {
+            let entries = store
+                .get_many(myspace.id(), query)?
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].id(), &expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_signed_entry_same_entry() {
+        let mut rng = rand::thread_rng();
+        let alice = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let record = Record::new(Hash::new(b"hello"), 5, 1);
+        let a = SignedEntry::from_parts(&myspace, &alice, b"foo", record.clone());
+        let b = SignedEntry::new(
+            EntrySignature::from_parts(&[1u8; 64], &[2u8; 64]),
+            a.entry().clone(),
+        );
+        assert!(a.same_entry(&b));
+        assert_ne!(a, b);
+
+        let c = SignedEntry::from_parts(&myspace, &alice, b"bar", record);
+        assert!(!a.same_entry(&c));
+    }
+
+    #[test]
+    fn test_touch() -> Result<()> {
+        let mut store = store::Store::memory();
+        let mut rng = rand::thread_rng();
+        let alice = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(myspace.clone())?;
+
+        assert!(replica.touch(&alice, "missing")?.is_none());
+
+        let hash = replica.hash_and_insert("key", &alice, "hello")?;
+        let before = replica.get_exact(alice.id(), "key", false)?.unwrap();
+        let touched = replica.touch(&alice, "key")?.unwrap();
+        assert!(touched.timestamp() > before.timestamp());
+        assert_eq!(touched.content_hash(), hash);
+        assert_eq!(touched.content_len(), before.content_len());
+        let after = replica.get_exact(alice.id(), "key", false)?.unwrap();
+        assert_eq!(after, touched);
+
+        // an entry ahead of our clock is moved forward as well
+        let future = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT / 2;
+        replica.insert_with_timestamp("future", &alice, hash, 5, future)?;
+        let touched = replica.touch(&alice, "future")?.unwrap();
+        assert_eq!(touched.timestamp(), future + 1);
+
+        // deleted entries are not touched
+        replica.delete_prefix("key", &alice)?;
+        assert!(replica.touch(&alice, "key")?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_event_serde() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let hash = Hash::new(b"hello");
+        let entry = Entry::new(
+            RecordIdentifier::new(namespace.id(), author.id(), b"key"),
+            Record::new(hash, 5, 1),
+        )
+        .sign(&namespace, &author);
+        let events = [
+            Event::LocalInsert {
+                namespace: namespace.id(),
+                entry: entry.clone(),
+            },
+            Event::RemoteInsert {
+                namespace: namespace.id(),
+                entry,
+                from: [1u8; 32],
+                should_download: true,
+                remote_content_status: ContentStatus::Incomplete,
+            },
+            Event::SyncBatchApplied {
+                namespace: namespace.id(),
+                count: 3,
+            },
+            Event::ContentStatusChanged {
+                namespace: namespace.id(),
+                hash,
+                status: ContentStatus::Complete,
+            },
+            Event::Closed {
+                namespace: namespace.id(),
+            },
+        ];
+        for event in events {
+            let bytes = postcard::to_stdvec(&event)?;
+            let decoded: Event = postcard::from_bytes(&bytes)?;
+            assert_eq!(decoded, event);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_replica_clear() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let mut store = store::Store::memory();
+        let author = store.new_author(&mut rng)?;
+        let namespace = NamespaceSecret::new(&mut rng);
+        let other = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(other.clone())?;
+        replica.hash_and_insert("a", &author, "other")?;
+        store.close_replica(other.id());
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.info.enable_read_cache(8);
+        let (tx, rx) = async_channel::bounded(10);
+        replica.info.subscribe(tx);
+        replica.hash_and_insert("a", &author, "a")?;
+        replica.hash_and_insert("b", &author, "b")?;
+        replica.delete_prefix("b", &author)?;
+        assert!(replica.get_exact(author.id(), "a", false)?.is_some());
+        // the tombstone is removed as well
+        assert_eq!(replica.clear()?, 2);
+        // cached records are dropped
+        assert!(replica.get_exact(author.id(), "a", false)?.is_none());
+        while rx.try_recv().is_ok() {}
+
+        // the replica stays open and writable, and subscribers are kept
+        replica.hash_and_insert("c", &author, "c")?;
+        assert!(
+            matches!(rx.try_recv(), Ok(Event::LocalInsert { entry, .. }) if entry.key() == b"c")
+        );
+        drop(replica);
+        let entries = store
+            .get_many(namespace.id(), Query::all())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key(), b"c");
+
+        // the namespace still opens after closing it
+        store.close_replica(namespace.id());
+        assert!(store.open_replica(&namespace.id()).is_ok());
+        // other namespaces are not affected
+        assert_eq!(store.get_many(other.id(), Query::all())?.count(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_counter_value_encoding() {
+        let value = CounterValue::new(Bytes::from_static(b"likes"), -3);
+        let entry_key = value.to_entry_key();
+        assert!(entry_key.starts_with(&CounterValue::entry_key_prefix(b"likes")));
+        assert!(is_reserved_key(&entry_key));
+        assert_eq!(CounterValue::from_entry_key(&entry_key), Some(value));
+        // the prefix of one counter key is not a prefix of another counter key
+        assert!(!CounterValue::entry_key_prefix(b"likes")
+            .starts_with(&CounterValue::entry_key_prefix(b"like")));
+        assert_eq!(CounterValue::from_entry_key(b"likes"), None);
+        assert_eq!(
+            CounterValue::from_entry_key(&entry_key[..entry_key.len() - 1]),
+            None
+        );
+        assert_eq!(
+            CounterValue::aggregate([CounterValue::new("a", 2), CounterValue::new("a", 5)]),
+            7
+        );
+    }
+
+    #[test]
+    fn test_record_identifier_from_bytes() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let id = RecordIdentifier::new(
+            NamespaceSecret::new(&mut rng).id(),
+            Author::new(&mut rng).id(),
+            "key",
+        );
+        assert_eq!(RecordIdentifier::from_bytes(id.as_bytes())?, id);
+        let empty_key = RecordIdentifier::from_bytes(id.as_bytes().slice(..64))?;
+        assert_eq!(empty_key.key(), b"");
+        assert_eq!(
+            RecordIdentifier::from_bytes(id.as_bytes().slice(..63)),
+            Err(InvalidRecordIdentifier { len: 63 })
+        );
+
+        // decoding goes through the same check
+        let encoded = postcard::to_stdvec(&id)?;
+        assert_eq!(postcard::from_bytes::<RecordIdentifier>(&encoded)?, id);
+        let short = postcard::to_stdvec(&Bytes::from_static(&[0u8; 10]))?;
+        assert!(postcard::from_bytes::<RecordIdentifier>(&short).is_err());
+        Ok(())
+    }
+
+    /// Decodes `bytes` with all decoders for untrusted input and uses the decoded values.
+    fn decode_all(bytes: &[u8]) {
+        if let Ok(id) = RecordIdentifier::from_bytes(Bytes::copy_from_slice(bytes)) {
+            let _ = (id.parts(), id.to_byte_tuple(), format!("{id:?}"));
+        }
+        if let Ok(id) = postcard::from_bytes::<RecordIdentifier>(bytes) {
+            let _ = (id.as_byte_tuple(), id.namespace(), id.author());
+        }
+        if let Ok(entry) = postcard::from_bytes::<SignedEntry>(bytes) {
+            let _ = (entry.id().parts(), entry.verify(&()).is_ok());
+        }
+        let limits = MessageLimits::default();
+        if let Ok(message) = decode_protocol_message(bytes, &limits) {
+            let _ = message.describe();
+        }
+        if let Ok(message) = ProtocolMessage::decode_compact(bytes, &limits) {
+            let _ = message.describe();
+        }
+        if let Some(value) = CounterValue::from_entry_key(bytes) {
+            let _ = value.to_entry_key();
+        }
+    }
+
+    #[test_strategy::proptest]
+    fn test_decode_random_bytes_never_panics(
+        #[strategy(proptest::collection::vec(proptest::num::u8::ANY, 0..256))] bytes: Vec<u8>,
+    ) {
+        decode_all(&bytes);
+    }
+
+    #[test_strategy::proptest]
+    fn test_decode_corrupted_entry_never_panics(
+        #[strategy(proptest::collection::vec(proptest::num::u8::ANY, 0..64))] key: Vec<u8>,
+        truncate: usize,
+        flip: (usize, u8),
+    ) {
+        let namespace = NamespaceSecret::from_bytes(&[1u8; 32]);
+        let author = Author::from_bytes(&[2u8; 32]);
+        let id = RecordIdentifier::new(namespace.id(), author.id(), &key);
+        let entry = Entry::new(id, Record::new(Hash::new(&key), key.len() as u64, 1));
+        let entry = SignedEntry::from_entry(entry, &namespace, &author);
+        let mut bytes = postcard::to_stdvec(&entry).unwrap();
+        decode_all(&bytes);
+        let (index, byte) = flip;
+        let index = index % bytes.len();
+        bytes[index] ^= byte;
+        decode_all(&bytes);
+        bytes.truncate(truncate % bytes.len());
+        decode_all(&bytes);
+    }
+
+    #[test]
+    fn test_counter_add() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let alice = Author::new(&mut rng);
+        let bob = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+
+        let mut alice_replica = alice_store.new_replica(myspace.clone())?;
+        let mut bob_replica = bob_store.new_replica(myspace.clone())?;
+        assert_eq!(alice_replica.counter_add("likes", &alice, 2)?, 2);
+        assert_eq!(alice_replica.counter_add("likes", &alice, 3)?, 5);
+        assert_eq!(bob_replica.counter_add("likes", &bob, 10)?, 10);
+        assert_eq!(bob_replica.counter_add("likes", &bob, -1)?, 9);
+        // a different counter whose key extends the first one
+        bob_replica.counter_add("likes2", &bob, 100)?;
+        drop(alice_replica);
+        drop(bob_replica);
+        assert_eq!(alice_store.get_counter(myspace.id(), "likes")?, 5);
+        assert_eq!(bob_store.get_counter(myspace.id(), "likes")?, 9);
+
+        let mut alice_replica = alice_store.open_replica(&myspace.id())?;
+        let mut bob_replica = bob_store.open_replica(&myspace.id())?;
+        sync(&mut alice_replica, &mut bob_replica)?;
+        drop(alice_replica);
+        drop(bob_replica);
+        assert_eq!(alice_store.get_counter(myspace.id(), "likes")?, 14);
+        assert_eq!(bob_store.get_counter(myspace.id(), "likes")?, 14);
+        assert_eq!(alice_store.get_counter(myspace.id(), "likes2")?, 100);
+        assert_eq!(alice_store.get_counter(myspace.id(), "missing")?, 0);
+
+        // counter entries are not returned from regular queries
+        let entries = alice_store.get_many(myspace.id(), Query::all())?.count();
+        assert_eq!(entries, 0);
+
+        // a previous contribution written with a clock ahead of ours
+        let mut alice_replica = alice_store.open_replica(&myspace.id())?;
+        let key = CounterValue::new(Bytes::from_static(b"skewed"), 7).to_entry_key();
+        let bytes = 7i64.to_be_bytes();
+        let id = RecordIdentifier::new(myspace.id(), alice.id(), key);
+        let timestamp = system_time_now() + MAX_TIMESTAMP_FUTURE_SHIFT / 2;
+        let record = Record::new(Hash::new(bytes), bytes.len() as u64, timestamp);
+        let entry = Entry::new(id, record).sign(&myspace, &alice);
+        alice_replica.insert_entry(entry, InsertOrigin::Local)?;
+        assert_eq!(alice_replica.counter_add("skewed", &alice, 1)?, 8);
+        assert_eq!(alice_replica.counter_add("skewed", &alice, 1)?, 9);
+        drop(alice_replica);
+        assert_eq!(alice_store.get_counter(myspace.id(), "skewed")?, 9);
+        Ok(())
+    }
+
     #[test]
     fn test_prefix_delete_memory() -> Result<()> {
         let store = store::Store::memory();
@@ -1813,10 +5458,31 @@ mod tests {
             Some(hash2)
         );
 
+        // preview the deletion, this must not change anything
+        let mut replica = store.new_replica(myspace.clone())?;
+        let hash3 = replica.hash_and_insert(b"fob", &alice, b"not deleted")?;
+        let preview = replica.delete_prefix_preview(b"foo", alice.id())?;
+        assert_eq!(
+            preview,
+            vec![
+                RecordIdentifier::new(myspace.id(), alice.id(), b"foobar"),
+                RecordIdentifier::new(myspace.id(), alice.id(), b"fooboo"),
+            ]
+        );
+        assert_eq!(
+            get_content_hash(&mut store, myspace.id(), alice.id(), b"foobar")?,
+            Some(hash1)
+        );
+
         // delete
         let mut replica = store.new_replica(myspace.clone())?;
         let deleted = replica.delete_prefix(b"foo", &alice)?;
         assert_eq!(deleted, 2);
+        assert_eq!(preview.len(), 2);
+        assert_eq!(
+            get_content_hash(&mut store, myspace.id(), alice.id(), b"fob")?,
+            Some(hash3)
+        );
         assert_eq!(
             store.get_exact(myspace.id(), alice.id(), b"foobar", false)?,
             None
@@ -1826,10 +5492,104 @@ mod tests {
             None
         );
         assert_eq!(
-            store.get_exact(myspace.id(), alice.id(), b"foo", false)?,
-            None
+            store.get_exact(myspace.id(), alice.id(), b"foo", false)?,
+            None
+        );
+        store.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_range() -> Result<()> {
+        let mut store = store::Store::memory();
+        let mut rng = rand::thread_rng();
+        let alice = Author::new(&mut rng);
+        let bob = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let id = |author: &Author, key: &str| RecordIdentifier::new(myspace.id(), author.id(), key);
+
+        let mut replica = store.new_replica(myspace.clone())?;
+        for key in ["a", "c", "e", "ez", "g", "m", "z"] {
+            replica.hash_and_insert(key, &alice, key)?;
+        }
+        replica.hash_and_insert("e", &bob, "e")?;
+
+        // delete the keys from `c` up to but excluding `m`.
+        let range = Range::new(id(&alice, "c"), id(&alice, "m"));
+        let deleted = replica.delete_range(range.clone(), &alice)?;
+        assert_eq!(deleted, 4);
+        drop(replica);
+
+        for key in ["c", "e", "ez", "g"] {
+            assert!(store
+                .get_exact(myspace.id(), alice.id(), key, false)?
+                .is_none());
+        }
+        for key in ["a", "m", "z"] {
+            assert!(store
+                .get_exact(myspace.id(), alice.id(), key, false)?
+                .is_some());
+        }
+        // entries of other authors are not touched
+        assert!(store
+            .get_exact(myspace.id(), bob.id(), "e", false)?
+            .is_some());
+
+        // `ez` was deleted by the deletion of `e`, so only three empty entries were inserted
+        let empty = store
+            .get_many(myspace.id(), Query::author(alice.id()).include_empty())?
+            .filter(|entry| matches!(entry, Ok(entry) if entry.is_empty()))
+            .count();
+        assert_eq!(empty, 3);
+
+        // deleting again is a no-op
+        let mut replica = store.open_replica(&myspace.id())?;
+        assert_eq!(replica.delete_range(range, &alice)?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_namespace_digest() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let alice = Author::new(&mut rng);
+        let bob = Author::new(&mut rng);
+        let myspace = NamespaceSecret::new(&mut rng);
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+
+        let empty = alice_store.namespace_digest(myspace.id())?;
+        assert_eq!(empty, bob_store.namespace_digest(myspace.id())?);
+
+        let mut alice_replica = alice_store.new_replica(myspace.clone())?;
+        let mut bob_replica = bob_store.new_replica(myspace.clone())?;
+        for key in ["a", "b/1", "c"] {
+            alice_replica.hash_and_insert(key, &alice, key)?;
+        }
+        for key in ["b/2", "c", "d"] {
+            bob_replica.hash_and_insert(key, &bob, key)?;
+        }
+        bob_replica.delete_prefix("d", &bob)?;
+        sync(&mut alice_replica, &mut bob_replica)?;
+        drop(alice_replica);
+        drop(bob_replica);
+
+        let digest = alice_store.namespace_digest(myspace.id())?;
+        assert_ne!(digest, empty);
+        assert_eq!(digest, bob_store.namespace_digest(myspace.id())?);
+
+        // the digest is the hash of the canonical form, which contains the live entries only
+        let mut canonical = Vec::new();
+        assert_eq!(
+            alice_store.write_canonical(myspace.id(), &mut canonical)?,
+            5
         );
-        store.flush()?;
+        assert_eq!(Hash::new(&canonical), digest);
+
+        // a single differing entry changes the digest
+        let mut bob_replica = bob_store.open_replica(&myspace.id())?;
+        bob_replica.hash_and_insert("c", &bob, "changed")?;
+        drop(bob_replica);
+        assert_ne!(digest, bob_store.namespace_digest(myspace.id())?);
         Ok(())
     }
 
@@ -2006,92 +5766,685 @@ mod tests {
     }
 
     #[test]
-    fn test_latest_iter_memory() -> Result<()> {
+    fn test_latest_iter_memory() -> Result<()> {
+        let store = store::Store::memory();
+        test_latest_iter(store)
+    }
+
+    #[test]
+    fn test_latest_iter_fs() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let store = store::fs::Store::persistent(dbfile.path())?;
+        test_latest_iter(store)
+    }
+
+    fn test_latest_iter(mut store: Store) -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let author0 = Author::new(&mut rng);
+        let author1 = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(namespace.clone())?;
+
+        replica.hash_and_insert(b"a0.1", &author0, b"hi")?;
+        let latest = store
+            .get_latest_for_each_author(namespace.id())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].2, b"a0.1".to_vec());
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.hash_and_insert(b"a1.1", &author1, b"hi")?;
+        replica.hash_and_insert(b"a0.2", &author0, b"hi")?;
+        let latest = store
+            .get_latest_for_each_author(namespace.id())?
+            .collect::<Result<Vec<_>>>()?;
+        let mut latest_keys: Vec<Vec<u8>> = latest.iter().map(|r| r.2.to_vec()).collect();
+        latest_keys.sort();
+        assert_eq!(latest_keys, vec![b"a0.2".to_vec(), b"a1.1".to_vec()]);
+        store.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_replica_byte_keys_memory() -> Result<()> {
+        let store = store::Store::memory();
+
+        test_replica_byte_keys(store)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_replica_byte_keys_fs() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let store = store::fs::Store::persistent(dbfile.path())?;
+        test_replica_byte_keys(store)?;
+
+        Ok(())
+    }
+
+    fn test_replica_byte_keys(mut store: Store) -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+
+        let hash = Hash::new(b"foo");
+        let len = 3;
+
+        let key = vec![1u8, 0u8];
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.insert(key, &author, hash, len)?;
+        assert_keys(&mut store, namespace.id(), vec![vec![1u8, 0u8]]);
+        let key = vec![1u8, 2u8];
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.insert(key, &author, hash, len)?;
+        assert_keys(
+            &mut store,
+            namespace.id(),
+            vec![vec![1u8, 0u8], vec![1u8, 2u8]],
+        );
+
+        let key = vec![0u8, 255u8];
+        let mut replica = store.new_replica(namespace.clone())?;
+        replica.insert(key, &author, hash, len)?;
+        assert_keys(
+            &mut store,
+            namespace.id(),
+            vec![vec![1u8, 0u8], vec![1u8, 2u8], vec![0u8, 255u8]],
+        );
+        store.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_token_memory() -> Result<()> {
+        let alice_store = store::Store::memory();
+        let bob_store = store::Store::memory();
+
+        test_write_token(alice_store, bob_store)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_token_fs() -> Result<()> {
+        let alice_dbfile = tempfile::NamedTempFile::new()?;
+        let alice_store = store::fs::Store::persistent(alice_dbfile.path())?;
+        let bob_dbfile = tempfile::NamedTempFile::new()?;
+        let bob_store = store::fs::Store::persistent(bob_dbfile.path())?;
+        test_write_token(alice_store, bob_store)?;
+
+        Ok(())
+    }
+
+    fn test_write_token(mut alice_store: Store, mut bob_store: Store) -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let alice_author = alice_store.new_author(&mut rng)?;
+        let bob_author = bob_store.new_author(&mut rng)?;
+        let carol_author = bob_store.new_author(&mut rng)?;
+        let hash = Hash::new(b"hello");
+        let expires_at = system_time_now() + Duration::from_secs(3600).as_micros() as u64;
+
+        // alice owns the namespace, bob only has read access
+        let mut alice = alice_store.new_replica(namespace.clone())?;
+        alice.hash_and_insert("from-alice", &alice_author, b"hi")?;
+        bob_store.import_namespace(Capability::Read(namespace.id()))?;
+        let mut bob = bob_store.open_replica(&namespace.id())?;
+        let res = bob.insert("from-bob", &bob_author, hash, 5);
+        assert!(matches!(res, Err(InsertError::ReadOnly)));
+
+        // valid token: bob may write, and alice accepts the entry on sync
+        let token = WriteToken::issue(&mut rng, &namespace, bob_author.id(), expires_at);
+        bob.insert_with_token("from-bob", &bob_author, &token, hash, 5)?;
+        // the token is bound to bob's author
+        let res = bob.insert_with_token("from-carol", &carol_author, &token, hash, 5);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(
+                ValidationFailure::InvalidWriteToken
+            ))
+        ));
+        let (tx, rx) = async_channel::bounded(10);
+        alice.info.subscribe(tx);
+        let (alice_outcome, _) = sync(&mut alice, &mut bob)?;
+        drop(alice);
+        drop(bob);
+        // the token entry and bob's entry are each inserted and reported once
+        assert_eq!(alice_outcome.num_inserted, 2);
+        let mut events = Vec::new();
+        while let Ok(Event::RemoteInsert {
+            entry,
+            should_download,
+            ..
+        }) = rx.try_recv()
+        {
+            events.push((entry.key().to_vec(), should_download));
+        }
+        events.sort();
+        let token_key = [WRITE_TOKEN_PREFIX, &token.to_bytes()].concat();
+        assert_eq!(
+            events,
+            vec![(token_key, false), (b"from-bob".to_vec(), true)]
+        );
+        let entry = alice_store
+            .get_exact(namespace.id(), bob_author.id(), "from-bob", false)?
+            .unwrap();
+        assert!(entry.verify(&()).is_err());
+        assert_eq!(
+            alice_store
+                .get_many(namespace.id(), Query::all())?
+                .collect::<Result<Vec<_>>>()?
+                .len(),
+            2,
+            "token entries are excluded from queries"
+        );
+
+        // expired token
+        let expired = WriteToken::issue(
+            &mut rng,
+            &namespace,
+            carol_author.id(),
+            system_time_now() - 1,
+        );
+        let mut bob = bob_store.open_replica(&namespace.id())?;
+        let res = bob.insert_with_token("from-carol", &carol_author, &expired, hash, 5);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(
+                ValidationFailure::WriteTokenExpired { timestamp, expires_at, .. }
+            )) if expires_at == expired.expires_at() && timestamp > expires_at
+        ));
+
+        // entries backdated to before the expiry of the token are rejected as well
+        let backdated = expired.expires_at() - 1;
+        let token_bytes = expired.to_bytes();
+        let token_key = [WRITE_TOKEN_PREFIX, &token_bytes].concat();
+        let sign = |key: &[u8], record: Record| {
+            let id = RecordIdentifier::new(namespace.id(), carol_author.id(), key);
+            let entry = Entry::new(id, record);
+            let signature =
+                EntrySignature::from_entry_with_token(&entry, expired.id(), &carol_author);
+            SignedEntry::new(signature, entry)
+        };
+        let token_entry = sign(
+            &token_key,
+            Record::new(Hash::new(&token_bytes), token_bytes.len() as u64, backdated),
+        );
+        let backdated_entry = sign(b"backdated", Record::new(hash, 5, backdated));
+        assert_eq!(backdated_entry.write_token_id(), Some(expired.id()));
+        let mut alice = alice_store.open_replica(&namespace.id())?;
+        let is_expired = |res: Result<usize, InsertError>| {
+            matches!(
+                res,
+                Err(InsertError::Validation(ValidationFailure::WriteTokenExpired {
+                    timestamp,
+                    now,
+                    expires_at,
+                })) if timestamp == backdated && now > expires_at
+            )
+        };
+        let res = alice.insert_remote_entry(token_entry.clone(), [1u8; 32], ContentStatus::Missing);
+        assert!(is_expired(res));
+        // also if the token was accepted before it expired
+        alice.insert_entry(token_entry, InsertOrigin::Local)?;
+        let res = alice.insert_remote_entry(backdated_entry, [1u8; 32], ContentStatus::Missing);
+        assert!(is_expired(res));
+        drop(alice);
+
+        // revoked token: entries bob signs after the revocation are rejected
+        let mut alice = alice_store.open_replica(&namespace.id())?;
+        alice.revoke_write_token(&alice_author, token.id())?;
+        bob.insert_with_token("late", &bob_author, &token, hash, 5)?;
+        sync(&mut alice, &mut bob)?;
+        let res = bob.insert_with_token("later", &bob_author, &token, hash, 5);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(
+                ValidationFailure::WriteTokenRevoked { token: id }
+            )) if id == token.id()
+        ));
+        drop(alice);
+        drop(bob);
+        assert!(alice_store
+            .get_exact(namespace.id(), bob_author.id(), "late", false)?
+            .is_none());
+
+        // only the namespace owner can revoke
+        let mut bob = bob_store.open_replica(&namespace.id())?;
+        let res = bob.revoke_write_token(&bob_author, token.id());
+        assert!(matches!(res, Err(InsertError::ReadOnly)));
+        drop(bob);
+
+        alice_store.flush()?;
+        bob_store.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_trust_local_memory() -> Result<()> {
+        let store = store::Store::memory();
+        test_trust_local(store)
+    }
+
+    #[test]
+    fn test_trust_local_fs() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let store = store::fs::Store::persistent(dbfile.path())?;
+        test_trust_local(store)
+    }
+
+    fn test_trust_local(mut store: Store) -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let author = store.new_author(&mut rng)?;
+        let namespace = NamespaceSecret::new(&mut rng);
+        let wrong_namespace = NamespaceSecret::new(&mut rng);
+        let hash = Hash::new(b"hello");
+        // an entry of our namespace, but signed with the wrong namespace key
+        let bad_entry = |key: &str| {
+            let id = RecordIdentifier::new(namespace.id(), author.id(), key);
+            let entry = Entry::new(id, Record::new_current(hash, 5));
+            SignedEntry::from_entry(entry, &wrong_namespace, &author)
+        };
+
+        assert!(store.trust_local());
+        let mut replica = store.new_replica(namespace.clone())?;
+        // remote entries are always verified
+        let res =
+            replica.insert_remote_entry(bad_entry("remote"), [1u8; 32], ContentStatus::Complete);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::BadSignature))
+        ));
+        // local entries are trusted
+        replica.insert_entry(bad_entry("local"), InsertOrigin::Local)?;
+        drop(replica);
+
+        store.set_trust_local(false);
+        let mut replica = store.open_replica(&namespace.id())?;
+        let res = replica.insert_entry(bad_entry("untrusted"), InsertOrigin::Local);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::BadSignature))
+        ));
+        replica.insert(b"good", &author, hash, 5)?;
+        let res =
+            replica.insert_remote_entry(bad_entry("remote"), [1u8; 32], ContentStatus::Complete);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::BadSignature))
+        ));
+        drop(replica);
+
+        assert!(store
+            .get_exact(namespace.id(), author.id(), "remote", false)?
+            .is_none());
+        assert!(store
+            .get_exact(namespace.id(), author.id(), "untrusted", false)?
+            .is_none());
+        assert!(store
+            .get_exact(namespace.id(), author.id(), "good", false)?
+            .is_some());
+        store.flush()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_batch() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let authors = [Author::new(&mut rng), Author::new(&mut rng)];
+        let namespace = NamespaceSecret::new(&mut rng);
+        let namespace_key = namespace.public_key();
+        let mut entries = (0..8)
+            .map(|i| {
+                let author = &authors[i % 2];
+                let entry = Entry::new(
+                    RecordIdentifier::new(namespace.id(), author.id(), format!("k{i}")),
+                    Record::new_current(Hash::new(format!("v{i}")), 2),
+                );
+                let signature = EntrySignature::from_entry(&entry, &namespace, author);
+                (entry, author.public_key(), signature)
+            })
+            .collect::<Vec<_>>();
+        let batch = |entries: &[(Entry, AuthorPublicKey, EntrySignature)]| {
+            let entries = entries
+                .iter()
+                .map(|(entry, author, signature)| (entry, &namespace_key, author, signature))
+                .collect::<Vec<_>>();
+            EntrySignature::verify_batch(&entries)
+        };
+        batch(&entries)?;
+        batch(&[])?;
+
+        // a single bad signature fails the whole batch
+        entries[5].2 = entries[4].2.clone();
+        assert!(batch(&entries).is_err());
+        let invalid = entries
+            .iter()
+            .position(|(entry, author, signature)| {
+                signature.verify(entry, &namespace_key, author).is_err()
+            })
+            .unwrap();
+        assert_eq!(invalid, 5);
+
+        // entries signed under the wrong namespace fail too
+        let mut entries = entries[..4].to_vec();
+        let wrong_namespace = NamespaceSecret::new(&mut rng);
+        entries[1].2 = EntrySignature::from_entry(&entries[1].0, &wrong_namespace, &authors[1]);
+        assert!(batch(&entries).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_batch_verify_rejects_invalid_entry() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let wrong_namespace = NamespaceSecret::new(&mut rng);
+
+        let mut alice = alice_store.new_replica(namespace.clone())?;
+        for i in 0..4 {
+            alice.hash_and_insert(format!("good{i}"), &author, "v")?;
+        }
+        // local entries are trusted, so alice stores an entry with a bad signature
+        let id = RecordIdentifier::new(namespace.id(), author.id(), "bad");
+        let entry = Entry::new(id, Record::new_current(Hash::new(b"v"), 1));
+        let bad = SignedEntry::from_entry(entry, &wrong_namespace, &author);
+        alice.insert_entry(bad, InsertOrigin::Local)?;
+
+        let mut bob = bob_store.new_replica(namespace.clone())?;
+        let (_alice_outcome, bob_outcome) = sync(&mut alice, &mut bob)?;
+        assert_eq!(bob_outcome.num_invalid, 1);
+        assert_eq!(bob_outcome.num_inserted, 4);
+        drop(alice);
+        drop(bob);
+        for i in 0..4 {
+            assert!(bob_store
+                .get_exact(namespace.id(), author.id(), format!("good{i}"), false)?
+                .is_some());
+        }
+        assert!(bob_store
+            .get_exact(namespace.id(), author.id(), "bad", false)?
+            .is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_author_only() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let mut store = store::Store::memory();
+        let author = Author::new(&mut rng);
+        let wrong_author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let wrong_namespace = NamespaceSecret::new(&mut rng);
+        let hash = Hash::new(b"hello");
+        let entry = |key: &str| {
+            let id = RecordIdentifier::new(namespace.id(), author.id(), key);
+            Entry::new(id, Record::new_current(hash, 5))
+        };
+        // signed by the author, but with the wrong namespace key
+        let forged = |key: &str| SignedEntry::from_entry(entry(key), &wrong_namespace, &author);
+
+        let entry_forged = forged("a");
+        assert!(entry_forged.verify(&()).is_err());
+        entry_forged.verify_author_only(&())?;
+        let entry_bad_author = SignedEntry::from_entry(entry("a"), &namespace, &wrong_author);
+        assert!(entry_bad_author.verify(&()).is_err());
+        assert!(entry_bad_author.verify_author_only(&()).is_err());
+
+        // the sync path verifies both signatures by default
+        let mut replica = store.new_replica(namespace.clone())?;
+        let res = replica.insert_remote_entry(forged("remote"), [1u8; 32], ContentStatus::Complete);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::BadSignature))
+        ));
+        drop(replica);
+
+        // with a trusted namespace, only the author signature is verified
+        store.set_trust_namespace(namespace.id(), true);
+        assert!(store.trusts_namespace(&namespace.id()));
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.insert_remote_entry(forged("remote"), [1u8; 32], ContentStatus::Complete)?;
+        let res = replica.insert_remote_entry(
+            SignedEntry::from_entry(entry("bad"), &namespace, &wrong_author),
+            [1u8; 32],
+            ContentStatus::Complete,
+        );
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::BadSignature))
+        ));
+        drop(replica);
+        assert!(store
+            .get_exact(namespace.id(), author.id(), "remote", false)?
+            .is_some());
+
+        store.set_trust_namespace(namespace.id(), false);
+        let mut replica = store.open_replica(&namespace.id())?;
+        let res = replica.insert_remote_entry(forged("again"), [1u8; 32], ContentStatus::Complete);
+        assert!(matches!(
+            res,
+            Err(InsertError::Validation(ValidationFailure::BadSignature))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_entries_received_memory() -> Result<()> {
+        let alice_store = store::Store::memory();
+        let bob_store = store::Store::memory();
+        test_max_entries_received(alice_store, bob_store)
+    }
+
+    #[test]
+    fn test_max_entries_received_fs() -> Result<()> {
+        let alice_dbfile = tempfile::NamedTempFile::new()?;
+        let alice_store = store::fs::Store::persistent(alice_dbfile.path())?;
+        let bob_dbfile = tempfile::NamedTempFile::new()?;
+        let bob_store = store::fs::Store::persistent(bob_dbfile.path())?;
+        test_max_entries_received(alice_store, bob_store)
+    }
+
+    fn test_max_entries_received(mut alice_store: Store, mut bob_store: Store) -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let limit = 10;
+        bob_store.set_sync_config(SyncConfig::default().with_max_entries_received(limit));
+
+        let mut alice = alice_store.new_replica(namespace.clone())?;
+        for i in 0..100 {
+            alice.hash_and_insert(format!("alice/{i}"), &author, format!("{i}"))?;
+        }
+        let mut bob = bob_store.new_replica(namespace.clone())?;
+        bob.hash_and_insert("bob", &author, "bob")?;
+
+        let err = sync(&mut alice, &mut bob).expect_err("sync exceeds the limit");
+        assert!(matches!(
+            err.downcast_ref::<SyncError>(),
+            Some(SyncError::LimitExceeded { count, limit: 10 }) if *count > limit
+        ));
+        drop(alice);
+        drop(bob);
+
+        // entries received before the limit was hit are kept
+        let received = bob_store
+            .get_many(namespace.id(), Query::key_prefix("alice/"))?
+            .count();
+        assert!(received <= limit);
+        assert!(bob_store
+            .get_exact(namespace.id(), author.id(), "bob", false)?
+            .is_some());
+
+        // without a limit, the sync completes
+        bob_store.set_sync_config(SyncConfig::default());
+        let mut alice = alice_store.new_replica(namespace.clone())?;
+        let mut bob = bob_store.open_replica(&namespace.id())?;
+        sync(&mut alice, &mut bob)?;
+        drop(alice);
+        drop(bob);
+        let received = bob_store
+            .get_many(namespace.id(), Query::key_prefix("alice/"))?
+            .count();
+        assert_eq!(received, 100);
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflict_resolver_memory() -> Result<()> {
         let store = store::Store::memory();
-        test_latest_iter(store)
+        test_conflict_resolver(store)
     }
 
     #[test]
-    fn test_latest_iter_fs() -> Result<()> {
+    fn test_conflict_resolver_fs() -> Result<()> {
         let dbfile = tempfile::NamedTempFile::new()?;
         let store = store::fs::Store::persistent(dbfile.path())?;
-        test_latest_iter(store)
+        test_conflict_resolver(store)
     }
 
-    fn test_latest_iter(mut store: Store) -> Result<()> {
-        let mut rng = rand::thread_rng();
-        let author0 = Author::new(&mut rng);
-        let author1 = Author::new(&mut rng);
+    /// Prefers entries of one author, and longer content for entries of the same author.
+    #[derive(Debug)]
+    struct AuthorPriority(AuthorId);
+
+    impl ConflictResolver for AuthorPriority {
+        fn resolve(&self, a: &Entry, b: &Entry) -> Ordering {
+            (a.author() == self.0)
+                .cmp(&(b.author() == self.0))
+                .then_with(|| a.content_len().cmp(&b.content_len()))
+        }
+    }
+
+    fn test_conflict_resolver(mut store: Store) -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
         let namespace = NamespaceSecret::new(&mut rng);
+        let alice = store.new_author(&mut rng)?;
+        let bob = store.new_author(&mut rng)?;
+        let timestamp = system_time_now();
+        let entry = |author: &Author, key: &str, content: &[u8]| {
+            let id = RecordIdentifier::new(namespace.id(), author.id(), key);
+            let record = Record::new(Hash::new(content), content.len() as u64, timestamp);
+            SignedEntry::from_entry(Entry::new(id, record), &namespace, author)
+        };
+        let latest = |store: &mut Store| -> Result<AuthorId> {
+            let entries = store
+                .get_many(
+                    namespace.id(),
+                    Query::single_latest_per_key().key_exact("k"),
+                )?
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(entries.len(), 1);
+            Ok(entries[0].author())
+        };
+
         let mut replica = store.new_replica(namespace.clone())?;
+        replica.insert_entry(entry(&alice, "k", b"alice"), InsertOrigin::Local)?;
+        replica.insert_entry(entry(&bob, "k", b"bob"), InsertOrigin::Local)?;
+        drop(replica);
 
-        replica.hash_and_insert(b"a0.1", &author0, b"hi")?;
-        let latest = store
-            .get_latest_for_each_author(namespace.id())?
-            .collect::<Result<Vec<_>>>()?;
-        assert_eq!(latest.len(), 1);
-        assert_eq!(latest[0].2, b"a0.1".to_vec());
+        // by default, the first entry in key and author order wins
+        let first = alice.id().min(bob.id());
+        assert_eq!(latest(&mut store)?, first);
 
-        let mut replica = store.new_replica(namespace.clone())?;
-        replica.hash_and_insert(b"a1.1", &author1, b"hi")?;
-        replica.hash_and_insert(b"a0.2", &author0, b"hi")?;
-        let latest = store
-            .get_latest_for_each_author(namespace.id())?
-            .collect::<Result<Vec<_>>>()?;
-        let mut latest_keys: Vec<Vec<u8>> = latest.iter().map(|r| r.2.to_vec()).collect();
-        latest_keys.sort();
-        assert_eq!(latest_keys, vec![b"a0.2".to_vec(), b"a1.1".to_vec()]);
+        // with the hash resolver, the entry with the greater hash wins
+        let (winner, loser) = if Hash::new(b"alice") > Hash::new(b"bob") {
+            (alice.id(), bob.id())
+        } else {
+            (bob.id(), alice.id())
+        };
+        store.set_conflict_resolver(namespace.id(), Some(Arc::new(HashConflictResolver)));
+        assert_eq!(latest(&mut store)?, winner);
+
+        // with a resolver, the preferred author wins
+        store.set_conflict_resolver(namespace.id(), Some(Arc::new(AuthorPriority(loser))));
+        assert_eq!(latest(&mut store)?, loser);
+
+        // the resolver is consulted when replacing entries of the same author and key
+        let mut replica = store.open_replica(&namespace.id())?;
+        replica.insert_entry(entry(&alice, "l", b"longer"), InsertOrigin::Local)?;
+        let res = replica.insert_entry(entry(&alice, "l", b"short"), InsertOrigin::Local);
+        assert!(matches!(res, Err(InsertError::NewerEntryExists)));
+        drop(replica);
+        let entry = store
+            .get_exact(namespace.id(), alice.id(), "l", false)?
+            .unwrap();
+        assert_eq!(entry.content_hash(), Hash::new(b"longer"));
+
+        // resetting the resolver restores the default
+        store.set_conflict_resolver(namespace.id(), None);
+        assert_eq!(latest(&mut store)?, first);
         store.flush()?;
         Ok(())
     }
 
     #[test]
-    fn test_replica_byte_keys_memory() -> Result<()> {
-        let store = store::Store::memory();
-
-        test_replica_byte_keys(store)?;
-        Ok(())
+    fn test_silent_sync_memory() -> Result<()> {
+        let alice_store = store::Store::memory();
+        let bob_store = store::Store::memory();
+        test_silent_sync(alice_store, bob_store)
     }
 
     #[test]
-    fn test_replica_byte_keys_fs() -> Result<()> {
-        let dbfile = tempfile::NamedTempFile::new()?;
-        let store = store::fs::Store::persistent(dbfile.path())?;
-        test_replica_byte_keys(store)?;
-
-        Ok(())
+    fn test_silent_sync_fs() -> Result<()> {
+        let alice_dbfile = tempfile::NamedTempFile::new()?;
+        let alice_store = store::fs::Store::persistent(alice_dbfile.path())?;
+        let bob_dbfile = tempfile::NamedTempFile::new()?;
+        let bob_store = store::fs::Store::persistent(bob_dbfile.path())?;
+        test_silent_sync(alice_store, bob_store)
     }
 
-    fn test_replica_byte_keys(mut store: Store) -> Result<()> {
-        let mut rng = rand::thread_rng();
+    fn test_silent_sync(mut alice_store: Store, mut bob_store: Store) -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
         let author = Author::new(&mut rng);
         let namespace = NamespaceSecret::new(&mut rng);
 
-        let hash = Hash::new(b"foo");
-        let len = 3;
-
-        let key = vec![1u8, 0u8];
-        let mut replica = store.new_replica(namespace.clone())?;
-        replica.insert(key, &author, hash, len)?;
-        assert_keys(&mut store, namespace.id(), vec![vec![1u8, 0u8]]);
-        let key = vec![1u8, 2u8];
-        let mut replica = store.new_replica(namespace.clone())?;
-        replica.insert(key, &author, hash, len)?;
-        assert_keys(
-            &mut store,
-            namespace.id(),
-            vec![vec![1u8, 0u8], vec![1u8, 2u8]],
-        );
+        let mut alice = alice_store.new_replica(namespace.clone())?;
+        for i in 0..20 {
+            alice.hash_and_insert(format!("alice/{i}"), &author, format!("{i}"))?;
+        }
+        let (all_tx, all_rx) = async_channel::unbounded();
+        bob_store.subscribe_all(all_tx);
+        let mut bob = bob_store.new_replica(namespace.clone())?;
+        let (tx, rx) = async_channel::unbounded();
+        bob.info.subscribe(tx);
+        let (internal_tx, internal_rx) = async_channel::unbounded();
+        bob.info.subscribe_internal(internal_tx);
+        bob.info.set_sync_event_mode(SyncEventMode::Silent);
+
+        let (_alice_out, bob_out) = sync(&mut alice, &mut bob)?;
+        assert_eq!(bob_out.num_inserted, 20);
+        assert!(drain(rx.clone()).is_empty());
+        assert!(drain(all_rx.clone()).is_empty());
+        // internal subscribers still see each entry, to queue the content downloads
+        let internal = drain(internal_rx.clone());
+        assert_eq!(internal.len(), 20);
+        assert!(internal.iter().all(|event| matches!(
+            event,
+            Event::RemoteInsert {
+                should_download: true,
+                ..
+            }
+        )));
 
-        let key = vec![0u8, 255u8];
-        let mut replica = store.new_replica(namespace.clone())?;
-        replica.insert(key, &author, hash, len)?;
-        assert_keys(
-            &mut store,
-            namespace.id(),
-            vec![vec![1u8, 0u8], vec![1u8, 2u8], vec![0u8, 255u8]],
-        );
-        store.flush()?;
+        bob.sync_batch_applied(bob_out.num_inserted);
+        let events = drain(rx.clone());
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0],
+            Event::SyncBatchApplied { namespace: ns, count: 20 } if ns == namespace.id()
+        ));
+        assert_eq!(drain(all_rx.clone()).len(), 1);
+
+        // in the default mode, events are emitted per entry and no summary is sent
+        alice.hash_and_insert("alice/last", &author, "last")?;
+        bob.info.set_sync_event_mode(SyncEventMode::PerEntry);
+        let (_alice_out, bob_out) = sync(&mut alice, &mut bob)?;
+        assert_eq!(bob_out.num_inserted, 1);
+        bob.sync_batch_applied(bob_out.num_inserted);
+        let events = drain(rx);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], Event::RemoteInsert { .. }));
         Ok(())
     }
 
@@ -2143,6 +6496,60 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_open_or_create_replica_memory() -> Result<()> {
+        let store = store::Store::memory();
+        test_open_or_create_replica(store)
+    }
+
+    #[test]
+    fn test_open_or_create_replica_fs() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let store = store::fs::Store::persistent(dbfile.path())?;
+        test_open_or_create_replica(store)
+    }
+
+    fn test_open_or_create_replica(mut store: Store) -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let author = store.new_author(&mut rng)?;
+
+        // create
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.open_or_create_replica(namespace.clone())?;
+        assert!(matches!(replica.capability().kind(), CapabilityKind::Write));
+        replica.hash_and_insert(b"foo", &author, b"bar")?;
+        store.close_replica(namespace.id());
+
+        // reopen keeps the existing entries
+        let mut replica = store.open_or_create_replica(namespace.clone())?;
+        replica.hash_and_insert(b"baz", &author, b"qux")?;
+        store.close_replica(namespace.id());
+        assert_eq!(
+            store
+                .get_many(namespace.id(), Query::single_latest_per_key())?
+                .count(),
+            2
+        );
+
+        // upgrade from read to write
+        let namespace = NamespaceSecret::new(&mut rng);
+        store.import_namespace(Capability::Read(namespace.id()))?;
+        let mut replica = store.open_replica(&namespace.id())?;
+        let res = replica.hash_and_insert(b"foo", &author, b"bar");
+        assert!(matches!(res, Err(InsertError::ReadOnly)));
+        store.close_replica(namespace.id());
+        let mut replica = store.open_or_create_replica(namespace.clone())?;
+        assert!(matches!(replica.capability().kind(), CapabilityKind::Write));
+        replica.hash_and_insert(b"foo", &author, b"bar")?;
+        store.close_replica(namespace.id());
+        assert!(matches!(
+            store.load_replica_info(&namespace.id())?.capability.kind(),
+            CapabilityKind::Write
+        ));
+        store.flush()?;
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_actor_capability_memory() -> Result<()> {
         let store = store::Store::memory();
@@ -2196,7 +6603,7 @@ mod tests {
         Ok(())
     }
 
-    fn drain(events: async_channel::Receiver<Event>) -> Vec<Event> {
+    fn drain<T>(events: async_channel::Receiver<T>) -> Vec<T> {
         let mut res = vec![];
         while let Ok(ev) = events.try_recv() {
             res.push(ev);
@@ -2261,6 +6668,400 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_latest_per_key_pagination_memory() -> Result<()> {
+        let store = store::Store::memory();
+        test_latest_per_key_pagination(store)
+    }
+
+    #[test]
+    fn test_latest_per_key_pagination_fs() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let store = store::fs::Store::persistent(dbfile.path())?;
+        test_latest_per_key_pagination(store)
+    }
+
+    fn test_latest_per_key_pagination(mut store: Store) -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let authors = [
+            store.new_author(&mut rng)?,
+            store.new_author(&mut rng)?,
+            store.new_author(&mut rng)?,
+        ];
+
+        // several versions per key, by several authors, in interleaved order
+        let mut replica = store.new_replica(namespace.clone())?;
+        let keys = ["a", "b", "c", "d", "e"];
+        for round in 0..3 {
+            for (i, key) in keys.iter().enumerate() {
+                let author = &authors[(i + round) % authors.len()];
+                replica.hash_and_insert(key, author, format!("{key}{round}"))?;
+            }
+        }
+        // the latest version of "c" is a deletion, so "c" must not show up at all
+        replica.delete_prefix("c", &authors[2])?;
+        replica.delete_prefix("c", &authors[0])?;
+        replica.delete_prefix("c", &authors[1])?;
+        drop(replica);
+
+        for direction in [SortDirection::Asc, SortDirection::Desc] {
+            let query = || Query::single_latest_per_key().sort_direction(direction);
+            let all = store
+                .get_many(namespace.id(), query())?
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(all.len(), 4);
+
+            let mut paged = Vec::new();
+            for offset in 0..all.len() as u64 + 1 {
+                let page = store
+                    .get_many(namespace.id(), query().offset(offset).limit(1))?
+                    .collect::<Result<Vec<_>>>()?;
+                assert!(page.len() <= 1);
+                paged.extend(page);
+            }
+            assert_eq!(paged, all, "{direction:?}");
+            for entry in &all {
+                assert!(!entry.is_empty());
+                let latest = all
+                    .iter()
+                    .chain(paged.iter())
+                    .filter(|e| e.key() == entry.key())
+                    .count();
+                assert_eq!(latest, 2, "key must appear exactly once per listing");
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_tracing_spans() -> Result<()> {
+        use std::sync::{Arc, Mutex};
+
+        use tracing_subscriber::{layer::SubscriberExt, registry::LookupSpan, Layer};
+
+        /// Records the names of all created spans.
+        #[derive(Clone, Default)]
+        struct SpanNames(Arc<Mutex<Vec<&'static str>>>);
+
+        impl<S: tracing::Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanNames {
+            fn on_new_span(
+                &self,
+                attrs: &tracing::span::Attributes<'_>,
+                _id: &tracing::span::Id,
+                _ctx: tracing_subscriber::layer::Context<'_, S>,
+            ) {
+                self.0.lock().unwrap().push(attrs.metadata().name());
+            }
+        }
+
+        let names = SpanNames::default();
+        let subscriber = tracing_subscriber::registry().with(names.clone());
+        tracing::subscriber::with_default(subscriber, || -> Result<()> {
+            let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+            let mut alice_store = store::Store::memory();
+            let mut bob_store = store::Store::memory();
+            let author = Author::new(&mut rng);
+            let namespace = NamespaceSecret::new(&mut rng);
+            let mut alice = alice_store.new_replica(namespace.clone())?;
+            let mut bob = bob_store.new_replica(namespace)?;
+            for i in 0..100 {
+                alice.hash_and_insert(format!("alice/{i}"), &author, "a")?;
+                bob.hash_and_insert(format!("bob/{i}"), &author, "b")?;
+            }
+            let (alice_out, bob_out) = sync(&mut alice, &mut bob)?;
+            assert!(alice_out.num_rounds > 1);
+            assert!(bob_out.num_rounds > 1);
+            Ok(())
+        })?;
+
+        let names = names.0.lock().unwrap();
+        for name in [
+            "sync_round",
+            "process_items",
+            "range_item",
+            "process_fingerprints",
+            "range_fingerprint",
+            "split_range",
+        ] {
+            assert!(names.contains(&name), "missing span {name}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_remove_author_entries_memory() -> Result<()> {
+        let store = store::Store::memory();
+        test_remove_author_entries(store)
+    }
+
+    #[test]
+    fn test_remove_author_entries_fs() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let store = store::fs::Store::persistent(dbfile.path())?;
+        test_remove_author_entries(store)
+    }
+
+    fn test_remove_author_entries(mut store: Store) -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let alice = store.new_author(&mut rng)?;
+        let bob = store.new_author(&mut rng)?;
+
+        let count = |store: &mut Store, namespace: NamespaceId, author: AuthorId| -> Result<_> {
+            Ok(store
+                .get_many(namespace, Query::author(author).include_empty())?
+                .count())
+        };
+
+        for mode in [RemoveMode::Tombstone, RemoveMode::Local] {
+            let namespace = NamespaceSecret::new(&mut rng);
+            let mut replica = store.new_replica(namespace.clone())?;
+            replica.hash_and_insert("a/1", &alice, "one")?;
+            replica.hash_and_insert("a/2", &alice, "two")?;
+            replica.hash_and_insert("b", &alice, "three")?;
+            replica.hash_and_insert("a/1", &bob, "four")?;
+            if mode == RemoveMode::Tombstone {
+                replica.counter_add("visits", &alice, 1)?;
+                replica.set_namespace_meta(&alice, Bytes::from_static(b"title"))?;
+            }
+
+            let removed = replica.remove_author_entries(&alice, mode)?;
+            assert_eq!(removed, 3, "{mode:?}");
+            drop(replica);
+
+            let found = store
+                .get_many(namespace.id(), Query::author(alice.id()))?
+                .count();
+            assert_eq!(found, 0, "{mode:?}");
+            let found = store
+                .get_many(namespace.id(), Query::key_exact("a/1"))?
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(found.len(), 1, "{mode:?}");
+            assert_eq!(found[0].author(), bob.id());
+            assert_eq!(count(&mut store, namespace.id(), bob.id())?, 1);
+
+            let latest = store
+                .get_latest_for_each_author(namespace.id())?
+                .map(|res| res.map(|(author, _timestamp, _key)| author))
+                .collect::<Result<Vec<_>>>()?;
+            match mode {
+                RemoveMode::Tombstone => {
+                    // only the deletions are left, which sync to other peers
+                    assert_eq!(count(&mut store, namespace.id(), alice.id())?, 3);
+                    for key in ["a/1", "a/2", "b"] {
+                        let deletion = store
+                            .get_exact(namespace.id(), alice.id(), key, true)?
+                            .expect("deletion exists");
+                        assert!(deletion.is_empty());
+                    }
+                    assert!(latest.contains(&alice.id()));
+                    // entries under reserved keys are kept
+                    assert_eq!(store.get_counter(namespace.id(), "visits")?, 1);
+                    assert_eq!(
+                        store.get_namespace_meta(namespace.id())?,
+                        Some(Bytes::from_static(b"title"))
+                    );
+                }
+                RemoveMode::Local => {
+                    assert_eq!(count(&mut store, namespace.id(), alice.id())?, 0);
+                    assert!(!latest.contains(&alice.id()));
+                    // the removed entries can be synced again
+                    let mut replica = store.open_replica(&namespace.id())?;
+                    replica.hash_and_insert("a/1", &alice, "again")?;
+                    drop(replica);
+                    assert_eq!(count(&mut store, namespace.id(), alice.id())?, 1);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_initial_message_with_hint() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let authors = [Author::new(&mut rng), Author::new(&mut rng)];
+
+        // Returns the number of messages exchanged until the replicas are in sync.
+        let run = |hinted: bool| -> Result<usize> {
+            let mut alice_store = store::Store::memory();
+            let mut bob_store = store::Store::memory();
+            let mut alice = alice_store.new_replica(namespace.clone())?;
+            let mut bob = bob_store.new_replica(namespace.clone())?;
+            for i in 0..50 {
+                for author in &authors {
+                    alice.hash_and_insert(format!("shared/{i}"), author, "shared")?;
+                }
+            }
+            sync(&mut alice, &mut bob)?;
+            // alice is strictly ahead of bob for the first author
+            for i in 0..5 {
+                alice.hash_and_insert(format!("new/{i}"), &authors[0], "new")?;
+            }
+            alice.delete_prefix("new/0", &authors[0])?;
+
+            let mut bob_heads = AuthorHeads::default();
+            for res in bob.store.store.get_latest_for_each_author(namespace.id())? {
+                let (author, timestamp, _key) = res?;
+                bob_heads.insert(author, timestamp);
+            }
+
+            let mut alice_state = SyncOutcome::default();
+            let mut bob_state = SyncOutcome::default();
+            let mut next_to_bob = Some(match hinted {
+                true => alice.sync_initial_message_with_hint(&bob_heads)?,
+                false => alice.sync_initial_message()?,
+            });
+            let mut messages = 1;
+            while let Some(msg) = next_to_bob.take() {
+                let Some(msg) = bob.sync_process_message(msg, [1u8; 32], &mut bob_state)? else {
+                    break;
+                };
+                messages += 1;
+                next_to_bob = alice.sync_process_message(msg, [2u8; 32], &mut alice_state)?;
+                if next_to_bob.is_some() {
+                    messages += 1;
+                }
+            }
+            assert_eq!(alice.fingerprint()?, bob.fingerprint()?);
+            Ok(messages)
+        };
+
+        let plain = run(false)?;
+        let hinted = run(true)?;
+        assert_eq!(hinted, 1);
+        assert!(hinted < plain, "hinted {hinted} plain {plain}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_initial_message_with_hint_fallback() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let mut alice_store = store::Store::memory();
+        let mut bob_store = store::Store::memory();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let author = Author::new(&mut rng);
+        let mut alice = alice_store.new_replica(namespace.clone())?;
+        let mut bob = bob_store.new_replica(namespace.clone())?;
+        alice.hash_and_insert("alice", &author, "a")?;
+        bob.hash_and_insert("bob", &author, "b")?;
+
+        // bob is ahead of alice, so the hint offers nothing
+        let mut bob_heads = AuthorHeads::default();
+        bob_heads.insert(author.id(), u64::MAX);
+        let message = alice.sync_initial_message_with_hint(&bob_heads)?;
+        assert_eq!(message, alice.sync_initial_message()?);
+
+        // a stale hint still leads to a full reconciliation
+        let mut state = SyncOutcome::default();
+        let mut next_to_bob = Some(alice.sync_initial_message_with_hint(&AuthorHeads::default())?);
+        while let Some(msg) = next_to_bob.take() {
+            if let Some(msg) = bob.sync_process_message(msg, [1u8; 32], &mut state)? {
+                next_to_bob = alice.sync_process_message(msg, [2u8; 32], &mut state)?;
+            }
+        }
+        assert_eq!(alice.fingerprint()?, bob.fingerprint()?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_key_normalizer() -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let mut store = store::Store::memory();
+        let author = Author::new(&mut rng);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let mut replica = store.new_replica(namespace.clone())?;
+        let normalizer: KeyNormalizer = Arc::new(|key: &[u8]| match key.strip_suffix(b"/") {
+            Some(key) => Cow::Owned(key.to_vec()),
+            None => Cow::Borrowed(key),
+        });
+        replica.info.set_key_normalizer(Some(normalizer.clone()));
+
+        replica.hash_and_insert("foo/", &author, "one")?;
+        replica.hash_and_insert("foo", &author, "two")?;
+        assert_eq!(replica.record_id("foo/", &author).key(), b"foo");
+        drop(replica);
+
+        let entries = store
+            .get_many(namespace.id(), Query::all())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].key(), b"foo");
+        assert_eq!(entries[0].content_hash(), Hash::new("two"));
+
+        let mut replica = store.open_replica(&namespace.id())?;
+        // the normalizer is not persisted, but set for each opened replica
+        replica.info.set_key_normalizer(Some(normalizer));
+        assert_eq!(replica.delete_prefix("foo/", &author)?, 1);
+        drop(replica);
+        let entries = store
+            .get_many(namespace.id(), Query::all())?
+            .collect::<Result<Vec<_>>>()?;
+        assert!(entries.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_content_hash_memory() -> Result<()> {
+        let store = store::Store::memory();
+        test_query_content_hash(store)
+    }
+
+    #[test]
+    fn test_query_content_hash_fs() -> Result<()> {
+        let dbfile = tempfile::NamedTempFile::new()?;
+        let store = store::fs::Store::persistent(dbfile.path())?;
+        test_query_content_hash(store)
+    }
+
+    fn test_query_content_hash(mut store: Store) -> Result<()> {
+        let mut rng = rand_chacha::ChaCha12Rng::seed_from_u64(1);
+        let namespace = NamespaceSecret::new(&mut rng);
+        let alice = store.new_author(&mut rng)?;
+        let bob = store.new_author(&mut rng)?;
+
+        let mut replica = store.new_replica(namespace.clone())?;
+        let hash = replica.hash_and_insert("a", &alice, "shared")?;
+        replica.hash_and_insert("b", &alice, "shared")?;
+        replica.hash_and_insert("a", &bob, "shared")?;
+        replica.hash_and_insert("c", &bob, "other")?;
+        // overwritten entries no longer reference the content
+        replica.hash_and_insert("d", &bob, "shared")?;
+        replica.hash_and_insert("d", &bob, "changed")?;
+        drop(replica);
+
+        let found = store
+            .get_many(namespace.id(), Query::content_hash(hash))?
+            .collect::<Result<Vec<_>>>()?;
+        let mut found = found
+            .iter()
+            .map(|e| (e.author(), e.key().to_vec()))
+            .collect::<Vec<_>>();
+        found.sort();
+        let mut expected = vec![
+            (alice.id(), b"a".to_vec()),
+            (alice.id(), b"b".to_vec()),
+            (bob.id(), b"a".to_vec()),
+        ];
+        expected.sort();
+        assert_eq!(found, expected);
+
+        // the hash filter combines with the other filters
+        let found = store
+            .get_many(namespace.id(), Query::author(bob.id()).content_hash(hash))?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(found.len(), 1);
+        let found = store
+            .get_many(
+                namespace.id(),
+                Query::single_latest_per_key().content_hash(hash).limit(1),
+            )?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].key(), b"a");
+        Ok(())
+    }
+
     #[test]
     fn test_replica_queries_mem() -> Result<()> {
         let store = store::Store::memory();
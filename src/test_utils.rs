@@ -0,0 +1,173 @@
+//! Utilities to set up documents in tests.
+//!
+//! The [`ReplicaFixture`] builds a [`Store`] with a document and a set of authors, all derived
+//! from a seed, and populates the document with entries at fixed timestamps. Building the same
+//! fixture twice yields identical namespaces, authors and signed entries.
+
+use anyhow::Result;
+use iroh_blobs::Hash;
+
+use crate::{store::Store, Author, NamespaceSecret};
+
+/// Timestamp of the first entry inserted by a [`ReplicaFixture`], in microseconds since the
+/// unix epoch. Each following entry is one microsecond newer.
+pub const FIXTURE_START_TIMESTAMP: u64 = 1_700_000_000_000_000;
+
+/// Builder for a deterministically populated document.
+#[derive(Debug, Clone)]
+pub struct ReplicaFixture {
+    seed: u64,
+    authors: usize,
+    entries: Vec<(usize, Vec<u8>, Vec<u8>)>,
+}
+
+/// The handles created by [`ReplicaFixture::build`].
+#[derive(Debug)]
+pub struct ReplicaHandles {
+    /// The store holding the document and the authors.
+    pub store: Store,
+    /// The secret of the document.
+    pub namespace: NamespaceSecret,
+    /// The authors, in the order of their index.
+    pub authors: Vec<Author>,
+}
+
+impl ReplicaFixture {
+    /// Create a new fixture with a single author, with all keys derived from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            authors: 1,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Set the number of authors to create.
+    pub fn authors(mut self, count: usize) -> Self {
+        self.authors = count;
+        self
+    }
+
+    /// Insert `value` at `key`, written by the author with index `author`.
+    ///
+    /// Entries are inserted in the order they were added, so a later entry for the same key and
+    /// author replaces an earlier one.
+    pub fn entry(mut self, author: usize, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> Self {
+        self.entries
+            .push((author, key.as_ref().to_vec(), value.as_ref().to_vec()));
+        self
+    }
+
+    /// Insert all key/value pairs, written by the author with index `author`.
+    pub fn entries<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        self,
+        author: usize,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        entries.into_iter().fold(self, |fixture, (key, value)| {
+            fixture.entry(author, key, value)
+        })
+    }
+
+    /// Get the namespace secret of this fixture.
+    pub fn namespace(&self) -> NamespaceSecret {
+        NamespaceSecret::from_bytes(&self.derive_key("namespace", 0))
+    }
+
+    /// Get the author with index `index` of this fixture.
+    pub fn author(&self, index: usize) -> Author {
+        Author::from_bytes(&self.derive_key("author", index))
+    }
+
+    /// Create an in-memory store and populate it.
+    pub fn build(self) -> Result<ReplicaHandles> {
+        self.build_in(Store::memory())
+    }
+
+    /// Populate `store` with the document and authors of this fixture.
+    pub fn build_in(self, mut store: Store) -> Result<ReplicaHandles> {
+        let namespace = self.namespace();
+        let authors: Vec<_> = (0..self.authors).map(|i| self.author(i)).collect();
+        for author in &authors {
+            store.import_author(author.clone())?;
+        }
+        let mut replica = store.new_replica(namespace.clone())?;
+        for (i, (author, key, value)) in self.entries.into_iter().enumerate() {
+            let Some(author) = authors.get(author) else {
+                anyhow::bail!("author index {author} out of range");
+            };
+            replica.insert_with_timestamp(
+                key,
+                author,
+                Hash::new(&value),
+                value.len() as u64,
+                FIXTURE_START_TIMESTAMP + i as u64,
+            )?;
+        }
+        store.close_replica(namespace.id());
+        Ok(ReplicaHandles {
+            store,
+            namespace,
+            authors,
+        })
+    }
+
+    fn derive_key(&self, kind: &str, index: usize) -> [u8; 32] {
+        let mut material = self.seed.to_be_bytes().to_vec();
+        material.extend_from_slice(&(index as u64).to_be_bytes());
+        blake3::derive_key(&format!("iroh-docs test-utils {kind}"), &material)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Query;
+
+    fn two_author_doc(seed: u64) -> ReplicaFixture {
+        ReplicaFixture::new(seed)
+            .authors(2)
+            .entries(0, [("a/1", "one"), ("a/2", "two")])
+            .entry(1, "b/1", "three")
+            .entry(0, "a/1", "updated")
+    }
+
+    #[test]
+    fn test_replica_fixture() -> Result<()> {
+        let ReplicaHandles {
+            mut store,
+            namespace,
+            authors,
+        } = two_author_doc(1).build()?;
+        assert_eq!(authors.len(), 2);
+        assert_ne!(authors[0].id(), authors[1].id());
+        assert!(store.get_author(&authors[1].id())?.is_some());
+
+        let entries = store
+            .get_many(namespace.id(), Query::all())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(entries.len(), 3);
+        let a1 = store
+            .get_exact(namespace.id(), authors[0].id(), b"a/1", false)?
+            .expect("entry exists");
+        assert_eq!(a1.content_hash(), Hash::new(b"updated"));
+        assert_eq!(a1.timestamp(), FIXTURE_START_TIMESTAMP + 3);
+
+        // the same seed yields identical documents
+        let mut other = two_author_doc(1).build()?;
+        assert_eq!(other.namespace.id(), namespace.id());
+        let other_entries = other
+            .store
+            .get_many(namespace.id(), Query::all())?
+            .collect::<Result<Vec<_>>>()?;
+        assert_eq!(other_entries, entries);
+
+        // while a different seed does not
+        let other = two_author_doc(2);
+        assert_ne!(other.namespace().id(), namespace.id());
+        assert_ne!(other.author(0).id(), authors[0].id());
+
+        assert!(ReplicaFixture::new(1).entry(1, "a", "b").build().is_err());
+        Ok(())
+    }
+}
@@ -4,7 +4,7 @@ use iroh::NodeAddr;
 use iroh_base::ticket;
 use serde::{Deserialize, Serialize};
 
-use crate::Capability;
+use crate::{Capability, CapabilityKind, NamespaceId};
 
 /// Contains both a key (either secret or public) to a document, and a list of peers to join.
 #[derive(Serialize, Deserialize, Clone, Debug, derive_more::Display)]
@@ -16,6 +16,19 @@ pub struct DocTicket {
     pub nodes: Vec<NodeAddr>,
 }
 
+/// The contents of a [`DocTicket`], without the namespace secret of write tickets.
+///
+/// Created with [`DocTicket::inspect`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocTicketInfo {
+    /// The namespace of the document.
+    pub namespace: NamespaceId,
+    /// Whether the ticket grants write or read access.
+    pub capability_kind: CapabilityKind,
+    /// The nodes to contact.
+    pub nodes: Vec<NodeAddr>,
+}
+
 /// Wire format for [`DocTicket`].
 ///
 /// In the future we might have multiple variants (not versions, since they
@@ -52,6 +65,17 @@ impl DocTicket {
             nodes: peers,
         }
     }
+
+    /// Get the namespace, capability kind and nodes of this ticket.
+    ///
+    /// The result does not contain the namespace secret, so it can be logged or shown to users.
+    pub fn inspect(&self) -> DocTicketInfo {
+        DocTicketInfo {
+            namespace: self.capability.id(),
+            capability_kind: self.capability.kind(),
+            nodes: self.nodes.clone(),
+        }
+    }
 }
 
 impl std::str::FromStr for DocTicket {
@@ -69,7 +93,7 @@ mod tests {
     use iroh::PublicKey;
 
     use super::*;
-    use crate::NamespaceId;
+    use crate::NamespaceSecret;
 
     #[test]
     fn test_ticket_base32() {
@@ -109,6 +133,36 @@ mod tests {
         assert_eq!(base32, expected);
     }
 
+    #[test]
+    fn test_ticket_inspect() -> Result<()> {
+        let mut rng = rand::thread_rng();
+        let namespace = NamespaceSecret::new(&mut rng);
+        let node_id = iroh::SecretKey::generate(&mut rng).public();
+        let addr = "127.0.0.1:4433".parse()?;
+        let nodes = vec![NodeAddr::from_parts(node_id, None, [addr])];
+
+        let ticket = DocTicket::new(Capability::Write(namespace.clone()), nodes.clone());
+        let ticket = DocTicket::from_str(&ticket.to_string())?;
+        let info = ticket.inspect();
+        assert_eq!(
+            info,
+            DocTicketInfo {
+                namespace: namespace.id(),
+                capability_kind: CapabilityKind::Write,
+                nodes: nodes.clone(),
+            }
+        );
+        // the secret is not part of the info
+        let secret = hex::encode(namespace.to_bytes());
+        assert!(!format!("{info:?}").contains(&secret));
+
+        let ticket = DocTicket::new(Capability::Read(namespace.id()), nodes);
+        let info = DocTicket::from_str(&ticket.to_string())?.inspect();
+        assert_eq!(info.namespace, namespace.id());
+        assert_eq!(info.capability_kind, CapabilityKind::Read);
+        Ok(())
+    }
+
     /// Parses a commented multi line hexdump into a vector of bytes.
     ///
     /// This is useful to write wire level protocol tests.
@@ -1,5 +1,6 @@
 #![cfg(feature = "rpc")]
 use anyhow::{Context, Result};
+use bytes::Bytes;
 use futures_util::TryStreamExt;
 use iroh_blobs::{
     store::ExportMode,
@@ -109,6 +110,46 @@ async fn test_doc_import_export() -> TestResult<()> {
     Ok(())
 }
 
+/// Test setting many entries with a single request.
+#[tokio::test]
+#[traced_test]
+async fn test_doc_set_many() -> Result<()> {
+    let node = Node::memory().spawn().await?;
+    let author = node.authors().default().await?;
+    let doc = node.docs().create().await?;
+
+    let entries = (0..100)
+        .map(|i| (format!("key/{i}").into(), format!("value {i}").into()))
+        .collect::<Vec<(Bytes, Bytes)>>();
+    let hashes = doc.set_many(author, entries.clone()).await?;
+    assert_eq!(hashes.len(), entries.len());
+    for ((key, value), hash) in entries.iter().zip(hashes.iter()) {
+        let entry = doc
+            .get_exact(author, key.clone(), false)
+            .await?
+            .context("entry missing")?;
+        assert_eq!(entry.content_hash(), *hash);
+        assert_eq!(node.blobs().read_to_bytes(*hash).await?, *value);
+    }
+
+    // set the same content under other keys via the hashes
+    let entries = entries
+        .iter()
+        .zip(hashes)
+        .enumerate()
+        .map(|(i, ((_key, value), hash))| (format!("copy/{i}").into(), hash, value.len() as u64))
+        .collect::<Vec<(Bytes, _, _)>>();
+    doc.set_hash_many(author, entries).await?;
+    let count = doc
+        .get_many(Query::key_prefix("copy/"))
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?
+        .len();
+    assert_eq!(count, 100);
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_authors() -> Result<()> {
     let node = Node::memory().spawn().await?;
@@ -13,12 +13,13 @@ use futures_util::{FutureExt, StreamExt, TryStreamExt};
 use iroh::{PublicKey, RelayMode, SecretKey};
 use iroh_blobs::Hash;
 use iroh_docs::{
+    engine::HeadsGossip,
     rpc::{
-        client::docs::{Doc, Entry, LiveEvent, ShareMode},
+        client::docs::{ContentOpts, Doc, Entry, LiveEvent, ShareMode},
         AddrInfoOptions,
     },
     store::{DownloadPolicy, FilterKind, Query},
-    AuthorId, ContentStatus,
+    AuthorId, ContentStatus, SyncEventMode,
 };
 use rand::{CryptoRng, Rng, SeedableRng};
 use tracing::{debug, error_span, info, Instrument};
@@ -131,6 +132,201 @@ async fn sync_simple() -> Result<()> {
     Ok(())
 }
 
+/// Test that a silent sync emits a single batch event, and still downloads the content.
+#[tokio::test]
+#[traced_test]
+async fn sync_silent() -> Result<()> {
+    let mut rng = test_rng(b"sync_silent");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+
+    let peer0 = nodes[0].node_id();
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    let mut hashes = Vec::new();
+    for i in 0..3 {
+        let hash = doc0
+            .set_bytes(author0, format!("k{i}"), format!("v{i}"))
+            .await?;
+        hashes.push(hash);
+    }
+    let ticket = doc0
+        .share(ShareMode::Write, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+
+    info!("node1: join silently");
+    let doc1 = clients[1]
+        .docs()
+        .import_namespace(ticket.capability.clone())
+        .await?;
+    doc1.set_sync_event_mode(SyncEventMode::Silent).await?;
+    let mut events1 = doc1.subscribe().await?;
+    doc1.start_sync(ticket.nodes.clone()).await?;
+    let (hash0, hash1, hash2) = (hashes[0], hashes[1], hashes[2]);
+    assert_next_unordered(
+        &mut events1,
+        TIMEOUT,
+        vec![
+            Box::new(move |e| matches!(e, LiveEvent::NeighborUp(peer) if *peer == peer0)),
+            match_event!(LiveEvent::SyncBatchApplied { count: 3 }),
+            Box::new(move |e| match_sync_finished(e, peer0)),
+            Box::new(move |e| matches!(e, LiveEvent::ContentReady { hash } if *hash == hash0)),
+            Box::new(move |e| matches!(e, LiveEvent::ContentReady { hash } if *hash == hash1)),
+            Box::new(move |e| matches!(e, LiveEvent::ContentReady { hash } if *hash == hash2)),
+            match_event!(LiveEvent::PendingContentReady),
+        ],
+    )
+    .await;
+    assert_latest(clients[1].blobs(), &doc1, b"k2", b"v2").await;
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+/// Test that pausing the live sync stops remote inserts, and resuming catches up.
+#[tokio::test]
+#[traced_test]
+async fn sync_pause_resume() -> Result<()> {
+    let mut rng = test_rng(b"sync_pause_resume");
+    let nodes = spawn_nodes(2, &mut rng).await?;
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+
+    let peer0 = nodes[0].node_id();
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    let hash0 = doc0
+        .set_bytes(author0, b"k1".to_vec(), b"v1".to_vec())
+        .await?;
+    let ticket = doc0
+        .share(ShareMode::Write, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+
+    info!("node1: join");
+    let doc1 = clients[1].docs().import(ticket).await?;
+    let blobs1 = clients[1].blobs();
+    let mut events1 = doc1.subscribe().await?;
+    assert_next_unordered(
+        &mut events1,
+        TIMEOUT,
+        vec![
+            Box::new(move |e| matches!(e, LiveEvent::NeighborUp(peer) if *peer == peer0)),
+            Box::new(move |e| matches!(e, LiveEvent::InsertRemote { from, .. } if *from == peer0 )),
+            Box::new(move |e| match_sync_finished(e, peer0)),
+            Box::new(move |e| matches!(e, LiveEvent::ContentReady { hash } if *hash == hash0)),
+            match_event!(LiveEvent::PendingContentReady),
+        ],
+    )
+    .await;
+    assert_latest(blobs1, &doc1, b"k1", b"v1").await;
+
+    info!("node1: pause");
+    doc1.pause_sync().await?;
+    doc0.set_bytes(author0, b"k2".to_vec(), b"v2".to_vec())
+        .await?;
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert!(get_latest(blobs1, &doc1, b"k2").await.is_err());
+
+    info!("node1: resume");
+    doc1.resume_sync().await?;
+    tokio::time::timeout(TIMEOUT, async {
+        loop {
+            let event = next(&mut events1).await;
+            if matches!(&event, LiveEvent::InsertRemote { from, entry, .. } if *from == peer0 && entry.key() == b"k2")
+            {
+                break;
+            }
+        }
+    })
+    .await?;
+    doc1.get_exact(author0, b"k2".to_vec(), false)
+        .await?
+        .context("entry missing after resume")?;
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
+/// Test that nodes in heads gossip mode do not start further syncs once they are in sync.
+#[tokio::test]
+#[traced_test]
+async fn sync_heads_gossip_no_resync() -> Result<()> {
+    let mut rng = test_rng(b"sync_heads_gossip_no_resync");
+    let interval = Duration::from_millis(200);
+    let mut nodes = vec![];
+    for _ in 0..2 {
+        let node = test_node(SecretKey::generate(&mut rng))
+            .heads_gossip(HeadsGossip::new(interval))
+            .spawn()
+            .await?;
+        nodes.push(node);
+    }
+    let clients = nodes.iter().map(|node| node.client()).collect::<Vec<_>>();
+
+    let peer0 = nodes[0].node_id();
+    let peer1 = nodes[1].node_id();
+    let author0 = clients[0].authors().create().await?;
+    let doc0 = clients[0].docs().create().await?;
+    doc0.set_bytes(author0, b"k1".to_vec(), b"v1".to_vec())
+        .await?;
+    let ticket = doc0
+        .share(ShareMode::Write, AddrInfoOptions::RelayAndAddresses)
+        .await?;
+    let mut events0 = doc0.subscribe().await?;
+
+    info!("node1: join");
+    let doc1 = clients[1].docs().import(ticket).await?;
+    let mut events1 = doc1.subscribe().await?;
+    assert_next_unordered_with_optionals(
+        &mut events1,
+        TIMEOUT,
+        vec![
+            Box::new(move |e| matches!(e, LiveEvent::NeighborUp(peer) if *peer == peer0)),
+            Box::new(move |e| matches!(e, LiveEvent::InsertRemote { from, .. } if *from == peer0 )),
+            Box::new(move |e| match_sync_finished(e, peer0)),
+        ],
+        vec![
+            Box::new(move |e| matches!(e, LiveEvent::ContentReady { .. })),
+            match_event!(LiveEvent::PendingContentReady),
+        ],
+    )
+    .await;
+    assert_latest(clients[1].blobs(), &doc1, b"k1", b"v1").await;
+    assert_next(
+        &mut events0,
+        TIMEOUT,
+        vec![
+            Box::new(move |e| matches!(e, LiveEvent::NeighborUp(peer) if *peer == peer1)),
+            Box::new(move |e| match_sync_finished(e, peer1)),
+        ],
+    )
+    .await;
+
+    info!("exchange heads for a few intervals");
+    // a sync report from before the first sync finished may still trigger a single resync
+    tokio::time::sleep(interval * 3).await;
+    while events0.next().now_or_never().is_some() {}
+    while events1.next().now_or_never().is_some() {}
+    tokio::time::sleep(interval * 10).await;
+    for events in [&mut events0, &mut events1] {
+        while let Some(event) = events.next().now_or_never().flatten() {
+            let event = event?;
+            assert!(
+                !matches!(event, LiveEvent::SyncFinished(_)),
+                "unexpected sync: {event:?}"
+            );
+        }
+    }
+
+    for node in nodes {
+        node.shutdown().await?;
+    }
+    Ok(())
+}
+
 /// Test subscribing to replica events (without sync)
 #[tokio::test]
 #[traced_test]
@@ -1185,6 +1381,62 @@ async fn doc_delete() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+#[traced_test]
+async fn doc_get_many_with_content() -> Result<()> {
+    let node = Node::memory().spawn().await?;
+    let client = node.client();
+    let doc = client.docs().create().await?;
+    let blobs = client.blobs();
+    let author = client.authors().create().await?;
+    for i in 0..10 {
+        doc.set_bytes(author, format!("key/{i}"), format!("value/{i}"))
+            .await?;
+    }
+
+    let opts = ContentOpts {
+        concurrency: 4,
+        ..Default::default()
+    };
+    let entries = doc
+        .get_many_with_content(Query::all(), blobs, opts)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(entries, get_all_with_content(blobs, &doc).await?);
+    assert_eq!(entries.len(), 10);
+    for (i, (entry, content)) in entries.iter().enumerate() {
+        assert_eq!(entry.key(), format!("key/{i}").as_bytes());
+        assert_eq!(content, format!("value/{i}").as_bytes());
+    }
+
+    // an entry whose content is not stored locally
+    let missing = Hash::new(b"not stored");
+    doc.set_hash(author, b"missing".to_vec(), missing, 10)
+        .await?;
+    let res = doc
+        .get_many_with_content(Query::all(), blobs, opts)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await;
+    assert!(res.is_err());
+
+    let opts = ContentOpts {
+        skip_missing: true,
+        ..opts
+    };
+    let entries = doc
+        .get_many_with_content(Query::all(), blobs, opts)
+        .await?
+        .try_collect::<Vec<_>>()
+        .await?;
+    assert_eq!(entries.len(), 10);
+    assert!(entries.iter().all(|(entry, _)| entry.key() != b"missing"));
+
+    node.shutdown().await?;
+    Ok(())
+}
+
 #[tokio::test]
 #[traced_test]
 async fn sync_drop_doc() -> Result<()> {
@@ -12,7 +12,7 @@ use iroh_blobs::{
     net_protocol::Blobs,
     store::{GcConfig, Store as BlobStore},
 };
-use iroh_docs::protocol::Docs;
+use iroh_docs::{engine::HeadsGossip, protocol::Docs};
 use iroh_gossip::net::Gossip;
 use nested_enum_utils::enum_conversions;
 use quic_rpc::transport::{Connector, Listener};
@@ -112,6 +112,7 @@ pub struct Builder<S> {
     register_gc_done_cb: Option<Box<dyn Fn() + Send + 'static>>,
     insecure_skip_relay_cert_verify: bool,
     bind_random_port: bool,
+    heads_gossip: Option<HeadsGossip>,
     _p: PhantomData<S>,
 }
 
@@ -137,10 +138,13 @@ impl<S: BlobStore> Builder<S> {
         let mut router = iroh::protocol::Router::builder(endpoint.clone());
         let blobs = Blobs::builder(store.clone()).build(&endpoint);
         let gossip = Gossip::builder().spawn(endpoint.clone()).await?;
-        let builder = match self.path {
+        let mut builder = match self.path {
             Some(ref path) => Docs::persistent(path.to_path_buf()),
             None => Docs::memory(),
         };
+        if let Some(config) = self.heads_gossip {
+            builder = builder.heads_gossip(config);
+        }
         let docs = match builder.spawn(&blobs, &gossip).await {
             Ok(docs) => docs,
             Err(err) => {
@@ -150,6 +154,7 @@ impl<S: BlobStore> Builder<S> {
         };
         router = router.accept(iroh_blobs::ALPN, blobs.clone());
         router = router.accept(iroh_docs::ALPN, docs.clone());
+        router = router.accept(iroh_docs::ALPN_MULTI, docs.clone());
         router = router.accept(iroh_gossip::ALPN, gossip.clone());
 
         // Build the router
@@ -249,6 +254,11 @@ impl<S: BlobStore> Builder<S> {
         self
     }
 
+    pub fn heads_gossip(mut self, value: HeadsGossip) -> Self {
+        self.heads_gossip = Some(value);
+        self
+    }
+
     fn new(path: Option<PathBuf>) -> Self {
         Self {
             path,
@@ -260,6 +270,7 @@ impl<S: BlobStore> Builder<S> {
             dns_resolver: None,
             node_discovery: None,
             register_gc_done_cb: None,
+            heads_gossip: None,
             _p: PhantomData,
         }
     }